@@ -20,3 +20,4 @@ pub use get_address::get_address_from_pk;
 mod test_base;
 pub use test_base::{get_a_random_nonce, get_a_static_address, get_a_static_keypair};
 pub mod strings;
+pub mod value_codec;
@@ -16,6 +16,8 @@
 //
 
 use db3_proto::db3_base_proto::{UnitType, Units};
+/// the number of tai in one db3
+pub const TAI_PER_DB3: i64 = 1000_000_000;
 const STORAGE_LABELS: [char; 7] = [' ', 'K', 'M', 'G', 'T', 'P', 'E'];
 pub fn bytes_to_readable_num_str(bytes_size: u64) -> String {
     let max_shift = 7;
@@ -33,8 +35,121 @@ pub fn bytes_to_readable_num_str(bytes_size: u64) -> String {
 
 pub fn units_to_readable_num_str(units: &Units) -> String {
     if units.utype == UnitType::Tai as i32 {
-        format!("{:.6} db3", units.amount as f64 / 1000_000_000.0)
+        format!("{:.6} db3", units.amount as f64 / TAI_PER_DB3 as f64)
     } else {
         format!("{} db3", units.amount)
     }
 }
+
+///
+/// show the units in the unit type, the fraction of db3 is kept when it's shown in db3
+///
+pub fn units_to_readable_num_str_in(units: &Units, utype: UnitType) -> String {
+    match utype {
+        UnitType::Db3 => units_to_readable_num_str(units),
+        UnitType::Tai => format!("{} tai", convert_units(units, UnitType::Tai).amount),
+    }
+}
+
+///
+/// convert the units to the unit type, the fraction of db3 is dropped when tai is
+/// converted to db3
+///
+pub fn convert_units(units: &Units, utype: UnitType) -> Units {
+    let amount = match (UnitType::from_i32(units.utype), utype) {
+        (Some(UnitType::Db3), UnitType::Tai) => units.amount.saturating_mul(TAI_PER_DB3),
+        (Some(UnitType::Tai), UnitType::Db3) => units.amount / TAI_PER_DB3,
+        _ => units.amount,
+    };
+    Units {
+        utype: utype.into(),
+        amount,
+    }
+}
+
+/// parse the name of unit type, e.g. db3 or tai
+pub fn parse_unit_type(input: &str) -> Result<UnitType, String> {
+    match input.trim().to_lowercase().as_str() {
+        "db3" => Ok(UnitType::Db3),
+        "tai" => Ok(UnitType::Tai),
+        _ => Err(format!("invalid unit {input}, db3 or tai is expected")),
+    }
+}
+
+///
+/// parse the amount with an optional unit, e.g. 1db3, 1 db3 or 100 tai. the amount is
+/// in tai if the unit is omitted
+///
+pub fn parse_units(input: &str) -> Result<Units, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount = amount
+        .parse::<i64>()
+        .map_err(|e| format!("invalid amount {input} for error {e}"))?;
+    let utype = match unit.trim() {
+        "" => UnitType::Tai,
+        unit => parse_unit_type(unit)?,
+    };
+    Ok(Units {
+        utype: utype.into(),
+        amount,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_convert_units_round_trip() {
+        let db3 = Units {
+            utype: UnitType::Db3.into(),
+            amount: 3,
+        };
+        let tai = convert_units(&db3, UnitType::Tai);
+        assert_eq!(UnitType::Tai as i32, tai.utype);
+        assert_eq!(3 * TAI_PER_DB3, tai.amount);
+        assert_eq!(db3, convert_units(&tai, UnitType::Db3));
+        assert_eq!(db3, convert_units(&db3, UnitType::Db3));
+        // the fraction of db3 is dropped
+        let tai = Units {
+            utype: UnitType::Tai.into(),
+            amount: TAI_PER_DB3 + 1,
+        };
+        assert_eq!(1, convert_units(&tai, UnitType::Db3).amount);
+    }
+
+    #[test]
+    fn it_parse_units() {
+        let expected = Units {
+            utype: UnitType::Db3.into(),
+            amount: 1,
+        };
+        assert_eq!(Ok(expected.clone()), parse_units("1db3"));
+        assert_eq!(Ok(expected), parse_units(" 1 DB3 "));
+        let units = parse_units("100").unwrap();
+        assert_eq!(UnitType::Tai as i32, units.utype);
+        assert_eq!(100, units.amount);
+        assert!(parse_units("db3").is_err());
+        assert!(parse_units("1 eth").is_err());
+    }
+
+    #[test]
+    fn it_show_units_in_unit_type() {
+        let units = Units {
+            utype: UnitType::Tai.into(),
+            amount: TAI_PER_DB3 / 2,
+        };
+        assert_eq!(
+            "0.500000 db3",
+            units_to_readable_num_str_in(&units, UnitType::Db3)
+        );
+        assert_eq!(
+            "500000000 tai",
+            units_to_readable_num_str_in(&units, UnitType::Tai)
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//
+// value_codec.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+/// the values shorter than it are sent raw
+pub const DEFAULT_COMPRESS_THRESHOLD: usize = 1024;
+
+///
+/// gzip the value if it's not shorter than the threshold and the compressed one is
+/// smaller, none means the value should be sent raw. the caller records the compression
+/// in the compressed flag of the pair
+///
+pub fn compress(value: &[u8], threshold: usize) -> Option<Vec<u8>> {
+    if value.len() < threshold {
+        return None;
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(value)
+        .and_then(|_| encoder.finish())
+        .ok()?;
+    if compressed.len() < value.len() {
+        Some(compressed)
+    } else {
+        None
+    }
+}
+
+///
+/// decompress the value written by compress, it fails if the value is not gzip
+/// compressed or the decompressed value exceeds the max length
+///
+pub fn decompress(value: &[u8], max_len: usize) -> Result<Vec<u8>, String> {
+    let mut decompressed: Vec<u8> = Vec::new();
+    GzDecoder::new(value)
+        .take((max_len as u64).saturating_add(1))
+        .read_to_end(&mut decompressed)
+        .map_err(|e| format!("invalid compressed value for error {e}"))?;
+    if decompressed.len() > max_len {
+        return Err(format!("the decompressed value exceeds {max_len} bytes"));
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_compress_value_round_trip() {
+        let value = "{\"name\": \"db3\"}".repeat(100);
+        let compressed = compress(value.as_bytes(), DEFAULT_COMPRESS_THRESHOLD).unwrap();
+        assert!(compressed.len() < value.len());
+        assert_eq!(
+            value.as_bytes(),
+            decompress(&compressed, usize::MAX).unwrap().as_slice()
+        );
+        // the limit applies to the decompressed length
+        assert!(decompress(&compressed, value.len() - 1).is_err());
+        // the short value is kept raw
+        assert!(compress("v1".as_bytes(), DEFAULT_COMPRESS_THRESHOLD).is_none());
+    }
+
+    #[test]
+    fn it_keep_incompressible_value_raw() {
+        // the value grows when it's gzipped
+        let raw = vec![0x1f, 0x8b, 0x08, 0x00];
+        assert!(compress(&raw, 0).is_none());
+        // a raw value looking like a gzip header is not a valid compressed one
+        assert!(decompress(&raw, 1024).is_err());
+    }
+}
@@ -18,20 +18,62 @@
 use clap::*;
 
 use crate::keystore::KeyStore;
+use anyhow::anyhow;
+use base64ct::Encoding as _;
+use db3_base::{strings, value_codec};
+use db3_crypto::db3_address::DB3Address;
+use db3_crypto::db3_signer::{Db3MultiSchemeSigner, Signer};
+use db3_crypto::db3_verifier::DB3Verifier;
 use db3_crypto::id::{AccountId, DbId, TxId};
-use db3_proto::db3_base_proto::{BroadcastMeta, ChainId, ChainRole};
-use db3_proto::db3_database_proto::{Database, Index};
-use db3_proto::db3_mutation_proto::{CollectionMutation, DatabaseAction, DatabaseMutation};
-use db3_sdk::{mutation_sdk::MutationSDK, store_sdk::StoreSDK};
-use prettytable::{format, Table};
+use db3_proto::db3_account_proto::Account;
+use db3_proto::db3_base_proto::{BroadcastMeta, ChainId, ChainRole, UnitType, Units};
+use db3_proto::db3_bill_proto::{Bill, BillType};
+use db3_proto::db3_database_proto::field_schema::FieldType;
+use db3_proto::db3_database_proto::index::index_field::{ArrayConfig, Order, ValueMode};
+use db3_proto::db3_database_proto::index::IndexField;
+use db3_proto::db3_database_proto::{Database, FieldSchema, Index};
+use db3_proto::db3_mutation_proto::{
+    CollectionMutation, DatabaseAction, DatabaseMutation, KvPair, Mutation, MutationAction,
+    NsGrant, PayloadType, WriteRequest,
+};
+use db3_proto::db3_session_proto::QuerySession;
+use db3_sdk::event_subscriber::{self, NewBlockEvent};
+use db3_sdk::mutation_sdk::{self, MutationSDK};
+use db3_sdk::store_sdk::StoreSDK;
+use futures_util::StreamExt;
+use prettytable::{format, Cell, Row, Table};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{stdout, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
+use tendermint_rpc::{Client, HttpClient, Url};
+use tonic::{Code, Status};
+
+/// the format of tables printed by commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Csv,
+    Tsv,
+}
 
 pub struct DB3ClientContext {
     pub mutation_sdk: Option<MutationSDK>,
     pub store_sdk: Option<StoreSDK>,
+    /// the namespace used when a command omits it
+    pub default_ns: Option<String>,
+    /// the name of key in keystore, the default key is used if it's none
+    pub key_name: Option<String>,
+    /// the format of tables printed by commands
+    pub output: OutputFormat,
+    /// the url of the db3 node
+    pub url: String,
 }
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Clone, Parser)]
 #[clap(rename_all = "kebab-case")]
 pub enum DB3ClientCommand {
     /// Init the client config file
@@ -40,9 +82,77 @@ pub enum DB3ClientCommand {
     /// Create a new key
     #[clap(name = "show-key")]
     ShowKey {},
+    /// Show the address, node url and chain of the active identity
+    #[clap(name = "whoami")]
+    WhoAmI {},
+    /// List all the keys in keystore
+    #[clap(name = "list-keys")]
+    ListKeys {},
+    /// Export the key to a file
+    #[clap(name = "export-key")]
+    ExportKey {
+        /// the file to write the key to, an encrypted key stays encrypted
+        #[clap(long = "out")]
+        out: String,
+    },
+    /// Import the key from a file
+    #[clap(name = "import-key")]
+    ImportKey {
+        /// the file with the plaintext or the encrypted key, it is imported as it is
+        #[clap(long = "in")]
+        input: String,
+        /// overwrite the existing key
+        #[clap(long, default_value = "false")]
+        force: bool,
+    },
+    /// Remove the key from keystore, the key can not be recovered without a backup
+    #[clap(name = "purge-key")]
+    PurgeKey {
+        /// skip the confirmation
+        #[clap(long, default_value = "false")]
+        yes: bool,
+    },
+    /// Encrypt the key with a new passphrase, the old passphrase is required if it's encrypted
+    #[clap(name = "rotate-key-encryption")]
+    RotateKeyEncryption {
+        /// store the key in plaintext instead of encrypting it
+        #[clap(long, default_value = "false")]
+        plaintext: bool,
+    },
+    /// Show the account of the key
+    #[clap(name = "show-account")]
+    ShowAccount {
+        /// print the account as json
+        #[clap(long, default_value = "false")]
+        json: bool,
+        /// show the bills of the account from the block height
+        #[clap(long)]
+        from_height: Option<u64>,
+        /// the number of blocks to show the bills of
+        #[clap(long, default_value = "10")]
+        blocks: u64,
+        /// the unit to show the bills and credits in, db3 or tai
+        #[clap(long, value_parser = strings::parse_unit_type, default_value = "db3")]
+        unit: UnitType,
+        /// fetch the account on the interval and redraw it until ctrl-c, the changed
+        /// fields are highlighted
+        #[clap(long, default_value = "false")]
+        watch: bool,
+        /// the interval in seconds to fetch the account in the watch mode
+        #[clap(long, default_value = "5")]
+        interval: u64,
+    },
     /// Create a database
     #[clap(name = "new-db")]
-    NewDB {},
+    NewDB {
+        /// the nonce of the database mutation, the current seconds is used if it's omitted
+        #[clap(long)]
+        nonce: Option<u64>,
+        /// print the address of the database without creating it, the same address is
+        /// created later with the same nonce
+        #[clap(long, default_value = "false")]
+        dry_run: bool,
+    },
     /// Show the database with an address
     #[clap(name = "show-db")]
     ShowDB {
@@ -50,6 +160,13 @@ pub enum DB3ClientCommand {
         #[clap(long)]
         addr: String,
     },
+    /// List the databases created by an account
+    #[clap(name = "list-dbs")]
+    ListDBs {
+        /// the address of the owner, the address of the key is used if it's omitted
+        #[clap(long)]
+        addr: Option<String>,
+    },
     /// Create a new collection
     #[clap(name = "new-collection")]
     NewCollection {
@@ -59,9 +176,15 @@ pub enum DB3ClientCommand {
         /// the name of collection
         #[clap(long)]
         name: String,
-        /// the json style config of index
+        /// the index, e.g. --index name=idx1,field=age,order=asc,field=tags,array=contains.
+        /// the order and the array config apply to the field before them. the json style
+        /// config of index is also accepted
         #[clap(long = "index")]
         index_list: Vec<String>,
+        /// the type of an indexed field, e.g. --schema age=int64. the type is string, int64,
+        /// double, bool, array or map
+        #[clap(long = "schema")]
+        schema_list: Vec<String>,
     },
     #[clap(name = "show-collection")]
     ShowCollection {
@@ -69,6 +192,203 @@ pub enum DB3ClientCommand {
         #[clap(long)]
         addr: String,
     },
+    /// Set the default namespace of the session
+    #[clap(name = "use-ns")]
+    UseNs {
+        /// the namespace
+        ns: String,
+    },
+    /// Put and delete keys of a namespace in one atomic mutation
+    #[clap(name = "put")]
+    Put {
+        /// the namespace of keys, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the key value pair to put, e.g. --kv k1=v1
+        #[clap(long = "kv")]
+        kv_list: Vec<String>,
+        /// the key to delete
+        #[clap(long = "del")]
+        del_list: Vec<String>,
+        /// the gas price with an optional unit, e.g. --gas-price 1db3, the unit is tai if it's omitted.
+        /// the min gas price of the node is used if it's omitted
+        #[clap(long, value_parser = strings::parse_units)]
+        gas_price: Option<Units>,
+        /// send the large values raw instead of compressing them
+        #[clap(long)]
+        no_compress: bool,
+        /// the seconds the put keys live after the block they are written in, e.g. --ttl 3600
+        #[clap(long)]
+        ttl: Option<u64>,
+    },
+    /// Get the values of keys in a namespace
+    #[clap(name = "get")]
+    Get {
+        /// the namespace of keys, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the keys to get
+        keys: Vec<String>,
+        /// show the block height each value was last written at
+        #[clap(long)]
+        with_metadata: bool,
+        /// print keys and values as hex strings, non-UTF8 bytes are always printed as hex
+        #[clap(long)]
+        hex: bool,
+        /// get all the keys starting with the prefix instead, e.g. --prefix user/ or
+        /// --prefix 0x757365 for a binary prefix
+        #[clap(long, conflicts_with = "keys")]
+        prefix: Option<String>,
+        /// the max number of keys returned by --prefix, all of them are returned if it's omitted
+        #[clap(long, requires = "prefix")]
+        limit: Option<u32>,
+    },
+    /// Show the sender and the decoded payload of a transaction
+    #[clap(name = "show-tx")]
+    ShowTx {
+        /// the base64 encoded tx id
+        id: String,
+    },
+    /// Count the keys in a namespace
+    #[clap(name = "count")]
+    Count {
+        /// the namespace, the default namespace is used if it's omitted
+        ns: Option<String>,
+    },
+    /// List the namespaces owned by the key
+    #[clap(name = "namespaces")]
+    Namespaces {},
+    /// Estimate the gas of putting and deleting keys without sending the mutation
+    #[clap(name = "estimate")]
+    Estimate {
+        /// the namespace of keys, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the key value pair to put, e.g. --kv k1=v1
+        #[clap(long = "kv")]
+        kv_list: Vec<String>,
+        /// the key to delete
+        #[clap(long = "del")]
+        del_list: Vec<String>,
+        /// the unit to show the gas in, db3 or tai
+        #[clap(long, value_parser = strings::parse_unit_type, default_value = "db3")]
+        unit: UnitType,
+    },
+    /// Grant an account to write a namespace owned by the key
+    #[clap(name = "grant")]
+    Grant {
+        /// the namespace, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the hex address of the account
+        #[clap(long)]
+        addr: String,
+    },
+    /// Revoke the permission of an account to write a namespace owned by the key
+    #[clap(name = "revoke")]
+    Revoke {
+        /// the namespace, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the hex address of the account
+        #[clap(long)]
+        addr: String,
+    },
+    /// Export all the keys of a namespace to a newline delimited json file
+    #[clap(name = "export-ns")]
+    ExportNs {
+        /// the namespace, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the file to write the hex encoded key value pairs to
+        #[clap(long = "out")]
+        out: String,
+    },
+    /// Import the keys from a file written by export-ns to a namespace
+    #[clap(name = "import-ns")]
+    ImportNs {
+        /// the namespace, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the file with the hex encoded key value pairs
+        #[clap(long = "in")]
+        input: String,
+    },
+    /// Build and sign a put or create database transaction without connecting to the node
+    #[clap(name = "build-tx")]
+    BuildTx {
+        /// the namespace of keys, the default namespace is used if it's omitted
+        #[clap(long)]
+        ns: Option<String>,
+        /// the key value pair to put, e.g. --kv k1=v1
+        #[clap(long = "kv")]
+        kv_list: Vec<String>,
+        /// the key to delete
+        #[clap(long = "del")]
+        del_list: Vec<String>,
+        /// build a create database transaction instead of a mutation
+        #[clap(long, conflicts_with_all = ["ns", "kv_list", "del_list"])]
+        new_db: bool,
+        /// the nonce of the transaction, the current seconds are used if it's omitted
+        #[clap(long)]
+        nonce: Option<u64>,
+        /// the file to write the base64 encoded transaction to, it's printed if omitted
+        #[clap(long = "out")]
+        out: Option<String>,
+    },
+    /// Verify the signatures and payloads of the stored transactions
+    #[clap(name = "verify-tx")]
+    VerifyTx {
+        /// the base64 encoded tx ids
+        #[clap(required = true)]
+        ids: Vec<String>,
+    },
+    /// Verify the signatures and payloads of all the transactions in a range of blocks
+    #[clap(name = "verify-block")]
+    VerifyBlock {
+        /// the first height to verify
+        from_height: u32,
+        /// the last height to verify, only the first block is verified if it's omitted
+        #[clap(long)]
+        to_height: Option<u32>,
+        /// the rpc url of tendermint
+        #[clap(long, default_value = "http://127.0.0.1:26657")]
+        tm_url: String,
+    },
+    /// Print the height, time, app hash and tx count of the new blocks
+    #[clap(name = "blocks")]
+    Blocks {
+        /// keep printing the new blocks until ctrl-c, only the next block is printed if omitted
+        #[clap(long)]
+        follow: bool,
+        /// the websocket url of tendermint
+        #[clap(long, default_value = "ws://127.0.0.1:26657/websocket")]
+        ws_url: String,
+    },
+    /// Broadcast a transaction built by build-tx
+    #[clap(name = "broadcast-tx")]
+    BroadcastTx {
+        /// the file with the base64 encoded transaction
+        file: String,
+    },
+    /// Verify and print the sender and the decoded payload of a transaction built by build-tx
+    /// without connecting to the node
+    #[clap(name = "decode-tx")]
+    DecodeTx {
+        /// the file with the base64 encoded transaction
+        #[clap(long = "in")]
+        input: String,
+    },
+}
+
+/// the number of key value pairs put in one mutation when importing a namespace
+const IMPORT_BATCH_SIZE: usize = 128;
+
+/// a key value pair of the namespace dump, the key and value are hex encoded
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct NsRecord {
+    key: String,
+    value: String,
 }
 
 impl DB3ClientCommand {
@@ -79,7 +399,557 @@ impl DB3ClientCommand {
         }
     }
 
-    fn show_collection(database: &Database) {
+    /// the explicit namespace overrides the default one
+    fn resolve_ns(ns: Option<String>, default_ns: &Option<String>) -> Option<String> {
+        ns.or_else(|| default_ns.clone())
+    }
+
+    /// print the bytes as a string, or as a hex string if asked or they are not valid UTF8
+    fn format_bytes(bytes: &[u8], as_hex: bool) -> String {
+        match std::str::from_utf8(bytes) {
+            Ok(s) if !as_hex => s.to_string(),
+            _ => format!("0x{}", hex::encode(bytes)),
+        }
+    }
+
+    ///
+    /// parse the bytes printed by format_bytes, the input starting with 0x is decoded as hex
+    ///
+    fn parse_bytes(input: &str) -> anyhow::Result<Vec<u8>> {
+        match input.strip_prefix("0x") {
+            Some(hex_str) => {
+                hex::decode(hex_str).map_err(|e| anyhow!("bad hex bytes {input} with error {e}"))
+            }
+            None => Ok(input.as_bytes().to_vec()),
+        }
+    }
+
+    ///
+    /// parse the index from the json style config or the comma separated options, e.g.
+    /// name=idx1,field=age,order=asc
+    ///
+    fn parse_index(input: &str) -> anyhow::Result<Index> {
+        let input = input.trim();
+        if input.starts_with('{') {
+            return serde_json::from_str::<Index>(input)
+                .map_err(|e| anyhow!("bad index {input} with error {e}"));
+        }
+        let mut index = Index {
+            name: "".to_string(),
+            fields: vec![],
+        };
+        for option in input.split(',') {
+            let (key, value) = option
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| anyhow!("bad index option {option}, key=value is expected"))?;
+            match key {
+                "name" => index.name = value.to_string(),
+                "field" => {
+                    if value.is_empty() || value.contains(char::is_whitespace) {
+                        return Err(anyhow!("bad field {value} of index"));
+                    }
+                    index.fields.push(IndexField {
+                        field_path: value.to_string(),
+                        value_mode: None,
+                    });
+                }
+                "order" | "array" => {
+                    let value_mode = match (key, value.to_lowercase().as_str()) {
+                        ("order", "asc") => ValueMode::Order(Order::Ascending.into()),
+                        ("order", "desc") => ValueMode::Order(Order::Descending.into()),
+                        ("array", "contains") => {
+                            ValueMode::ArrayConfig(ArrayConfig::Contains.into())
+                        }
+                        _ => {
+                            return Err(anyhow!(
+                                "bad {key} {value}, the order is asc or desc and the array is contains"
+                            ));
+                        }
+                    };
+                    let field = index
+                        .fields
+                        .last_mut()
+                        .ok_or_else(|| anyhow!("the {key} must follow a field"))?;
+                    if field.value_mode.is_some() {
+                        return Err(anyhow!(
+                            "field {} has more than one order or array option",
+                            field.field_path
+                        ));
+                    }
+                    field.value_mode = Some(value_mode);
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "unknown index option {key}, name, field, order or array is expected"
+                    ));
+                }
+            }
+        }
+        if index.fields.is_empty() {
+            return Err(anyhow!("at least one field is required by the index"));
+        }
+        Ok(index)
+    }
+
+    /// parse the type of a field from the input like age=int64
+    fn parse_field_schema(input: &str) -> anyhow::Result<FieldSchema> {
+        let (field_path, field_type) = input
+            .split_once('=')
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .ok_or_else(|| anyhow!("bad schema {input}, field=type is expected"))?;
+        if field_path.is_empty() || field_path.contains(char::is_whitespace) {
+            return Err(anyhow!("bad field {field_path} of schema"));
+        }
+        let field_type = match field_type.to_lowercase().as_str() {
+            "string" => FieldType::String,
+            "int64" => FieldType::Int64,
+            "double" => FieldType::Double,
+            "bool" => FieldType::Bool,
+            "array" => FieldType::Array,
+            "map" => FieldType::Map,
+            _ => {
+                return Err(anyhow!(
+                    "bad type {field_type}, string, int64, double, bool, array or map is expected"
+                ));
+            }
+        };
+        Ok(FieldSchema {
+            field_path: field_path.to_string(),
+            field_type: field_type.into(),
+        })
+    }
+
+    /// build one mutation from the kv pairs to put and the keys to delete
+    fn build_mutation(ns: &str, kv_list: &[String], del_list: &[String]) -> Option<Mutation> {
+        let mut kv_pairs: Vec<KvPair> = Vec::new();
+        for kv in kv_list {
+            let (key, value) = kv.split_once('=')?;
+            kv_pairs.push(KvPair {
+                key: key.as_bytes().to_vec(),
+                value: value.as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            });
+        }
+        for key in del_list {
+            kv_pairs.push(KvPair {
+                key: key.as_bytes().to_vec(),
+                value: vec![],
+                action: MutationAction::DeleteKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            });
+        }
+        if ns.is_empty() || kv_pairs.is_empty() {
+            return None;
+        }
+        Some(Mutation {
+            ns: ns.as_bytes().to_vec(),
+            kv_pairs,
+            //TODO get from network
+            nonce: Self::current_seconds(),
+            //TODO use config
+            chain_id: ChainId::DevNet.into(),
+            //TODO use config
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        })
+    }
+
+    /// ask the user to confirm and return true if the answer is yes
+    fn confirm<R: BufRead, W: Write>(
+        reader: &mut R,
+        writer: &mut W,
+        prompt: &str,
+    ) -> std::io::Result<bool> {
+        write!(writer, "{prompt} (yes/no): ")?;
+        writer.flush()?;
+        let mut answer = String::new();
+        reader.read_line(&mut answer)?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "yes" | "y"))
+    }
+
+    /// write the kv pair as one line of json
+    fn write_ns_record<W: Write>(writer: &mut W, kv: &KvPair) -> anyhow::Result<()> {
+        let record = NsRecord {
+            key: hex::encode(&kv.key),
+            value: hex::encode(&kv.value),
+        };
+        serde_json::to_writer(&mut *writer, &record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// parse one line of the namespace dump to an insert kv pair
+    fn parse_ns_record(line: &str) -> anyhow::Result<KvPair> {
+        let record: NsRecord = serde_json::from_str(line)?;
+        Ok(KvPair {
+            key: hex::decode(record.key)?,
+            value: hex::decode(record.value)?,
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        })
+    }
+
+    async fn submit_import_batch(
+        ctx: &DB3ClientContext,
+        ns: &str,
+        kv_pairs: Vec<KvPair>,
+        nonce: u64,
+    ) -> anyhow::Result<()> {
+        let mutation = Mutation {
+            ns: ns.as_bytes().to_vec(),
+            kv_pairs,
+            nonce,
+            //TODO use config
+            chain_id: ChainId::DevNet.into(),
+            //TODO use config
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        ctx.mutation_sdk
+            .as_ref()
+            .unwrap()
+            .submit_mutation(&mutation)
+            .await
+            .map_err(|e| anyhow!("fail to send mutation with error {e}"))?;
+        Ok(())
+    }
+
+    /// render the table in the output format, the titles are the first record of csv and tsv
+    fn render_table(table: &Table, output: OutputFormat) -> anyhow::Result<String> {
+        let delimiter = match output {
+            OutputFormat::Table => return Ok(table.to_string()),
+            OutputFormat::Csv => b',',
+            OutputFormat::Tsv => b'\t',
+        };
+        let writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(vec![]);
+        let buf = table
+            .to_csv_writer(writer)?
+            .into_inner()
+            .map_err(|e| anyhow!("fail to render table with error {e}"))?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn print_table(table: &Table, output: OutputFormat) -> anyhow::Result<()> {
+        print!("{}", Self::render_table(table, output)?);
+        Ok(())
+    }
+
+    ///
+    /// decode the base64 encoded tx written by build-tx, the blank chars around it are ignored
+    ///
+    fn parse_tx_text(content: &str) -> anyhow::Result<Vec<u8>> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(anyhow!("no tx was found, a base64 encoded tx is required"));
+        }
+        base64ct::Base64::decode_vec(content)
+            .map_err(|e| anyhow!("bad tx, a base64 encoded tx is required but {e}"))
+    }
+
+    /// verify the signature of the write request and decode its payload
+    fn decode_tx(tx: &[u8]) -> anyhow::Result<(AccountId, PayloadType, serde_json::Value)> {
+        let request = WriteRequest::decode(tx)
+            .map_err(|e| anyhow!("malformed write request with error {e}"))?;
+        let sender = DB3Verifier::verify_with_scheme(
+            request.payload.as_ref(),
+            request.signature.as_ref(),
+            request.signature_scheme,
+        )
+        .map_err(|e| anyhow!("fail to verify the tx with error {e}"))?;
+        let payload_type = PayloadType::from_i32(request.payload_type)
+            .ok_or_else(|| anyhow!("unknown payload type {}", request.payload_type))?;
+        let payload = match payload_type {
+            PayloadType::MutationPayload => {
+                serde_json::to_value(Mutation::decode(request.payload.as_ref())?)?
+            }
+            PayloadType::DatabasePayload => {
+                serde_json::to_value(DatabaseMutation::decode(request.payload.as_ref())?)?
+            }
+            PayloadType::QuerySessionPayload => {
+                serde_json::to_value(QuerySession::decode(request.payload.as_ref())?)?
+            }
+        };
+        Ok((sender, payload_type, payload))
+    }
+
+    ///
+    /// build a mutation or a create database mutation and sign it with the signer, the
+    /// encoded write request is returned in base64
+    ///
+    fn build_offline_tx(
+        signer: &dyn Signer,
+        ns: Option<&str>,
+        kv_list: &[String],
+        del_list: &[String],
+        new_db: bool,
+        nonce: u64,
+    ) -> anyhow::Result<String> {
+        let mut payload: Vec<u8> = Vec::new();
+        let payload_type = if new_db {
+            let dm = DatabaseMutation {
+                meta: Some(BroadcastMeta {
+                    nonce,
+                    //TODO use config
+                    chain_id: ChainId::DevNet.into(),
+                    //TODO use config
+                    chain_role: ChainRole::StorageShardChain.into(),
+                }),
+                collection_mutations: vec![],
+                db_address: vec![],
+                action: DatabaseAction::CreateDb.into(),
+                ns_grant: None,
+            };
+            dm.encode(&mut payload)?;
+            PayloadType::DatabasePayload
+        } else {
+            let ns = ns.ok_or_else(|| anyhow!("no namespace, use --ns or use-ns to set one"))?;
+            let mut mutation = Self::build_mutation(ns, kv_list, del_list)
+                .ok_or_else(|| anyhow!("bad kv pairs, use --kv k1=v1 or --del k1"))?;
+            mutation.nonce = nonce;
+            mutation.encode(&mut payload)?;
+            PayloadType::MutationPayload
+        };
+        let tx = mutation_sdk::build_write_request(signer, payload.as_ref(), payload_type)
+            .map_err(|e| anyhow!("fail to sign the tx with error {e}"))?;
+        Ok(base64ct::Base64::encode_string(tx.as_ref()))
+    }
+
+    ///
+    /// verify the signature and decode the payload of the tx without executing it, the
+    /// reason is returned if the tx does not validate any more
+    ///
+    fn audit_tx(tx: &[u8]) -> Option<String> {
+        Self::decode_tx(tx).err().map(|e| format!("{e}"))
+    }
+
+    /// print the audit results and fail if any tx does not validate
+    fn print_audit_results(
+        results: &[(String, i64, Option<String>)],
+        output: OutputFormat,
+    ) -> anyhow::Result<()> {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(row!["tx id", "height", "result"]);
+        for (id, height, error) in results {
+            table.add_row(row![id, height, error.as_deref().unwrap_or("ok")]);
+        }
+        Self::print_table(&table, output)?;
+        let failed = results.iter().filter(|r| r.2.is_some()).count();
+        if failed > 0 {
+            return Err(anyhow!(
+                "{failed} of {} txs failed the verification",
+                results.len()
+            ));
+        }
+        println!("{} txs verified", results.len());
+        Ok(())
+    }
+
+    /// one line of the blocks command
+    fn format_block(block: &NewBlockEvent) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            block.height, block.time, block.app_hash, block.tx_count
+        )
+    }
+
+    ///
+    /// print the new blocks until ctrl-c or only the next one, the subscription reconnects
+    /// when the websocket is dropped
+    ///
+    async fn print_blocks(ws_url: &str, follow: bool) -> anyhow::Result<()> {
+        let url = ws_url
+            .parse::<Url>()
+            .map_err(|e| anyhow!("invalid websocket url {ws_url} with error {e}"))?;
+        let mut blocks = Box::pin(event_subscriber::subscribe_blocks(url));
+        let mut out = stdout();
+        writeln!(out, "height\ttime\tapp hash\ttxs")?;
+        out.flush()?;
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => break,
+                block = blocks.next() => match block {
+                    Some(block) => {
+                        // flush every line so the output can be piped
+                        writeln!(out, "{}", Self::format_block(&block))?;
+                        out.flush()?;
+                        if !follow {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// grant or revoke the permission of writing the namespace
+    async fn submit_ns_grant(
+        ctx: &DB3ClientContext,
+        ns: Option<String>,
+        addr: &str,
+        action: DatabaseAction,
+    ) -> anyhow::Result<()> {
+        let ns = match Self::resolve_ns(ns, &ctx.default_ns) {
+            Some(ns) => ns,
+            None => {
+                println!("no namespace, use --ns or use-ns to set one");
+                return Ok(());
+            }
+        };
+        let grantee = DB3Address::try_from(addr)
+            .map_err(|e| anyhow!("invalid address {addr} with error {e}"))?;
+        let meta = BroadcastMeta {
+            //TODO get from network
+            nonce: Self::current_seconds(),
+            //TODO use config
+            chain_id: ChainId::DevNet.into(),
+            //TODO use config
+            chain_role: ChainRole::StorageShardChain.into(),
+        };
+        let dm = DatabaseMutation {
+            meta: Some(meta),
+            collection_mutations: vec![],
+            db_address: vec![],
+            action: action.into(),
+            ns_grant: Some(NsGrant {
+                ns: ns.as_bytes().to_vec(),
+                grantee: grantee.to_vec(),
+            }),
+        };
+        let (_, tx_id) = ctx
+            .mutation_sdk
+            .as_ref()
+            .unwrap()
+            .submit_database_mutation(&dm)
+            .await
+            .map_err(|e| anyhow!("fail to submit the grant with error {e}"))?;
+        println!("send {:?} done with tx\n{}", action, tx_id.to_base64());
+        Ok(())
+    }
+
+    /// the fields of the account in the columns of the account table
+    fn account_fields(account: &Account, unit: UnitType) -> Vec<String> {
+        let bills = account.total_bills.clone().unwrap_or_default();
+        let credits = account.credits.clone().unwrap_or_default();
+        vec![
+            strings::units_to_readable_num_str_in(&bills, unit),
+            strings::bytes_to_readable_num_str(account.total_storage_in_bytes),
+            account.total_mutation_count.to_string(),
+            account.total_query_session_count.to_string(),
+            strings::units_to_readable_num_str_in(&credits, unit),
+        ]
+    }
+
+    ///
+    /// the table of the account, the fields changed since the previous account are
+    /// highlighted
+    ///
+    fn account_table(account: &Account, previous: Option<&Account>, unit: UnitType) -> Table {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(row![
+            "total bills",
+            "storage used",
+            "mutation",
+            "querys",
+            "credits"
+        ]);
+        let fields = Self::account_fields(account, unit);
+        let previous_fields = previous.map(|p| Self::account_fields(p, unit));
+        let cells: Vec<Cell> = fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let cell = Cell::new(field.as_str());
+                match &previous_fields {
+                    Some(previous_fields) if &previous_fields[i] != field => cell.style_spec("Fgb"),
+                    _ => cell,
+                }
+            })
+            .collect();
+        table.add_row(Row::new(cells));
+        table
+    }
+
+    fn show_account(account: &Account, unit: UnitType, output: OutputFormat) -> anyhow::Result<()> {
+        Self::print_table(&Self::account_table(account, None, unit), output)
+    }
+
+    ///
+    /// fetch the account on the interval and redraw it in place until ctrl-c
+    ///
+    async fn watch_account(
+        store_sdk: &StoreSDK,
+        addr: &DB3Address,
+        unit: UnitType,
+        interval: u64,
+        output: OutputFormat,
+    ) -> anyhow::Result<()> {
+        let mut previous: Option<Account> = None;
+        loop {
+            let account = store_sdk
+                .get_account(addr)
+                .await
+                .map_err(|e| Self::node_error("get account", e))?;
+            let table = Self::account_table(&account, previous.as_ref(), unit);
+            match output {
+                OutputFormat::Table => {
+                    // clear the screen and move the cursor to the top left
+                    print!("\x1b[2J\x1b[H");
+                    table.printstd();
+                }
+                _ => Self::print_table(&table, output)?,
+            }
+            stdout().flush()?;
+            previous = Some(account);
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => break,
+                _ = tokio::time::sleep(std::time::Duration::from_secs(interval.max(1))) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// sum up the bills of the owner in a block, the gas fees are in tai
+    fn sum_bills(bills: &[Bill], owner: &[u8]) -> (u64, u64, Units) {
+        let mut mutation_count: u64 = 0;
+        let mut query_session_count: u64 = 0;
+        let mut amount: i64 = 0;
+        for bill in bills.iter().filter(|b| b.owner == owner) {
+            match BillType::from_i32(bill.bill_type) {
+                Some(BillType::BillForMutation) => mutation_count += 1,
+                Some(BillType::BillForQuery) => query_session_count += 1,
+                _ => {}
+            }
+            if let Some(fee) = &bill.gas_fee {
+                amount += fee.amount;
+            }
+        }
+        let total = Units {
+            utype: UnitType::Tai.into(),
+            amount,
+        };
+        (mutation_count, query_session_count, total)
+    }
+
+    fn show_collection(database: &Database, output: OutputFormat) -> anyhow::Result<()> {
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
         table.set_titles(row!["name", "index",]);
@@ -92,10 +962,10 @@ impl DB3ClientCommand {
                 .collect();
             table.add_row(row![collection.name, index_str]);
         }
-        table.printstd();
+        Self::print_table(&table, output)
     }
 
-    fn show_database(database: &Database) {
+    fn show_database(database: &Database, output: OutputFormat) -> anyhow::Result<()> {
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
         table.set_titles(row![
@@ -124,40 +994,269 @@ impl DB3ClientCommand {
             tx_list,
             collections
         ]);
-        table.printstd();
+        Self::print_table(&table, output)
+    }
+
+    ///
+    /// the error of a request to db3 node, the status is kept as the source so the callers
+    /// can check its code
+    ///
+    fn node_error(action: &str, status: Status) -> anyhow::Error {
+        let message = format!("fail to {action} with error {status}");
+        anyhow::Error::new(status).context(message)
     }
 
-    pub async fn execute(self, ctx: &mut DB3ClientContext) {
+    ///
+    /// the command only reads from db3 node, so it's safe to run it again after the connection
+    /// is lost. a write may have reached the node before the connection is lost, running it
+    /// again signs a new tx and could apply the write twice
+    ///
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            DB3ClientCommand::ShowAccount { .. }
+                | DB3ClientCommand::ShowDB { .. }
+                | DB3ClientCommand::ListDBs { .. }
+                | DB3ClientCommand::ShowCollection { .. }
+                | DB3ClientCommand::Get { .. }
+                | DB3ClientCommand::ShowTx { .. }
+                | DB3ClientCommand::Count { .. }
+                | DB3ClientCommand::Namespaces { .. }
+                | DB3ClientCommand::Estimate { .. }
+                | DB3ClientCommand::ExportNs { .. }
+                | DB3ClientCommand::VerifyBlock { .. }
+                | DB3ClientCommand::Blocks { .. }
+        )
+    }
+
+    /// the grpc channel cancels the request with this message when the timeout expires
+    fn is_timeout_error(e: &anyhow::Error) -> bool {
+        e.to_string().contains("Timeout expired")
+    }
+
+    /// execute the command and return the error that comes from the db3 node
+    pub async fn execute(self, ctx: &mut DB3ClientContext) -> anyhow::Result<()> {
+        self.do_execute(ctx).await.map_err(|e| {
+            if Self::is_timeout_error(&e) {
+                anyhow!("request timed out, {e}")
+            } else {
+                e
+            }
+        })
+    }
+
+    async fn do_execute(self, ctx: &mut DB3ClientContext) -> anyhow::Result<()> {
+        let output = ctx.output;
         match self {
             DB3ClientCommand::Init {} => {
-                if let Ok(_) = KeyStore::recover_keypair() {
+                if let Ok(_) = KeyStore::recover_keypair(ctx.key_name.as_deref()) {
                     println!("Init key successfully!");
                 }
             }
 
             DB3ClientCommand::ShowKey {} => {
-                if let Ok(ks) = KeyStore::recover_keypair() {
+                if let Ok(ks) = KeyStore::recover_keypair(ctx.key_name.as_deref()) {
                     ks.show_key();
                 } else {
                     println!("no key was found, you can use init command to create a new one");
                 }
             }
+            DB3ClientCommand::WhoAmI {} => {
+                let kp = KeyStore::get_keypair(ctx.key_name.as_deref())
+                    .map_err(|e| anyhow!("fail to load key with error {e}"))?;
+                let id = AccountId::new(DB3Address::from(&kp.public()));
+                let mut table = Table::new();
+                table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                table.set_titles(row!["key", "address", "url", "chain id", "chain role"]);
+                table.add_row(row![
+                    ctx.key_name.as_deref().unwrap_or("default"),
+                    id.to_hex(),
+                    ctx.url,
+                    format!("{:?}", ChainId::DevNet),
+                    format!("{:?}", ChainRole::StorageShardChain)
+                ]);
+                Self::print_table(&table, output)?;
+            }
+            DB3ClientCommand::ShowAccount {
+                json,
+                from_height,
+                blocks,
+                unit,
+                watch,
+                interval,
+            } => {
+                let kp = KeyStore::get_keypair(ctx.key_name.as_deref())
+                    .map_err(|e| anyhow!("fail to load key with error {e}"))?;
+                let addr = DB3Address::from(&kp.public());
+                let store_sdk = ctx.store_sdk.as_mut().unwrap();
+                if watch {
+                    return Self::watch_account(store_sdk, &addr, unit, interval, output).await;
+                }
+                let account = store_sdk
+                    .get_account(&addr)
+                    .await
+                    .map_err(|e| Self::node_error("get account", e))?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&account)?);
+                } else {
+                    Self::show_account(&account, unit, output)?;
+                }
+                if let Some(from_height) = from_height {
+                    let token = store_sdk
+                        .keep_session()
+                        .await
+                        .map_err(|e| Self::node_error("open session", e))?;
+                    let mut table = Table::new();
+                    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                    table.set_titles(row!["height", "mutation", "querys", "bills"]);
+                    for height in from_height..from_height + blocks {
+                        let bills = store_sdk
+                            .get_bills_by_block(height, 0, u64::MAX, &token)
+                            .await
+                            .map_err(|e| Self::node_error("get bills", e))?;
+                        let (mutation_count, query_session_count, total) =
+                            Self::sum_bills(&bills, addr.as_ref());
+                        table.add_row(row![
+                            height,
+                            mutation_count,
+                            query_session_count,
+                            strings::units_to_readable_num_str_in(&total, unit)
+                        ]);
+                    }
+                    Self::print_table(&table, output)?;
+                }
+            }
+            DB3ClientCommand::ListKeys {} => match KeyStore::list_keys() {
+                Ok(keys) => {
+                    let mut table = Table::new();
+                    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                    table.set_titles(row!["name", "address"]);
+                    for (name, id) in keys {
+                        match id {
+                            Ok(id) => table.add_row(row![name, id.to_hex()]),
+                            Err(e) => table.add_row(row![name, format!("invalid key for {e}")]),
+                        };
+                    }
+                    Self::print_table(&table, output)?;
+                }
+                Err(e) => {
+                    println!("fail to list keys with error {e}");
+                }
+            },
+            DB3ClientCommand::ExportKey { out } => {
+                match KeyStore::export_key(ctx.key_name.as_deref(), Path::new(&out)) {
+                    Ok(id) => {
+                        println!("export key of {} to {out}", id.to_hex());
+                    }
+                    Err(e) => {
+                        println!("fail to export key with error {e}");
+                    }
+                }
+            }
+            DB3ClientCommand::PurgeKey { yes } => {
+                let kp = KeyStore::get_keypair(ctx.key_name.as_deref())
+                    .map_err(|e| anyhow!("fail to load key with error {e}"))?;
+                let id = AccountId::new(DB3Address::from(&kp.public()));
+                println!("the key of {} will be destroyed", id.to_hex());
+                if !yes
+                    && !Self::confirm(
+                        &mut std::io::stdin().lock(),
+                        &mut std::io::stdout(),
+                        "are you sure",
+                    )?
+                {
+                    println!("purge key is cancelled");
+                    return Ok(());
+                }
+                match KeyStore::purge_key(ctx.key_name.as_deref()) {
+                    Ok(id) => {
+                        println!("purge key of {} successfully", id.to_hex());
+                    }
+                    Err(e) => {
+                        println!("fail to purge key with error {e}");
+                    }
+                }
+            }
+            DB3ClientCommand::RotateKeyEncryption { plaintext } => {
+                let new_passphrase = if plaintext {
+                    None
+                } else {
+                    let passphrase = rpassword::prompt_password("new passphrase: ")?;
+                    if passphrase.is_empty() {
+                        return Err(anyhow!("the passphrase can not be empty"));
+                    }
+                    if passphrase != rpassword::prompt_password("confirm new passphrase: ")? {
+                        return Err(anyhow!("the passphrases do not match"));
+                    }
+                    Some(passphrase)
+                };
+                match KeyStore::rotate_key_encryption(
+                    ctx.key_name.as_deref(),
+                    new_passphrase.as_deref(),
+                ) {
+                    Ok(id) if plaintext => {
+                        println!("store the key of {} in plaintext", id.to_hex());
+                    }
+                    Ok(id) => {
+                        println!("encrypt the key of {} successfully", id.to_hex());
+                    }
+                    Err(e) => {
+                        println!("fail to rotate the key encryption with error {e}");
+                    }
+                }
+            }
+            DB3ClientCommand::ImportKey { input, force } => {
+                match KeyStore::import_key(ctx.key_name.as_deref(), Path::new(&input), force) {
+                    Ok(id) => {
+                        println!("import key of {} successfully", id.to_hex());
+                    }
+                    Err(e) => {
+                        println!("fail to import key with error {e}");
+                    }
+                }
+            }
             DB3ClientCommand::NewCollection {
                 addr,
                 name,
                 index_list,
+                schema_list,
             } => {
-                //TODO validate the index
                 let index_vec: Vec<Index> = index_list
                     .iter()
-                    .map(|i| serde_json::from_str::<Index>(i.as_str()).unwrap())
-                    .collect();
+                    .map(|i| Self::parse_index(i.as_str()))
+                    .collect::<anyhow::Result<Vec<Index>>>()?;
+                let schema: Vec<FieldSchema> = schema_list
+                    .iter()
+                    .map(|f| Self::parse_field_schema(f.as_str()))
+                    .collect::<anyhow::Result<Vec<FieldSchema>>>()?;
                 let collection = CollectionMutation {
                     index: index_vec.to_owned(),
                     collection_id: name.to_string(),
+                    schema,
                 };
-                //TODO check database id and collection name
+                //TODO check database id
                 let db_id = DbId::try_from(addr.as_str()).unwrap();
+                match ctx
+                    .store_sdk
+                    .as_mut()
+                    .unwrap()
+                    .get_database(addr.as_ref())
+                    .await
+                {
+                    Ok(Some(database)) => {
+                        if database.collections.iter().any(|c| c.name == name) {
+                            println!("collection {name} already exists in database {addr}");
+                            return Ok(());
+                        }
+                    }
+                    Ok(None) => {
+                        println!("no database with target address");
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        return Err(Self::node_error("show database", e));
+                    }
+                }
                 let meta = BroadcastMeta {
                     //TODO get from network
                     nonce: Self::current_seconds(),
@@ -171,17 +1270,21 @@ impl DB3ClientCommand {
                     collection_mutations: vec![collection],
                     db_address: db_id.as_ref().to_vec(),
                     action: DatabaseAction::AddCollection.into(),
+                    ns_grant: None,
                 };
-                if let Ok((_, tx_id)) = ctx
+                match ctx
                     .mutation_sdk
                     .as_ref()
                     .unwrap()
                     .submit_database_mutation(&dm)
                     .await
                 {
-                    println!("send add collection done with tx\n{}", tx_id.to_base64());
-                } else {
-                    println!("fail to add collection");
+                    Ok((_, tx_id)) => {
+                        println!("send add collection done with tx\n{}", tx_id.to_base64());
+                    }
+                    Err(e) => {
+                        return Err(anyhow!("fail to add collection with error {e}"));
+                    }
                 }
             }
             DB3ClientCommand::ShowCollection { addr } => {
@@ -193,13 +1296,13 @@ impl DB3ClientCommand {
                     .await
                 {
                     Ok(Some(database)) => {
-                        Self::show_collection(&database);
+                        Self::show_collection(&database, output)?;
                     }
                     Ok(None) => {
                         println!("no collection with target address");
                     }
                     Err(e) => {
-                        println!("fail to show collections with error {e}");
+                        return Err(Self::node_error("show collections", e));
                     }
                 }
             }
@@ -213,21 +1316,499 @@ impl DB3ClientCommand {
                     .await
                 {
                     Ok(Some(database)) => {
-                        Self::show_database(&database);
+                        Self::show_database(&database, output)?;
                     }
                     Ok(None) => {
                         println!("no database with target address");
                     }
                     Err(e) => {
-                        println!("fail to show database with error {e}");
+                        return Err(Self::node_error("show database", e));
+                    }
+                }
+            }
+
+            DB3ClientCommand::ListDBs { addr } => {
+                let owner = match addr {
+                    Some(addr) => DB3Address::try_from(addr.as_str())
+                        .map_err(|e| anyhow!("invalid owner address {addr} with error {e}"))?,
+                    None => {
+                        let kp = KeyStore::get_keypair(ctx.key_name.as_deref())
+                            .map_err(|e| anyhow!("fail to load key with error {e}"))?;
+                        DB3Address::from(&kp.public())
                     }
+                };
+                let db_ids = ctx
+                    .store_sdk
+                    .as_mut()
+                    .unwrap()
+                    .list_databases_by_owner(&owner)
+                    .await
+                    .map_err(|e| Self::node_error("list databases", e))?;
+                let mut table = Table::new();
+                table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                table.set_titles(row!["database address"]);
+                for db_id in db_ids.iter() {
+                    table.add_row(row![db_id.to_hex()]);
                 }
+                Self::print_table(&table, output)?;
             }
 
-            DB3ClientCommand::NewDB {} => {
+            DB3ClientCommand::UseNs { ns } => {
+                println!("use namespace {ns}");
+                ctx.default_ns = Some(ns);
+            }
+            DB3ClientCommand::Get {
+                ns,
+                keys,
+                with_metadata,
+                hex,
+                prefix: Some(prefix),
+                limit,
+            } => {
+                let ns = match Self::resolve_ns(ns, &ctx.default_ns) {
+                    Some(ns) => ns,
+                    None => {
+                        println!("no namespace, use --ns or use-ns to set one");
+                        return Ok(());
+                    }
+                };
+                if with_metadata {
+                    println!("--with-metadata is ignored with --prefix");
+                }
+                let prefix_bytes = Self::parse_bytes(prefix.as_str())?;
+                let store_sdk = ctx.store_sdk.as_mut().unwrap();
+                let token = store_sdk
+                    .keep_session()
+                    .await
+                    .map_err(|e| Self::node_error("open session", e))?;
+                let kv_pairs = store_sdk
+                    .scan_prefix(
+                        ns.as_bytes(),
+                        prefix_bytes.as_ref(),
+                        limit.unwrap_or(0),
+                        &token,
+                    )
+                    .await
+                    .map_err(|e| Self::node_error("scan prefix", e))?;
+                if kv_pairs.is_empty() {
+                    println!("no keys with prefix {prefix} in namespace {ns}");
+                    return Ok(());
+                }
+                let mut table = Table::new();
+                table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                table.set_titles(row!["key", "value"]);
+                for kv in kv_pairs.iter() {
+                    table.add_row(row![
+                        Self::format_bytes(kv.key.as_ref(), hex),
+                        Self::format_bytes(kv.value.as_ref(), hex)
+                    ]);
+                }
+                Self::print_table(&table, output)?;
+            }
+            DB3ClientCommand::Get {
+                ns,
+                keys,
+                with_metadata,
+                hex,
+                ..
+            } => {
+                let ns = match Self::resolve_ns(ns, &ctx.default_ns) {
+                    Some(ns) => ns,
+                    None => {
+                        println!("no namespace, use --ns or use-ns to set one");
+                        return Ok(());
+                    }
+                };
+                if keys.is_empty() {
+                    println!("bad input, at least one key is required");
+                    return Ok(());
+                }
+                let store_sdk = ctx.store_sdk.as_mut().unwrap();
+                let token = store_sdk
+                    .keep_session()
+                    .await
+                    .map_err(|e| Self::node_error("open session", e))?;
+                let keys: Vec<Vec<u8>> = keys.iter().map(|k| k.as_bytes().to_vec()).collect();
+                let result = store_sdk
+                    .batch_get_entries(ns.as_bytes(), keys, &token, with_metadata)
+                    .await;
+                match result {
+                    Ok(entries) => {
+                        let mut table = Table::new();
+                        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                        if with_metadata {
+                            table.set_titles(row!["key", "value", "height"]);
+                        } else {
+                            table.set_titles(row!["key", "value"]);
+                        }
+                        for entry in entries.iter() {
+                            let key = Self::format_bytes(entry.key.as_ref(), hex);
+                            // tell the missing key from the one with an empty value
+                            let value = match &entry.value {
+                                Some(value) => Self::format_bytes(value.as_ref(), hex),
+                                None => "<missing>".to_string(),
+                            };
+                            if with_metadata {
+                                let height = entry
+                                    .height
+                                    .map(|h| h.to_string())
+                                    .unwrap_or_else(|| "-".to_string());
+                                table.add_row(row![key, value, height]);
+                            } else {
+                                table.add_row(row![key, value]);
+                            }
+                        }
+                        Self::print_table(&table, output)?;
+                    }
+                    Err(e) => {
+                        return Err(Self::node_error("get keys", e));
+                    }
+                }
+            }
+            DB3ClientCommand::ShowTx { id } => {
+                let tx_id = TxId::try_from_base64(id.as_str())
+                    .map_err(|_| anyhow!("bad tx id {id}, a base64 tx id is required"))?;
+                let store_sdk = ctx.store_sdk.as_ref().unwrap();
+                let response = match store_sdk.get_tx(&tx_id).await {
+                    Ok(response) => response,
+                    Err(e) if e.code() == Code::NotFound => {
+                        println!("no transaction with id {id}");
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        return Err(Self::node_error("get tx", e));
+                    }
+                };
+                let (sender, payload_type, payload) = Self::decode_tx(response.tx.as_ref())?;
+                let mut table = Table::new();
+                table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                table.set_titles(row!["tx id", "height", "sender", "payload type"]);
+                table.add_row(row![
+                    id,
+                    response.height,
+                    sender.to_hex(),
+                    format!("{:?}", payload_type)
+                ]);
+                Self::print_table(&table, output)?;
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+            }
+            DB3ClientCommand::Count { ns } => {
+                let ns = match Self::resolve_ns(ns, &ctx.default_ns) {
+                    Some(ns) => ns,
+                    None => {
+                        println!("no namespace, use count <ns> or use-ns to set one");
+                        return Ok(());
+                    }
+                };
+                let store_sdk = ctx.store_sdk.as_mut().unwrap();
+                match store_sdk.count_keys(ns.as_bytes()).await {
+                    Ok(response) => {
+                        println!(
+                            "{} keys, {} bytes in namespace {}",
+                            response.key_count, response.total_bytes, ns
+                        );
+                    }
+                    Err(e) => {
+                        return Err(Self::node_error("count keys", e));
+                    }
+                }
+            }
+            DB3ClientCommand::Namespaces {} => {
+                let store_sdk = ctx.store_sdk.as_mut().unwrap();
+                let namespaces = store_sdk
+                    .list_namespaces()
+                    .await
+                    .map_err(|e| Self::node_error("list namespaces", e))?;
+                let mut table = Table::new();
+                table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                table.set_titles(row!["namespace", "keys", "bytes"]);
+                for stat in namespaces.iter() {
+                    table.add_row(row![
+                        Self::format_bytes(&stat.ns, false),
+                        stat.key_count,
+                        strings::bytes_to_readable_num_str(stat.total_bytes)
+                    ]);
+                }
+                Self::print_table(&table, output)?;
+            }
+            DB3ClientCommand::Estimate {
+                ns,
+                kv_list,
+                del_list,
+                unit,
+            } => {
+                let ns = Self::resolve_ns(ns, &ctx.default_ns).unwrap_or_default();
+                let mutation = match Self::build_mutation(&ns, &kv_list, &del_list) {
+                    Some(m) => m,
+                    None => {
+                        println!("bad input, a namespace and at least one --kv key=value or --del key are required");
+                        return Ok(());
+                    }
+                };
+                let buf = mutation.encode_to_vec();
+                match ctx
+                    .store_sdk
+                    .as_ref()
+                    .unwrap()
+                    .estimate_gas(PayloadType::MutationPayload, buf.as_ref())
+                    .await
+                {
+                    Ok(gas) => {
+                        println!(
+                            "the estimated gas is {}",
+                            strings::units_to_readable_num_str_in(&gas, unit)
+                        );
+                    }
+                    Err(e) => {
+                        return Err(Self::node_error("estimate gas", e));
+                    }
+                }
+            }
+            DB3ClientCommand::ExportNs { ns, out } => {
+                let ns = match Self::resolve_ns(ns, &ctx.default_ns) {
+                    Some(ns) => ns,
+                    None => {
+                        println!("no namespace, use --ns or use-ns to set one");
+                        return Ok(());
+                    }
+                };
+                let store_sdk = ctx.store_sdk.as_mut().unwrap();
+                let token = store_sdk
+                    .keep_session()
+                    .await
+                    .map_err(|e| anyhow!("fail to open session with error {e}"))?;
+                let kv_pairs = store_sdk
+                    .scan_ns(ns.as_bytes(), &token)
+                    .await
+                    .map_err(|e| Self::node_error("scan namespace", e))?;
+                let mut writer = BufWriter::new(File::create(&out)?);
+                for kv in kv_pairs.iter() {
+                    Self::write_ns_record(&mut writer, kv)?;
+                }
+                writer.flush()?;
+                println!("export {} keys of namespace {ns} to {out}", kv_pairs.len());
+            }
+            DB3ClientCommand::ImportNs { ns, input } => {
+                let ns = match Self::resolve_ns(ns, &ctx.default_ns) {
+                    Some(ns) => ns,
+                    None => {
+                        println!("no namespace, use --ns or use-ns to set one");
+                        return Ok(());
+                    }
+                };
+                let reader = BufReader::new(File::open(&input)?);
+                let mut nonce = Self::current_seconds();
+                let mut total: usize = 0;
+                let mut kv_pairs: Vec<KvPair> = Vec::new();
+                for (i, line) in reader.lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let kv = Self::parse_ns_record(&line)
+                        .map_err(|e| anyhow!("bad record at line {} with error {e}", i + 1))?;
+                    kv_pairs.push(kv);
+                    if kv_pairs.len() == IMPORT_BATCH_SIZE {
+                        total += kv_pairs.len();
+                        Self::submit_import_batch(ctx, &ns, std::mem::take(&mut kv_pairs), nonce)
+                            .await?;
+                        nonce += 1;
+                    }
+                }
+                if !kv_pairs.is_empty() {
+                    total += kv_pairs.len();
+                    Self::submit_import_batch(ctx, &ns, kv_pairs, nonce).await?;
+                }
+                println!("import {total} keys to namespace {ns}");
+            }
+            DB3ClientCommand::Put {
+                ns,
+                kv_list,
+                del_list,
+                gas_price,
+                no_compress,
+                ttl,
+            } => {
+                let ns = Self::resolve_ns(ns, &ctx.default_ns).unwrap_or_default();
+                let mut mutation = match Self::build_mutation(&ns, &kv_list, &del_list) {
+                    Some(m) => m,
+                    None => {
+                        println!("bad input, a namespace and at least one --kv key=value or --del key are required");
+                        return Ok(());
+                    }
+                };
+                if let Some(ttl) = ttl {
+                    for kv in mutation.kv_pairs.iter_mut() {
+                        if kv.action == MutationAction::InsertKv as i32 {
+                            kv.ttl = ttl;
+                        }
+                    }
+                }
+                mutation.gas_price = match gas_price {
+                    Some(price) => Some(price),
+                    None => match ctx.store_sdk.as_ref() {
+                        Some(store_sdk) => store_sdk.get_min_gas_price().await.ok(),
+                        None => None,
+                    },
+                };
+                if !no_compress {
+                    mutation = mutation_sdk::compress_mutation(
+                        &mutation,
+                        value_codec::DEFAULT_COMPRESS_THRESHOLD,
+                    );
+                }
+                match ctx
+                    .mutation_sdk
+                    .as_ref()
+                    .unwrap()
+                    .submit_mutation(&mutation)
+                    .await
+                {
+                    Ok(tx_id) => {
+                        println!("send mutation done with tx\n{}", tx_id.to_base64());
+                    }
+                    Err(e) => {
+                        return Err(anyhow!("fail to send mutation with error {e}"));
+                    }
+                }
+            }
+
+            DB3ClientCommand::Grant { ns, addr } => {
+                Self::submit_ns_grant(ctx, ns, &addr, DatabaseAction::GrantNs).await?;
+            }
+            DB3ClientCommand::Revoke { ns, addr } => {
+                Self::submit_ns_grant(ctx, ns, &addr, DatabaseAction::RevokeNs).await?;
+            }
+            DB3ClientCommand::BuildTx {
+                ns,
+                kv_list,
+                del_list,
+                new_db,
+                nonce,
+                out,
+            } => {
+                let kp = KeyStore::get_keypair(ctx.key_name.as_deref()).map_err(|e| {
+                    anyhow!("no key was found with error {e}, use init to create one")
+                })?;
+                let signer = Db3MultiSchemeSigner::new(kp);
+                let ns = Self::resolve_ns(ns, &ctx.default_ns);
+                let tx = Self::build_offline_tx(
+                    &signer,
+                    ns.as_deref(),
+                    &kv_list,
+                    &del_list,
+                    new_db,
+                    nonce.unwrap_or_else(Self::current_seconds),
+                )?;
+                match out {
+                    Some(path) => {
+                        std::fs::write(&path, format!("{tx}\n"))?;
+                        println!("write the tx to {path}");
+                    }
+                    None => println!("{tx}"),
+                }
+            }
+            DB3ClientCommand::VerifyTx { ids } => {
+                let store_sdk = ctx.store_sdk.as_ref().unwrap();
+                let mut results: Vec<(String, i64, Option<String>)> = Vec::new();
+                for id in ids {
+                    let tx_id = TxId::try_from_base64(id.as_str())
+                        .map_err(|_| anyhow!("bad tx id {id}, a base64 tx id is required"))?;
+                    match store_sdk.get_tx(&tx_id).await {
+                        Ok(response) => {
+                            let error = Self::audit_tx(response.tx.as_ref());
+                            results.push((id, response.height, error));
+                        }
+                        Err(e) if e.code() == Code::NotFound => {
+                            results.push((id, 0, Some("not found".to_string())));
+                        }
+                        Err(e) => {
+                            return Err(Self::node_error("get tx", e));
+                        }
+                    }
+                }
+                Self::print_audit_results(&results, output)?;
+            }
+            DB3ClientCommand::VerifyBlock {
+                from_height,
+                to_height,
+                tm_url,
+            } => {
+                let client = HttpClient::new(tm_url.as_str())
+                    .map_err(|e| anyhow!("invalid tendermint url {tm_url} with error {e}"))?;
+                let to_height = to_height.unwrap_or(from_height);
+                let mut results: Vec<(String, i64, Option<String>)> = Vec::new();
+                for height in from_height..=to_height {
+                    let response = client
+                        .block(height)
+                        .await
+                        .map_err(|e| anyhow!("fail to get block {height} with error {e}"))?;
+                    for tx in response.block.data.iter() {
+                        let tx_id = TxId::from(tx.as_ref());
+                        results.push((
+                            tx_id.to_base64(),
+                            height as i64,
+                            Self::audit_tx(tx.as_ref()),
+                        ));
+                    }
+                }
+                Self::print_audit_results(&results, output)?;
+            }
+            DB3ClientCommand::Blocks { follow, ws_url } => {
+                Self::print_blocks(ws_url.as_str(), follow).await?;
+            }
+            DB3ClientCommand::BroadcastTx { file } => {
+                let content = std::fs::read_to_string(&file)?;
+                let tx = Self::parse_tx_text(content.as_str())
+                    .map_err(|e| anyhow!("fail to read {file} for {e}"))?;
+                // reject the malformed tx before sending it
+                Self::decode_tx(tx.as_ref())?;
+                let tx_id = ctx
+                    .mutation_sdk
+                    .as_ref()
+                    .unwrap()
+                    .broadcast_tx(tx.as_ref())
+                    .await
+                    .map_err(|e| anyhow!("fail to broadcast the tx with error {e}"))?;
+                println!("send tx done with tx\n{}", tx_id.to_base64());
+            }
+            DB3ClientCommand::DecodeTx { input } => {
+                let content = std::fs::read_to_string(&input)
+                    .map_err(|e| anyhow!("fail to read {input} with error {e}"))?;
+                let tx = Self::parse_tx_text(content.as_str())
+                    .map_err(|e| anyhow!("fail to read {input} for {e}"))?;
+                let (sender, payload_type, payload) = Self::decode_tx(tx.as_ref())
+                    .map_err(|e| anyhow!("invalid tx in {input} for {e}"))?;
+                let mut table = Table::new();
+                table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                table.set_titles(row!["tx id", "sender", "payload type"]);
+                table.add_row(row![
+                    TxId::from(tx.as_ref()).to_base64(),
+                    sender.to_hex(),
+                    format!("{:?}", payload_type)
+                ]);
+                Self::print_table(&table, output)?;
+                println!("{}", serde_json::to_string_pretty(&payload)?);
+            }
+            DB3ClientCommand::NewDB { nonce, dry_run } => {
+                //TODO get from network
+                let nonce = nonce.unwrap_or_else(Self::current_seconds);
+                if dry_run {
+                    let db_id = ctx
+                        .mutation_sdk
+                        .as_ref()
+                        .unwrap()
+                        .derive_db_id(nonce)
+                        .map_err(|e| anyhow!("fail to derive the database address {e}"))?;
+                    let mut table = Table::new();
+                    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                    table.set_titles(row!["database address", "nonce"]);
+                    table.add_row(row![db_id.to_hex(), nonce]);
+                    Self::print_table(&table, output)?;
+                    return Ok(());
+                }
                 let meta = BroadcastMeta {
-                    //TODO get from network
-                    nonce: Self::current_seconds(),
+                    nonce,
                     //TODO use config
                     chain_id: ChainId::DevNet.into(),
                     //TODO use config
@@ -238,23 +1819,623 @@ impl DB3ClientCommand {
                     collection_mutations: vec![],
                     db_address: vec![],
                     action: DatabaseAction::CreateDb.into(),
+                    ns_grant: None,
                 };
-                if let Ok((db_id, tx_id)) = ctx
+                match ctx
                     .mutation_sdk
                     .as_ref()
                     .unwrap()
                     .submit_database_mutation(&dm)
                     .await
                 {
-                    let mut table = Table::new();
-                    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-                    table.set_titles(row!["database address", "transaction id"]);
-                    table.add_row(row![db_id.to_hex(), tx_id.to_base64()]);
-                    table.printstd();
-                } else {
-                    println!("fail to create database");
+                    Ok((db_id, tx_id)) => {
+                        let mut table = Table::new();
+                        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                        table.set_titles(row!["database address", "transaction id"]);
+                        table.add_row(row![db_id.to_hex(), tx_id.to_base64()]);
+                        Self::print_table(&table, output)?;
+                    }
+                    Err(e) => {
+                        return Err(anyhow!("fail to create database with error {e}"));
+                    }
                 }
             }
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_build_mixed_mutation() {
+        let kv_list = vec!["k1=v1".to_string(), "k2=a=b".to_string()];
+        let del_list = vec!["k3".to_string()];
+        let mutation = DB3ClientCommand::build_mutation("ns1", &kv_list, &del_list).unwrap();
+        assert_eq!(3, mutation.kv_pairs.len());
+        assert_eq!("k2".as_bytes(), mutation.kv_pairs[1].key.as_slice());
+        assert_eq!("a=b".as_bytes(), mutation.kv_pairs[1].value.as_slice());
+        let action: i32 = MutationAction::DeleteKv.into();
+        assert_eq!(action, mutation.kv_pairs[2].action);
+    }
+
+    #[test]
+    fn it_write_and_parse_ns_record() {
+        let kv = KvPair {
+            key: vec![0xff, 0x00],
+            value: "v1".as_bytes().to_vec(),
+            action: MutationAction::Nonce.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mut buf: Vec<u8> = Vec::new();
+        DB3ClientCommand::write_ns_record(&mut buf, &kv).unwrap();
+        let line = String::from_utf8(buf).unwrap();
+        assert_eq!("{\"key\":\"ff00\",\"value\":\"7631\"}\n", line);
+        let parsed = DB3ClientCommand::parse_ns_record(line.trim()).unwrap();
+        assert_eq!(kv.key, parsed.key);
+        assert_eq!(kv.value, parsed.value);
+        let action: i32 = MutationAction::InsertKv.into();
+        assert_eq!(action, parsed.action);
+        assert!(DB3ClientCommand::parse_ns_record("{\"key\":\"zz\",\"value\":\"\"}").is_err());
+    }
+
+    #[test]
+    fn it_resolve_ns() {
+        let default_ns = Some("ns1".to_string());
+        assert_eq!(
+            Some("ns1".to_string()),
+            DB3ClientCommand::resolve_ns(None, &default_ns)
+        );
+        assert_eq!(
+            Some("ns2".to_string()),
+            DB3ClientCommand::resolve_ns(Some("ns2".to_string()), &default_ns)
+        );
+        assert_eq!(None, DB3ClientCommand::resolve_ns(None, &None));
+    }
+
+    #[test]
+    fn it_parse_put_with_default_ns() {
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "put", "--kv", "k1=v1"]).unwrap();
+        match cmd {
+            DB3ClientCommand::Put { ns, .. } => {
+                assert_eq!(None, ns);
+            }
+            _ => assert!(false),
+        }
+        let cmd =
+            DB3ClientCommand::try_parse_from(["db3", "get", "--ns", "ns2", "k1", "k2"]).unwrap();
+        match cmd {
+            DB3ClientCommand::Get {
+                ns,
+                keys,
+                with_metadata,
+                hex,
+                ..
+            } => {
+                assert_eq!(Some("ns2".to_string()), ns);
+                assert_eq!(2, keys.len());
+                assert!(!with_metadata);
+                assert!(!hex);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_sum_bills_of_owner() {
+        let bill = |owner: u8, bill_type: BillType, amount: i64| Bill {
+            gas_fee: Some(Units {
+                utype: UnitType::Tai.into(),
+                amount,
+            }),
+            block_height: 1,
+            bill_id: 0,
+            bill_type: bill_type.into(),
+            time: 0,
+            bill_target_id: vec![],
+            owner: vec![owner; 20],
+            query_addr: vec![],
+        };
+        let bills = vec![
+            bill(1, BillType::BillForMutation, 10),
+            bill(1, BillType::BillForQuery, 5),
+            bill(2, BillType::BillForMutation, 100),
+            bill(1, BillType::BillForMutation, 20),
+        ];
+        let (mutation_count, query_session_count, total) =
+            DB3ClientCommand::sum_bills(&bills, &[1; 20]);
+        assert_eq!(2, mutation_count);
+        assert_eq!(1, query_session_count);
+        assert_eq!(35, total.amount);
+        let cmd = DB3ClientCommand::try_parse_from([
+            "db3",
+            "show-account",
+            "--json",
+            "--from-height",
+            "3",
+        ])
+        .unwrap();
+        match cmd {
+            DB3ClientCommand::ShowAccount {
+                json,
+                from_height,
+                blocks,
+                unit,
+                watch,
+                interval,
+            } => {
+                assert!(json);
+                assert_eq!(Some(3), from_height);
+                assert_eq!(10, blocks);
+                assert_eq!(UnitType::Db3, unit);
+                assert!(!watch);
+                assert_eq!(5, interval);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_highlight_changed_account_fields() {
+        let previous = Account {
+            total_bills: Some(Units {
+                utype: UnitType::Tai.into(),
+                amount: 100,
+            }),
+            total_storage_in_bytes: 1024,
+            total_mutation_count: 1,
+            total_query_session_count: 0,
+            credits: Some(Units {
+                utype: UnitType::Db3.into(),
+                amount: 10,
+            }),
+            nonce: 0,
+            bill_next_id: 0,
+            seen_nonces: 0,
+        };
+        let account = Account {
+            total_bills: Some(Units {
+                utype: UnitType::Tai.into(),
+                amount: 200,
+            }),
+            total_mutation_count: 2,
+            ..previous.clone()
+        };
+        let fields = DB3ClientCommand::account_fields(&account, UnitType::Tai);
+        let previous_fields = DB3ClientCommand::account_fields(&previous, UnitType::Tai);
+        let changed: Vec<bool> = fields
+            .iter()
+            .zip(previous_fields.iter())
+            .map(|(f, p)| f != p)
+            .collect();
+        assert_eq!(vec![true, false, true, false, false], changed);
+        let table = DB3ClientCommand::account_table(&account, Some(&previous), UnitType::Tai);
+        let row = table.get_row(0).unwrap();
+        assert_eq!(fields.len(), row.len());
+        assert_eq!("2", row.get_cell(2).unwrap().get_content());
+        let cmd =
+            DB3ClientCommand::try_parse_from(["db3", "show-account", "--watch", "--interval", "2"])
+                .unwrap();
+        match cmd {
+            DB3ClientCommand::ShowAccount {
+                watch, interval, ..
+            } => {
+                assert!(watch);
+                assert_eq!(2, interval);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_build_mutation_with_bad_input() {
+        let kv_list = vec!["k1".to_string()];
+        assert!(DB3ClientCommand::build_mutation("ns1", &kv_list, &[]).is_none());
+        assert!(DB3ClientCommand::build_mutation("ns1", &[], &[]).is_none());
+        let del_list = vec!["k1".to_string()];
+        assert!(DB3ClientCommand::build_mutation("", &[], &del_list).is_none());
+    }
+
+    #[test]
+    fn it_render_table_as_csv_and_tsv() {
+        let mut table = Table::new();
+        table.set_titles(row!["key", "value"]);
+        table.add_row(row!["k1", "v1,v2"]);
+        let csv = DB3ClientCommand::render_table(&table, OutputFormat::Csv).unwrap();
+        assert_eq!("key,value\nk1,\"v1,v2\"\n", csv);
+        let tsv = DB3ClientCommand::render_table(&table, OutputFormat::Tsv).unwrap();
+        assert_eq!("key\tvalue\nk1\tv1,v2\n", tsv);
+        let text = DB3ClientCommand::render_table(&table, OutputFormat::Table).unwrap();
+        assert!(text.contains("v1,v2"));
+        assert_eq!(OutputFormat::Table, OutputFormat::default());
+    }
+
+    #[test]
+    fn it_format_non_utf8_bytes() {
+        let value: Vec<u8> = vec![0xff, 0xfe, 0x00, 0x61];
+        assert!(std::str::from_utf8(value.as_ref()).is_err());
+        assert_eq!("0xfffe0061", DB3ClientCommand::format_bytes(&value, false));
+        assert_eq!("0xfffe0061", DB3ClientCommand::format_bytes(&value, true));
+        assert_eq!("k1", DB3ClientCommand::format_bytes(b"k1", false));
+        assert_eq!("0x6b31", DB3ClientCommand::format_bytes(b"k1", true));
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "get", "--hex", "k1"]).unwrap();
+        match cmd {
+            DB3ClientCommand::Get { hex, keys, .. } => {
+                assert!(hex);
+                assert_eq!(vec!["k1".to_string()], keys);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_parse_get_prefix() {
+        let cmd = DB3ClientCommand::try_parse_from([
+            "db3", "get", "--ns", "ns1", "--prefix", "user/", "--limit", "10",
+        ])
+        .unwrap();
+        match cmd {
+            DB3ClientCommand::Get {
+                keys,
+                prefix,
+                limit,
+                ..
+            } => {
+                assert!(keys.is_empty());
+                assert_eq!(Some("user/".to_string()), prefix);
+                assert_eq!(Some(10), limit);
+            }
+            _ => assert!(false),
+        }
+        assert!(DB3ClientCommand::try_parse_from(["db3", "get", "--prefix", "u", "k1"]).is_err());
+        assert!(DB3ClientCommand::try_parse_from(["db3", "get", "--limit", "1", "k1"]).is_err());
+        assert_eq!(
+            vec![0xff, 0x00],
+            DB3ClientCommand::parse_bytes("0xff00").unwrap()
+        );
+        assert_eq!(
+            b"user/".to_vec(),
+            DB3ClientCommand::parse_bytes("user/").unwrap()
+        );
+        assert!(DB3ClientCommand::parse_bytes("0xzz").is_err());
+    }
+
+    #[test]
+    fn it_build_offline_tx() {
+        use db3_crypto::key_derive;
+        use db3_crypto::signature_scheme::SignatureScheme;
+        let seed: [u8; 32] = [0; 32];
+        let (address, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::ED25519).unwrap();
+        let signer = Db3MultiSchemeSigner::new(kp);
+        let kv_list = vec!["k1=v1".to_string()];
+        let tx = DB3ClientCommand::build_offline_tx(&signer, Some("ns1"), &kv_list, &[], false, 7)
+            .unwrap();
+        let tx = base64ct::Base64::decode_vec(tx.as_str()).unwrap();
+        let (sender, payload_type, value) = DB3ClientCommand::decode_tx(tx.as_ref()).unwrap();
+        assert_eq!(AccountId::new(address).to_hex(), sender.to_hex());
+        assert_eq!(PayloadType::MutationPayload, payload_type);
+        assert_eq!(7, value["nonce"].as_u64().unwrap());
+        let tx = DB3ClientCommand::build_offline_tx(&signer, None, &[], &[], true, 7).unwrap();
+        let tx = base64ct::Base64::decode_vec(tx.as_str()).unwrap();
+        let (_, payload_type, _) = DB3ClientCommand::decode_tx(tx.as_ref()).unwrap();
+        assert_eq!(PayloadType::DatabasePayload, payload_type);
+        // a mutation requires a namespace
+        assert!(
+            DB3ClientCommand::build_offline_tx(&signer, None, &kv_list, &[], false, 7).is_err()
+        );
+        let cmd =
+            DB3ClientCommand::try_parse_from(["db3", "build-tx", "--new-db", "--kv", "k1=v1"]);
+        assert!(cmd.is_err());
+    }
+
+    #[test]
+    fn it_decode_offline_tx() {
+        use db3_crypto::key_derive;
+        use db3_crypto::signature_scheme::SignatureScheme;
+        let seed: [u8; 32] = [0; 32];
+        let (address, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::ED25519).unwrap();
+        let signer = Db3MultiSchemeSigner::new(kp);
+        let tx = DB3ClientCommand::build_offline_tx(&signer, None, &[], &[], true, 7).unwrap();
+        // the tx file written by build-tx ends with a new line
+        let bytes = DB3ClientCommand::parse_tx_text(format!("{tx}\n").as_str()).unwrap();
+        let (sender, payload_type, payload) = DB3ClientCommand::decode_tx(&bytes).unwrap();
+        assert_eq!(AccountId::new(address).to_hex(), sender.to_hex());
+        assert_eq!(PayloadType::DatabasePayload, payload_type);
+        assert_eq!(7, payload["meta"]["nonce"]);
+        // flip a byte of the signed payload
+        let mut request = WriteRequest::decode(bytes.as_ref()).unwrap();
+        let last = request.payload.len() - 1;
+        request.payload[last] ^= 0x01;
+        let mut tampered: Vec<u8> = Vec::new();
+        request.encode(&mut tampered).unwrap();
+        assert!(DB3ClientCommand::decode_tx(&tampered).is_err());
+        assert!(DB3ClientCommand::parse_tx_text("not base64!").is_err());
+        assert!(DB3ClientCommand::parse_tx_text(" \n").is_err());
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "decode-tx", "--in", "signed.b64"]);
+        match cmd.unwrap() {
+            DB3ClientCommand::DecodeTx { input } => assert_eq!("signed.b64", input),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_format_block() {
+        let block = NewBlockEvent {
+            height: 10,
+            block_hash: "AB".to_string(),
+            time: "2023-01-01T00:00:00Z".to_string(),
+            app_hash: "CD".to_string(),
+            tx_count: 2,
+        };
+        assert_eq!(
+            "10\t2023-01-01T00:00:00Z\tCD\t2",
+            DB3ClientCommand::format_block(&block)
+        );
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "blocks", "--follow"]).unwrap();
+        match cmd {
+            DB3ClientCommand::Blocks { follow, ws_url } => {
+                assert!(follow);
+                assert_eq!("ws://127.0.0.1:26657/websocket", ws_url);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_audit_tx() {
+        use db3_crypto::key_derive;
+        use db3_crypto::signature_scheme::SignatureScheme;
+        let seed: [u8; 32] = [0; 32];
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::ED25519).unwrap();
+        let signer = Db3MultiSchemeSigner::new(kp);
+        let kv_list = vec!["k1=v1".to_string()];
+        let tx = DB3ClientCommand::build_offline_tx(&signer, Some("ns1"), &kv_list, &[], false, 7)
+            .unwrap();
+        let tx = base64ct::Base64::decode_vec(tx.as_str()).unwrap();
+        assert_eq!(None, DB3ClientCommand::audit_tx(tx.as_ref()));
+        // flip a byte of the payload
+        let mut request = WriteRequest::decode(tx.as_ref()).unwrap();
+        request.payload[0] ^= 0xff;
+        let mut tampered: Vec<u8> = Vec::new();
+        request.encode(&mut tampered).unwrap();
+        assert!(DB3ClientCommand::audit_tx(tampered.as_ref()).is_some());
+        let results = vec![
+            ("a".to_string(), 1, None),
+            ("b".to_string(), 1, Some("bad".to_string())),
+        ];
+        assert!(DB3ClientCommand::print_audit_results(&results, OutputFormat::Csv).is_err());
+        assert!(DB3ClientCommand::print_audit_results(&results[..1], OutputFormat::Csv).is_ok());
+        let cmd =
+            DB3ClientCommand::try_parse_from(["db3", "verify-block", "10", "--to-height", "12"])
+                .unwrap();
+        match cmd {
+            DB3ClientCommand::VerifyBlock {
+                from_height,
+                to_height,
+                ..
+            } => {
+                assert_eq!(10, from_height);
+                assert_eq!(Some(12), to_height);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_decode_tx() {
+        use db3_crypto::db3_signature::DB3Signature;
+        use db3_crypto::key_derive;
+        use db3_crypto::signature_scheme::SignatureScheme;
+        let seed: [u8; 32] = [0; 32];
+        let (address, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::ED25519).unwrap();
+        let kv_list = vec!["k1=v1".to_string()];
+        let mutation = DB3ClientCommand::build_mutation("ns1", &kv_list, &[]).unwrap();
+        let mut payload: Vec<u8> = Vec::new();
+        mutation.encode(&mut payload).unwrap();
+        let signature = Db3MultiSchemeSigner::new(kp)
+            .sign(payload.as_ref())
+            .unwrap();
+        let request = WriteRequest {
+            signature: signature.as_ref().to_vec(),
+            payload: payload.clone(),
+            payload_type: PayloadType::MutationPayload.into(),
+            signature_scheme: signature.scheme().to_proto(),
+        };
+        let mut tx: Vec<u8> = Vec::new();
+        request.encode(&mut tx).unwrap();
+        let (sender, payload_type, value) = DB3ClientCommand::decode_tx(tx.as_ref()).unwrap();
+        assert_eq!(AccountId::new(address).to_hex(), sender.to_hex());
+        assert_eq!(PayloadType::MutationPayload, payload_type);
+        assert_eq!(serde_json::to_value(&mutation).unwrap(), value);
+        // a tampered payload fails the verification
+        let request = WriteRequest {
+            payload: "bad payload".as_bytes().to_vec(),
+            ..request
+        };
+        let mut tx: Vec<u8> = Vec::new();
+        request.encode(&mut tx).unwrap();
+        assert!(DB3ClientCommand::decode_tx(tx.as_ref()).is_err());
+        assert!(DB3ClientCommand::decode_tx("bad tx".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn it_parse_whoami() {
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "whoami"]).unwrap();
+        assert!(matches!(cmd, DB3ClientCommand::WhoAmI {}));
+    }
+
+    #[test]
+    fn it_parse_list_dbs() {
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "list-dbs", "--addr", "0x01"]).unwrap();
+        match cmd {
+            DB3ClientCommand::ListDBs { addr } => assert_eq!(Some("0x01".to_string()), addr),
+            _ => assert!(false),
+        }
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "list-dbs"]).unwrap();
+        assert!(matches!(cmd, DB3ClientCommand::ListDBs { addr: None }));
+    }
+
+    #[test]
+    fn it_parse_count() {
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "count", "ns1"]).unwrap();
+        match cmd {
+            DB3ClientCommand::Count { ns } => assert_eq!(Some("ns1".to_string()), ns),
+            _ => assert!(false),
+        }
+        let cmd = DB3ClientCommand::try_parse_from(["db3", "count"]).unwrap();
+        match cmd {
+            DB3ClientCommand::Count { ns } => assert!(ns.is_none()),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_parse_estimate() {
+        let cmd = DB3ClientCommand::try_parse_from([
+            "db3", "estimate", "--ns", "ns1", "--kv", "k1=v1", "--del", "k2", "--unit", "tai",
+        ])
+        .unwrap();
+        match cmd {
+            DB3ClientCommand::Estimate {
+                ns,
+                kv_list,
+                del_list,
+                unit,
+            } => {
+                assert_eq!(Some("ns1".to_string()), ns);
+                assert_eq!(vec!["k1=v1".to_string()], kv_list);
+                assert_eq!(vec!["k2".to_string()], del_list);
+                assert_eq!(UnitType::Tai, unit);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_confirm_with_answer() {
+        for (answer, expected) in [("yes\n", true), ("Y\n", true), ("no\n", false), ("", false)] {
+            let mut output: Vec<u8> = Vec::new();
+            let confirmed =
+                DB3ClientCommand::confirm(&mut answer.as_bytes(), &mut output, "are you sure")
+                    .unwrap();
+            assert_eq!(expected, confirmed);
+            assert_eq!("are you sure (yes/no): ".as_bytes(), output.as_slice());
+        }
+    }
+
+    #[test]
+    fn it_parse_grant() {
+        let cmd = DB3ClientCommand::try_parse_from([
+            "db3",
+            "grant",
+            "--ns",
+            "ns1",
+            "--addr",
+            "0x0000000000000000000000000000000000000001",
+        ])
+        .unwrap();
+        match cmd {
+            DB3ClientCommand::Grant { ns, addr } => {
+                assert_eq!(Some("ns1".to_string()), ns);
+                assert!(DB3Address::try_from(addr.as_str()).is_ok());
+            }
+            _ => assert!(false),
+        }
+        assert!(DB3ClientCommand::try_parse_from(["db3", "revoke", "--ns", "ns1"]).is_err());
+    }
+
+    #[test]
+    fn it_parse_index() {
+        let json = r#"{"name":"idx1","fields":[{"field_path":"age","value_mode":{"Order":1}},{"field_path":"tags","value_mode":{"ArrayConfig":1}}]}"#;
+        let from_json = DB3ClientCommand::parse_index(json).unwrap();
+        let from_options = DB3ClientCommand::parse_index(
+            "name=idx1,field=age,order=asc,field=tags,array=contains",
+        )
+        .unwrap();
+        assert_eq!(from_json, from_options);
+        assert_eq!(2, from_options.fields.len());
+        let index = DB3ClientCommand::parse_index("field=name").unwrap();
+        assert_eq!("", index.name);
+        assert_eq!(None, index.fields[0].value_mode);
+        let index = DB3ClientCommand::parse_index("field=age, order=DESC").unwrap();
+        assert_eq!(
+            Some(ValueMode::Order(Order::Descending.into())),
+            index.fields[0].value_mode
+        );
+        for bad in [
+            "name=idx1",
+            "order=asc,field=age",
+            "field=age,order=up",
+            "field=age,order=asc,array=contains",
+            "field=age,type=int",
+            "field=",
+            "field",
+            "{bad json",
+        ] {
+            assert!(DB3ClientCommand::parse_index(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn it_parse_field_schema() {
+        let field = DB3ClientCommand::parse_field_schema("profile.age = INT64").unwrap();
+        assert_eq!("profile.age", field.field_path);
+        assert_eq!(FieldType::Int64, field.field_type());
+        for bad in ["age", "age=int", "=string", "my age=string"] {
+            assert!(DB3ClientCommand::parse_field_schema(bad).is_err(), "{bad}");
+        }
+        let cmd = DB3ClientCommand::try_parse_from([
+            "db3",
+            "new-collection",
+            "--addr",
+            "0x00",
+            "--name",
+            "users",
+            "--index",
+            "field=age",
+            "--schema",
+            "age=int64",
+        ])
+        .unwrap();
+        match cmd {
+            DB3ClientCommand::NewCollection { schema_list, .. } => {
+                assert_eq!(vec!["age=int64".to_string()], schema_list);
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn it_parse_put_with_gas_price() {
+        let cmd = DB3ClientCommand::try_parse_from([
+            "db3",
+            "put",
+            "--kv",
+            "k1=v1",
+            "--gas-price",
+            "1 db3",
+        ])
+        .unwrap();
+        match cmd {
+            DB3ClientCommand::Put { gas_price, .. } => {
+                let gas_price = gas_price.unwrap();
+                assert_eq!(UnitType::Db3 as i32, gas_price.utype);
+                assert_eq!(1, gas_price.amount);
+            }
+            _ => assert!(false),
+        }
+        assert!(DB3ClientCommand::try_parse_from([
+            "db3", "estimate", "--kv", "k1=v1", "--unit", "eth"
+        ])
+        .is_err());
     }
 }
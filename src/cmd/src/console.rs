@@ -15,7 +15,7 @@
 // limitations under the License.
 //
 
-use std::io::{stderr, Write};
+use std::io::{stderr, BufRead, Write};
 
 use async_trait::async_trait;
 use clap::Command;
@@ -23,9 +23,14 @@ use clap::CommandFactory;
 use clap::FromArgMatches;
 use clap::Parser;
 use colored::Colorize;
+use tonic::{Code, Status};
 
 use crate::command::{DB3ClientCommand, DB3ClientContext};
-use crate::shell::{install_shell_plugins, AsyncHandler, CommandStructure, CompletionCache, Shell};
+use crate::keystore::KeyStore;
+use crate::shell::{
+    install_shell_plugins, split_and_unescape, AsyncHandler, CacheKey, CommandStructure,
+    CompletionCache, Shell,
+};
 const DB3: &str = "
 ██████╗ ██████╗ ██████╗ 
 ██╔══██╗██╔══██╗╚════██╗
@@ -35,6 +40,9 @@ const DB3: &str = "
 ╚═════╝ ╚═════╝ ╚═════╝ 
 @db3.network🚀🚀🚀";
 
+/// rebuild the client context when the connection to db3 node is lost
+pub type ContextBuilder = Box<dyn Fn() -> std::io::Result<DB3ClientContext> + Send + Sync>;
+
 #[derive(Parser)]
 #[clap(name = "", rename_all = "kebab-case", no_binary_name = true)]
 pub struct ConsoleOpts {
@@ -42,8 +50,14 @@ pub struct ConsoleOpts {
     pub command: DB3ClientCommand,
 }
 
+///
+/// start the interactive console, the console will rebuild the context with `reconnect`
+/// and retry the read only command once if the connection to db3 node is lost. the writes
+/// are never retried, they may have reached the node before the connection is lost
+///
 pub async fn start_console(
     ctx: DB3ClientContext,
+    reconnect: Option<ContextBuilder>,
     out: &mut (dyn Write + Send),
     err: &mut (dyn Write + Send),
 ) -> Result<(), anyhow::Error> {
@@ -52,13 +66,65 @@ pub async fn start_console(
     let mut shell = Shell::new(
         "db3>-$ ",
         ctx,
-        ClientCommandHandler,
+        ClientCommandHandler { reconnect },
         CommandStructure::from_clap(&install_shell_plugins(app)),
     );
+    if let Ok(dir) = KeyStore::get_db3_dir() {
+        shell = shell.with_history_file(dir.join("history"));
+    }
     shell.run_async(out, err).await
 }
 
-struct ClientCommandHandler;
+///
+/// run the newline delimited commands of the input one by one and print the result of
+/// every line, it stops on the first failed command unless `continue_on_error` is set.
+/// the empty lines and the lines starting with # are skipped
+///
+pub async fn run_batch(
+    ctx: &mut DB3ClientContext,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+    continue_on_error: bool,
+) -> Result<(), anyhow::Error> {
+    let mut succeeded: usize = 0;
+    let mut failed: usize = 0;
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let result = match split_and_unescape(line) {
+            Ok(args) => match ConsoleOpts::try_parse_from(args) {
+                Ok(opts) => opts.command.execute(ctx).await,
+                Err(e) => Err(e.into()),
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => {
+                succeeded += 1;
+                writeln!(out, "line {}: ok", index + 1)?;
+            }
+            Err(e) => {
+                failed += 1;
+                writeln!(out, "line {}: {}", index + 1, e.to_string().trim_end())?;
+                if !continue_on_error {
+                    break;
+                }
+            }
+        }
+    }
+    writeln!(out, "{succeeded} succeeded, {failed} failed")?;
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{failed} commands failed"));
+    }
+    Ok(())
+}
+
+struct ClientCommandHandler {
+    reconnect: Option<ContextBuilder>,
+}
 
 #[async_trait]
 impl AsyncHandler<DB3ClientContext> for ClientCommandHandler {
@@ -68,7 +134,14 @@ impl AsyncHandler<DB3ClientContext> for ClientCommandHandler {
         context: &mut DB3ClientContext,
         completion_cache: CompletionCache,
     ) -> bool {
-        match handle_command(get_command(args), context, completion_cache).await {
+        match handle_command(
+            get_command(args),
+            context,
+            self.reconnect.as_ref(),
+            completion_cache,
+        )
+        .await
+        {
             Err(e) => {
                 let _err = writeln!(stderr(), "{}", e.to_string().red());
                 false
@@ -76,6 +149,10 @@ impl AsyncHandler<DB3ClientContext> for ClientCommandHandler {
             Ok(return_value) => return_value,
         }
     }
+
+    fn prompt_context(&self, state: &DB3ClientContext) -> Option<String> {
+        state.default_ns.clone()
+    }
 }
 
 fn get_command(args: Vec<String>) -> Result<ConsoleOpts, anyhow::Error> {
@@ -85,12 +162,390 @@ fn get_command(args: Vec<String>) -> Result<ConsoleOpts, anyhow::Error> {
     )?)
 }
 
+///
+/// the request failed for a refused or dropped connection rather than an error returned by
+/// db3 node, the grpc channel reports it as a transport error or an unavailable status
+///
+fn is_connection_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| match cause.downcast_ref::<Status>() {
+        Some(status) => status.code() == Code::Unavailable,
+        None => cause.downcast_ref::<tonic::transport::Error>().is_some(),
+    })
+}
+
+/// the namespace used by the command
+fn command_ns(command: &DB3ClientCommand) -> Option<&str> {
+    match command {
+        DB3ClientCommand::UseNs { ns } => Some(ns.as_str()),
+        DB3ClientCommand::Put { ns, .. }
+        | DB3ClientCommand::Get { ns, .. }
+        | DB3ClientCommand::Count { ns }
+        | DB3ClientCommand::ExportNs { ns, .. }
+        | DB3ClientCommand::ImportNs { ns, .. }
+        | DB3ClientCommand::Grant { ns, .. }
+        | DB3ClientCommand::Revoke { ns, .. } => ns.as_deref(),
+        _ => None,
+    }
+}
+
+/// complete the namespaces used before for the commands taking a namespace
+fn remember_ns(completion_cache: &CompletionCache, ns: &str) {
+    if let Ok(mut cache) = completion_cache.write() {
+        for key in [
+            CacheKey::new("put", "--ns"),
+            CacheKey::new("get", "--ns"),
+            CacheKey::new("use-ns", "use-ns"),
+            CacheKey::new("count", "count"),
+            CacheKey::new("export-ns", "--ns"),
+            CacheKey::new("import-ns", "--ns"),
+            CacheKey::new("grant", "--ns"),
+            CacheKey::new("revoke", "--ns"),
+        ] {
+            let namespaces = cache.entry(key).or_default();
+            if !namespaces.iter().any(|n| n == ns) {
+                namespaces.push(ns.to_string());
+            }
+        }
+    }
+}
+
 async fn handle_command(
     opts: Result<ConsoleOpts, anyhow::Error>,
     ctx: &mut DB3ClientContext,
-    _completion_cache: CompletionCache,
+    reconnect: Option<&ContextBuilder>,
+    completion_cache: CompletionCache,
 ) -> Result<bool, anyhow::Error> {
     let opts = opts?;
-    opts.command.execute(ctx).await;
+    if let Some(ns) = command_ns(&opts.command) {
+        remember_ns(&completion_cache, ns);
+    }
+    let read_only = opts.command.is_read_only();
+    match (opts.command.clone().execute(ctx).await, reconnect) {
+        (Err(e), Some(builder)) if read_only && is_connection_error(&e) => {
+            writeln!(
+                stderr(),
+                "{}",
+                "the connection to db3 node is lost, reconnecting...".yellow()
+            )?;
+            let default_ns = ctx.default_ns.take();
+            *ctx = builder()?;
+            ctx.default_ns = default_ns;
+            opts.command.execute(ctx).await?;
+        }
+        (result, _) => result?,
+    }
     Ok(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::OutputFormat;
+    use db3_crypto::{
+        db3_signer::Db3MultiSchemeSigner, key_derive, signature_scheme::SignatureScheme,
+    };
+    use db3_proto::db3_node_proto::storage_node_client::StorageNodeClient;
+    use db3_proto::db3_node_proto::{OpenSessionResponse, ShowDatabaseResponse};
+    use db3_proto::db3_session_proto::QuerySessionInfo;
+    use db3_sdk::{mutation_sdk::MutationSDK, store_sdk::StoreSDK};
+    use prost::bytes::Bytes;
+    use prost::Message;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::codegen::{empty_body, http, Body as HttpBody, BoxBody, BoxFuture, Service};
+    use tonic::transport::{Body, Endpoint, NamedService, Server};
+
+    const DEAD_URL: &str = "http://127.0.0.1:1";
+
+    fn build_context(url: &str) -> DB3ClientContext {
+        build_context_with_endpoint(url, Endpoint::new(url.to_string()).unwrap())
+    }
+
+    fn build_context_with_endpoint(url: &str, endpoint: Endpoint) -> DB3ClientContext {
+        let seed: [u8; 32] = [0; 32];
+        let channel = endpoint.connect_lazy();
+        let node = Arc::new(StorageNodeClient::new(channel));
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let mutation_sdk = MutationSDK::new(node.clone(), Box::new(Db3MultiSchemeSigner::new(kp)));
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let store_sdk = StoreSDK::new(node, Box::new(Db3MultiSchemeSigner::new(kp)));
+        DB3ClientContext {
+            mutation_sdk: Some(mutation_sdk),
+            store_sdk: Some(store_sdk),
+            default_ns: None,
+            key_name: None,
+            output: OutputFormat::Table,
+            url: url.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_run_batch() {
+        let input = "# switch namespaces\nuse-ns ns1\n\nbad-cmd\nuse-ns ns2\n";
+        let mut ctx = build_context(DEAD_URL);
+        let mut out: Vec<u8> = Vec::new();
+        let result = run_batch(&mut ctx, &mut input.as_bytes(), &mut out, false).await;
+        assert!(result.is_err());
+        assert_eq!(Some("ns1".to_string()), ctx.default_ns);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("line 2: ok\nline 4: "));
+        assert!(out.ends_with("1 succeeded, 1 failed\n"));
+        let mut ctx = build_context(DEAD_URL);
+        let mut out: Vec<u8> = Vec::new();
+        let result = run_batch(&mut ctx, &mut input.as_bytes(), &mut out, true).await;
+        assert!(result.is_err());
+        assert_eq!(Some("ns2".to_string()), ctx.default_ns);
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("2 succeeded, 1 failed\n"));
+        let mut ctx = build_context(DEAD_URL);
+        let mut out: Vec<u8> = Vec::new();
+        let result = run_batch(&mut ctx, &mut "use-ns ns1\n".as_bytes(), &mut out, false).await;
+        assert!(result.is_ok());
+    }
+
+    fn show_db_args() -> Vec<String> {
+        vec![
+            "show-db".to_string(),
+            "--addr".to_string(),
+            format!("0x{}", "00".repeat(20)),
+        ]
+    }
+
+    #[tokio::test]
+    async fn it_retry_once_when_connection_dropped() {
+        let counter = Arc::new(AtomicU32::new(0));
+        let local_counter = counter.clone();
+        let builder: ContextBuilder = Box::new(move || {
+            local_counter.fetch_add(1, Ordering::SeqCst);
+            Ok(build_context(DEAD_URL))
+        });
+        let mut ctx = build_context(DEAD_URL);
+        let cache = Arc::new(std::sync::RwLock::new(std::collections::BTreeMap::new()));
+        let result =
+            handle_command(get_command(show_db_args()), &mut ctx, Some(&builder), cache).await;
+        assert!(result.is_err());
+        assert_eq!(1, counter.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn it_not_retry_write_when_connection_dropped() {
+        let counter = Arc::new(AtomicU32::new(0));
+        let local_counter = counter.clone();
+        let builder: ContextBuilder = Box::new(move || {
+            local_counter.fetch_add(1, Ordering::SeqCst);
+            Ok(build_context(DEAD_URL))
+        });
+        let mut ctx = build_context(DEAD_URL);
+        let cache = Arc::new(std::sync::RwLock::new(std::collections::BTreeMap::new()));
+        let args = ["put", "--ns", "ns1", "--kv", "k1=v1"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let result = handle_command(get_command(args), &mut ctx, Some(&builder), cache).await;
+        assert!(result.is_err());
+        assert_eq!(0, counter.load(Ordering::SeqCst));
+    }
+
+    /// serve the node on a free local port and return its url
+    async fn serve_node<S>(node: S) -> String
+    where
+        S: Service<
+                http::Request<Body>,
+                Response = http::Response<BoxBody>,
+                Error = std::convert::Infallible,
+            > + NamedService
+            + Clone
+            + Send
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(node)
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+                .unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    /// the body of a unary grpc reply, the message is followed by the ok status
+    struct UnaryReply(Option<Bytes>);
+
+    impl HttpBody for UnaryReply {
+        type Data = Bytes;
+        type Error = Status;
+
+        fn poll_data(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+            Poll::Ready(self.0.take().map(Ok))
+        }
+
+        fn poll_trailers(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+            Poll::Ready(Ok(Some(trailers)))
+        }
+    }
+
+    /// a storage node opening query sessions and holding no database
+    #[derive(Clone)]
+    struct EmptyNode;
+
+    impl NamedService for EmptyNode {
+        const NAME: &'static str = "db3_node_proto.StorageNode";
+    }
+
+    impl Service<http::Request<Body>> for EmptyNode {
+        type Response = http::Response<BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+            let message = match req.uri().path() {
+                "/db3_node_proto.StorageNode/OpenQuerySession" => OpenSessionResponse {
+                    query_session_info: Some(QuerySessionInfo {
+                        id: 1,
+                        start_time: SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs() as i64,
+                        query_count: 0,
+                    }),
+                    session_timeout_second: 3600,
+                    max_query_limit: 1000,
+                    session_token: "token".to_string(),
+                }
+                .encode_to_vec(),
+                "/db3_node_proto.StorageNode/ShowDatabase" => {
+                    ShowDatabaseResponse { db: None }.encode_to_vec()
+                }
+                _ => {
+                    return Box::pin(async {
+                        Ok(http::Response::builder()
+                            .header("grpc-status", "12")
+                            .header("content-type", "application/grpc")
+                            .body(empty_body())
+                            .unwrap())
+                    })
+                }
+            };
+            // the uncompressed flag and the length of the message precede it
+            let mut frame = vec![0u8];
+            frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            frame.extend(message);
+            Box::pin(async move {
+                Ok(http::Response::builder()
+                    .header("content-type", "application/grpc")
+                    .body(UnaryReply(Some(Bytes::from(frame))).boxed_unsync())
+                    .unwrap())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reconnect_to_restarted_node() {
+        let url = serve_node(EmptyNode).await;
+        let builder: ContextBuilder = Box::new(move || Ok(build_context(url.as_str())));
+        let mut ctx = build_context(DEAD_URL);
+        let cache = Arc::new(std::sync::RwLock::new(std::collections::BTreeMap::new()));
+        let result =
+            handle_command(get_command(show_db_args()), &mut ctx, Some(&builder), cache).await;
+        assert!(result.is_ok());
+    }
+
+    /// a storage node accepting every request but never responding in time
+    #[derive(Clone)]
+    struct StalledNode;
+
+    impl NamedService for StalledNode {
+        const NAME: &'static str = "db3_node_proto.StorageNode";
+    }
+
+    impl Service<http::Request<Body>> for StalledNode {
+        type Response = http::Response<BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: http::Request<Body>) -> Self::Future {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(http::Response::new(empty_body()))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn it_time_out_when_node_stalls() {
+        let url = serve_node(StalledNode).await;
+        let endpoint = Endpoint::new(url.to_string())
+            .unwrap()
+            .timeout(Duration::from_secs(1));
+        let mut ctx = build_context_with_endpoint(url.as_str(), endpoint);
+        let cache = Arc::new(std::sync::RwLock::new(std::collections::BTreeMap::new()));
+        let result = handle_command(get_command(show_db_args()), &mut ctx, None, cache).await;
+        assert!(result.is_err());
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .starts_with("request timed out"));
+    }
+
+    #[test]
+    fn it_remember_ns_for_completion() {
+        let cache: CompletionCache =
+            Arc::new(std::sync::RwLock::new(std::collections::BTreeMap::new()));
+        let opts = get_command(vec!["use-ns".to_string(), "ns1".to_string()]).unwrap();
+        assert_eq!(Some("ns1"), command_ns(&opts.command));
+        remember_ns(&cache, "ns1");
+        remember_ns(&cache, "ns1");
+        remember_ns(&cache, "ns2");
+        let cache = cache.read().unwrap();
+        assert_eq!(
+            Some(&vec!["ns1".to_string(), "ns2".to_string()]),
+            cache.get(&CacheKey::new("get", "--ns"))
+        );
+        assert!(cache.get(&CacheKey::new("show-db", "--addr")).is_none());
+    }
+
+    #[test]
+    fn it_check_connection_error() {
+        let node_error =
+            |status: Status| anyhow::Error::new(status).context("fail to show database");
+        assert!(is_connection_error(&node_error(Status::unavailable(
+            "the node is down"
+        ))));
+        assert!(!is_connection_error(&node_error(Status::internal(
+            "invalid database address"
+        ))));
+        // the message alone does not make a connection error
+        assert!(!is_connection_error(&anyhow::anyhow!(
+            "fail to show database with error status: Unavailable, message: \"transport error\""
+        )));
+    }
+}
@@ -15,7 +15,10 @@
 // limitations under the License.
 //
 
+use base64ct::{Base64, Encoding};
 use bip32::Mnemonic;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
 use db3_crypto::{
     db3_address::DB3Address,
     db3_keypair::{DB3KeyPair, EncodeDecodeBase64},
@@ -23,16 +26,56 @@ use db3_crypto::{
     key_derive,
     signature_scheme::SignatureScheme,
 };
-use db3_error::Result;
+use db3_error::{DB3Error, Result};
 use dirs;
-use rand_core::OsRng;
-use std::fs::File;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
 use std::io::Write;
 use std::io::{Error, ErrorKind};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use prettytable::{format, Table};
 
+const DEFAULT_KEY: &str = ".default";
+const KEYS_DIR: &str = "keys";
+/// the passphrase of the encrypted keys is read from it before prompting
+const PASSPHRASE_ENV: &str = "DB3_KEY_PASSPHRASE";
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+// the plaintext key file is <base64 key>:<hex checksum>
+const CHECKSUM_SEPARATOR: char = ':';
+const CHECKSUM_LENGTH: usize = 4;
+
+///
+/// the key file encrypted by a passphrase, the key is derived from the passphrase by
+/// scrypt and the base64 encoded keypair is sealed by chacha20poly1305. the address
+/// is kept in plaintext to list the keys without the passphrase
+///
+#[derive(Serialize, Deserialize)]
+struct EncryptedKey {
+    address: String,
+    kdf: String,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn invalid_key_file(path: &Path, reason: &str) -> Error {
+    DB3Error::InvalidKeyFile(format!("{} for {reason}", path.display())).into()
+}
+
 pub struct KeyStore {
     key_pair: DB3KeyPair,
 }
@@ -60,54 +103,374 @@ impl KeyStore {
         ))
     }
 
-    pub fn has_key() -> bool {
-        let mut home_dir = dirs::home_dir().unwrap();
-        home_dir.push(".db3");
-        home_dir.push(".default");
-        let key_path = home_dir.as_path();
-        key_path.exists()
+    pub fn has_key(key_name: Option<&str>) -> bool {
+        match Self::get_key_path(key_name) {
+            Ok(key_path) => key_path.exists(),
+            Err(_) => false,
+        }
     }
 
     //
     // recover the from local filesystem
     //
-    pub fn recover_keypair() -> std::io::Result<Self> {
-        let mut home_dir = dirs::home_dir().unwrap();
-        home_dir.push(".db3");
-        let user_dir = home_dir.as_path();
-        std::fs::create_dir_all(user_dir)?;
-        home_dir.push(".default");
-        let key_path = home_dir.as_path();
+    pub fn recover_keypair(key_name: Option<&str>) -> std::io::Result<Self> {
+        let key_path = Self::get_key_path(key_name)?;
         if key_path.exists() {
-            let kp_bytes = std::fs::read(key_path)?;
-            let b64_str = std::str::from_utf8(kp_bytes.as_ref()).unwrap();
-            let key_pair = DB3KeyPair::from_str(b64_str).unwrap();
+            let key_pair = Self::read_key_file(key_path.as_path())?;
             Ok(KeyStore::new(key_pair))
         } else {
-            let (_, kp, _) = Self::generate_keypair().unwrap();
-            let b64_str = kp.encode_base64();
-            let mut f = File::create(key_path)?;
-            f.write_all(b64_str.as_bytes())?;
-            f.sync_all()?;
+            let (_, kp, _) = Self::generate_keypair()?;
+            Self::write_key_file(&kp, key_path.as_path(), false)?;
             Ok(KeyStore::new(kp))
         }
     }
 
-    pub fn get_keypair() -> std::io::Result<DB3KeyPair> {
-        if Self::has_key() {
-            let mut home_dir = dirs::home_dir().unwrap();
-            home_dir.push(".db3");
-            home_dir.push(".default");
-            let key_path = home_dir.as_path();
-            let kp_bytes = std::fs::read(key_path)?;
-            let b64_str = std::str::from_utf8(kp_bytes.as_ref()).unwrap();
-            let key_pair = DB3KeyPair::from_str(b64_str).unwrap();
-            Ok(key_pair)
+    pub fn get_keypair(key_name: Option<&str>) -> std::io::Result<DB3KeyPair> {
+        if Self::has_key(key_name) {
+            let key_path = Self::get_key_path(key_name)?;
+            Self::read_key_file(key_path.as_path())
         } else {
             Err(Error::new(ErrorKind::Other, "no key was found"))
         }
     }
 
+    /// the directory keeps the keys and the console history
+    pub fn get_db3_dir() -> std::io::Result<PathBuf> {
+        let mut home_dir =
+            dirs::home_dir().ok_or_else(|| Error::new(ErrorKind::NotFound, "no home dir"))?;
+        home_dir.push(".db3");
+        Ok(home_dir)
+    }
+
+    //
+    // the default key lives at ~/.db3/.default and the named key at ~/.db3/keys/<key_name>
+    //
+    fn get_key_path(key_name: Option<&str>) -> std::io::Result<PathBuf> {
+        let mut key_path = Self::get_db3_dir()?;
+        match key_name {
+            Some(name) => {
+                if !Self::is_valid_key_name(name) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("invalid key name {name}"),
+                    ));
+                }
+                key_path.push(KEYS_DIR);
+                std::fs::create_dir_all(key_path.as_path())?;
+                key_path.push(name);
+            }
+            None => {
+                std::fs::create_dir_all(key_path.as_path())?;
+                key_path.push(DEFAULT_KEY);
+            }
+        }
+        Ok(key_path)
+    }
+
+    fn is_valid_key_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+
+    //
+    // list the default key and all the named keys with their addresses, the files not
+    // named as a key, e.g. the temporary file of rotating, are skipped. a key file failing
+    // to read is listed with its error rather than hiding the other keys
+    //
+    pub fn list_keys() -> std::io::Result<Vec<(String, std::io::Result<AccountId>)>> {
+        Self::list_keys_in(Self::get_db3_dir()?.as_path())
+    }
+
+    fn list_keys_in(db3_dir: &Path) -> std::io::Result<Vec<(String, std::io::Result<AccountId>)>> {
+        let mut keys: Vec<(String, std::io::Result<AccountId>)> = Vec::new();
+        let default_key = db3_dir.join(DEFAULT_KEY);
+        if default_key.exists() {
+            keys.push((
+                DEFAULT_KEY.to_string(),
+                Self::read_key_address(default_key.as_path()),
+            ));
+        }
+        let keys_dir = db3_dir.join(KEYS_DIR);
+        if keys_dir.exists() {
+            let mut names: Vec<String> = std::fs::read_dir(keys_dir.as_path())?
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| Self::is_valid_key_name(name))
+                .collect();
+            names.sort();
+            for name in names {
+                let id = Self::read_key_address(keys_dir.join(name.as_str()).as_path());
+                keys.push((name, id));
+            }
+        }
+        Ok(keys)
+    }
+
+    //
+    // write the base64 encoded keypair to a file only readable by the owner
+    //
+    fn write_key_file(key_pair: &DB3KeyPair, path: &Path, force: bool) -> std::io::Result<()> {
+        Self::write_key_content(Self::encode_key(key_pair).as_str(), path, force)
+    }
+
+    fn checksum(b64_str: &str) -> String {
+        hex::encode(&Sha256::digest(b64_str.as_bytes())[..CHECKSUM_LENGTH])
+    }
+
+    //
+    // the base64 encoded keypair with the checksum of it
+    //
+    fn encode_key(key_pair: &DB3KeyPair) -> String {
+        let b64_str = key_pair.encode_base64();
+        let checksum = Self::checksum(b64_str.as_str());
+        format!("{b64_str}{CHECKSUM_SEPARATOR}{checksum}")
+    }
+
+    //
+    // decode the plaintext key, the key files written without a checksum are still accepted
+    //
+    fn decode_key(content: &str, path: &Path) -> std::io::Result<DB3KeyPair> {
+        let content = content.trim();
+        let b64_str = match content.split_once(CHECKSUM_SEPARATOR) {
+            Some((b64_str, checksum)) => {
+                if Self::checksum(b64_str) != checksum {
+                    return Err(invalid_key_file(path, "checksum mismatch"));
+                }
+                b64_str
+            }
+            None => content,
+        };
+        DB3KeyPair::from_str(b64_str).map_err(|e| invalid_key_file(path, &format!("{e}")))
+    }
+
+    fn write_key_content(content: &str, path: &Path, force: bool) -> std::io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).mode(0o600);
+        if force {
+            options.create(true).truncate(true);
+        } else {
+            options.create_new(true);
+        }
+        let mut f = options.open(path)?;
+        f.write_all(content.as_bytes())?;
+        f.sync_all()
+    }
+
+    fn is_encrypted(content: &str) -> bool {
+        content.trim_start().starts_with('{')
+    }
+
+    //
+    // read the plaintext or the encrypted key file, the passphrase is read from
+    // DB3_KEY_PASSPHRASE or prompted if the file is encrypted
+    //
+    fn read_key_file(path: &Path) -> std::io::Result<DB3KeyPair> {
+        let content = Self::read_key_content(path)?;
+        if Self::is_encrypted(content.as_str()) {
+            let passphrase =
+                Self::read_passphrase(format!("passphrase of {}: ", path.display()).as_str())?;
+            Self::decrypt_key(content.as_str(), passphrase.as_str())
+        } else {
+            Self::decode_key(content.as_str(), path)
+        }
+    }
+
+    fn read_key_content(path: &Path) -> std::io::Result<String> {
+        let kp_bytes = std::fs::read(path)?;
+        String::from_utf8(kp_bytes).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    //
+    // the address of the key file, the encrypted key is not decrypted
+    //
+    fn read_key_address(path: &Path) -> std::io::Result<AccountId> {
+        let content = Self::read_key_content(path)?;
+        if Self::is_encrypted(content.as_str()) {
+            let encrypted: EncryptedKey = serde_json::from_str(content.as_str())
+                .map_err(|e| invalid_data(format!("bad encrypted key {e}")))?;
+            let address = DB3Address::try_from(encrypted.address.as_str())
+                .map_err(|e| invalid_data(format!("bad address of encrypted key {e}")))?;
+            Ok(AccountId::new(address))
+        } else {
+            let kp = Self::decode_key(content.as_str(), path)?;
+            Ok(AccountId::new(DB3Address::from(&kp.public())))
+        }
+    }
+
+    pub fn read_passphrase(prompt: &str) -> std::io::Result<String> {
+        match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) => Ok(passphrase),
+            Err(_) => rpassword::prompt_password(prompt),
+        }
+    }
+
+    fn derive_cipher(
+        passphrase: &str,
+        salt: &[u8],
+        log_n: u8,
+        r: u32,
+        p: u32,
+    ) -> std::io::Result<ChaCha20Poly1305> {
+        let params = scrypt::Params::new(log_n, r, p)
+            .map_err(|e| invalid_data(format!("bad scrypt params {e}")))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+            .map_err(|e| invalid_data(format!("fail to derive key {e}")))?;
+        ChaCha20Poly1305::new_from_slice(&key).map_err(|e| invalid_data(format!("{e}")))
+    }
+
+    fn encrypt_key(key_pair: &DB3KeyPair, passphrase: &str) -> std::io::Result<String> {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let cipher = Self::derive_cipher(passphrase, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                key_pair.encode_base64().as_bytes(),
+            )
+            .map_err(|e| invalid_data(format!("fail to encrypt key {e}")))?;
+        let encrypted = EncryptedKey {
+            address: AccountId::new(DB3Address::from(&key_pair.public())).to_hex(),
+            kdf: "scrypt".to_string(),
+            log_n: SCRYPT_LOG_N,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt: Base64::encode_string(&salt),
+            nonce: Base64::encode_string(&nonce),
+            ciphertext: Base64::encode_string(&ciphertext),
+        };
+        serde_json::to_string_pretty(&encrypted).map_err(|e| invalid_data(format!("{e}")))
+    }
+
+    fn decrypt_key(content: &str, passphrase: &str) -> std::io::Result<DB3KeyPair> {
+        let encrypted: EncryptedKey = serde_json::from_str(content)
+            .map_err(|e| invalid_data(format!("bad encrypted key {e}")))?;
+        if encrypted.kdf != "scrypt" {
+            return Err(invalid_data(format!("unsupported kdf {}", encrypted.kdf)));
+        }
+        let decode = |value: &str| {
+            Base64::decode_vec(value).map_err(|e| invalid_data(format!("bad encrypted key {e}")))
+        };
+        let salt = decode(encrypted.salt.as_str())?;
+        let nonce = decode(encrypted.nonce.as_str())?;
+        let ciphertext = decode(encrypted.ciphertext.as_str())?;
+        if nonce.len() != 12 {
+            return Err(invalid_data("bad nonce of encrypted key".to_string()));
+        }
+        let cipher = Self::derive_cipher(
+            passphrase,
+            salt.as_ref(),
+            encrypted.log_n,
+            encrypted.r,
+            encrypted.p,
+        )?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce.as_ref()), ciphertext.as_ref())
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::PermissionDenied,
+                    "wrong passphrase or corrupted key file",
+                )
+            })?;
+        let b64_str =
+            std::str::from_utf8(plaintext.as_ref()).map_err(|e| invalid_data(format!("{e}")))?;
+        DB3KeyPair::from_str(b64_str).map_err(|e| invalid_data(format!("{e}")))
+    }
+
+    //
+    // encrypt the key with the new passphrase, or store it in plaintext if the passphrase
+    // is none. the old passphrase is required if the key is encrypted
+    //
+    pub fn rotate_key_encryption(
+        key_name: Option<&str>,
+        new_passphrase: Option<&str>,
+    ) -> std::io::Result<AccountId> {
+        Self::rotate_key_file(Self::get_key_path(key_name)?.as_path(), new_passphrase)
+    }
+
+    fn rotate_key_file(
+        key_path: &Path,
+        new_passphrase: Option<&str>,
+    ) -> std::io::Result<AccountId> {
+        if !key_path.exists() {
+            return Err(Error::new(ErrorKind::NotFound, "no key was found"));
+        }
+        let key_pair = Self::read_key_file(key_path)?;
+        let content = match new_passphrase {
+            Some(passphrase) => Self::encrypt_key(&key_pair, passphrase)?,
+            None => Self::encode_key(&key_pair),
+        };
+        // write a new file and rename it, so the key is never lost halfway
+        let tmp_path = key_path.with_extension("tmp");
+        Self::write_key_content(content.as_str(), tmp_path.as_path(), true)?;
+        std::fs::rename(tmp_path.as_path(), key_path)?;
+        Ok(AccountId::new(DB3Address::from(&key_pair.public())))
+    }
+
+    //
+    // export the key to a file, the file must not exist. the content is copied as it is,
+    // so an encrypted key stays encrypted
+    //
+    pub fn export_key(key_name: Option<&str>, out: &Path) -> std::io::Result<AccountId> {
+        let key_path = Self::get_key_path(key_name)?;
+        if !key_path.exists() {
+            return Err(Error::new(ErrorKind::NotFound, "no key was found"));
+        }
+        Self::copy_key_file(key_path.as_path(), out, false)
+    }
+
+    //
+    // install the key from a file, the existing key will be kept unless force is true
+    //
+    pub fn import_key(
+        key_name: Option<&str>,
+        input: &Path,
+        force: bool,
+    ) -> std::io::Result<AccountId> {
+        Self::import_key_to(input, Self::get_key_path(key_name)?.as_path(), force)
+    }
+
+    fn import_key_to(input: &Path, key_path: &Path, force: bool) -> std::io::Result<AccountId> {
+        if key_path.exists() && !force {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                "a key already exists, use --force to overwrite it",
+            ));
+        }
+        Self::copy_key_file(input, key_path, force)
+    }
+
+    //
+    // copy the plaintext or the encrypted key file without decrypting it, the content is
+    // checked to be a key before writing it
+    //
+    fn copy_key_file(from: &Path, to: &Path, force: bool) -> std::io::Result<AccountId> {
+        let id = Self::read_key_address(from)?;
+        let content = Self::read_key_content(from)?;
+        Self::write_key_content(content.as_str(), to, force)?;
+        Ok(id)
+    }
+
+    //
+    // remove the key file, the address of the removed key is returned
+    //
+    pub fn purge_key(key_name: Option<&str>) -> std::io::Result<AccountId> {
+        Self::remove_key_file(Self::get_key_path(key_name)?.as_path())
+    }
+
+    fn remove_key_file(key_path: &Path) -> std::io::Result<AccountId> {
+        if !key_path.exists() {
+            return Err(Error::new(ErrorKind::NotFound, "no key was found"));
+        }
+        let id = Self::read_key_address(key_path)?;
+        std::fs::remove_file(key_path)?;
+        Ok(id)
+    }
+
     pub fn show_key(&self) {
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
@@ -129,6 +492,162 @@ impl KeyStore {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn it_export_and_import_key() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("db3_keystore_{}", std::process::id()));
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let out = dir.join("key.b64");
+        let key_path = dir.join(".default");
+        let (id, kp, _) = KeyStore::generate_keypair().unwrap();
+        KeyStore::write_key_file(&kp, out.as_path(), false).unwrap();
+        let mode = std::fs::metadata(out.as_path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(0o600, mode & 0o777);
+        // the exported file will not be overwritten
+        assert!(KeyStore::write_key_file(&kp, out.as_path(), false).is_err());
+        let imported = KeyStore::import_key_to(out.as_path(), key_path.as_path(), false).unwrap();
+        assert_eq!(id.to_hex(), imported.to_hex());
+        // refuse to overwrite the existing key without force
+        let result = KeyStore::import_key_to(out.as_path(), key_path.as_path(), false);
+        assert_eq!(ErrorKind::AlreadyExists, result.err().unwrap().kind());
+        assert!(KeyStore::import_key_to(out.as_path(), key_path.as_path(), true).is_ok());
+        // the encrypted key is copied without decrypting it
+        let encrypted = KeyStore::encrypt_key(&kp, "pass1").unwrap();
+        std::fs::write(out.as_path(), encrypted.as_str()).unwrap();
+        let imported = KeyStore::import_key_to(out.as_path(), key_path.as_path(), true).unwrap();
+        assert_eq!(id.to_hex(), imported.to_hex());
+        let content = KeyStore::read_key_content(key_path.as_path()).unwrap();
+        assert_eq!(encrypted, content);
+        let exported = dir.join("exported.json");
+        KeyStore::copy_key_file(key_path.as_path(), exported.as_path(), false).unwrap();
+        let content = KeyStore::read_key_content(exported.as_path()).unwrap();
+        assert_eq!(encrypted, content);
+        // the invalid key file is rejected
+        let bad = dir.join("bad.b64");
+        std::fs::write(bad.as_path(), "not a key").unwrap();
+        let result = KeyStore::import_key_to(bad.as_path(), key_path.as_path(), true);
+        assert_eq!(ErrorKind::InvalidData, result.err().unwrap().kind());
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+    }
+
     #[test]
-    fn it_works() {}
+    fn it_remove_key_file() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("db3_keystore_purge_{}", std::process::id()));
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let key_path = dir.join(".default");
+        let (id, kp, _) = KeyStore::generate_keypair().unwrap();
+        KeyStore::write_key_file(&kp, key_path.as_path(), false).unwrap();
+        let removed = KeyStore::remove_key_file(key_path.as_path()).unwrap();
+        assert_eq!(id.to_hex(), removed.to_hex());
+        assert!(!key_path.exists());
+        let result = KeyStore::remove_key_file(key_path.as_path());
+        assert_eq!(ErrorKind::NotFound, result.err().unwrap().kind());
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+    }
+
+    #[test]
+    fn it_encrypt_and_decrypt_key() {
+        let (id, kp, _) = KeyStore::generate_keypair().unwrap();
+        let content = KeyStore::encrypt_key(&kp, "pass1").unwrap();
+        assert!(KeyStore::is_encrypted(content.as_str()));
+        assert!(!content.contains(kp.encode_base64().as_str()));
+        let decrypted = KeyStore::decrypt_key(content.as_str(), "pass1").unwrap();
+        assert_eq!(kp.encode_base64(), decrypted.encode_base64());
+        let result = KeyStore::decrypt_key(content.as_str(), "pass2");
+        assert_eq!(ErrorKind::PermissionDenied, result.err().unwrap().kind());
+        // the address is read without the passphrase
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("db3_keystore_encrypt_{}", std::process::id()));
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let key_path = dir.join(".default");
+        KeyStore::write_key_content(content.as_str(), key_path.as_path(), false).unwrap();
+        let address = KeyStore::read_key_address(key_path.as_path()).unwrap();
+        assert_eq!(id.to_hex(), address.to_hex());
+        // the plaintext key is encrypted by rotating
+        KeyStore::write_key_file(&kp, key_path.as_path(), true).unwrap();
+        let rotated = KeyStore::rotate_key_file(key_path.as_path(), Some("pass3")).unwrap();
+        assert_eq!(id.to_hex(), rotated.to_hex());
+        let content = KeyStore::read_key_content(key_path.as_path()).unwrap();
+        let decrypted = KeyStore::decrypt_key(content.as_str(), "pass3").unwrap();
+        assert_eq!(kp.encode_base64(), decrypted.encode_base64());
+        let mode = std::fs::metadata(key_path.as_path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(0o600, mode & 0o777);
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+    }
+
+    #[test]
+    fn it_reject_corrupted_key_file() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("db3_keystore_checksum_{}", std::process::id()));
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let key_path = dir.join(".default");
+        let (id, kp, _) = KeyStore::generate_keypair().unwrap();
+        KeyStore::write_key_file(&kp, key_path.as_path(), false).unwrap();
+        let content = KeyStore::read_key_content(key_path.as_path()).unwrap();
+        assert!(content.contains(CHECKSUM_SEPARATOR));
+        let loaded = KeyStore::read_key_file(key_path.as_path()).unwrap();
+        assert_eq!(kp.encode_base64(), loaded.encode_base64());
+        // the key file written without a checksum is accepted
+        std::fs::write(key_path.as_path(), kp.encode_base64()).unwrap();
+        let address = KeyStore::read_key_address(key_path.as_path()).unwrap();
+        assert_eq!(id.to_hex(), address.to_hex());
+        // a flipped character of the key still decodes, the checksum catches it
+        let mut corrupted: Vec<char> = content.chars().collect();
+        corrupted[10] = if corrupted[10] == 'A' { 'B' } else { 'A' };
+        let corrupted: String = corrupted.into_iter().collect();
+        let truncated = content[..content.len() / 2].to_string();
+        for bad in [corrupted, truncated, "".to_string()] {
+            std::fs::write(key_path.as_path(), bad).unwrap();
+            let result = KeyStore::read_key_file(key_path.as_path());
+            let e = result.err().unwrap();
+            assert_eq!(ErrorKind::InvalidData, e.kind());
+            assert!(e.to_string().starts_with("invalid key file"));
+            assert!(KeyStore::read_key_address(key_path.as_path()).is_err());
+        }
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+    }
+
+    #[test]
+    fn it_list_keys_with_invalid_key_file() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("db3_keystore_list_{}", std::process::id()));
+        let keys_dir = dir.join(KEYS_DIR);
+        std::fs::create_dir_all(keys_dir.as_path()).unwrap();
+        let (id, kp, _) = KeyStore::generate_keypair().unwrap();
+        KeyStore::write_key_file(&kp, dir.join(DEFAULT_KEY).as_path(), false).unwrap();
+        KeyStore::write_key_file(&kp, keys_dir.join("key2").as_path(), false).unwrap();
+        std::fs::write(keys_dir.join("key1"), "not a key").unwrap();
+        std::fs::write(keys_dir.join("key2.tmp"), "not a key").unwrap();
+        let keys = KeyStore::list_keys_in(dir.as_path()).unwrap();
+        let names: Vec<&str> = keys.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec![DEFAULT_KEY, "key1", "key2"], names);
+        assert_eq!(id.to_hex(), keys[0].1.as_ref().unwrap().to_hex());
+        assert_eq!(
+            ErrorKind::InvalidData,
+            keys[1].1.as_ref().err().unwrap().kind()
+        );
+        assert_eq!(id.to_hex(), keys[2].1.as_ref().unwrap().to_hex());
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+    }
+
+    #[test]
+    fn it_reject_invalid_key_name() {
+        for name in ["", "../key", "a/b", "key name"] {
+            let result = KeyStore::get_key_path(Some(name));
+            assert_eq!(ErrorKind::InvalidInput, result.err().unwrap().kind());
+        }
+        // the temporary file of rotating is not listed as a key
+        assert!(!KeyStore::is_valid_key_name("key1.tmp"));
+        assert!(KeyStore::is_valid_key_name("key_1-a"));
+    }
 }
@@ -23,6 +23,7 @@ use std::collections::BTreeMap;
 use std::env;
 use std::fmt::Display;
 use std::io::Write;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
@@ -43,6 +44,7 @@ pub struct Shell<P: Display, S, H> {
     state: S,
     handler: H,
     command: CommandStructure,
+    history_file: Option<PathBuf>,
 }
 
 impl<P: Display, S: Send, H: AsyncHandler<S>> Shell<P, S, H> {
@@ -60,9 +62,16 @@ impl<P: Display, S: Send, H: AsyncHandler<S>> Shell<P, S, H> {
             state,
             handler,
             command,
+            history_file: None,
         }
     }
 
+    /// load the history from the file on start and save it back on exit
+    pub fn with_history_file(mut self, history_file: PathBuf) -> Self {
+        self.history_file = Some(history_file);
+        self
+    }
+
     pub async fn run_async(
         &mut self,
         out: &mut (dyn Write + Send),
@@ -83,9 +92,18 @@ impl<P: Display, S: Send, H: AsyncHandler<S>> Shell<P, S, H> {
             completion_cache: completion_cache.clone(),
         }));
 
+        if let Some(path) = &self.history_file {
+            // the history file does not exist on the first run
+            let _ = rl.load_history(path);
+        }
+
         loop {
             // Read a line
-            let readline = rl.readline(&self.prompt.to_string());
+            let prompt = match self.handler.prompt_context(&self.state) {
+                Some(c) => format!("({c}) {}", self.prompt),
+                None => self.prompt.to_string(),
+            };
+            let readline = rl.readline(&prompt);
             let line = match readline {
                 Ok(rl_line) => rl_line,
                 Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
@@ -133,11 +151,19 @@ impl<P: Display, S: Send, H: AsyncHandler<S>> Shell<P, S, H> {
                 Err(e) => writeln!(err, "{}", e.to_string().red())?,
             }
         }
+        if let Some(path) = &self.history_file {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            if let Err(e) = rl.save_history(path) {
+                writeln!(err, "fail to save history with error {e}")?;
+            }
+        }
         Ok(())
     }
 }
 
-fn split_and_unescape(line: &str) -> Result<Vec<String>, anyhow::Error> {
+pub(crate) fn split_and_unescape(line: &str) -> Result<Vec<String>, anyhow::Error> {
     let mut commands = Vec::new();
     let split: Vec<String> = shell_words::split(line)?;
 
@@ -316,6 +342,11 @@ pub trait AsyncHandler<T: Send> {
         state: &mut T,
         completion_cache: CompletionCache,
     ) -> bool;
+
+    /// the extra context shown in the prompt
+    fn prompt_context(&self, _state: &T) -> Option<String> {
+        None
+    }
 }
 
 pub type CompletionCache = Arc<RwLock<BTreeMap<CacheKey, Vec<String>>>>;
@@ -364,3 +395,28 @@ impl Ord for CacheKey {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_split_empty_and_spaced_input() {
+        assert!(split_and_unescape("").unwrap().is_empty());
+        assert!(split_and_unescape("  \t ".trim()).unwrap().is_empty());
+        assert!(split_and_unescape("  \t ").unwrap().is_empty());
+        assert_eq!(
+            vec!["get", "ns", "k1"],
+            split_and_unescape("get  ns   k1").unwrap()
+        );
+        assert_eq!(
+            vec!["get", "ns", "k1"],
+            split_and_unescape("  get\tns k1  ").unwrap()
+        );
+        // the quoted spaces are kept
+        assert_eq!(
+            vec!["put", "ns", "k1=v  1"],
+            split_and_unescape("put ns 'k1=v  1'").unwrap()
+        );
+    }
+}
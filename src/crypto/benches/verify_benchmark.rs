@@ -0,0 +1,66 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use db3_crypto::db3_verifier::{DB3Verifier, SignedPayload};
+use db3_crypto::key_derive;
+use db3_crypto::signature_scheme::SignatureScheme;
+use signature::Signer;
+
+// sign the payloads of count txs with different keys
+fn build_payloads(count: usize, scheme: &SignatureScheme) -> Vec<SignedPayload> {
+    (0..count)
+        .map(|i| {
+            let mut seed: [u8; 32] = [0; 32];
+            seed[..8].copy_from_slice(&(i as u64).to_be_bytes());
+            let (_, keypair) = key_derive::derive_key_pair_from_path(&seed, None, scheme).unwrap();
+            let msg = format!("mutation payload {i}").into_bytes();
+            let signature = keypair.try_sign(&msg).unwrap();
+            SignedPayload {
+                msg,
+                signature: signature.as_ref().to_vec(),
+                scheme: scheme.to_proto(),
+            }
+        })
+        .collect()
+}
+
+// compare verifying the signatures of 1k txs one by one with the batch verification
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify signatures");
+    group.sample_size(10);
+    let count = 1000;
+    for (name, scheme) in [
+        ("ed25519", SignatureScheme::ED25519),
+        ("secp256k1", SignatureScheme::Secp256k1),
+    ] {
+        let payloads = build_payloads(count, &scheme);
+        group.bench_with_input(
+            BenchmarkId::new(format!("{name}/serial"), count),
+            &payloads,
+            |b, payloads| {
+                b.iter(|| {
+                    for payload in payloads.iter() {
+                        let result = DB3Verifier::verify_with_scheme(
+                            payload.msg.as_ref(),
+                            payload.signature.as_ref(),
+                            payload.scheme,
+                        );
+                        assert!(result.is_ok());
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new(format!("{name}/batch"), count),
+            &payloads,
+            |b, payloads| {
+                b.iter(|| {
+                    let results = DB3Verifier::verify_batch(payloads);
+                    assert!(results.iter().all(|r| r.is_ok()));
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -52,6 +52,14 @@ impl DB3KeyPair {
             DB3KeyPair::Secp256k1(kp) => DB3PublicKey::Secp256k1(kp.public().clone()),
         }
     }
+
+    /// copy the keypair in memory
+    pub fn copy(&self) -> Self {
+        match self {
+            DB3KeyPair::Ed25519(kp) => DB3KeyPair::Ed25519(kp.copy()),
+            DB3KeyPair::Secp256k1(kp) => DB3KeyPair::Secp256k1(kp.copy()),
+        }
+    }
 }
 
 impl Signer<Signature> for DB3KeyPair {
@@ -209,4 +217,14 @@ mod tests {
         let result = signature.verify(&msg);
         assert_eq!(true, result.is_ok());
     }
+
+    #[test]
+    fn keypair_copy_test() {
+        let seed: [u8; 32] = [0; 32];
+        for scheme in [SignatureScheme::ED25519, SignatureScheme::Secp256k1] {
+            let (_, keypair) = key_derive::derive_key_pair_from_path(&seed, None, &scheme).unwrap();
+            let copied = keypair.copy();
+            assert_eq!(keypair.encode_base64(), copied.encode_base64());
+        }
+    }
 }
@@ -262,7 +262,9 @@ impl<S: DB3SignatureInner + Sized> DB3Signature for S {
     fn verify(&self, value: &[u8]) -> Result<DB3Address> {
         let (sig, pk) = &self.get_verification_inputs()?;
         pk.verify(value, sig)
-            .map_err(|e| DB3Error::InvalidSignature(format!("{e}")))?;
+            .map_err(|e| DB3Error::VerificationError {
+                reason: format!("{e}"),
+            })?;
         Ok(DB3Address::from(pk))
     }
 }
@@ -321,5 +323,10 @@ mod tests {
             serde_json::to_string(&result.unwrap()).unwrap()
         );
         assert_eq!(SignatureScheme::ED25519.flag(), signature.scheme().flag());
+        let other_msg: [u8; 1] = [1; 1];
+        assert!(matches!(
+            signature.verify(&other_msg),
+            Err(DB3Error::VerificationError { .. })
+        ));
     }
 }
@@ -17,9 +17,23 @@
 
 use crate::db3_address::DB3Address;
 use crate::db3_keypair::DB3KeyPair;
-use crate::db3_signature::Signature;
+use crate::db3_signature::{DB3Signature, Signature};
 use db3_error::{DB3Error, Result};
-use signature::Signer;
+use signature::Signature as _;
+use signature::Signer as _;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+///
+/// the signer of write requests, an implementation can keep the private key
+/// out of the process, e.g. in a hardware wallet or a local signing service
+///
+pub trait Signer: Send + Sync {
+    // sign msg
+    fn sign(&self, msg: &[u8]) -> Result<Signature>;
+    // the address of signer
+    fn get_address(&self) -> Result<DB3Address>;
+}
 
 pub struct Db3MultiSchemeSigner {
     kp: DB3KeyPair,
@@ -45,6 +59,100 @@ impl Db3MultiSchemeSigner {
     }
 }
 
+impl Signer for Db3MultiSchemeSigner {
+    fn sign(&self, msg: &[u8]) -> Result<Signature> {
+        Db3MultiSchemeSigner::sign(self, msg)
+    }
+
+    fn get_address(&self) -> Result<DB3Address> {
+        Db3MultiSchemeSigner::get_address(self)
+    }
+}
+
+///
+/// the signer running an external program, e.g. the cli of a hardware wallet or the client
+/// of a local signing service, so the private key never enters this process.
+/// `<program> <args> address` prints the hex address of the key, and
+/// `<program> <args> sign` reads the hex encoded message from stdin and prints the hex
+/// encoded signature, which is the scheme flag, the signature and the public key
+///
+#[derive(Clone)]
+pub struct CommandSigner {
+    program: String,
+    args: Vec<String>,
+    address: DB3Address,
+}
+
+impl CommandSigner {
+    /// the address is read from the program once
+    pub fn new(program: &str, args: &[String]) -> Result<Self> {
+        let mut signer = Self {
+            program: program.to_string(),
+            args: args.to_vec(),
+            address: DB3Address::ZERO,
+        };
+        let output = signer.run("address", None)?;
+        signer.address = DB3Address::try_from(output.as_str())?;
+        Ok(signer)
+    }
+
+    fn run(&self, action: &str, input: Option<&str>) -> Result<String> {
+        let sign_error =
+            |e: String| DB3Error::SignMessageError(format!("{} {action} {e}", self.program));
+        let mut child = Command::new(self.program.as_str())
+            .args(self.args.iter())
+            .arg(action)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| sign_error(format!("fails to start for {e}")))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input.unwrap_or_default().as_bytes())
+                .map_err(|e| sign_error(format!("fails to read the input for {e}")))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| sign_error(format!("fails for {e}")))?;
+        if !output.status.success() {
+            return Err(sign_error(format!(
+                "exits with {} for {}",
+                output.status,
+                String::from_utf8_lossy(output.stderr.as_ref()).trim()
+            )));
+        }
+        String::from_utf8(output.stdout)
+            .map(|out| out.trim().to_string())
+            .map_err(|e| sign_error(format!("prints a bad output for {e}")))
+    }
+}
+
+impl Signer for CommandSigner {
+    ///
+    /// the signature is verified before it's returned, so a wrong key or a broken program
+    /// is found here rather than by the node
+    ///
+    fn sign(&self, msg: &[u8]) -> Result<Signature> {
+        let output = self.run("sign", Some(hex::encode(msg).as_str()))?;
+        let bytes = hex::decode(output.trim_start_matches("0x"))
+            .map_err(|e| DB3Error::SignMessageError(format!("bad signature hex {e}")))?;
+        let signature = Signature::from_bytes(bytes.as_ref())
+            .map_err(|e| DB3Error::SignMessageError(format!("bad signature {e}")))?;
+        let address = signature.verify(msg)?;
+        if address != self.address {
+            return Err(DB3Error::SignMessageError(
+                "the signature is not signed by the key of the signer".to_string(),
+            ));
+        }
+        Ok(signature)
+    }
+
+    fn get_address(&self) -> Result<DB3Address> {
+        Ok(self.address)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -61,6 +169,10 @@ mod tests {
             key: "k1".as_bytes().to_vec(),
             value: "value1".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let mutation = Mutation {
             ns: "my_twitter".as_bytes().to_vec(),
@@ -92,6 +204,75 @@ mod tests {
         );
     }
 
+    // a signer outside the process only exposes the signature
+    struct ExternalSigner {
+        inner: Db3MultiSchemeSigner,
+    }
+
+    impl Signer for ExternalSigner {
+        fn sign(&self, msg: &[u8]) -> Result<Signature> {
+            self.inner.sign(msg)
+        }
+
+        fn get_address(&self) -> Result<DB3Address> {
+            self.inner.get_address()
+        }
+    }
+
+    #[test]
+    fn db3_external_signer_test() {
+        let seed: [u8; 32] = [0; 32];
+        let (address, keypair) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let signer: Box<dyn Signer> = Box::new(ExternalSigner {
+            inner: Db3MultiSchemeSigner::new(keypair),
+        });
+        let msg: [u8; 4] = [1, 2, 3, 4];
+        let signature = signer.sign(&msg).unwrap();
+        let result = signature.verify(&msg).unwrap();
+        assert_eq!(address.to_vec(), result.to_vec());
+        assert_eq!(address.to_vec(), signer.get_address().unwrap().to_vec());
+    }
+
+    fn build_command_signer(address: &str, signature: &str) -> Result<CommandSigner> {
+        // the action is $0 of the script
+        let script = format!(
+            "if [ \"$0\" = address ]; then echo {address}; else cat > /dev/null; echo {signature}; fi"
+        );
+        CommandSigner::new("sh", &["-c".to_string(), script])
+    }
+
+    #[test]
+    fn db3_command_signer_test() {
+        let seed: [u8; 32] = [0; 32];
+        let (address, keypair) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::ED25519).unwrap();
+        let msg: [u8; 4] = [1, 2, 3, 4];
+        let signature = Db3MultiSchemeSigner::new(keypair).sign(&msg).unwrap();
+        let address_hex = format!("0x{}", hex::encode(address.as_ref()));
+        let signature_hex = hex::encode(signature.as_ref());
+        let signer: Box<dyn Signer> =
+            Box::new(build_command_signer(address_hex.as_str(), signature_hex.as_str()).unwrap());
+        assert_eq!(address.to_vec(), signer.get_address().unwrap().to_vec());
+        let signed = signer.sign(&msg).unwrap();
+        assert_eq!(signature.as_ref(), signed.as_ref());
+        // the signature of another message is rejected
+        let other_msg: [u8; 4] = [4, 3, 2, 1];
+        assert!(signer.sign(&other_msg).is_err());
+        // the signature of another key is rejected
+        let signer =
+            build_command_signer(hex::encode([1u8; 20]).as_str(), signature_hex.as_str()).unwrap();
+        assert!(matches!(
+            signer.sign(&msg),
+            Err(DB3Error::SignMessageError(_))
+        ));
+        assert!(build_command_signer("bad", signature_hex.as_str()).is_err());
+        let signer = build_command_signer(address_hex.as_str(), "not hex").unwrap();
+        assert!(signer.sign(&msg).is_err());
+        assert!(CommandSigner::new("false", &[]).is_err());
+    }
+
     #[test]
     fn db3_signer_ed25519_smoke_test() {
         db3_signer_smoke_test(&SignatureScheme::ED25519);
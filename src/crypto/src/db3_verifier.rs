@@ -17,10 +17,22 @@
 //
 
 use crate::account_id::AccountId;
-use crate::db3_signature::{DB3Signature, Signature};
+use crate::db3_address::DB3Address;
+use crate::db3_signature::{DB3Signature, DB3SignatureInner, Signature};
+use crate::signature_scheme::SignatureScheme;
 use db3_error::{DB3Error, Result};
 use signature::Signature as _;
 
+///
+/// the payload and the signature of a write request to verify later
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPayload {
+    pub msg: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub scheme: i32,
+}
+
 pub struct DB3Verifier {}
 
 impl DB3Verifier {
@@ -30,4 +42,226 @@ impl DB3Verifier {
         let db3_address = signature.verify(&msg)?;
         Ok(AccountId::new(db3_address))
     }
+
+    ///
+    /// verify the signature with the scheme tag carried by the write request,
+    /// the signature must be signed with the same scheme unless the tag is unset
+    ///
+    pub fn verify_with_scheme(msg: &[u8], signature_raw: &[u8], scheme: i32) -> Result<AccountId> {
+        let signature = Self::parse_with_scheme(signature_raw, scheme)?;
+        let db3_address = signature.verify(&msg)?;
+        Ok(AccountId::new(db3_address))
+    }
+
+    fn parse_with_scheme(signature_raw: &[u8], scheme: i32) -> Result<Signature> {
+        let expected_scheme = SignatureScheme::from_proto(scheme)?;
+        let signature = Signature::from_bytes(signature_raw)
+            .map_err(|e| DB3Error::InvalidSignature(format!("{e}")))?;
+        // the unset tag takes the scheme from the flag byte of the signature
+        let expected_scheme = match expected_scheme {
+            Some(expected_scheme) => expected_scheme,
+            None => return Ok(signature),
+        };
+        if signature.scheme().flag() != expected_scheme.flag() {
+            return Err(DB3Error::InvalidSignature(format!(
+                "the signature does not match the scheme {scheme}"
+            )));
+        }
+        Ok(signature)
+    }
+
+    ///
+    /// the account of the public key carried by the signature. the signature is NOT
+    /// verified, so the account must not be trusted before verify_batch accepts it
+    ///
+    pub fn signer_with_scheme(signature_raw: &[u8], scheme: i32) -> Result<AccountId> {
+        let signature = Self::parse_with_scheme(signature_raw, scheme)?;
+        let db3_address = match &signature {
+            Signature::Ed25519DB3Signature(sig) => {
+                let (_, pk) = sig.get_verification_inputs()?;
+                DB3Address::from(&pk)
+            }
+            Signature::Secp256k1DB3Signature(sig) => {
+                let (_, pk) = sig.get_verification_inputs()?;
+                DB3Address::from(&pk)
+            }
+        };
+        Ok(AccountId::new(db3_address))
+    }
+
+    ///
+    /// verify the signatures of many payloads, the results are in the order of payloads.
+    /// the ed25519 signatures are verified in one batch and one by one only if the batch
+    /// fails, so the bad ones are found. the secp256k1 signatures are ecdsa ones which
+    /// can't be batch verified, they are verified one by one. the random coefficients of
+    /// the batch are derived from the payloads, so every node gets the same results for
+    /// the same payloads
+    ///
+    pub fn verify_batch(payloads: &[SignedPayload]) -> Vec<Result<AccountId>> {
+        let mut results: Vec<Option<Result<AccountId>>> = Vec::with_capacity(payloads.len());
+        let mut batch: Vec<(usize, DB3Address)> = Vec::new();
+        let mut messages: Vec<&[u8]> = Vec::new();
+        let mut signatures: Vec<ed25519_dalek::Signature> = Vec::new();
+        let mut public_keys: Vec<ed25519_dalek::PublicKey> = Vec::new();
+        for (i, payload) in payloads.iter().enumerate() {
+            let signature =
+                match Self::parse_with_scheme(payload.signature.as_ref(), payload.scheme) {
+                    Ok(signature) => signature,
+                    Err(e) => {
+                        results.push(Some(Err(e)));
+                        continue;
+                    }
+                };
+            let batch_inputs = match &signature {
+                Signature::Ed25519DB3Signature(sig) => {
+                    sig.get_verification_inputs().ok().and_then(|(_, pk)| {
+                        let dalek_sig = ed25519_dalek::Signature::try_from(sig.signature_bytes());
+                        let dalek_pk = ed25519_dalek::PublicKey::from_bytes(sig.public_key_bytes());
+                        match (dalek_sig, dalek_pk) {
+                            (Ok(s), Ok(p)) => Some((DB3Address::from(&pk), s, p)),
+                            _ => None,
+                        }
+                    })
+                }
+                Signature::Secp256k1DB3Signature(_) => None,
+            };
+            match batch_inputs {
+                Some((db3_address, s, p)) => {
+                    batch.push((i, db3_address));
+                    messages.push(payload.msg.as_ref());
+                    signatures.push(s);
+                    public_keys.push(p);
+                    results.push(None);
+                }
+                None => {
+                    let result = signature.verify(payload.msg.as_ref()).map(AccountId::new);
+                    results.push(Some(result));
+                }
+            }
+        }
+        if !batch.is_empty() {
+            if ed25519_dalek::verify_batch(&messages, &signatures, &public_keys).is_ok() {
+                for (i, db3_address) in batch {
+                    results[i] = Some(Ok(AccountId::new(db3_address)));
+                }
+            } else {
+                // find the bad signatures
+                for (i, _) in batch {
+                    let payload = &payloads[i];
+                    results[i] = Some(Self::verify_with_scheme(
+                        payload.msg.as_ref(),
+                        payload.signature.as_ref(),
+                        payload.scheme,
+                    ));
+                }
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                result
+                    .unwrap_or_else(|| Err(DB3Error::InvalidSignature("not verified".to_string())))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_derive;
+    use signature::Signer;
+
+    fn sign(scheme: &SignatureScheme, msg: &[u8]) -> (AccountId, Signature) {
+        let seed: [u8; 32] = [0; 32];
+        let (address, keypair) =
+            key_derive::derive_key_pair_from_path(&seed, None, scheme).unwrap();
+        (AccountId::new(address), keypair.try_sign(msg).unwrap())
+    }
+
+    #[test]
+    fn it_verify_ed25519_with_scheme() {
+        let msg: [u8; 4] = [1, 2, 3, 4];
+        let (account_id, signature) = sign(&SignatureScheme::ED25519, &msg);
+        let result = DB3Verifier::verify_with_scheme(
+            &msg,
+            signature.as_ref(),
+            SignatureScheme::ED25519.to_proto(),
+        );
+        assert_eq!(account_id.addr.to_vec(), result.unwrap().addr.to_vec());
+    }
+
+    #[test]
+    fn it_verify_with_unset_scheme() {
+        let msg: [u8; 4] = [1, 2, 3, 4];
+        // the scheme tag is absent in the requests of old clients
+        for scheme in [SignatureScheme::Secp256k1, SignatureScheme::ED25519] {
+            let (account_id, signature) = sign(&scheme, &msg);
+            let result = DB3Verifier::verify_with_scheme(&msg, signature.as_ref(), 0);
+            assert_eq!(account_id.addr.to_vec(), result.unwrap().addr.to_vec());
+        }
+    }
+
+    #[test]
+    fn it_verify_batch_and_find_bad_signatures() {
+        let mut payloads: Vec<SignedPayload> = Vec::new();
+        let mut expected: Vec<Option<AccountId>> = Vec::new();
+        for i in 0..8u8 {
+            let msg = vec![i; 4];
+            let scheme = if i % 3 == 0 {
+                SignatureScheme::Secp256k1
+            } else {
+                SignatureScheme::ED25519
+            };
+            let (account_id, signature) = sign(&scheme, &msg);
+            payloads.push(SignedPayload {
+                msg,
+                signature: signature.as_ref().to_vec(),
+                scheme: scheme.to_proto(),
+            });
+            expected.push(Some(account_id));
+        }
+        let signer =
+            DB3Verifier::signer_with_scheme(payloads[1].signature.as_ref(), payloads[1].scheme)
+                .unwrap();
+        assert_eq!(
+            expected[1].as_ref().unwrap().addr.to_vec(),
+            signer.addr.to_vec()
+        );
+        let results = DB3Verifier::verify_batch(&payloads);
+        assert!(results.iter().all(|r| r.is_ok()));
+        // tamper an ed25519 payload and a secp256k1 one
+        payloads[2].msg = vec![100; 4];
+        expected[2] = None;
+        payloads[3].msg = vec![100; 4];
+        expected[3] = None;
+        payloads[4].scheme = 100;
+        expected[4] = None;
+        let results = DB3Verifier::verify_batch(&payloads);
+        assert_eq!(expected.len(), results.len());
+        for (result, expected) in results.iter().zip(expected.iter()) {
+            match expected {
+                Some(account_id) => {
+                    assert_eq!(
+                        account_id.addr.to_vec(),
+                        result.as_ref().unwrap().addr.to_vec()
+                    )
+                }
+                None => assert!(result.is_err()),
+            }
+        }
+    }
+
+    #[test]
+    fn it_reject_mismatched_or_unsupported_scheme() {
+        let msg: [u8; 4] = [1, 2, 3, 4];
+        let (_, signature) = sign(&SignatureScheme::ED25519, &msg);
+        assert!(DB3Verifier::verify_with_scheme(
+            &msg,
+            signature.as_ref(),
+            SignatureScheme::Secp256k1.to_proto()
+        )
+        .is_err());
+        assert!(DB3Verifier::verify_with_scheme(&msg, signature.as_ref(), 100).is_err());
+    }
 }
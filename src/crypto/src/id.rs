@@ -65,6 +65,11 @@ impl TxId {
         base64ct::Base64::encode_string(self.as_ref())
     }
 
+    pub fn try_from_base64(data: &str) -> std::result::Result<Self, DB3Error> {
+        let bytes = base64ct::Base64::decode_vec(data).map_err(|_| DB3Error::HashCodecError)?;
+        Self::try_from_bytes(bytes.as_ref())
+    }
+
     pub fn try_from_bytes(data: &[u8]) -> std::result::Result<Self, DB3Error> {
         let arr: [u8; TX_ID_LENGTH] = data.try_into().map_err(|_| DB3Error::InvalidAddress)?;
         Ok(Self { data: arr })
@@ -122,6 +127,26 @@ impl DbId {
     pub fn address(&self) -> &DB3Address {
         &self.addr
     }
+
+    ///
+    /// the address of the database created by the sender with the nonce, the client can
+    /// get the address before the database mutation is submitted
+    ///
+    pub fn derive(sender: &DB3Address, nonce: u64) -> std::result::Result<Self, DB3Error> {
+        let mut bs = [0u8; std::mem::size_of::<u64>()];
+        bs.as_mut()
+            .write_u64::<BigEndian>(nonce)
+            .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+        let mut hasher = Sha3_256::default();
+        hasher.update(bs.as_ref());
+        hasher.update(sender);
+        let g_arr = hasher.finalize();
+        let mut res = [0u8; DB3_ADDRESS_LENGTH];
+        res.copy_from_slice(&AsRef::<[u8]>::as_ref(&g_arr)[..DB3_ADDRESS_LENGTH]);
+        Ok(Self {
+            addr: DB3Address::from(&res),
+        })
+    }
 }
 
 impl AsRef<[u8]> for DbId {
@@ -165,19 +190,7 @@ impl From<DB3Address> for DbId {
 impl TryFrom<(&DB3Address, u64)> for DbId {
     type Error = DB3Error;
     fn try_from(input: (&DB3Address, u64)) -> std::result::Result<Self, DB3Error> {
-        let mut bs = [0u8; std::mem::size_of::<u64>()];
-        bs.as_mut()
-            .write_u64::<BigEndian>(input.1)
-            .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
-        let mut hasher = Sha3_256::default();
-        hasher.update(bs.as_ref());
-        hasher.update(input.0);
-        let g_arr = hasher.finalize();
-        let mut res = [0u8; DB3_ADDRESS_LENGTH];
-        res.copy_from_slice(&AsRef::<[u8]>::as_ref(&g_arr)[..DB3_ADDRESS_LENGTH]);
-        Ok(Self {
-            addr: DB3Address::from(&res),
-        })
+        Self::derive(input.0, input.1)
     }
 }
 
@@ -187,4 +200,13 @@ mod tests {
 
     #[test]
     fn it_works() {}
+
+    #[test]
+    fn it_tx_id_base64_roundtrip() {
+        let tx_id = TxId::from("tx".as_bytes());
+        let decoded = TxId::try_from_base64(tx_id.to_base64().as_str()).unwrap();
+        assert!(tx_id == decoded);
+        assert!(TxId::try_from_base64("not base64!").is_err());
+        assert!(TxId::try_from_base64("AAAA").is_err());
+    }
 }
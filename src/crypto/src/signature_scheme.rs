@@ -16,6 +16,7 @@
 //
 
 use db3_error::{DB3Error, Result};
+use db3_proto::db3_base_proto::SignatureScheme as ProtoSignatureScheme;
 
 pub enum SignatureScheme {
     // the validator can use ed25519
@@ -46,4 +47,24 @@ impl SignatureScheme {
             _ => Err(DB3Error::KeyCodecError("Invalid key scheme".to_string())),
         }
     }
+
+    /// convert to the scheme tag carried by the write request
+    pub fn to_proto(&self) -> i32 {
+        match self {
+            SignatureScheme::ED25519 => ProtoSignatureScheme::Ed25519.into(),
+            SignatureScheme::Secp256k1 => ProtoSignatureScheme::Secp256k1.into(),
+        }
+    }
+
+    /// convert from the scheme tag carried by the write request, none if the tag is unset
+    pub fn from_proto(scheme: i32) -> Result<Option<SignatureScheme>> {
+        match ProtoSignatureScheme::from_i32(scheme) {
+            Some(ProtoSignatureScheme::FromSignature) => Ok(None),
+            Some(ProtoSignatureScheme::Ed25519) => Ok(Some(SignatureScheme::ED25519)),
+            Some(ProtoSignatureScheme::Secp256k1) => Ok(Some(SignatureScheme::Secp256k1)),
+            None => Err(DB3Error::InvalidSignature(format!(
+                "unsupported signature scheme {scheme}"
+            ))),
+        }
+    }
 }
@@ -52,6 +52,10 @@ mod tests {
             key: "k1".as_bytes().to_vec(),
             value: "value1".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let mutation = Mutation {
             ns: "my_twitter".as_bytes().to_vec(),
@@ -75,6 +75,86 @@ pub enum DB3Error {
     SignatureKeyGenError(String),
     #[error("fail to sign message for {0}")]
     SignMessageError(String),
+    #[error("invalid nonce {0}, the nonce has been used")]
+    InvalidNonceError(u64),
+    #[error("insufficient credits with error {0}")]
+    InsufficientCreditsError(String),
+    #[error("fail to verify the request for {reason}")]
+    VerificationError { reason: String },
+    #[error("fail to compact the store with error {0}")]
+    CompactStoreError(String),
+    #[error("the tx is rejected with code {code} for {log}")]
+    TxRejectedError { code: u32, log: String },
+    #[error("no permission to the namespace for {0}")]
+    NsPermissionError(String),
+    #[error("invalid genesis config with error {0}")]
+    InvalidGenesisError(String),
+    #[error("the compare and swap conflicts for {0}")]
+    CompareAndSwapConflict(String),
+    #[error("invalid data root with error {0}")]
+    InvalidDataRootError(String),
+    #[error("the namespace quota is exceeded for {0}")]
+    QuotaExceededError(String),
+    #[error("fail to record metrics with error {0}")]
+    MetricsError(String),
+    #[error("fail to reset the store with error {0}")]
+    ResetStoreError(String),
+    #[error("invalid key file {0}")]
+    InvalidKeyFile(String),
+    #[error("invalid nonce {nonce}, the nonce should be greater than {floor}")]
+    StaleNonceError { nonce: u64, floor: u64 },
+    #[error("the document does not match the schema for {0}")]
+    InvalidDocumentError(String),
 }
 
 pub type Result<T> = std::result::Result<T, DB3Error>;
+
+impl From<DB3Error> for std::io::Error {
+    fn from(e: DB3Error) -> Self {
+        use std::io::ErrorKind;
+        let kind = match &e {
+            DB3Error::DatabaseNotFound(_) => ErrorKind::NotFound,
+            DB3Error::InvalidAddress
+            | DB3Error::InvalidSigner
+            | DB3Error::InvalidNonceError(_)
+            | DB3Error::StaleNonceError { .. }
+            | DB3Error::InvalidDocumentError(_)
+            | DB3Error::InvalidGenesisError(_)
+            | DB3Error::InvalidDataRootError(_)
+            | DB3Error::KeyCodecError(_) => ErrorKind::InvalidInput,
+            DB3Error::LoadKeyPairError(_)
+            | DB3Error::InvalidKeyFile(_)
+            | DB3Error::InvalidSignature(_)
+            | DB3Error::HashCodecError => ErrorKind::InvalidData,
+            DB3Error::VerifyFailed(_)
+            | DB3Error::VerificationError { .. }
+            | DB3Error::NsPermissionError(_)
+            | DB3Error::QuerySessionVerifyError(_) => ErrorKind::PermissionDenied,
+            DB3Error::StateLockBusyError => ErrorKind::WouldBlock,
+            _ => ErrorKind::Other,
+        };
+        std::io::Error::new(kind, format!("{e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn it_keep_kind_and_message_in_io_error() {
+        let e: std::io::Error = DB3Error::DatabaseNotFound("0x1".to_string()).into();
+        assert_eq!(ErrorKind::NotFound, e.kind());
+        assert_eq!("database with addr 0x1 was not found", e.to_string());
+        let e: std::io::Error = DB3Error::KeyCodecError("bad key".to_string()).into();
+        assert_eq!(ErrorKind::InvalidInput, e.kind());
+        assert_eq!("fail to codec key with error bad key", e.to_string());
+        let e: std::io::Error = DB3Error::LoadKeyPairError("bad file".to_string()).into();
+        assert_eq!(ErrorKind::InvalidData, e.kind());
+        assert_eq!("fail to load key pair bad file", e.to_string());
+        let e: std::io::Error = DB3Error::SubmitRequestError("timeout".to_string()).into();
+        assert_eq!(ErrorKind::Other, e.kind());
+        assert_eq!("fail to submit request with error timeout", e.to_string());
+    }
+}
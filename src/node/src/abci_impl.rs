@@ -18,105 +18,835 @@
 use shadow_rs::shadow;
 shadow!(build);
 use crate::node_storage::NodeStorage;
+use crate::rate_limiter::RateLimiter;
+use crate::tx_dedup_cache::TxDedupCache;
 use bytes::Bytes;
-use db3_crypto::{db3_address::DB3Address as AccountAddress, db3_verifier, id::TxId};
-use db3_proto::db3_mutation_proto::{DatabaseMutation, Mutation, PayloadType, WriteRequest};
+use db3_base::strings;
+use db3_crypto::{
+    db3_address::DB3Address as AccountAddress,
+    db3_verifier::{self, SignedPayload},
+    id::{AccountId, TxId},
+};
+use db3_error::DB3Error;
+use db3_proto::db3_base_proto::{ChainId, UnitType, Units};
+use db3_proto::db3_mutation_proto::{
+    CheckTxCode, DatabaseMutation, Mutation, MutationAction, PayloadType, WriteRequest,
+};
+use db3_proto::db3_node_proto::PendingQueue;
 use db3_proto::db3_session_proto::{QuerySession, QuerySessionInfo};
 use db3_session::query_session_verifier;
-use db3_storage::kv_store::KvStore;
+use db3_session::session_manager::DEFAULT_SESSION_QUERY_LIMIT;
+use db3_storage::kv_store::{KvStore, MutationLimits};
 use hex;
 use prost::Message;
 use std::pin::Pin;
 use std::sync::atomic::AtomicU64;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use tendermint_abci::Application;
 use tendermint_proto::abci::{
-    Event, RequestBeginBlock, RequestCheckTx, RequestDeliverTx, RequestInfo, RequestQuery,
-    ResponseBeginBlock, ResponseCheckTx, ResponseCommit, ResponseDeliverTx, ResponseInfo,
-    ResponseQuery,
+    CheckTxType, Event, RequestBeginBlock, RequestCheckTx, RequestDeliverTx, RequestInfo,
+    RequestQuery, ResponseBeginBlock, ResponseCheckTx, ResponseCommit, ResponseDeliverTx,
+    ResponseInfo, ResponseQuery,
 };
-use tracing::{debug, info, span, warn, Level};
+use tracing::{debug, field, info, span, warn, Level, Span};
+
+/// the codespace of the txs rejected by check_tx
+const CHECK_TX_CODESPACE: &str = "db3";
 
 #[derive(Clone)]
 pub struct NodeState {
     total_storage_bytes: Arc<AtomicU64>,
     total_mutations: Arc<AtomicU64>,
     total_query_sessions: Arc<AtomicU64>,
+    last_commit_txs: Arc<AtomicU64>,
+    last_commit_micros: Arc<AtomicU64>,
+}
+
+impl NodeState {
+    pub fn total_storage_bytes(&self) -> u64 {
+        self.total_storage_bytes
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn total_mutations(&self) -> u64 {
+        self.total_mutations
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn total_query_sessions(&self) -> u64 {
+        self.total_query_sessions
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// the number of txs applied by the last commit with pending txs
+    pub fn last_commit_txs(&self) -> u64 {
+        self.last_commit_txs
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// the microseconds the last commit with pending txs took
+    pub fn last_commit_micros(&self) -> u64 {
+        self.last_commit_micros
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+///
+/// the summary of a commit, the operators tune the block time of tendermint by it
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommitStats {
+    pub applied: usize,
+    /// the txs with bad signatures or applied before
+    pub skipped: usize,
+    /// the txs failing to apply
+    pub failed: usize,
+    /// the time of applying the txs
+    pub apply_time: Duration,
+    /// the time of persisting the store and computing the root hash
+    pub hash_time: Duration,
+}
+
+impl CommitStats {
+    pub fn total_time(&self) -> Duration {
+        self.apply_time + self.hash_time
+    }
+
+    pub fn summary(&self, height: i64) -> String {
+        format!(
+            "commit block {height} with {} applied, {} skipped and {} failed txs in {}ms, apply {}ms, hash {}ms",
+            self.applied,
+            self.skipped,
+            self.failed,
+            self.total_time().as_millis(),
+            self.apply_time.as_millis(),
+            self.hash_time.as_millis()
+        )
+    }
+}
+
+///
+/// the tx delivered in the current block
+///
+enum PendingTx {
+    Mutation(AccountAddress, TxId, Mutation),
+    QuerySession(AccountAddress, AccountAddress, TxId, QuerySessionInfo),
+    Database(AccountAddress, DatabaseMutation, TxId),
+}
+
+impl PendingTx {
+    fn queue_name(&self) -> &'static str {
+        match self {
+            PendingTx::Mutation(..) => "mutation",
+            PendingTx::QuerySession(..) => "query_session",
+            PendingTx::Database(..) => "database",
+        }
+    }
+
+    fn tx_id(&self) -> &TxId {
+        match self {
+            PendingTx::Mutation(_, tx_id, _) => tx_id,
+            PendingTx::QuerySession(_, _, tx_id, _) => tx_id,
+            PendingTx::Database(_, _, tx_id) => tx_id,
+        }
+    }
+}
+
+///
+/// a shared handle to the txs delivered but not committed, only the metadata of
+/// the txs is exposed
+///
+#[derive(Clone)]
+pub struct PendingTxQueue {
+    txs: Arc<Mutex<Vec<PendingTx>>>,
+}
+
+impl PendingTxQueue {
+    ///
+    /// the length and the first tx ids of every payload type
+    ///
+    pub fn summarize(&self, sample_size: usize) -> Vec<PendingQueue> {
+        let mut queues: Vec<PendingQueue> = ["mutation", "query_session", "database"]
+            .iter()
+            .map(|name| PendingQueue {
+                name: name.to_string(),
+                len: 0,
+                sample_tx_ids: vec![],
+            })
+            .collect();
+        if let Ok(txs) = self.txs.lock() {
+            for tx in txs.iter() {
+                if let Some(queue) = queues.iter_mut().find(|q| q.name == tx.queue_name()) {
+                    queue.len += 1;
+                    if queue.sample_tx_ids.len() < sample_size {
+                        queue.sample_tx_ids.push(tx.tx_id().as_ref().to_vec());
+                    }
+                }
+            }
+        }
+        queues
+    }
+}
+
+///
+/// the retain height of ResponseCommit, the blocks below it can be pruned by tendermint
+/// and 0 keeps all the blocks
+///
+fn retain_height(height: u64, retain_blocks: u64) -> i64 {
+    if retain_blocks == 0 {
+        return 0;
+    }
+    height.saturating_sub(retain_blocks) as i64
 }
 
 #[derive(Clone)]
 pub struct AbciImpl {
     node_store: Arc<Mutex<Pin<Box<NodeStorage>>>>,
-    pending_mutation: Arc<Mutex<Vec<(AccountAddress, TxId, Mutation)>>>,
-    pending_query_session:
-        Arc<Mutex<Vec<(AccountAddress, AccountAddress, TxId, QuerySessionInfo)>>>,
+    // the txs of all payload types are kept in the order of delivery, so a tx can
+    // depend on the one delivered before it in the same block
+    pending_txs: Arc<Mutex<Vec<PendingTx>>>,
     node_state: Arc<NodeState>,
-    pending_databases: Arc<Mutex<Vec<(AccountAddress, DatabaseMutation, TxId)>>>,
+    recent_txs: Arc<Mutex<TxDedupCache>>,
+    mutation_limits: MutationLimits,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+    // the txs signed for other chains are rejected
+    chain_id: ChainId,
+    // the node is paid by the query count, a session claiming more queries is rejected
+    max_queries_per_session: i32,
+    // the mutations with a lower gas price are rejected
+    min_gas_price: Units,
+    // the number of recent blocks tendermint keeps, 0 keeps all the blocks
+    retain_blocks: u64,
+    // verify the signatures of the delivered txs in one batch when committing
+    batch_verify: bool,
+    // the signatures of the delivered txs to verify in commit
+    unverified_txs: Arc<Mutex<Vec<(TxId, SignedPayload)>>>,
 }
 
 impl AbciImpl {
-    pub fn new(node_store: Arc<Mutex<Pin<Box<NodeStorage>>>>) -> Self {
+    pub fn new(
+        node_store: Arc<Mutex<Pin<Box<NodeStorage>>>>,
+        mempool_dedup_size: usize,
+        mutation_limits: MutationLimits,
+        max_tx_per_account_per_sec: u64,
+    ) -> Self {
         Self {
             node_store,
-            pending_mutation: Arc::new(Mutex::new(Vec::new())),
-            pending_query_session: Arc::new(Mutex::new(Vec::new())),
+            pending_txs: Arc::new(Mutex::new(Vec::new())),
             node_state: Arc::new(NodeState {
                 total_storage_bytes: Arc::new(AtomicU64::new(0)),
                 total_mutations: Arc::new(AtomicU64::new(0)),
                 total_query_sessions: Arc::new(AtomicU64::new(0)),
+                last_commit_txs: Arc::new(AtomicU64::new(0)),
+                last_commit_micros: Arc::new(AtomicU64::new(0)),
             }),
-            pending_databases: Arc::new(Mutex::new(Vec::new())),
+            recent_txs: Arc::new(Mutex::new(TxDedupCache::new(mempool_dedup_size))),
+            mutation_limits,
+            rate_limiter: Arc::new(Mutex::new(RateLimiter::new(max_tx_per_account_per_sec))),
+            chain_id: ChainId::DevNet,
+            max_queries_per_session: DEFAULT_SESSION_QUERY_LIMIT,
+            min_gas_price: Units {
+                utype: UnitType::Tai.into(),
+                amount: 0,
+            },
+            retain_blocks: 0,
+            batch_verify: false,
+            unverified_txs: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// the chain id of the node, the default is devnet
+    pub fn with_chain_id(mut self, chain_id: ChainId) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// the max query count of a query session, the default is the query limit of a session
+    pub fn with_max_queries_per_session(mut self, max_queries_per_session: i32) -> Self {
+        self.max_queries_per_session = max_queries_per_session;
+        self
+    }
+
+    /// the min gas price of a mutation, the default is 0 tai
+    pub fn with_min_gas_price(mut self, min_gas_price: Units) -> Self {
+        self.min_gas_price = min_gas_price;
+        self
+    }
+
+    ///
+    /// let tendermint prune the blocks older than the recent ones, the state in the
+    /// store is not affected and only the block history is pruned
+    ///
+    pub fn with_retain_blocks(mut self, retain_blocks: u64) -> Self {
+        self.retain_blocks = retain_blocks;
+        self
+    }
+
+    ///
+    /// defer verifying the signatures of the delivered txs to commit, where they are
+    /// verified in one batch. the txs with a bad signature are skipped in commit rather
+    /// than rejected by deliver_tx, which changes the block results, so it's a setting
+    /// of the chain taken from the genesis and never one of a node
+    ///
+    pub fn with_batch_verify(mut self, batch_verify: bool) -> Self {
+        self.batch_verify = batch_verify;
+        self
+    }
+
     #[inline]
     pub fn get_node_state(&self) -> &Arc<NodeState> {
         &self.node_state
     }
-}
 
-impl Application for AbciImpl {
-    fn info(&self, _request: RequestInfo) -> ResponseInfo {
-        // the store must be ready when using it
-        match self.node_store.lock() {
-            Ok(mut store) => {
-                let s = store.get_auth_store();
-                info!(
-                    "height {} hash {}",
-                    s.get_last_block_state().block_height,
-                    hex::encode_upper(s.get_last_block_state().abci_hash)
+    pub fn get_pending_queue(&self) -> PendingTxQueue {
+        PendingTxQueue {
+            txs: self.pending_txs.clone(),
+        }
+    }
+
+    fn acquire_tx_quota(
+        &self,
+        addr: &AccountAddress,
+    ) -> std::result::Result<bool, (CheckTxCode, String)> {
+        match self.rate_limiter.lock() {
+            Ok(mut limiter) => Ok(limiter.try_acquire(addr, Instant::now())),
+            Err(e) => Err((
+                CheckTxCode::InternalError,
+                format!("fail to lock the rate limiter for {e}"),
+            )),
+        }
+    }
+
+    ///
+    /// the response of check_tx, the tx is removed from mempool if it's not accepted
+    ///
+    fn check_tx_response(code: CheckTxCode, log: String) -> ResponseCheckTx {
+        let codespace = match code {
+            CheckTxCode::Accepted => "",
+            _ => CHECK_TX_CODESPACE,
+        };
+        ResponseCheckTx {
+            code: code as u32,
+            data: Bytes::new(),
+            log,
+            info: "".to_string(),
+            gas_wanted: 1,
+            gas_used: 0,
+            events: vec![],
+            codespace: codespace.to_string(),
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// check the signature and the payload of a write request, the new txs are
+    /// throttled by the rate limiter of the sender
+    ///
+    fn check_write_request(&self, tx: &[u8], is_new: bool) -> ResponseCheckTx {
+        match self.do_check_write_request(tx, is_new) {
+            Ok(_) => {
+                debug!("transaction has been accepted");
+                Self::check_tx_response(CheckTxCode::Accepted, "".to_string())
+            }
+            Err((code, log)) => {
+                warn!("invalid transaction has been checked for error {}", log);
+                Self::check_tx_response(code, log)
+            }
+        }
+    }
+
+    ///
+    /// fill the sender and the payload type of the span of check_tx or deliver_tx once
+    /// the signature is verified
+    ///
+    fn record_tx_fields(account_id: &AccountId, payload_type: i32) {
+        let span = Span::current();
+        span.record("sender", account_id.to_hex().as_str());
+        let payload_type = match PayloadType::from_i32(payload_type) {
+            Some(t) => format!("{:?}", t),
+            None => format!("{payload_type}"),
+        };
+        span.record("payload_type", payload_type.as_str());
+    }
+
+    ///
+    /// the signer of the delivered tx. the signature is verified here unless the batch
+    /// verification is enabled, then the signer is taken from the public key and the
+    /// signature is kept to verify in commit
+    ///
+    fn deliver_signer(&self, request: &WriteRequest, tx_id: &TxId) -> db3_error::Result<AccountId> {
+        if !self.batch_verify {
+            return db3_verifier::DB3Verifier::verify_with_scheme(
+                request.payload.as_ref(),
+                request.signature.as_ref(),
+                request.signature_scheme,
+            );
+        }
+        let account_id = db3_verifier::DB3Verifier::signer_with_scheme(
+            request.signature.as_ref(),
+            request.signature_scheme,
+        )?;
+        match self.unverified_txs.lock() {
+            Ok(mut txs) => txs.push((
+                *tx_id,
+                SignedPayload {
+                    msg: request.payload.to_vec(),
+                    signature: request.signature.to_vec(),
+                    scheme: request.signature_scheme,
+                },
+            )),
+            Err(e) => {
+                // the tx is rejected by deliver_tx rather than applied without verification
+                warn!(
+                    "fail to keep the signature of tx {} for {}",
+                    tx_id.to_base64(),
+                    e
+                );
+                return Err(DB3Error::StateLockBusyError);
+            }
+        }
+        Ok(account_id)
+    }
+
+    ///
+    /// verify the signatures kept by deliver_tx in one batch and return the ids of the
+    /// txs with a bad signature. the signatures are still verified if the lock is
+    /// poisoned, the list is never left half updated
+    ///
+    fn verify_unverified_txs(&self) -> Vec<TxId> {
+        let unverified_txs: Vec<(TxId, SignedPayload)> = match self.unverified_txs.lock() {
+            Ok(mut txs) => txs.drain(..).collect(),
+            Err(e) => {
+                warn!("the lock of the unverified txs is poisoned");
+                e.into_inner().drain(..).collect()
+            }
+        };
+        if unverified_txs.is_empty() {
+            return vec![];
+        }
+        let payloads: Vec<SignedPayload> = unverified_txs
+            .iter()
+            .map(|(_, payload)| payload.clone())
+            .collect();
+        let results = db3_verifier::DB3Verifier::verify_batch(&payloads);
+        unverified_txs
+            .iter()
+            .zip(results.iter())
+            .filter_map(|((tx_id, _), result)| match result {
+                Ok(_) => None,
+                Err(e) => {
+                    warn!("bad signature of tx {} for {}", tx_id.to_base64(), e);
+                    Some(*tx_id)
+                }
+            })
+            .collect()
+    }
+
+    ///
+    /// the pending txs stay valid when a thread panics holding the lock, so a poisoned
+    /// lock is recovered rather than making this node deliver the block differently
+    ///
+    fn lock_pending_txs(&self) -> MutexGuard<'_, Vec<PendingTx>> {
+        self.pending_txs.lock().unwrap_or_else(|e| {
+            warn!("recover the poisoned lock of the pending txs");
+            e.into_inner()
+        })
+    }
+
+    ///
+    /// the height and the app hash of the last committed block
+    ///
+    fn try_info(&self) -> db3_error::Result<ResponseInfo> {
+        let mut store = self
+            .node_store
+            .lock()
+            .map_err(|_| DB3Error::StateLockBusyError)?;
+        let s = store.get_auth_store();
+        info!(
+            "height {} hash {}",
+            s.get_last_block_state().block_height,
+            hex::encode_upper(s.get_last_block_state().abci_hash)
+        );
+        Ok(ResponseInfo {
+            data: "db3".to_string(),
+            version: shadow_rs::tag(),
+            app_version: 1,
+            last_block_height: s.get_last_block_state().block_height,
+            last_block_app_hash: Bytes::copy_from_slice(&s.get_last_block_state().abci_hash),
+        })
+    }
+
+    fn try_begin_block(&self, request: RequestBeginBlock) -> db3_error::Result<()> {
+        let header = request
+            .header
+            .ok_or_else(|| DB3Error::ApplyCommitError("the block has no header".to_string()))?;
+        let time = header.time.ok_or_else(|| {
+            DB3Error::ApplyCommitError(format!("the block {} has no time", header.height))
+        })?;
+        let mut store = self
+            .node_store
+            .lock()
+            .map_err(|_| DB3Error::StateLockBusyError)?;
+        store
+            .get_auth_store()
+            .begin_block(header.height as u64, time.seconds as u64);
+        Ok(())
+    }
+
+    ///
+    /// apply the txs delivered in the block and return the app hash. the txs failing to
+    /// apply are skipped, an error means the block can't be committed at all
+    ///
+    fn try_commit(&self) -> db3_error::Result<ResponseCommit> {
+        let pending_txs: Vec<PendingTx> = self.lock_pending_txs().drain(..).collect();
+        let bad_signature_txs = self.verify_unverified_txs();
+        let mut store = self
+            .node_store
+            .lock()
+            .map_err(|_| DB3Error::StateLockBusyError)?;
+        let s = store.get_auth_store();
+        let retain_height = retain_height(
+            s.get_current_block_state().block_height as u64,
+            self.retain_blocks,
+        );
+        let span = span!(Level::INFO, "commit").entered();
+        let pending_txs_len = pending_txs.len();
+        let mut stats = CommitStats::default();
+        let apply_start = Instant::now();
+        for item in pending_txs {
+            if bad_signature_txs.contains(item.tx_id()) {
+                warn!(
+                    "skip the tx {} with a bad signature",
+                    item.tx_id().to_base64()
                 );
-                ResponseInfo {
-                    data: "db3".to_string(),
-                    version: shadow_rs::tag(),
-                    app_version: 1,
-                    last_block_height: s.get_last_block_state().block_height,
-                    last_block_app_hash: Bytes::copy_from_slice(
-                        &s.get_last_block_state().abci_hash,
-                    ),
+                stats.skipped += 1;
+                continue;
+            }
+            // the tx delivered again after a crash or twice in a block is applied once
+            match s.is_tx_applied(item.tx_id()) {
+                Ok(false) => {}
+                Ok(true) => {
+                    info!("skip the applied tx {}", item.tx_id().to_base64());
+                    stats.skipped += 1;
+                    continue;
+                }
+                Err(e) => {
+                    warn!(
+                        "fail to check the tx {} for {}",
+                        item.tx_id().to_base64(),
+                        e
+                    );
+                    stats.failed += 1;
+                    continue;
+                }
+            }
+            match item {
+                PendingTx::Mutation(addr, tx_id, mutation) => {
+                    match s.apply_mutation(&addr, &tx_id, &mutation) {
+                        Ok((_gas, total_bytes)) => {
+                            stats.applied += 1;
+                            self.node_state
+                                .total_mutations
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            self.node_state.total_storage_bytes.fetch_add(
+                                total_bytes as u64,
+                                std::sync::atomic::Ordering::Relaxed,
+                            );
+                        }
+                        Err(e) => {
+                            // the mutation failing to apply, e.g. rejected by the
+                            // namespace acl, a quota or a compare and swap, is skipped
+                            // as a whole and nothing of it is written
+                            stats.failed += 1;
+                            warn!(
+                                "fail to apply mutation with tx {} for {}",
+                                tx_id.to_base64(),
+                                e
+                            );
+                        }
+                    }
+                }
+                PendingTx::QuerySession(addr, query_addr, tx_id, query_session_info) => {
+                    match s.apply_query_session(&addr, &query_addr, &tx_id, &query_session_info) {
+                        Ok(_) => {
+                            stats.applied += 1;
+                            self.node_state
+                                .total_query_sessions
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            stats.failed += 1;
+                            warn!(
+                                "fail to apply query session with tx {} for {}",
+                                tx_id.to_base64(),
+                                e
+                            );
+                        }
+                    }
+                }
+                PendingTx::Database(addr, mutation, tx_id) => {
+                    let nonce: u64 = match &mutation.meta {
+                        Some(m) => m.nonce,
+                        //TODO will not go to here
+                        None => 1,
+                    };
+                    match s.apply_database(&addr, nonce, &tx_id, &mutation) {
+                        Ok(_) => stats.applied += 1,
+                        Err(e) => {
+                            stats.failed += 1;
+                            warn!(
+                                "fail to apply database mutation with tx {} for {}",
+                                tx_id.to_base64(),
+                                e
+                            );
+                        }
+                    }
                 }
             }
-            Err(_) => todo!(),
+        }
+        span.exit();
+        stats.apply_time = apply_start.elapsed();
+        if pending_txs_len > 0 {
+            let height = s.get_current_block_state().block_height;
+            let hash_start = Instant::now();
+            //TODO how to revert
+            let result = s.commit();
+            stats.hash_time = hash_start.elapsed();
+            info!("{}", stats.summary(height));
+            self.node_state
+                .last_commit_txs
+                .store(stats.applied as u64, std::sync::atomic::Ordering::Relaxed);
+            self.node_state.last_commit_micros.store(
+                stats.total_time().as_micros() as u64,
+                std::sync::atomic::Ordering::Relaxed,
+            );
+            let hash = result?;
+            Ok(ResponseCommit {
+                data: Bytes::copy_from_slice(&hash),
+                retain_height,
+            })
+        } else {
+            let hash = s.root_hash();
+            debug!("commit hash {}", hex::encode_upper(hash));
+            Ok(ResponseCommit {
+                data: Bytes::copy_from_slice(&hash),
+                retain_height,
+            })
         }
     }
 
-    fn begin_block(&self, request: RequestBeginBlock) -> ResponseBeginBlock {
+    fn check_chain_id(&self, chain_id: i32) -> std::result::Result<(), (CheckTxCode, String)> {
+        if chain_id == self.chain_id as i32 {
+            return Ok(());
+        }
+        let tx_chain = match ChainId::from_i32(chain_id) {
+            Some(c) => format!("{:?}", c),
+            None => format!("{chain_id}"),
+        };
+        Err((
+            CheckTxCode::ChainIdMismatch,
+            format!(
+                "the tx is signed for chain {} but the node is on chain {:?}",
+                tx_chain, self.chain_id
+            ),
+        ))
+    }
+
+    ///
+    /// the prices are compared in tai and a mutation without gas price is taken as
+    /// the one with the price of 0
+    ///
+    fn check_gas_price(
+        &self,
+        gas_price: &Option<Units>,
+    ) -> std::result::Result<(), (CheckTxCode, String)> {
+        let min_gas_price = strings::convert_units(&self.min_gas_price, UnitType::Tai).amount;
+        let gas_price = gas_price
+            .as_ref()
+            .map(|price| strings::convert_units(price, UnitType::Tai).amount)
+            .unwrap_or(0);
+        if gas_price >= min_gas_price {
+            return Ok(());
+        }
+        Err((
+            CheckTxCode::GasPriceTooLow,
+            format!(
+                "the gas price {gas_price} tai is lower than the min gas price {min_gas_price} tai"
+            ),
+        ))
+    }
+
+    ///
+    /// reject the mutation whose compare and swap pairs conflict with the committed
+    /// values early, the pairs are checked again when the mutation is applied
+    ///
+    fn check_compare_and_swap(
+        &self,
+        addr: &AccountAddress,
+        mutation: &Mutation,
+    ) -> std::result::Result<(), (CheckTxCode, String)> {
+        let has_cas = mutation
+            .kv_pairs
+            .iter()
+            .any(|kv| kv.action == MutationAction::CompareAndSwapKv as i32);
+        if !has_cas {
+            return Ok(());
+        }
+        match self.node_store.lock() {
+            Ok(mut store) => store
+                .get_auth_store()
+                .check_compare_and_swap(addr, mutation)
+                .map_err(|e| match e {
+                    DB3Error::CompareAndSwapConflict(_) => {
+                        (CheckTxCode::CompareAndSwapConflict, format!("{e}"))
+                    }
+                    _ => (CheckTxCode::InvalidMutation, format!("{e}")),
+                }),
+            Err(e) => Err((
+                CheckTxCode::InternalError,
+                format!("fail to lock the node store for {e}"),
+            )),
+        }
+    }
+
+    ///
+    /// reject the mutation whose nonce has been used or is out of the nonce window early,
+    /// the nonce is checked again when the mutation is applied
+    ///
+    fn check_nonce(
+        &self,
+        addr: &AccountAddress,
+        mutation: &Mutation,
+    ) -> std::result::Result<(), (CheckTxCode, String)> {
         match self.node_store.lock() {
             Ok(mut store) => {
-                let s = store.get_auth_store();
-                if let Some(header) = request.header {
-                    if let Some(time) = header.time {
-                        s.begin_block(header.height as u64, time.seconds as u64);
-                    } else {
-                        todo!();
+                store
+                    .get_auth_store()
+                    .check_nonce(addr, mutation)
+                    .map_err(|e| match e {
+                        DB3Error::StaleNonceError { .. } | DB3Error::InvalidNonceError(_) => {
+                            (CheckTxCode::StaleNonce, format!("{e}"))
+                        }
+                        _ => (CheckTxCode::InvalidMutation, format!("{e}")),
+                    })
+            }
+            Err(e) => Err((
+                CheckTxCode::InternalError,
+                format!("fail to lock the node store for {e}"),
+            )),
+        }
+    }
+
+    fn do_check_write_request(
+        &self,
+        tx: &[u8],
+        is_new: bool,
+    ) -> std::result::Result<(), (CheckTxCode, String)> {
+        let request = WriteRequest::decode(tx).map_err(|e| {
+            (
+                CheckTxCode::DecodeError,
+                format!("invalid write request {e}"),
+            )
+        })?;
+        let account_id = db3_verifier::DB3Verifier::verify_with_scheme(
+            request.payload.as_ref(),
+            request.signature.as_ref(),
+            request.signature_scheme,
+        )
+        .map_err(|e| (CheckTxCode::VerifyError, format!("{e}")))?;
+        Self::record_tx_fields(&account_id, request.payload_type);
+        if is_new && !self.acquire_tx_quota(&account_id.addr)? {
+            warn!("too many transactions from {}", account_id.to_hex());
+            return Err((CheckTxCode::RateLimited, "rate limited".to_string()));
+        }
+        match PayloadType::from_i32(request.payload_type) {
+            Some(PayloadType::DatabasePayload) => {
+                let dm = DatabaseMutation::decode(request.payload.as_ref()).map_err(|e| {
+                    (
+                        CheckTxCode::InvalidDatabaseMutation,
+                        format!("invalid database mutation {e}"),
+                    )
+                })?;
+                match &dm.meta {
+                    Some(meta) => self.check_chain_id(meta.chain_id)?,
+                    None => {
+                        return Err((
+                            CheckTxCode::InvalidDatabaseMutation,
+                            "no meta for database mutation".to_string(),
+                        ));
                     }
-                } else {
-                    todo!();
                 }
             }
-            Err(_) => todo!(),
+            Some(PayloadType::MutationPayload) => {
+                let mutation = Mutation::decode(request.payload.as_ref()).map_err(|e| {
+                    (
+                        CheckTxCode::InvalidMutation,
+                        format!("invalid mutation {e}"),
+                    )
+                })?;
+                if !KvStore::is_valid(&mutation, &self.mutation_limits) {
+                    return Err((
+                        CheckTxCode::InvalidMutation,
+                        "invalid mutation for kv store".to_string(),
+                    ));
+                }
+                self.check_chain_id(mutation.chain_id)?;
+                self.check_gas_price(&mutation.gas_price)?;
+                self.check_nonce(&account_id.addr, &mutation)?;
+                self.check_compare_and_swap(&account_id.addr, &mutation)?;
+            }
+            Some(PayloadType::QuerySessionPayload) => {
+                let query_session =
+                    QuerySession::decode(request.payload.as_ref()).map_err(|e| {
+                        (
+                            CheckTxCode::InvalidQuerySession,
+                            format!("invalid query session {e}"),
+                        )
+                    })?;
+                let (_, query_session_info) =
+                    query_session_verifier::verify_query_session(&query_session)
+                        .map_err(|e| (CheckTxCode::InvalidQuerySession, format!("{e}")))?;
+                if query_session_info.query_count > self.max_queries_per_session {
+                    return Err((
+                        CheckTxCode::InvalidQuerySession,
+                        format!(
+                            "the query count {} of session {} exceeds the limit {}",
+                            query_session_info.query_count,
+                            query_session_info.id,
+                            self.max_queries_per_session
+                        ),
+                    ));
+                }
+                self.check_chain_id(query_session.chain_id)?;
+            }
+            None => {
+                return Err((
+                    CheckTxCode::InvalidPayloadType,
+                    format!("invalid payload type {}", request.payload_type),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Application for AbciImpl {
+    fn info(&self, _request: RequestInfo) -> ResponseInfo {
+        match self.try_info() {
+            Ok(response) => response,
+            // tendermint replays the blocks from the height reported here, a wrong one
+            // corrupts the store
+            Err(e) => panic!("fail to get the last block for {e}"),
+        }
+    }
+
+    fn begin_block(&self, request: RequestBeginBlock) -> ResponseBeginBlock {
+        match self.try_begin_block(request) {
+            Ok(_) => Default::default(),
+            // the txs of the block would be applied at a wrong height
+            Err(e) => panic!("fail to begin the block for {e}"),
         }
-        Default::default()
     }
 
     fn query(&self, _request: RequestQuery) -> ResponseQuery {
@@ -124,216 +854,144 @@ impl Application for AbciImpl {
     }
 
     fn check_tx(&self, request: RequestCheckTx) -> ResponseCheckTx {
-        // decode the request
-        match WriteRequest::decode(request.tx.as_ref()) {
-            Ok(request) => match db3_verifier::DB3Verifier::verify(
-                request.payload.as_ref(),
-                request.signature.as_ref(),
-            ) {
-                Ok(_) => {
-                    let payload_type = PayloadType::from_i32(request.payload_type);
-                    match payload_type {
-                        Some(PayloadType::DatabasePayload) => {
-                            match DatabaseMutation::decode(request.payload.as_ref()) {
-                                Ok(dm) => match &dm.meta {
-                                    Some(_) => {
-                                        return ResponseCheckTx {
-                                            code: 0,
-                                            data: Bytes::new(),
-                                            log: "".to_string(),
-                                            info: "".to_string(),
-                                            gas_wanted: 1,
-                                            gas_used: 0,
-                                            events: vec![],
-                                            codespace: "".to_string(),
-                                            ..Default::default()
-                                        };
-                                    }
-                                    None => {
-                                        //TODO add event
-                                        warn!("no meta for database mutation");
-                                    }
-                                },
-                                Err(_) => {
-                                    //TODO add event ?
-                                    warn!("invalid database byte data");
-                                }
-                            }
-                        }
-
-                        Some(PayloadType::MutationPayload) => {
-                            match Mutation::decode(request.payload.as_ref()) {
-                                Ok(mutation) => {
-                                    if KvStore::is_valid(&mutation) {
-                                        return ResponseCheckTx {
-                                            code: 0,
-                                            data: Bytes::new(),
-                                            log: "".to_string(),
-                                            info: "".to_string(),
-                                            gas_wanted: 1,
-                                            gas_used: 0,
-                                            events: vec![],
-                                            codespace: "".to_string(),
-                                            ..Default::default()
-                                        };
-                                    } else {
-                                        warn!("invalid mutation for kv store");
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!("invalid transaction has been checked for error {}", e);
-                                }
-                            }
-                        }
-
-                        Some(PayloadType::QuerySessionPayload) => {
-                            match QuerySession::decode(request.payload.as_ref()) {
-                                Ok(query_session) => {
-                                    match query_session_verifier::verify_query_session(
-                                        &query_session,
-                                    ) {
-                                        Ok(_) => {
-                                            return ResponseCheckTx {
-                                                code: 0,
-                                                data: Bytes::new(),
-                                                log: "".to_string(),
-                                                info: "".to_string(),
-                                                gas_wanted: 1,
-                                                gas_used: 0,
-                                                events: vec![],
-                                                codespace: "".to_string(),
-                                                ..Default::default()
-                                            };
-                                        }
-                                        Err(e) => {
-                                            warn!(
-                                                "invalid transaction has been checked for error {}",
-                                                e
-                                            );
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!("invalid transaction has been checked for error {}", e);
-                                }
-                            }
-                        }
-                        _ => {
-                            warn!("invalid transaction with null payload type");
-                        }
-                    }
+        let tx_id = TxId::from(request.tx.as_ref());
+        let _span = span!(
+            Level::INFO,
+            "check_tx",
+            tx_id = tx_id.to_base64().as_str(),
+            sender = field::Empty,
+            payload_type = field::Empty
+        )
+        .entered();
+        // the txs left in mempool are rechecked after every block, only the new ones are deduplicated
+        if request.r#type != CheckTxType::New as i32 {
+            return self.check_write_request(request.tx.as_ref(), false);
+        }
+        match self.recent_txs.lock() {
+            Ok(mut recent_txs) => {
+                if recent_txs.contains(&tx_id) {
+                    warn!("duplicated transaction {}", tx_id.to_base64());
+                    return Self::check_tx_response(
+                        CheckTxCode::DuplicatedTx,
+                        "duplicated tx".to_string(),
+                    );
                 }
-                Err(e) => {
-                    warn!("invalid transaction has been checked for error {}", e);
+                let response = self.check_write_request(request.tx.as_ref(), true);
+                if response.code == 0 {
+                    recent_txs.insert(tx_id);
                 }
-            },
-            Err(e) => {
-                warn!("invalid transaction has been checked for error {}", e);
+                response
             }
+            Err(e) => Self::check_tx_response(
+                CheckTxCode::InternalError,
+                format!("fail to lock the recent txs for {e}"),
+            ),
         }
-        // the tx should be removed from mempool
-        return ResponseCheckTx {
-            code: 1,
-            data: Bytes::new(),
-            log: "bad request".to_string(),
-            info: "".to_string(),
-            gas_wanted: 1,
-            gas_used: 0,
-            events: vec![],
-            codespace: "".to_string(),
-            ..Default::default()
-        };
     }
 
     fn deliver_tx(&self, request: RequestDeliverTx) -> ResponseDeliverTx {
         //TODO match the hash fucntion with tendermint
         let tx_id = TxId::from(request.tx.as_ref());
+        let _span = span!(
+            Level::INFO,
+            "deliver_tx",
+            tx_id = tx_id.to_base64().as_str(),
+            sender = field::Empty,
+            payload_type = field::Empty
+        )
+        .entered();
         if let Ok(wrequest) = WriteRequest::decode(request.tx.as_ref()) {
-            if let Ok(account_id) = db3_verifier::DB3Verifier::verify(
-                wrequest.payload.as_ref(),
-                wrequest.signature.as_ref(),
-            ) {
+            if let Ok(account_id) = self.deliver_signer(&wrequest, &tx_id) {
+                Self::record_tx_fields(&account_id, wrequest.payload_type);
+                debug!("the signature of transaction has been verified");
                 let payload_type = PayloadType::from_i32(wrequest.payload_type);
                 match payload_type {
                     Some(PayloadType::DatabasePayload) => {
                         if let Ok(dr) = DatabaseMutation::decode(wrequest.payload.as_ref()) {
-                            match self.pending_databases.lock() {
-                                Ok(mut s) => {
-                                    s.push((account_id.addr, dr, tx_id));
+                            self.lock_pending_txs().push(PendingTx::Database(
+                                account_id.addr,
+                                dr,
+                                tx_id,
+                            ));
+                            return ResponseDeliverTx {
+                                code: 0,
+                                data: Bytes::new(),
+                                log: "".to_string(),
+                                info: "apply_database".to_string(),
+                                gas_wanted: 0,
+                                gas_used: 0,
+                                events: vec![Event {
+                                    r#type: "apply".to_string(),
+                                    attributes: vec![],
+                                }],
+                                codespace: "".to_string(),
+                            };
+                        }
+                    }
+                    Some(PayloadType::MutationPayload) => {
+                        if let Ok(mutation) = Mutation::decode(wrequest.payload.as_ref()) {
+                            //TODO add gas check
+                            self.lock_pending_txs().push(PendingTx::Mutation(
+                                account_id.addr,
+                                tx_id,
+                                mutation,
+                            ));
+                            return ResponseDeliverTx {
+                                code: 0,
+                                data: Bytes::new(),
+                                log: "".to_string(),
+                                info: "deliver_mutation".to_string(),
+                                gas_wanted: 0,
+                                gas_used: 0,
+                                events: vec![Event {
+                                    r#type: "deliver".to_string(),
+                                    attributes: vec![],
+                                }],
+                                codespace: "".to_string(),
+                            };
+                        }
+                    }
+                    Some(PayloadType::QuerySessionPayload) => {
+                        if let Ok(query_session) = QuerySession::decode(wrequest.payload.as_ref()) {
+                            match query_session_verifier::verify_query_session(&query_session) {
+                                Ok((client_account_id, query_session_info)) => {
+                                    // the node query session info has been checked against
+                                    // the one signed by the client
+                                    self.lock_pending_txs().push(PendingTx::QuerySession(
+                                        client_account_id.addr,
+                                        account_id.addr,
+                                        tx_id,
+                                        query_session_info,
+                                    ));
                                     return ResponseDeliverTx {
                                         code: 0,
                                         data: Bytes::new(),
                                         log: "".to_string(),
-                                        info: "apply_database".to_string(),
+                                        info: "deliver_query_session".to_string(),
                                         gas_wanted: 0,
                                         gas_used: 0,
                                         events: vec![Event {
-                                            r#type: "apply".to_string(),
+                                            r#type: "deliver".to_string(),
                                             attributes: vec![],
                                         }],
                                         codespace: "".to_string(),
                                     };
                                 }
-                                _ => {
-                                    todo!();
-                                }
-                            }
-                        }
-                    }
-                    Some(PayloadType::MutationPayload) => {
-                        if let Ok(mutation) = Mutation::decode(wrequest.payload.as_ref()) {
-                            match self.pending_mutation.lock() {
-                                Ok(mut s) => {
-                                    //TODO add gas check
-                                    s.push((account_id.addr, tx_id, mutation));
+                                Err(e) => {
+                                    // the query session is controlled by the sender, reject it
+                                    // rather than crash the node
+                                    warn!("invalid query session for error {}", e);
                                     return ResponseDeliverTx {
-                                        code: 0,
+                                        code: 1,
                                         data: Bytes::new(),
-                                        log: "".to_string(),
-                                        info: "deliver_mutation".to_string(),
+                                        log: format!("{e}"),
+                                        info: "deliver_query_session".to_string(),
                                         gas_wanted: 0,
                                         gas_used: 0,
-                                        events: vec![Event {
-                                            r#type: "deliver".to_string(),
-                                            attributes: vec![],
-                                        }],
+                                        events: vec![],
                                         codespace: "".to_string(),
                                     };
                                 }
-                                Err(_) => todo!(),
-                            }
-                        }
-                    }
-                    Some(PayloadType::QuerySessionPayload) => {
-                        if let Ok(query_session) = QuerySession::decode(wrequest.payload.as_ref()) {
-                            if let Ok((client_account_id, _)) =
-                                query_session_verifier::verify_query_session(&query_session)
-                            {
-                                match self.pending_query_session.lock() {
-                                    Ok(mut s) => {
-                                        //TODO  check the node query session info
-                                        s.push((
-                                            client_account_id.addr,
-                                            account_id.addr,
-                                            tx_id,
-                                            query_session.node_query_session_info.unwrap(),
-                                        ));
-                                        return ResponseDeliverTx {
-                                            code: 0,
-                                            data: Bytes::new(),
-                                            log: "".to_string(),
-                                            info: "deliver_query_session".to_string(),
-                                            gas_wanted: 0,
-                                            gas_used: 0,
-                                            events: vec![Event {
-                                                r#type: "deliver".to_string(),
-                                                attributes: vec![],
-                                            }],
-                                            codespace: "".to_string(),
-                                        };
-                                    }
-                                    Err(_) => todo!(),
-                                }
                             }
                         }
                     }
@@ -360,120 +1018,762 @@ impl Application for AbciImpl {
     }
 
     fn commit(&self) -> ResponseCommit {
-        let pending_mutation: Vec<(AccountAddress, TxId, Mutation)> =
-            match self.pending_mutation.lock() {
-                Ok(mut q) => {
-                    let clone_q = q.drain(..).collect();
-                    clone_q
-                }
-                Err(_) => {
-                    todo!();
-                }
-            };
-        let pending_query_session: Vec<(AccountAddress, AccountAddress, TxId, QuerySessionInfo)> =
-            match self.pending_query_session.lock() {
-                Ok(mut q) => {
-                    let clone_q = q.drain(..).collect();
-                    clone_q
-                }
-                Err(_) => {
-                    todo!();
-                }
-            };
-        let pending_databases: Vec<(AccountAddress, DatabaseMutation, TxId)> =
-            match self.pending_databases.lock() {
-                Ok(mut q) => {
-                    let clone_q = q.drain(..).collect();
-                    clone_q
-                }
-                Err(_) => {
-                    todo!();
-                }
-            };
-
-        match self.node_store.lock() {
-            Ok(mut store) => {
-                let s = store.get_auth_store();
-                let span = span!(Level::INFO, "commit").entered();
-                let pending_mutation_len = pending_mutation.len();
-                for item in pending_mutation {
-                    match s.apply_mutation(&item.0, &item.1, &item.2) {
-                        Ok((_gas, total_bytes)) => {
-                            self.node_state
-                                .total_mutations
-                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                            self.node_state.total_storage_bytes.fetch_add(
-                                total_bytes as u64,
-                                std::sync::atomic::Ordering::Relaxed,
-                            );
-                        }
-                        Err(e) => {
-                            warn!("fail to apply mutation for {}", e);
-                            todo!();
-                        }
-                    }
-                }
-                let pending_query_session_len = pending_query_session.len();
-                for item in pending_query_session {
-                    match s.apply_query_session(&item.0, &item.1, &item.2, &item.3) {
-                        Ok(_) => {
-                            self.node_state
-                                .total_query_sessions
-                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        }
-                        Err(e) => {
-                            warn!("fail to apply mutation for {}", e);
-                            todo!();
-                        }
-                    }
-                }
-                let pending_databases_len = pending_databases.len();
-                for item in pending_databases {
-                    let nonce: u64 = match &item.1.meta {
-                        Some(m) => m.nonce,
-                        //TODO will not go to here
-                        None => 1,
-                    };
-                    match s.apply_database(&item.0, nonce, &item.2, &item.1) {
-                        Ok(_) => {}
-                        Err(_) => {
-                            todo!()
-                        }
-                    }
-                }
-                span.exit();
-                if pending_mutation_len > 0
-                    || pending_query_session_len > 0
-                    || pending_databases_len > 0
-                {
-                    //TODO how to revert
-                    if let Ok(hash) = s.commit() {
-                        ResponseCommit {
-                            data: Bytes::copy_from_slice(&hash),
-                            retain_height: 0,
-                        }
-                    } else {
-                        todo!();
-                    }
-                } else {
-                    let hash = s.root_hash();
-                    debug!("commit hash {}", hex::encode_upper(hash));
-                    ResponseCommit {
-                        data: Bytes::copy_from_slice(&hash),
-                        retain_height: 0,
-                    }
-                }
-            }
-            Err(_) => {
-                todo!();
-            }
+        match self.try_commit() {
+            Ok(response) => response,
+            // committing a wrong app hash forks the node, it stops instead and tendermint
+            // replays the block after the restart
+            Err(e) => panic!("fail to commit the block for {e}"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::auth_storage::AuthStorage;
+    use crate::tx_dedup_cache::DEFAULT_MEMPOOL_DEDUP_SIZE;
+    use bytes::BytesMut;
+    use db3_crypto::db3_signature::DB3Signature;
+    use db3_crypto::db3_signer::Db3MultiSchemeSigner;
+    use db3_crypto::id::DbId;
+    use db3_crypto::key_derive;
+    use db3_crypto::signature_scheme::SignatureScheme;
+    use db3_proto::db3_base_proto::{BroadcastMeta, ChainId, ChainRole};
+    use db3_proto::db3_mutation_proto::{
+        CollectionMutation, DatabaseAction, KvPair, MutationAction,
+    };
+    use db3_proto::db3_session_proto::CloseSessionPayload;
+    use merkdb::Merk;
+    use tempdir::TempDir;
 
     #[test]
     fn it_works() {}
+
+    fn build_abci(dir: &TempDir) -> AbciImpl {
+        let merk = Merk::open(dir.path()).unwrap();
+        let node_store = Arc::new(Mutex::new(Box::pin(NodeStorage::new(AuthStorage::new(
+            merk,
+        )))));
+        AbciImpl::new(
+            node_store,
+            DEFAULT_MEMPOOL_DEDUP_SIZE,
+            MutationLimits::default(),
+            0,
+        )
+    }
+
+    fn sign_request(payload: &[u8], payload_type: PayloadType) -> Bytes {
+        let seed: [u8; 32] = [0; 32];
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let signer = Db3MultiSchemeSigner::new(kp);
+        let signature = signer.sign(payload).unwrap();
+        let request = WriteRequest {
+            signature: signature.as_ref().to_vec(),
+            signature_scheme: signature.scheme().to_proto(),
+            payload: payload.to_vec(),
+            payload_type: payload_type.into(),
+        };
+        let mut buf = BytesMut::with_capacity(1024 * 4);
+        request.encode(&mut buf).unwrap();
+        buf.freeze()
+    }
+
+    #[test]
+    fn it_reject_query_session_without_node_info() {
+        let tmp_dir_path = TempDir::new("abci_query_session").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let client_query_session = CloseSessionPayload {
+            session_info: Some(QuerySessionInfo {
+                id: 1,
+                start_time: 0,
+                query_count: 10,
+            }),
+            session_token: "DummyToken".to_string(),
+        };
+        let mut buf = BytesMut::with_capacity(1024 * 4);
+        client_query_session.encode(&mut buf).unwrap();
+        let buf = buf.freeze();
+        let seed: [u8; 32] = [0; 32];
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let client_signature = Db3MultiSchemeSigner::new(kp).sign(buf.as_ref()).unwrap();
+        let query_session = QuerySession {
+            nonce: 1,
+            chain_id: ChainId::MainNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            node_query_session_info: None,
+            client_query_session: buf.as_ref().to_vec(),
+            client_signature: client_signature.as_ref().to_vec(),
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        query_session.encode(&mut payload).unwrap();
+        let tx = sign_request(payload.as_ref(), PayloadType::QuerySessionPayload);
+        let response = abci.check_tx(RequestCheckTx {
+            tx: tx.clone(),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::InvalidQuerySession as u32, response.code);
+        assert_eq!("db3", response.codespace);
+        let response = abci.deliver_tx(RequestDeliverTx { tx: tx.clone() });
+        assert_eq!(1, response.code);
+        assert_eq!(
+            "fail to verify query session node query session info is none",
+            response.log
+        );
+        assert!(abci.pending_txs.lock().unwrap().is_empty());
+        // the node keeps serving after the rejected tx
+        let response = abci.deliver_tx(RequestDeliverTx { tx });
+        assert_eq!(1, response.code);
+    }
+
+    fn build_query_session_tx(query_count: i32) -> Bytes {
+        let session_info = QuerySessionInfo {
+            id: 1,
+            start_time: 0,
+            query_count,
+        };
+        let client_query_session = CloseSessionPayload {
+            session_info: Some(session_info.clone()),
+            session_token: "DummyToken".to_string(),
+        };
+        let mut buf = BytesMut::with_capacity(1024 * 4);
+        client_query_session.encode(&mut buf).unwrap();
+        let buf = buf.freeze();
+        let seed: [u8; 32] = [1; 32];
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let client_signature = Db3MultiSchemeSigner::new(kp).sign(buf.as_ref()).unwrap();
+        let query_session = QuerySession {
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            node_query_session_info: Some(session_info),
+            client_query_session: buf.as_ref().to_vec(),
+            client_signature: client_signature.as_ref().to_vec(),
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        query_session.encode(&mut payload).unwrap();
+        sign_request(payload.as_ref(), PayloadType::QuerySessionPayload)
+    }
+
+    #[test]
+    fn it_reject_query_session_over_query_limit() {
+        let tmp_dir_path = TempDir::new("abci_query_limit").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path).with_max_queries_per_session(100);
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_query_session_tx(100),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::Accepted as u32, response.code);
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_query_session_tx(1_000_000_000),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::InvalidQuerySession as u32, response.code);
+        assert_eq!(
+            "the query count 1000000000 of session 1 exceeds the limit 100",
+            response.log
+        );
+    }
+
+    fn build_mutation_tx(gas_price: Option<Units>) -> Bytes {
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price,
+            gas: 10,
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        sign_request(payload.as_ref(), PayloadType::MutationPayload)
+    }
+
+    #[test]
+    fn it_reject_tx_with_poisoned_rate_limiter() {
+        let tmp_dir_path = TempDir::new("abci_poisoned_limiter").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let rate_limiter = abci.rate_limiter.clone();
+        let _ = std::thread::spawn(move || {
+            let _limiter = rate_limiter.lock().unwrap();
+            panic!("poison the rate limiter");
+        })
+        .join();
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_mutation_tx(None),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::InternalError as u32, response.code);
+    }
+
+    #[test]
+    fn it_reject_tx_with_poisoned_recent_txs() {
+        let tmp_dir_path = TempDir::new("abci_poisoned_recent_txs").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let recent_txs = abci.recent_txs.clone();
+        let _ = std::thread::spawn(move || {
+            let _txs = recent_txs.lock().unwrap();
+            panic!("poison the recent txs");
+        })
+        .join();
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_mutation_tx(None),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::InternalError as u32, response.code);
+    }
+
+    #[test]
+    fn it_reject_mutation_under_min_gas_price() {
+        let tmp_dir_path = TempDir::new("abci_min_gas_price").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path).with_min_gas_price(Units {
+            utype: UnitType::Tai.into(),
+            amount: 100,
+        });
+        let tai = |amount: u64| {
+            Some(Units {
+                utype: UnitType::Tai.into(),
+                amount,
+            })
+        };
+        // at the floor
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_mutation_tx(tai(100)),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::Accepted as u32, response.code);
+        // above the floor in db3
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_mutation_tx(Some(Units {
+                utype: UnitType::Db3.into(),
+                amount: 1,
+            })),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::Accepted as u32, response.code);
+        // below the floor
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_mutation_tx(tai(99)),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::GasPriceTooLow as u32, response.code);
+        assert_eq!(
+            "the gas price 99 tai is lower than the min gas price 100 tai",
+            response.log
+        );
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_mutation_tx(None),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::GasPriceTooLow as u32, response.code);
+    }
+
+    #[test]
+    fn it_summarize_commit() {
+        let stats = CommitStats {
+            applied: 3,
+            skipped: 1,
+            failed: 0,
+            apply_time: Duration::from_millis(12),
+            hash_time: Duration::from_millis(5),
+        };
+        assert_eq!(Duration::from_millis(17), stats.total_time());
+        assert_eq!(
+            "commit block 7 with 3 applied, 1 skipped and 0 failed txs in 17ms, apply 12ms, hash 5ms",
+            stats.summary(7)
+        );
+    }
+
+    #[test]
+    fn it_compute_retain_height() {
+        assert_eq!(0, retain_height(100, 0));
+        assert_eq!(90, retain_height(100, 10));
+        assert_eq!(0, retain_height(100, 100));
+        assert_eq!(0, retain_height(5, 10));
+        assert_eq!(1, retain_height(2, 1));
+    }
+
+    #[test]
+    fn it_reject_duplicated_tx_in_check_tx() {
+        let tmp_dir_path = TempDir::new("abci_dedup").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        let tx = sign_request(payload.as_ref(), PayloadType::MutationPayload);
+        let response = abci.check_tx(RequestCheckTx {
+            tx: tx.clone(),
+            ..Default::default()
+        });
+        assert_eq!(0, response.code);
+        let response = abci.check_tx(RequestCheckTx {
+            tx: tx.clone(),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::DuplicatedTx as u32, response.code);
+        assert_eq!("duplicated tx", response.log);
+        // the tx left in mempool passes the recheck
+        let response = abci.check_tx(RequestCheckTx {
+            tx,
+            r#type: CheckTxType::Recheck.into(),
+        });
+        assert_eq!(0, response.code);
+    }
+
+    fn build_database_tx(
+        action: DatabaseAction,
+        db_address: Vec<u8>,
+        collection_id: &str,
+        nonce: u64,
+    ) -> Bytes {
+        let dm = DatabaseMutation {
+            meta: Some(BroadcastMeta {
+                nonce,
+                chain_id: ChainId::DevNet.into(),
+                chain_role: ChainRole::StorageShardChain.into(),
+            }),
+            collection_mutations: vec![CollectionMutation {
+                index: vec![],
+                collection_id: collection_id.to_string(),
+                schema: vec![],
+            }],
+            db_address,
+            action: action.into(),
+            ns_grant: None,
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        dm.encode(&mut payload).unwrap();
+        sign_request(payload.as_ref(), PayloadType::DatabasePayload)
+    }
+
+    #[test]
+    fn it_commit_txs_in_delivery_order() {
+        let tmp_dir_path = TempDir::new("abci_commit_order").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let seed: [u8; 32] = [0; 32];
+        let (addr, _) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let db_id = DbId::try_from((&addr, 1)).unwrap();
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: 2,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        // create a database, write a key and then add a collection to the new database
+        let txs = vec![
+            build_database_tx(DatabaseAction::CreateDb, vec![], "collection1", 1),
+            sign_request(payload.as_ref(), PayloadType::MutationPayload),
+            build_database_tx(
+                DatabaseAction::AddCollection,
+                db_id.as_ref().to_vec(),
+                "collection2",
+                3,
+            ),
+        ];
+        for tx in txs {
+            let response = abci.deliver_tx(RequestDeliverTx { tx });
+            assert_eq!(0, response.code);
+        }
+        {
+            let pending_txs = abci.pending_txs.lock().unwrap();
+            assert_eq!(3, pending_txs.len());
+            assert!(matches!(pending_txs[0], PendingTx::Database(..)));
+            assert!(matches!(pending_txs[1], PendingTx::Mutation(..)));
+            assert!(matches!(pending_txs[2], PendingTx::Database(..)));
+        }
+        let queues = abci.get_pending_queue().summarize(1);
+        assert_eq!(3, queues.len());
+        assert_eq!("mutation", queues[0].name);
+        assert_eq!(1, queues[0].len);
+        assert_eq!("database", queues[2].name);
+        assert_eq!(2, queues[2].len);
+        assert_eq!(1, queues[2].sample_tx_ids.len());
+        assert_eq!(0, queues[1].len);
+        abci.commit();
+        assert!(abci.pending_txs.lock().unwrap().is_empty());
+        assert_eq!(0, abci.get_pending_queue().summarize(1)[0].len);
+        let mut store = abci.node_store.lock().unwrap();
+        let database = store
+            .get_auth_store()
+            .get_database(&db_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, database.collections.len());
+        let account = store.get_auth_store().get_account(&addr).unwrap();
+        assert_eq!(1, account.total_mutation_count);
+    }
+
+    #[test]
+    fn it_apply_redelivered_tx_once() {
+        let tmp_dir_path = TempDir::new("abci_redeliver").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let seed: [u8; 32] = [0; 32];
+        let (addr, _) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1)
+                .unwrap();
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        let tx = sign_request(payload.as_ref(), PayloadType::MutationPayload);
+        for _ in 0..2 {
+            let response = abci.deliver_tx(RequestDeliverTx { tx: tx.clone() });
+            assert_eq!(0, response.code);
+        }
+        abci.commit();
+        // the tx is delivered again after a crash
+        let response = abci.deliver_tx(RequestDeliverTx { tx: tx.clone() });
+        assert_eq!(0, response.code);
+        abci.commit();
+        assert_eq!(
+            1,
+            abci.get_node_state()
+                .total_mutations
+                .load(std::sync::atomic::Ordering::Relaxed)
+        );
+        let mut store = abci.node_store.lock().unwrap();
+        let auth_store = store.get_auth_store();
+        assert!(auth_store.is_tx_applied(&TxId::from(tx.as_ref())).unwrap());
+        let account = auth_store.get_account(&addr).unwrap();
+        assert_eq!(1, account.total_mutation_count);
+    }
+
+    #[test]
+    fn it_skip_bad_signature_with_batch_verify() {
+        let tmp_dir_path = TempDir::new("abci_batch_verify").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path).with_batch_verify(true);
+        let good_tx = build_mutation_tx(None);
+        // the payload is replaced after signing
+        let mut request = WriteRequest::decode(good_tx.as_ref()).unwrap();
+        let mut mutation = Mutation::decode(request.payload.as_ref()).unwrap();
+        mutation.nonce = 2;
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        request.payload = payload.to_vec();
+        let mut buf = BytesMut::with_capacity(1024 * 4);
+        request.encode(&mut buf).unwrap();
+        let bad_tx = buf.freeze();
+        for tx in [good_tx.clone(), bad_tx.clone()] {
+            let response = abci.deliver_tx(RequestDeliverTx { tx });
+            assert_eq!(0, response.code);
+        }
+        assert_eq!(2, abci.unverified_txs.lock().unwrap().len());
+        abci.commit();
+        assert!(abci.unverified_txs.lock().unwrap().is_empty());
+        assert_eq!(1, abci.get_node_state().total_mutations());
+        assert_eq!(1, abci.get_node_state().last_commit_txs());
+        let mut store = abci.node_store.lock().unwrap();
+        let auth_store = store.get_auth_store();
+        assert!(auth_store
+            .is_tx_applied(&TxId::from(good_tx.as_ref()))
+            .unwrap());
+        assert!(!auth_store
+            .is_tx_applied(&TxId::from(bad_tx.as_ref()))
+            .unwrap());
+    }
+
+    #[test]
+    fn it_reject_delivered_tx_with_poisoned_lock() {
+        let tmp_dir_path = TempDir::new("abci_poisoned_unverified").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path).with_batch_verify(true);
+        let tx = build_mutation_tx(None);
+        let response = abci.deliver_tx(RequestDeliverTx { tx: tx.clone() });
+        assert_eq!(0, response.code);
+        let unverified_txs = abci.unverified_txs.clone();
+        let _ = std::thread::spawn(move || {
+            let _txs = unverified_txs.lock().unwrap();
+            panic!("poison the unverified txs");
+        })
+        .join();
+        let other_tx = build_query_session_tx(100);
+        let response = abci.deliver_tx(RequestDeliverTx { tx: other_tx });
+        assert_ne!(0, response.code);
+        // the kept signature is still verified in commit
+        abci.commit();
+        assert_eq!(1, abci.get_node_state().total_mutations());
+        let mut store = abci.node_store.lock().unwrap();
+        assert!(store
+            .get_auth_store()
+            .is_tx_applied(&TxId::from(tx.as_ref()))
+            .unwrap());
+    }
+
+    #[test]
+    fn it_commit_with_poisoned_pending_txs() {
+        let tmp_dir_path = TempDir::new("abci_poisoned_pending").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let pending_txs = abci.pending_txs.clone();
+        let _ = std::thread::spawn(move || {
+            let _txs = pending_txs.lock().unwrap();
+            panic!("poison the pending txs");
+        })
+        .join();
+        let tx = build_mutation_tx(None);
+        let response = abci.deliver_tx(RequestDeliverTx { tx: tx.clone() });
+        assert_eq!(0, response.code);
+        abci.commit();
+        assert_eq!(1, abci.get_node_state().total_mutations());
+        let mut store = abci.node_store.lock().unwrap();
+        assert!(store
+            .get_auth_store()
+            .is_tx_applied(&TxId::from(tx.as_ref()))
+            .unwrap());
+    }
+
+    #[test]
+    fn it_fail_to_begin_block_without_header() {
+        let tmp_dir_path = TempDir::new("abci_begin_block").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let result = abci.try_begin_block(RequestBeginBlock::default());
+        assert!(matches!(result, Err(DB3Error::ApplyCommitError(_))));
+        let node_store = abci.node_store.clone();
+        let _ = std::thread::spawn(move || {
+            let _store = node_store.lock().unwrap();
+            panic!("poison the node store");
+        })
+        .join();
+        assert!(matches!(abci.try_info(), Err(DB3Error::StateLockBusyError)));
+        assert!(matches!(
+            abci.try_commit(),
+            Err(DB3Error::StateLockBusyError)
+        ));
+    }
+
+    #[test]
+    fn it_reject_used_nonce_in_check_tx() {
+        let tmp_dir_path = TempDir::new("abci_nonce_window").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path.path()).unwrap();
+        let node_store = Arc::new(Mutex::new(Box::pin(NodeStorage::new(
+            AuthStorage::new(merk).with_nonce_window(2),
+        ))));
+        let abci = AbciImpl::new(
+            node_store,
+            DEFAULT_MEMPOOL_DEDUP_SIZE,
+            MutationLimits::default(),
+            0,
+        );
+        let response = abci.deliver_tx(RequestDeliverTx {
+            tx: build_mutation_tx(None),
+        });
+        assert_eq!(0, response.code);
+        abci.commit();
+        // another tx with the applied nonce
+        let response = abci.check_tx(RequestCheckTx {
+            tx: build_mutation_tx(Some(Units {
+                utype: UnitType::Tai.into(),
+                amount: 1,
+            })),
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::StaleNonce as u32, response.code);
+        assert_eq!("invalid nonce 1, the nonce has been used", response.log);
+    }
+
+    #[test]
+    fn it_reject_compare_and_swap_with_poisoned_store() {
+        let tmp_dir_path = TempDir::new("abci_poisoned_store").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path);
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::CompareAndSwapKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        let tx = sign_request(payload.as_ref(), PayloadType::MutationPayload);
+        let node_store = abci.node_store.clone();
+        let _ = std::thread::spawn(move || {
+            let _store = node_store.lock().unwrap();
+            panic!("poison the node store");
+        })
+        .join();
+        let response = abci.check_tx(RequestCheckTx {
+            tx,
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::InternalError as u32, response.code);
+    }
+
+    #[test]
+    fn it_reject_tx_of_other_chain() {
+        let tmp_dir_path = TempDir::new("abci_chain_id").expect("create temp dir");
+        let abci = build_abci(&tmp_dir_path).with_chain_id(ChainId::MainNet);
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        let tx = sign_request(payload.as_ref(), PayloadType::MutationPayload);
+        let response = abci.check_tx(RequestCheckTx {
+            tx,
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::ChainIdMismatch as u32, response.code);
+        assert_eq!(
+            "the tx is signed for chain DevNet but the node is on chain MainNet",
+            response.log
+        );
+        let tx = build_database_tx(DatabaseAction::CreateDb, vec![], "collection1", 1);
+        let response = abci.check_tx(RequestCheckTx {
+            tx,
+            ..Default::default()
+        });
+        assert_eq!(CheckTxCode::ChainIdMismatch as u32, response.code);
+        let mutation = Mutation {
+            chain_id: ChainId::MainNet.into(),
+            ..mutation
+        };
+        let mut payload = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut payload).unwrap();
+        let tx = sign_request(payload.as_ref(), PayloadType::MutationPayload);
+        let response = abci.check_tx(RequestCheckTx {
+            tx,
+            ..Default::default()
+        });
+        assert_eq!(0, response.code);
+    }
+
+    #[test]
+    fn it_rate_limit_txs_of_an_account() {
+        let tmp_dir_path = TempDir::new("abci_rate_limit").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path.path()).unwrap();
+        let node_store = Arc::new(Mutex::new(Box::pin(NodeStorage::new(AuthStorage::new(
+            merk,
+        )))));
+        let max_tx_per_sec = 3;
+        let abci = AbciImpl::new(
+            node_store,
+            DEFAULT_MEMPOOL_DEDUP_SIZE,
+            MutationLimits::default(),
+            max_tx_per_sec,
+        );
+        let txs: Vec<Bytes> = (0..max_tx_per_sec + 1)
+            .map(|nonce| {
+                let mutation = Mutation {
+                    ns: "ns1".as_bytes().to_vec(),
+                    kv_pairs: vec![KvPair {
+                        key: "k1".as_bytes().to_vec(),
+                        value: "v1".as_bytes().to_vec(),
+                        action: MutationAction::InsertKv.into(),
+                        ns: vec![],
+                        expected_value: vec![],
+                        ttl: 0,
+                        compressed: false,
+                    }],
+                    nonce,
+                    chain_id: ChainId::DevNet.into(),
+                    chain_role: ChainRole::StorageShardChain.into(),
+                    gas_price: None,
+                    gas: 10,
+                };
+                let mut payload = BytesMut::with_capacity(1024 * 4);
+                mutation.encode(&mut payload).unwrap();
+                sign_request(payload.as_ref(), PayloadType::MutationPayload)
+            })
+            .collect();
+        for (i, tx) in txs.iter().enumerate() {
+            let response = abci.check_tx(RequestCheckTx {
+                tx: tx.clone(),
+                ..Default::default()
+            });
+            if i < max_tx_per_sec as usize {
+                assert_eq!(0, response.code);
+            } else {
+                assert_eq!(CheckTxCode::RateLimited as u32, response.code);
+                assert_eq!("rate limited", response.log);
+            }
+        }
+        // the recheck of the txs in mempool is not throttled
+        let response = abci.check_tx(RequestCheckTx {
+            tx: txs[0].clone(),
+            r#type: CheckTxType::Recheck.into(),
+        });
+        assert_eq!(0, response.code);
+    }
 }
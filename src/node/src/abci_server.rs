@@ -0,0 +1,268 @@
+//
+// abci_server.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use prost::Message;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tendermint_abci::{Application, RequestDispatcher};
+use tendermint_proto::abci::{Request, Response};
+use tracing::{info, warn};
+
+///
+/// the abci server limiting its concurrent connections. the connections beyond the limit
+/// are closed right after they are accepted, so they take no thread or read buffer. it
+/// speaks the socket protocol of tendermint-abci, every message is prefixed with its
+/// length in a signed varint
+///
+pub struct AbciServer<App> {
+    app: App,
+    listener: TcpListener,
+    read_buf_size: usize,
+    connections: Arc<AtomicUsize>,
+    // 0 disables the limit
+    max_connections: usize,
+}
+
+/// the slot of an admitted connection, it's freed when the connection is closed
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl<App: Application> AbciServer<App> {
+    pub fn bind<T: ToSocketAddrs>(
+        addr: T,
+        app: App,
+        read_buf_size: usize,
+        max_connections: usize,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            app,
+            listener: TcpListener::bind(addr)?,
+            read_buf_size,
+            connections: Arc::new(AtomicUsize::new(0)),
+            max_connections,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// the number of open connections
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    fn try_acquire(&self) -> Option<Slot> {
+        let max_connections = self.max_connections;
+        self.connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |c| {
+                if max_connections == 0 || c < max_connections {
+                    Some(c + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| Slot(self.connections.clone()))
+    }
+
+    ///
+    /// accept the connections and serve every admitted one in its own thread
+    ///
+    pub fn listen(&self) -> io::Result<()> {
+        loop {
+            let (stream, addr) = self.listener.accept()?;
+            match self.try_acquire() {
+                Some(slot) => {
+                    info!("incoming abci connection from {addr}");
+                    let app = self.app.clone();
+                    let read_buf_size = self.read_buf_size;
+                    thread::spawn(move || {
+                        if let Err(e) = Self::serve(stream, app, read_buf_size, slot) {
+                            warn!("fail to serve the abci connection from {addr} for {e}");
+                        }
+                    });
+                }
+                None => {
+                    warn!(
+                        "close the abci connection from {addr} for the limit {} is reached",
+                        self.max_connections
+                    );
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+            }
+        }
+    }
+
+    fn serve(stream: TcpStream, app: App, read_buf_size: usize, _slot: Slot) -> io::Result<()> {
+        // the abci requests and responses are small messages
+        stream.set_nodelay(true)?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::with_capacity(read_buf_size, stream);
+        while let Some(request) = read_request(&mut reader)? {
+            let response = app.handle(request);
+            write_response(&mut writer, &response)?;
+        }
+        Ok(())
+    }
+}
+
+/// read the length prefix, None if the connection is closed before it
+fn read_length<R: Read>(reader: &mut R) -> io::Result<Option<usize>> {
+    let mut value: u64 = 0;
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        if reader.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        value |= ((byte[0] & 0x7f) as u64) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            // the length is a zigzag encoded signed varint
+            return Ok(Some((value >> 1) as usize));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the length prefix is too long",
+    ))
+}
+
+fn read_request<R: Read>(reader: &mut R) -> io::Result<Option<Request>> {
+    let len = match read_length(reader)? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Request::decode(buf.as_slice())
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_response<W: Write>(writer: &mut W, response: &Response) -> io::Result<()> {
+    let len = response.encoded_len();
+    let mut buf = Vec::with_capacity(len + 10);
+    prost::encoding::encode_varint((len as u64) << 1, &mut buf);
+    response
+        .encode(&mut buf)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.write_all(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use tendermint_proto::abci::{request, response, RequestEcho};
+
+    #[derive(Clone)]
+    struct EchoApp;
+
+    impl Application for EchoApp {}
+
+    fn connect(addr: SocketAddr) -> TcpStream {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+    }
+
+    fn echo(stream: &mut TcpStream) -> bool {
+        let request = Request {
+            value: Some(request::Value::Echo(RequestEcho {
+                message: "db3".to_string(),
+            })),
+        };
+        let mut buf = Vec::new();
+        prost::encoding::encode_varint((request.encoded_len() as u64) << 1, &mut buf);
+        request.encode(&mut buf).unwrap();
+        if stream.write_all(&buf).is_err() {
+            return false;
+        }
+        match read_length(stream) {
+            Ok(Some(len)) => {
+                let mut buf = vec![0u8; len];
+                stream.read_exact(&mut buf).is_ok()
+                    && matches!(
+                        Response::decode(buf.as_slice()).map(|r| r.value),
+                        Ok(Some(response::Value::Echo(echo))) if echo.message == "db3"
+                    )
+            }
+            _ => false,
+        }
+    }
+
+    fn wait_connections(server: &AbciServer<EchoApp>, expected: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.connections() != expected && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(expected, server.connections());
+    }
+
+    fn start_server(max_connections: usize) -> (Arc<AbciServer<EchoApp>>, SocketAddr) {
+        let server =
+            Arc::new(AbciServer::bind("127.0.0.1:0", EchoApp, 1024, max_connections).unwrap());
+        let addr = server.local_addr().unwrap();
+        let listener = server.clone();
+        thread::spawn(move || listener.listen());
+        (server, addr)
+    }
+
+    #[test]
+    fn it_close_connections_over_limit() {
+        let (server, addr) = start_server(2);
+        let mut conn1 = connect(addr);
+        let mut conn2 = connect(addr);
+        assert!(echo(&mut conn1));
+        assert!(echo(&mut conn2));
+        assert_eq!(2, server.connections());
+        // the connection over the limit is closed without being served
+        let mut conn3 = connect(addr);
+        assert!(!echo(&mut conn3));
+        assert_eq!(2, server.connections());
+        // a closed connection frees its slot
+        drop(conn2);
+        wait_connections(&server, 1);
+        let mut conn4 = connect(addr);
+        assert!(echo(&mut conn4));
+        assert!(echo(&mut conn1));
+        assert_eq!(2, server.connections());
+    }
+
+    #[test]
+    fn it_serve_without_limit() {
+        let (server, addr) = start_server(0);
+        let mut conns: Vec<TcpStream> = (0..4).map(|_| connect(addr)).collect();
+        for conn in conns.iter_mut() {
+            assert!(echo(conn));
+        }
+        assert_eq!(4, server.connections());
+    }
+}
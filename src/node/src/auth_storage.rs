@@ -17,25 +17,27 @@
 
 use db3_crypto::id::DbId;
 use db3_crypto::{db3_address::DB3Address, id::TxId};
-use db3_error::Result;
+use db3_error::{DB3Error, Result};
 use db3_proto::db3_account_proto::Account;
 use db3_proto::db3_base_proto::Units;
 use db3_proto::db3_bill_proto::{Bill, BillType};
 use db3_proto::db3_database_proto::Database;
 use db3_proto::db3_mutation_proto::{DatabaseMutation, KvPair, Mutation, MutationAction};
 use db3_proto::db3_node_proto::{BatchGetKey, BatchGetValue, RangeKey, RangeValue};
-use db3_proto::db3_session_proto::QuerySessionInfo;
+use db3_proto::db3_session_proto::{QuerySessionInfo, QuerySessionReceipt};
 use db3_storage::account_store::AccountStore;
 use db3_storage::bill_store::BillStore;
 use db3_storage::commit_store::CommitStore;
 use db3_storage::db_store::DbStore;
 use db3_storage::key::Key;
-use db3_storage::kv_store::KvStore;
+use db3_storage::kv_store::{KvStore, NsQuotas, ValueMeta};
+use db3_storage::ns_acl_store::NsAclStore;
+use db3_storage::tx_store::{TxStore, APPLIED_TX_RETAIN_BLOCKS};
 use db3_types::cost;
 use db3_types::gas;
 use hex;
 use merkdb::proofs::{Node, Op as ProofOp};
-use merkdb::Merk;
+use merkdb::{Merk, Op, Query};
 use prost::Message;
 use std::boxed::Box;
 use std::pin::Pin;
@@ -78,6 +80,15 @@ pub struct AuthStorage {
     last_block_state: BlockState,
     current_block_state: BlockState,
     db: Pin<Box<Merk>>,
+    /// reject the writes to the namespaces owned by other accounts
+    ns_acl_enabled: bool,
+    /// the credits seeded to the accounts when the store is empty
+    genesis_credits: Vec<(DB3Address, Units)>,
+    /// the max bytes stored in a namespace
+    ns_quotas: NsQuotas,
+    /// the mutations with a nonce not greater than the last applied one minus the window are
+    /// rejected, 0 disables the check
+    nonce_window: u64,
     //TODO add chain id and chain role
 }
 
@@ -87,20 +98,117 @@ impl AuthStorage {
             last_block_state: BlockState::new(),
             current_block_state: BlockState::new(),
             db: Box::pin(merk),
+            ns_acl_enabled: false,
+            genesis_credits: vec![],
+            ns_quotas: NsQuotas::default(),
+            nonce_window: 0,
         }
     }
 
+    ///
+    /// enable the namespace acl, the account writing a namespace first becomes its owner
+    /// and the other accounts can write it only if the owner grants them
+    ///
+    pub fn with_ns_acl(mut self, ns_acl_enabled: bool) -> Self {
+        self.ns_acl_enabled = ns_acl_enabled;
+        self
+    }
+
+    ///
+    /// the credits of the genesis accounts, they are applied by init only if no block
+    /// has been committed to the store
+    ///
+    pub fn with_genesis_credits(mut self, genesis_credits: Vec<(DB3Address, Units)>) -> Self {
+        self.genesis_credits = genesis_credits;
+        self
+    }
+
+    ///
+    /// the quotas of namespaces, the mutation growing a namespace over its quota is rejected
+    ///
+    pub fn with_ns_quotas(mut self, ns_quotas: NsQuotas) -> Self {
+        self.ns_quotas = ns_quotas;
+        self
+    }
+
+    ///
+    /// the window of the nonces accepted out of order, a mutation is applied if its nonce is
+    /// greater than the last applied one minus the window and has not been seen
+    ///
+    pub fn with_nonce_window(mut self, nonce_window: u64) -> Self {
+        self.nonce_window = nonce_window;
+        self
+    }
+
+    ///
+    /// recover the height, the hash and the time of the last block, the expiry of values
+    /// is checked against the time before the next block is committed
+    ///
     pub fn init(&mut self) -> Result<()> {
-        if let Ok(Some(height)) = self.get_latest_height() {
+        if let Ok(Some((height, block_time))) = CommitStore::get_applied_block(self.db.as_ref()) {
             self.last_block_state.block_height = height as i64;
+            self.last_block_state.block_time = block_time;
             self.last_block_state.abci_hash = self.db.root_hash().clone();
             info!(
-                "recover state with height {} and hash {}",
+                "recover state with height {} time {} and hash {}",
                 height,
+                block_time,
                 hex::encode_upper(self.last_block_state.abci_hash)
             );
         } else {
             info!("a new node started");
+            self.apply_genesis_credits()?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// delete all the keys of the store and forget the block states, init seeds the
+    /// genesis credits to the empty store again
+    ///
+    pub fn reset(&mut self) -> Result<()> {
+        let root_hash: Hash = self.db.root_hash().clone();
+        // the empty tree has nothing to delete and can't be queried
+        if root_hash != [0; HASH_LENGTH] {
+            let mut query = Query::new();
+            query.insert_range_from(vec![]..);
+            let ops = self
+                .db
+                .execute_query(query)
+                .map_err(|e| DB3Error::ResetStoreError(format!("{e}")))?;
+            // the keys of the query are in order as the batch of merk requires
+            let entries: Vec<(Vec<u8>, Op)> = ops
+                .into_iter()
+                .filter_map(|op| match op {
+                    ProofOp::Push(Node::KV(k, _)) => Some((k, Op::Delete)),
+                    _ => None,
+                })
+                .collect();
+            info!("reset the store with {} keys deleted", entries.len());
+            let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
+            unsafe {
+                Pin::get_unchecked_mut(db)
+                    .apply(&entries, &[])
+                    .map_err(|e| DB3Error::ResetStoreError(format!("{e}")))?;
+            }
+        }
+        self.last_block_state.reset();
+        self.current_block_state.reset();
+        Ok(())
+    }
+
+    fn apply_genesis_credits(&mut self) -> Result<()> {
+        for (addr, credits) in &self.genesis_credits {
+            let mut account = AccountStore::get_account(self.db.as_ref(), addr)?;
+            account.credits = Some(credits.clone());
+            let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
+            AccountStore::apply(db, addr, &account)?;
+        }
+        if !self.genesis_credits.is_empty() {
+            info!(
+                "seed credits to {} genesis accounts",
+                self.genesis_credits.len()
+            );
         }
         Ok(())
     }
@@ -114,18 +222,55 @@ impl AuthStorage {
         &self.last_block_state
     }
 
+    /// the state of the block between begin_block and commit
+    pub fn get_current_block_state(&self) -> &BlockState {
+        &self.current_block_state
+    }
+
+    ///
+    /// the metadata of the value, none if the value is expired at the time of the last block.
+    /// the value written before the metadata was kept gets the default one
+    ///
+    fn get_live_meta(&self, addr: &DB3Address, ns: &[u8], key: &[u8]) -> Result<Option<ValueMeta>> {
+        let meta = KvStore::get_meta(self.db.as_ref(), addr, ns, key)?.unwrap_or_default();
+        if meta.is_expired(self.last_block_state.block_time) {
+            return Ok(None);
+        }
+        Ok(Some(meta))
+    }
+
+    ///
+    /// get the pairs in the range in the order of keys. at most limit pairs are returned
+    /// if it's set, and the next cursor resumes the range right after the last of them.
+    /// the expired pairs are skipped
+    ///
     pub fn get_range(&self, addr: &DB3Address, range_key: &RangeKey) -> Result<RangeValue> {
         let proofs_ops = KvStore::get_range(self.db.as_ref(), addr, range_key)?;
         let ns = range_key.ns.as_ref();
+        let limit = range_key.limit as usize;
         let mut kv_pairs: Vec<KvPair> = Vec::new();
+        let mut next_cursor: Vec<u8> = vec![];
         for op in proofs_ops {
             match op {
                 ProofOp::Push(Node::KV(k, v)) => {
+                    if limit > 0 && kv_pairs.len() == limit {
+                        // there are more pairs than the limit
+                        next_cursor = kv_pairs[limit - 1].key.to_vec();
+                        break;
+                    }
                     let new_key = Key::decode(k.as_ref(), ns)?;
+                    let meta = match self.get_live_meta(addr, ns, new_key.2)? {
+                        Some(meta) => meta,
+                        None => continue,
+                    };
                     kv_pairs.push(KvPair {
                         key: new_key.2.to_owned(),
                         value: v,
                         action: MutationAction::Nonce.into(),
+                        ns: vec![],
+                        expected_value: vec![],
+                        ttl: 0,
+                        compressed: meta.compressed,
                     });
                 }
                 _ => {}
@@ -136,6 +281,7 @@ impl AuthStorage {
             values: kv_pairs.to_owned(),
             ns: ns.to_vec(),
             session_token: range_key.session_token.clone(),
+            next_cursor,
         })
     }
 
@@ -147,14 +293,26 @@ impl AuthStorage {
         let proofs_ops = KvStore::batch_get(self.db.as_ref(), addr, batch_get_keys)?;
         let ns = batch_get_keys.ns.as_ref();
         let mut kv_pairs: Vec<KvPair> = Vec::new();
+        let mut heights: Vec<u64> = Vec::new();
         for op in proofs_ops {
             match op {
                 ProofOp::Push(Node::KV(k, v)) => {
                     let new_key = Key::decode(k.as_ref(), ns)?;
+                    let meta = match self.get_live_meta(addr, ns, new_key.2)? {
+                        Some(meta) => meta,
+                        None => continue,
+                    };
+                    if batch_get_keys.with_metadata {
+                        heights.push(meta.height);
+                    }
                     kv_pairs.push(KvPair {
                         key: new_key.2.to_owned(),
                         value: v,
                         action: MutationAction::Nonce.into(),
+                        ns: vec![],
+                        expected_value: vec![],
+                        ttl: 0,
+                        compressed: meta.compressed,
                     });
                 }
                 _ => {}
@@ -164,17 +322,64 @@ impl AuthStorage {
             values: kv_pairs.to_owned(),
             session_token: batch_get_keys.session_token.clone(),
             ns: ns.to_vec(),
+            heights,
         })
     }
 
+    ///
+    /// get the key count and the total bytes of a namespace
+    ///
+    pub fn count_keys(&self, addr: &DB3Address, ns: &[u8]) -> Result<(u64, u64)> {
+        KvStore::get_ns_stat(self.db.as_ref(), addr, ns)
+    }
+
+    ///
+    /// list the namespaces of the address with their key count and total bytes
+    ///
+    pub fn list_ns(&self, addr: &DB3Address) -> Result<Vec<(Vec<u8>, u64, u64)>> {
+        KvStore::list_ns(self.db.as_ref(), addr)
+    }
+
+    pub fn check_compare_and_swap(&self, addr: &DB3Address, mutation: &Mutation) -> Result<()> {
+        KvStore::check_compare_and_swap(
+            self.db.as_ref(),
+            addr,
+            mutation,
+            self.last_block_state.block_time,
+        )
+    }
+
+    ///
+    /// check the nonce of the mutation against the account without recording it, the
+    /// nonce is checked again when the mutation is applied
+    ///
+    pub fn check_nonce(&self, addr: &DB3Address, mutation: &Mutation) -> Result<()> {
+        let mut account = AccountStore::get_account(self.db.as_ref(), addr)?;
+        AccountStore::accept_nonce(&mut account, mutation.nonce, self.nonce_window)
+    }
+
     pub fn get_account(&self, addr: &DB3Address) -> Result<Account> {
         AccountStore::get_account(self.db.as_ref(), addr)
     }
 
+    ///
+    /// get the accounts of the addresses in order, none for the unknown address
+    ///
+    pub fn batch_get_account(&self, addrs: &[DB3Address]) -> Result<Vec<Option<Account>>> {
+        addrs
+            .iter()
+            .map(|addr| AccountStore::try_get_account(self.db.as_ref(), addr))
+            .collect()
+    }
+
     pub fn get_database(&self, id: &DbId) -> Result<Option<Database>> {
         DbStore::get_database(self.db.as_ref(), id)
     }
 
+    pub fn get_database_ids_by_owner(&self, owner: &DB3Address) -> Result<Vec<DbId>> {
+        DbStore::get_database_ids_by_owner(self.db.as_ref(), owner)
+    }
+
     pub fn get_bills(&self, height: u64, start_id: u64, end_id: u64) -> Result<Vec<Bill>> {
         let proofs_ops = BillStore::scan(self.db.as_ref(), height, start_id, end_id)?;
         let mut bills: Vec<Bill> = Vec::new();
@@ -191,6 +396,58 @@ impl AuthStorage {
         Ok(bills)
     }
 
+    ///
+    /// the bill of the query session tx, it's none if the tx has not been applied or was
+    /// applied more than APPLIED_TX_RETAIN_BLOCKS blocks ago
+    ///
+    pub fn get_query_bill(&self, tx_id: &TxId) -> Result<Option<Bill>> {
+        let height = match TxStore::get_applied_height(self.db.as_ref(), tx_id)? {
+            Some(height) => height,
+            None => return Ok(None),
+        };
+        let bill = self
+            .get_bills(height, 0, u64::MAX)?
+            .into_iter()
+            .find(|bill| {
+                bill.bill_type == BillType::BillForQuery as i32
+                    && bill.bill_target_id.as_slice() == tx_id.as_ref()
+            });
+        Ok(bill)
+    }
+
+    ///
+    /// the receipt of the query session applied by the tx, the session info is the one
+    /// carried by the tx
+    ///
+    pub fn get_query_session_receipt(
+        &self,
+        tx_id: &TxId,
+        query_session_info: &QuerySessionInfo,
+    ) -> Result<Option<QuerySessionReceipt>> {
+        let receipt = self.get_query_bill(tx_id)?.map(|bill| QuerySessionReceipt {
+            tx_id: tx_id.as_ref().to_vec(),
+            session_id: query_session_info.id,
+            query_count: query_session_info.query_count,
+            credits: bill.gas_fee,
+            block_height: bill.block_height,
+            client_addr: bill.owner,
+            node_addr: bill.query_addr,
+        });
+        Ok(receipt)
+    }
+
+    ///
+    /// check the tx has been applied, the txs delivered again after a crash are skipped
+    ///
+    pub fn is_tx_applied(&self, tx_id: &TxId) -> Result<bool> {
+        TxStore::is_applied(self.db.as_ref(), tx_id)
+    }
+
+    fn mark_tx_applied(&mut self, tx_id: &TxId) -> Result<()> {
+        let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
+        TxStore::apply(db, tx_id, self.current_block_state.block_height as u64)
+    }
+
     pub fn begin_block(&mut self, height: u64, time: u64) {
         self.current_block_state.block_time = time;
         self.current_block_state.block_height = height as i64;
@@ -227,6 +484,7 @@ impl AuthStorage {
             account.total_query_session_count + query_session_info.query_count as u64;
         let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
         AccountStore::apply(db, &addr, &account)?;
+        self.mark_tx_applied(tx_id)?;
         Ok(gas_fee)
     }
 
@@ -238,7 +496,8 @@ impl AuthStorage {
         mutation: &DatabaseMutation,
     ) -> Result<()> {
         let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
-        DbStore::apply_mutation(db, sender, nonce, tx, mutation)
+        DbStore::apply_mutation(db, sender, nonce, tx, mutation)?;
+        self.mark_tx_applied(tx)
     }
 
     pub fn apply_mutation(
@@ -248,8 +507,30 @@ impl AuthStorage {
         mutation: &Mutation,
     ) -> Result<(Units, u64)> {
         let mut account = AccountStore::get_account(self.db.as_ref(), &addr)?;
+        // the nonce is recorded with the account, so nothing is recorded if the mutation fails
+        AccountStore::accept_nonce(&mut account, mutation.nonce, self.nonce_window)?;
+        // every namespace is checked before writing, so the mutation is rejected as a whole
+        let mut claim_namespaces: Vec<&[u8]> = Vec::new();
+        if self.ns_acl_enabled {
+            for ns in KvStore::namespaces(mutation) {
+                if NsAclStore::check_write(self.db.as_ref(), ns, addr)? {
+                    claim_namespaces.push(ns);
+                }
+            }
+        }
         let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
-        let (gas_fee, total_bytes) = KvStore::apply(db, &addr, &mutation)?;
+        let (gas_fee, total_bytes) = KvStore::apply_with_quotas(
+            db,
+            &addr,
+            &mutation,
+            self.current_block_state.block_height as u64,
+            self.current_block_state.block_time,
+            &self.ns_quotas,
+        )?;
+        for ns in claim_namespaces {
+            let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
+            NsAclStore::claim(db, ns, addr)?;
+        }
         let accumulate_gas = gas::gas_add(&gas_fee, &account.total_bills.unwrap());
         account.total_bills = Some(accumulate_gas);
         account.total_mutation_count = account.total_mutation_count + 1;
@@ -269,13 +550,23 @@ impl AuthStorage {
         BillStore::apply(db, &bill)?;
         let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
         AccountStore::apply(db, &addr, &account)?;
+        self.mark_tx_applied(tx_id)?;
         Ok((gas_fee, total_bytes as u64))
     }
 
     /// return the root hash
     pub fn commit(&mut self) -> Result<Hash> {
+        let height = self.current_block_state.block_height as u64;
+        if height > APPLIED_TX_RETAIN_BLOCKS {
+            let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
+            TxStore::prune(db, height - APPLIED_TX_RETAIN_BLOCKS)?;
+        }
         let db: Pin<&mut Merk> = Pin::as_mut(&mut self.db);
-        CommitStore::apply(db, self.current_block_state.block_height as u64)?;
+        CommitStore::apply(
+            db,
+            self.current_block_state.block_height as u64,
+            self.current_block_state.block_time,
+        )?;
         let hash = self.db.root_hash().clone();
         self.current_block_state.abci_hash = hash.clone();
         self.last_block_state = self.current_block_state.clone();
@@ -290,6 +581,490 @@ impl AuthStorage {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::node_config::ChainInitConfig;
+    use db3_crypto::db3_address::DB3_ADDRESS_LENGTH;
+    use db3_proto::db3_base_proto::UnitType;
+    use db3_proto::db3_base_proto::{ChainId, ChainRole};
+    use db3_proto::db3_mutation_proto::{DatabaseAction, NsGrant};
+    use db3_proto::db3_node_proto::Range as DB3Range;
+    use tempdir::TempDir;
+
     #[test]
     fn it_works() {}
+
+    fn apply_k1(
+        storage: &mut AuthStorage,
+        addr: &DB3Address,
+        height: u64,
+        value: &str,
+    ) -> Result<()> {
+        apply_k1_with_nonce(storage, addr, height, height, value)
+    }
+
+    fn apply_k1_with_nonce(
+        storage: &mut AuthStorage,
+        addr: &DB3Address,
+        height: u64,
+        nonce: u64,
+        value: &str,
+    ) -> Result<()> {
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: value.as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let tx_id = TxId::from([height as u8; 32]);
+        storage.begin_block(height, height);
+        let result = storage.apply_mutation(addr, &tx_id, &mutation);
+        assert!(storage.commit().is_ok());
+        result.map(|_| ())
+    }
+
+    fn put_k1(storage: &mut AuthStorage, addr: &DB3Address, height: u64, value: &str) {
+        assert!(apply_k1(storage, addr, height, value).is_ok());
+    }
+
+    fn apply_ns_grant(
+        storage: &mut AuthStorage,
+        owner: &DB3Address,
+        grantee: &DB3Address,
+        action: DatabaseAction,
+    ) -> Result<()> {
+        let mutation = DatabaseMutation {
+            meta: None,
+            collection_mutations: vec![],
+            db_address: vec![],
+            action: action.into(),
+            ns_grant: Some(NsGrant {
+                ns: "ns1".as_bytes().to_vec(),
+                grantee: grantee.to_vec(),
+            }),
+        };
+        let tx_id = TxId::from([0; 32]);
+        storage.apply_database(owner, 1, &tx_id, &mutation)
+    }
+
+    #[test]
+    fn it_get_query_session_receipt() {
+        let tmp_dir_path = TempDir::new("auth_storage_receipt").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        let client_addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let node_addr = DB3Address::from(&[2; DB3_ADDRESS_LENGTH]);
+        let tx_id = TxId::from([3; 32]);
+        let info = QuerySessionInfo {
+            id: 7,
+            start_time: 1000,
+            query_count: 12,
+        };
+        assert!(storage
+            .get_query_session_receipt(&tx_id, &info)
+            .unwrap()
+            .is_none());
+        storage.begin_block(5, 1005);
+        let gas_fee = storage
+            .apply_query_session(&client_addr, &node_addr, &tx_id, &info)
+            .unwrap();
+        assert!(storage.commit().is_ok());
+        let receipt = storage
+            .get_query_session_receipt(&tx_id, &info)
+            .unwrap()
+            .unwrap();
+        assert_eq!(tx_id.as_ref(), receipt.tx_id.as_slice());
+        assert_eq!(info.id, receipt.session_id);
+        assert_eq!(info.query_count, receipt.query_count);
+        assert_eq!(
+            Some(cost::estimate_query_session_gas(&info)),
+            receipt.credits
+        );
+        assert_eq!(Some(gas_fee), receipt.credits);
+        assert_eq!(5, receipt.block_height);
+        assert_eq!(client_addr.to_vec(), receipt.client_addr);
+        assert_eq!(node_addr.to_vec(), receipt.node_addr);
+        // the other txs have no receipt
+        assert!(storage
+            .get_query_session_receipt(&TxId::from([4; 32]), &info)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn it_expire_key_with_ttl() {
+        let tmp_dir_path = TempDir::new("auth_storage_ttl").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        let addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 10,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        storage.begin_block(1, 100);
+        assert!(storage
+            .apply_mutation(&addr, &TxId::from([1; 32]), &mutation)
+            .is_ok());
+        assert!(storage.commit().is_ok());
+        let batch_get_key = BatchGetKey {
+            ns: "ns1".as_bytes().to_vec(),
+            keys: vec!["k1".as_bytes().to_vec()],
+            session_token: "MOCK_TOKEN".to_string(),
+            with_metadata: false,
+        };
+        let range_key = RangeKey {
+            ns: "ns1".as_bytes().to_vec(),
+            range: Some(DB3Range {
+                start: vec![],
+                end: "~".as_bytes().to_vec(),
+            }),
+            session_token: "token".to_string(),
+            limit: 0,
+            cursor: vec![],
+        };
+        let values = storage.batch_get(&addr, &batch_get_key).unwrap();
+        assert_eq!(1, values.values.len());
+        // the key lives until the block time reaches the expiry
+        storage.begin_block(2, 109);
+        assert!(storage.commit().is_ok());
+        let values = storage.batch_get(&addr, &batch_get_key).unwrap();
+        assert_eq!(1, values.values.len());
+        storage.begin_block(3, 110);
+        assert!(storage.commit().is_ok());
+        let values = storage.batch_get(&addr, &batch_get_key).unwrap();
+        assert!(values.values.is_empty());
+        let page = storage.get_range(&addr, &range_key).unwrap();
+        assert!(page.values.is_empty());
+        // writing the key again without a ttl keeps it forever
+        put_k1(&mut storage, &addr, 4, "v2");
+        let values = storage.batch_get(&addr, &batch_get_key).unwrap();
+        assert_eq!("v2".as_bytes(), values.values[0].value.as_slice());
+    }
+
+    #[test]
+    fn it_keep_key_expired_after_restart() {
+        let tmp_dir_path = TempDir::new("auth_storage_ttl_restart").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path.path()).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        let addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 10,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        storage.begin_block(1, 100);
+        assert!(storage
+            .apply_mutation(&addr, &TxId::from([1; 32]), &mutation)
+            .is_ok());
+        assert!(storage.commit().is_ok());
+        storage.begin_block(2, 110);
+        assert!(storage.commit().is_ok());
+        let batch_get_key = BatchGetKey {
+            ns: "ns1".as_bytes().to_vec(),
+            keys: vec!["k1".as_bytes().to_vec()],
+            session_token: "MOCK_TOKEN".to_string(),
+            with_metadata: false,
+        };
+        assert!(storage
+            .batch_get(&addr, &batch_get_key)
+            .unwrap()
+            .values
+            .is_empty());
+        drop(storage);
+        // the time of the last block is recovered with its height
+        let merk = Merk::open(tmp_dir_path.path()).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        assert_eq!(2, storage.get_last_block_state().block_height);
+        assert_eq!(110, storage.get_last_block_state().block_time);
+        assert!(storage
+            .batch_get(&addr, &batch_get_key)
+            .unwrap()
+            .values
+            .is_empty());
+    }
+
+    #[test]
+    fn it_page_range_with_cursor() {
+        let tmp_dir_path = TempDir::new("auth_storage_range_page").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        let addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let kv_pairs: Vec<KvPair> = (0..10)
+            .map(|i| KvPair {
+                key: format!("k{i}").as_bytes().to_vec(),
+                value: format!("v{i}").as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            })
+            .collect();
+        let mutation = Mutation {
+            ns: "ns1".as_bytes().to_vec(),
+            kv_pairs,
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        storage.begin_block(1, 1);
+        assert!(storage
+            .apply_mutation(&addr, &TxId::from([1; 32]), &mutation)
+            .is_ok());
+        assert!(storage.commit().is_ok());
+        let mut cursor: Vec<u8> = vec![];
+        let mut pages = 0;
+        let mut keys: Vec<Vec<u8>> = vec![];
+        loop {
+            let range_key = RangeKey {
+                ns: "ns1".as_bytes().to_vec(),
+                range: Some(DB3Range {
+                    start: vec![],
+                    end: "~".as_bytes().to_vec(),
+                }),
+                session_token: "token".to_string(),
+                limit: 3,
+                cursor: cursor.clone(),
+            };
+            let page = storage.get_range(&addr, &range_key).unwrap();
+            assert!(page.values.len() <= 3);
+            keys.extend(page.values.into_iter().map(|kv| kv.key));
+            pages += 1;
+            if page.next_cursor.is_empty() {
+                break;
+            }
+            cursor = page.next_cursor;
+        }
+        assert_eq!(4, pages);
+        let expected: Vec<Vec<u8>> = (0..10).map(|i| format!("k{i}").into_bytes()).collect();
+        assert_eq!(expected, keys);
+    }
+
+    #[test]
+    fn it_seed_genesis_credits_to_empty_store() {
+        let tmp_dir_path = TempDir::new("auth_storage_genesis").expect("create temp dir");
+        let addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let genesis_path = tmp_dir_path.path().join("genesis.json");
+        let content = format!(
+            r#"{{"accounts": [{{"addr": "0x{}", "credits": "100db3"}}]}}"#,
+            hex::encode(addr.as_ref())
+        );
+        std::fs::write(&genesis_path, content).unwrap();
+        let genesis_credits = ChainInitConfig::load(&genesis_path)
+            .unwrap()
+            .genesis_credits()
+            .unwrap();
+        let merk = Merk::open(tmp_dir_path.path().join("db")).unwrap();
+        let mut storage = AuthStorage::new(merk).with_genesis_credits(genesis_credits.clone());
+        assert!(storage.init().is_ok());
+        let expected = Units {
+            utype: UnitType::Db3.into(),
+            amount: 100,
+        };
+        let account = storage.get_account(&addr).unwrap();
+        assert_eq!(Some(expected.clone()), account.credits);
+        put_k1(&mut storage, &addr, 1, "v1");
+        drop(storage);
+        // the genesis is not applied again once a block was committed
+        let merk = Merk::open(tmp_dir_path.path().join("db")).unwrap();
+        let other_credits = vec![(
+            addr,
+            Units {
+                utype: UnitType::Db3.into(),
+                amount: 1,
+            },
+        )];
+        let mut storage = AuthStorage::new(merk).with_genesis_credits(other_credits);
+        assert!(storage.init().is_ok());
+        let account = storage.get_account(&addr).unwrap();
+        assert_eq!(Some(expected), account.credits);
+        assert_eq!(1, account.total_mutation_count);
+    }
+
+    #[test]
+    fn it_reset_populated_store() {
+        let tmp_dir_path = TempDir::new("auth_storage_reset").expect("create temp dir");
+        let addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let merk = Merk::open(tmp_dir_path.path().join("db")).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        put_k1(&mut storage, &addr, 1, "v1");
+        put_k1(&mut storage, &addr, 2, "v2");
+        assert_eq!(Some(2), storage.get_latest_height().unwrap());
+        drop(storage);
+        // force init on the next start
+        let merk = Merk::open(tmp_dir_path.path().join("db")).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.reset().is_ok());
+        assert!(storage.init().is_ok());
+        assert_eq!(None, storage.get_latest_height().unwrap());
+        assert_eq!(0, storage.get_last_block_state().block_height);
+        assert_eq!([0; HASH_LENGTH], storage.db.root_hash().clone());
+        let account = storage.get_account(&addr).unwrap();
+        assert_eq!(0, account.total_mutation_count);
+        // the store is usable after the reset
+        put_k1(&mut storage, &addr, 1, "v1");
+        // reset an empty store
+        let merk = Merk::open(tmp_dir_path.path().join("empty")).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.reset().is_ok());
+    }
+
+    #[test]
+    fn it_prune_applied_txs_of_old_blocks() {
+        let tmp_dir_path = TempDir::new("auth_storage_prune_txs").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        let addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        put_k1(&mut storage, &addr, 1, "v1");
+        let tx_id = TxId::from([1; 32]);
+        storage.begin_block(APPLIED_TX_RETAIN_BLOCKS + 1, 0);
+        assert!(storage.commit().is_ok());
+        assert!(storage.is_tx_applied(&tx_id).unwrap());
+        storage.begin_block(APPLIED_TX_RETAIN_BLOCKS + 2, 0);
+        assert!(storage.commit().is_ok());
+        assert!(!storage.is_tx_applied(&tx_id).unwrap());
+    }
+
+    #[test]
+    fn it_reject_replayed_nonce() {
+        let tmp_dir_path = TempDir::new("auth_storage_nonce_window").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk).with_nonce_window(2);
+        assert!(storage.init().is_ok());
+        let addr = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        assert!(apply_k1_with_nonce(&mut storage, &addr, 1, 10, "v1").is_ok());
+        assert!(apply_k1_with_nonce(&mut storage, &addr, 2, 9, "v2").is_ok());
+        // the duplicated nonce
+        assert!(apply_k1_with_nonce(&mut storage, &addr, 3, 10, "v3").is_err());
+        // the nonce out of the window
+        assert!(apply_k1_with_nonce(&mut storage, &addr, 4, 8, "v4").is_err());
+        let account = storage.get_account(&addr).unwrap();
+        assert_eq!(10, account.nonce);
+        assert_eq!(2, account.total_mutation_count);
+        // the other accounts have their own nonces
+        let other = DB3Address::from(&[2; DB3_ADDRESS_LENGTH]);
+        assert!(apply_k1_with_nonce(&mut storage, &other, 5, 8, "v5").is_ok());
+        // the check records nothing
+        let mutation = |nonce: u64| Mutation {
+            nonce,
+            ..Mutation::default()
+        };
+        assert!(storage.check_nonce(&addr, &mutation(10)).is_err());
+        assert!(storage.check_nonce(&addr, &mutation(8)).is_err());
+        assert!(storage.check_nonce(&addr, &mutation(11)).is_ok());
+        assert!(storage.check_nonce(&addr, &mutation(11)).is_ok());
+        assert_eq!(10, storage.get_account(&addr).unwrap().nonce);
+    }
+
+    #[test]
+    fn it_reject_write_to_ns_of_other_account() {
+        let tmp_dir_path = TempDir::new("auth_storage_ns_acl").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk).with_ns_acl(true);
+        assert!(storage.init().is_ok());
+        let owner = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let other = DB3Address::from(&[2; DB3_ADDRESS_LENGTH]);
+        put_k1(&mut storage, &owner, 1, "v1");
+        assert!(apply_k1(&mut storage, &other, 2, "v2").is_err());
+        assert!(apply_ns_grant(&mut storage, &other, &other, DatabaseAction::GrantNs).is_err());
+        assert!(apply_ns_grant(&mut storage, &owner, &other, DatabaseAction::GrantNs).is_ok());
+        put_k1(&mut storage, &other, 3, "v3");
+        assert!(apply_ns_grant(&mut storage, &owner, &other, DatabaseAction::RevokeNs).is_ok());
+        assert!(apply_k1(&mut storage, &other, 4, "v4").is_err());
+        put_k1(&mut storage, &owner, 5, "v5");
+    }
+
+    #[test]
+    fn it_write_any_ns_without_acl() {
+        let tmp_dir_path = TempDir::new("auth_storage_no_ns_acl").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        put_k1(
+            &mut storage,
+            &DB3Address::from(&[1; DB3_ADDRESS_LENGTH]),
+            1,
+            "v1",
+        );
+        put_k1(
+            &mut storage,
+            &DB3Address::from(&[2; DB3_ADDRESS_LENGTH]),
+            2,
+            "v2",
+        );
+    }
+
+    #[test]
+    fn it_batch_get_with_last_modified_height() {
+        let tmp_dir_path = TempDir::new("auth_storage").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut storage = AuthStorage::new(merk);
+        assert!(storage.init().is_ok());
+        let data: [u8; DB3_ADDRESS_LENGTH] = [1; DB3_ADDRESS_LENGTH];
+        let addr = DB3Address::from(&data);
+        let batch_get_key = BatchGetKey {
+            ns: "ns1".as_bytes().to_vec(),
+            keys: vec!["k1".as_bytes().to_vec()],
+            session_token: "MOCK_TOKEN".to_string(),
+            with_metadata: true,
+        };
+        put_k1(&mut storage, &addr, 1, "v1");
+        let values = storage.batch_get(&addr, &batch_get_key).unwrap();
+        assert_eq!("v1".as_bytes(), values.values[0].value.as_slice());
+        assert_eq!(vec![1], values.heights);
+        put_k1(&mut storage, &addr, 2, "v2");
+        let values = storage.batch_get(&addr, &batch_get_key).unwrap();
+        assert_eq!("v2".as_bytes(), values.values[0].value.as_slice());
+        assert_eq!(vec![2], values.heights);
+        let batch_get_key = BatchGetKey {
+            with_metadata: false,
+            ..batch_get_key
+        };
+        let values = storage.batch_get(&addr, &batch_get_key).unwrap();
+        assert!(values.heights.is_empty());
+    }
 }
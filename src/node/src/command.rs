@@ -15,40 +15,60 @@
 // limitations under the License.
 //
 
-use crate::abci_impl::{AbciImpl, NodeState};
+use crate::abci_impl::{build, AbciImpl, NodeState, PendingTxQueue};
+use crate::abci_server::AbciServer;
 use crate::auth_storage::AuthStorage;
 use crate::context::Context;
+use crate::cors_config::CorsOrigins;
+use crate::db_options;
+use crate::doctor;
+use crate::grpc_metrics::{GrpcMetrics, GrpcMetricsLayer};
 use crate::json_rpc_impl;
+use crate::node_config::{resolve_path, ChainInitConfig, DataRoot};
 use crate::node_storage::NodeStorage;
+use crate::proof_bench::{self, ProofBenchReport};
 use crate::storage_node_impl::StorageNodeImpl;
-use actix_cors::Cors;
+use crate::tx_dedup_cache::DEFAULT_MEMPOOL_DEDUP_SIZE;
 use actix_web::{rt, web, App, HttpServer};
 use clap::Parser;
-use db3_cmd::command::{DB3ClientCommand, DB3ClientContext};
-use db3_crypto::db3_signer::Db3MultiSchemeSigner;
+use db3_base::strings;
+use db3_cmd::command::{DB3ClientCommand, DB3ClientContext, OutputFormat};
+use db3_cmd::console::ContextBuilder;
+use db3_crypto::db3_address::{DB3Address, DB3_ADDRESS_LENGTH};
+use db3_crypto::db3_signer::{CommandSigner, Db3MultiSchemeSigner, Signer};
+use db3_proto::db3_base_proto::{ChainId, Units};
 use db3_proto::db3_node_proto::storage_node_client::StorageNodeClient;
 use db3_proto::db3_node_proto::storage_node_server::StorageNodeServer;
 use db3_sdk::mutation_sdk::MutationSDK;
 use db3_sdk::store_sdk::StoreSDK;
+use db3_session::session_manager::DEFAULT_SESSION_QUERY_LIMIT;
+use db3_storage::kv_store::{
+    MutationLimits, DEFAULT_MAX_KEY_LEN, DEFAULT_MAX_MUTATION_BYTES,
+    DEFAULT_MAX_PAIRS_PER_MUTATION, DEFAULT_MAX_VALUE_LEN,
+};
 use http::Uri;
 use merkdb::Merk;
 use std::boxed::Box;
-use std::io::{stderr, stdout};
+use std::io::{stderr, stdin, stdout};
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
 use std::thread::JoinHandle;
 use std::time::Duration;
-use tendermint_abci::ServerBuilder;
-use tendermint_rpc::HttpClient;
-use tonic::codegen::http::Method;
+use tempdir::TempDir;
+use tendermint_rpc::{Client, HttpClient};
+use tonic::server::NamedService;
 use tonic::transport::{ClientTlsConfig, Endpoint, Server};
-use tower_http::cors::{Any, CorsLayer};
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
 use tracing::{info, warn};
 use tracing_subscriber::filter::LevelFilter;
 
+/// the interval to check the store and tendermint for the health service
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
 const ABOUT: &str = "
 ██████╗ ██████╗ ██████╗ 
 ██╔══██╗██╔══██╗╚════██╗
@@ -61,6 +81,7 @@ const ABOUT: &str = "
 #[derive(Debug, Parser)]
 #[clap(name = "db3")]
 #[clap(about = ABOUT, long_about = None)]
+#[clap(version = build::PKG_VERSION, long_version = build::CLAP_LONG_VERSION)]
 pub enum DB3Command {
     /// Start db3 network
     #[clap(name = "start")]
@@ -83,19 +104,92 @@ pub enum DB3Command {
         /// connection.
         #[clap(short, long, default_value = "1048576")]
         read_buf_size: usize,
+        /// the max concurrent connections of the abci server, the connections beyond it
+        /// are closed right after they are accepted. every admitted connection holds a read
+        /// buffer of --read-buf-size, so the buffers take at most the product of the two.
+        /// tendermint opens 4 connections. 0 disables the limit
+        #[clap(long, default_value = "0")]
+        abci_max_connections: usize,
         /// Increase output logging verbosity to DEBUG level.
         #[clap(short, long)]
         verbose: bool,
         /// Suppress all output logging (overrides --verbose).
         #[clap(short, long)]
         quiet: bool,
+        /// the directory housing the db and the config files of the node, it's created if it
+        /// does not exist. the relative --db-path and --genesis-accounts are resolved against it
+        #[clap(long)]
+        data_root: Option<String>,
         #[clap(short, long, default_value = "./db")]
         db_path: String,
         #[clap(long, default_value = "16")]
         db_tree_level_in_memory: u8,
+        /// the size in MB of the block cache of the store, the default of merk is used if it's omitted
+        #[clap(long)]
+        db_cache_size_mb: Option<u64>,
+        /// the interval in seconds to compact the files of the store, the default of merk is used if it's omitted
+        #[clap(long)]
+        db_compaction_interval: Option<u64>,
         /// disable grpc-web
         #[clap(long, default_value = "false")]
         disable_grpc_web: bool,
+        /// record the bytes of grpc requests and responses by method and serve them on
+        /// /metrics of the json rpc port
+        #[clap(long, default_value = "false")]
+        enable_grpc_metrics: bool,
+        /// the number of recently accepted tx ids kept to reject duplicated txs, 0 disables it
+        #[clap(long, default_value_t = DEFAULT_MEMPOOL_DEDUP_SIZE)]
+        mempool_dedup_size: usize,
+        /// the max number of kv pairs in a mutation
+        #[clap(long, default_value_t = DEFAULT_MAX_PAIRS_PER_MUTATION)]
+        max_mutation_pairs: usize,
+        /// the max length of a key in bytes
+        #[clap(long, default_value_t = DEFAULT_MAX_KEY_LEN)]
+        max_key_len: usize,
+        /// the max length of a value in bytes
+        #[clap(long, default_value_t = DEFAULT_MAX_VALUE_LEN)]
+        max_value_len: usize,
+        /// the max length of an encoded mutation in bytes
+        #[clap(long, default_value_t = DEFAULT_MAX_MUTATION_BYTES)]
+        max_mutation_bytes: usize,
+        /// the max number of new txs accepted from an account per second, 0 disables the limit
+        #[clap(long, default_value = "0")]
+        max_tx_per_account_per_sec: u64,
+        /// the token required by the admin methods, the admin methods are disabled if it's omitted
+        #[clap(long)]
+        admin_token: Option<String>,
+        /// the chain of the node, mainnet, testnet or devnet. the txs signed for other chains are rejected
+        #[clap(long, value_parser = parse_chain_id, default_value = "devnet")]
+        chain_id: ChainId,
+        /// the max query count a query session can claim, the sessions claiming more are rejected
+        #[clap(long, default_value_t = DEFAULT_SESSION_QUERY_LIMIT)]
+        max_queries_per_session: i32,
+        /// the max query sessions running at the same time, the new sessions are rejected with
+        /// resource exhausted until some sessions are closed or expire. 0 disables the limit
+        #[clap(long, default_value = "0")]
+        max_open_sessions: usize,
+        /// the json file of the genesis of the chain. the initial credits of accounts are applied
+        /// only to an empty store and the chain settings in it decide which txs are applied, so
+        /// every validator must use the same file
+        #[clap(long)]
+        genesis_accounts: Option<String>,
+        /// the min gas price with an optional unit, e.g. 100tai, the mutations with a lower price are rejected
+        #[clap(long, value_parser = strings::parse_units, default_value = "0")]
+        min_gas_price: Units,
+        /// the number of recent blocks kept by tendermint, the older blocks are pruned and 0 keeps
+        /// all the blocks. the state of the store is not affected
+        #[clap(long, default_value = "0")]
+        retain_blocks: u64,
+        /// the comma separated origins allowed to call the grpc web and the json rpc services
+        /// from a browser, e.g. https://app.db3.network,http://localhost:3000. * allows any origin
+        #[clap(long, value_parser = CorsOrigins::parse, default_value = "*")]
+        cors_allowed_origins: CorsOrigins,
+        /// delete all the data of the store before starting, it requires --yes
+        #[clap(long, default_value = "false")]
+        force_init: bool,
+        /// confirm the deletion of --force-init
+        #[clap(long, default_value = "false")]
+        yes: bool,
     },
 
     /// Start db3 interactive console
@@ -104,6 +198,25 @@ pub enum DB3Command {
         /// the url of db3 grpc api
         #[clap(long = "url", global = true, default_value = "http://127.0.0.1:26659")]
         public_grpc_url: String,
+        /// the name of key in keystore
+        #[clap(long = "key-name", global = true)]
+        key_name: Option<String>,
+        /// sign the requests by running the program, e.g. the cli of a hardware wallet,
+        /// rather than with the key in keystore
+        #[clap(long, global = true, conflicts_with = "key_name")]
+        signer_command: Option<String>,
+        /// the format of tables, table, csv or tsv
+        #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+        output: OutputFormat,
+        /// the timeout in seconds of every request to db3 node
+        #[clap(long, global = true, default_value = "30")]
+        timeout: u64,
+        /// disable reconnecting to db3 node when the connection is lost
+        #[clap(long, default_value = "false")]
+        disable_auto_reconnect: bool,
+        /// skip checking the connection to db3 node before the console starts
+        #[clap(long, default_value = "false")]
+        skip_health_check: bool,
     },
 
     /// Run db3 client
@@ -112,60 +225,294 @@ pub enum DB3Command {
         /// the url of db3 grpc api
         #[clap(long = "url", global = true, default_value = "http://127.0.0.1:26659")]
         public_grpc_url: String,
+        /// the name of key in keystore
+        #[clap(long = "key-name", global = true)]
+        key_name: Option<String>,
+        /// sign the requests by running the program, e.g. the cli of a hardware wallet,
+        /// rather than with the key in keystore
+        #[clap(long, global = true, conflicts_with = "key_name")]
+        signer_command: Option<String>,
+        /// the format of tables, table, csv or tsv
+        #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+        output: OutputFormat,
+        /// the timeout in seconds of every request to db3 node
+        #[clap(long, global = true, default_value = "30")]
+        timeout: u64,
+        /// read the newline delimited commands from stdin and run them one by one
+        #[clap(long, default_value = "false")]
+        from_stdin: bool,
+        /// keep running the commands read from stdin after one fails
+        #[clap(long, default_value = "false", requires = "from_stdin")]
+        continue_on_error: bool,
         /// the subcommand
         #[clap(subcommand)]
         cmd: Option<DB3ClientCommand>,
     },
+
+    /// Show the version and build info of db3
+    #[clap(name = "version")]
+    Version {},
+
+    /// Compact the store of a stopped node
+    #[clap(name = "compact")]
+    Compact {
+        #[clap(short, long, default_value = "./db")]
+        db_path: String,
+    },
+
+    /// Check the environment of the node before starting it
+    #[clap(name = "doctor")]
+    Doctor {
+        #[clap(short, long, default_value = "./db")]
+        db_path: String,
+        #[clap(long, default_value = "16")]
+        db_tree_level_in_memory: u8,
+        /// Bind the abci server to this port.
+        #[clap(long, default_value = "26658")]
+        abci_port: u16,
+        /// The porf of tendemint
+        #[clap(long, default_value = "26657")]
+        tendermint_port: u16,
+    },
+
+    /// Benchmark the generation and verification of proofs
+    #[clap(name = "bench-proof")]
+    BenchProof {
+        /// the number of keys to generate proofs for, at least 1
+        #[clap(
+            long,
+            default_value = "1000",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        keys: u64,
+    },
+}
+
+/// parse the name of chain, e.g. mainnet, testnet or devnet
+fn parse_chain_id(input: &str) -> Result<ChainId, String> {
+    match input.to_lowercase().as_str() {
+        "mainnet" => Ok(ChainId::MainNet),
+        "testnet" => Ok(ChainId::TestNet),
+        "devnet" => Ok(ChainId::DevNet),
+        _ => Err(format!(
+            "invalid chain id {input}, mainnet, testnet or devnet is expected"
+        )),
+    }
 }
 
 impl DB3Command {
-    fn build_context(public_grpc_url: &str) -> DB3ClientContext {
-        let uri = public_grpc_url.parse::<Uri>().unwrap();
+    fn build_context(
+        public_grpc_url: &str,
+        key_name: Option<&str>,
+        signer_command: Option<&str>,
+        output: OutputFormat,
+        timeout: u64,
+    ) -> std::io::Result<DB3ClientContext> {
+        let invalid_url = |e: String| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid url {public_grpc_url} for error {e}"),
+            )
+        };
+        let uri = public_grpc_url
+            .parse::<Uri>()
+            .map_err(|e| invalid_url(e.to_string()))?;
+        let endpoint =
+            Endpoint::new(public_grpc_url.to_string()).map_err(|e| invalid_url(e.to_string()))?;
         let endpoint = match uri.scheme_str() == Some("https") {
-            true => {
-                let rpc_endpoint = Endpoint::new(public_grpc_url.to_string())
-                    .unwrap()
-                    .tls_config(ClientTlsConfig::new())
-                    .unwrap();
-                rpc_endpoint
-            }
-            false => {
-                let rpc_endpoint = Endpoint::new(public_grpc_url.to_string()).unwrap();
-                rpc_endpoint
-            }
+            true => endpoint
+                .tls_config(ClientTlsConfig::new())
+                .map_err(|e| invalid_url(e.to_string()))?,
+            false => endpoint,
         };
-        let channel = endpoint.connect_lazy();
+        let channel = endpoint
+            .timeout(Duration::from_secs(timeout))
+            .connect_lazy();
         let node = Arc::new(StorageNodeClient::new(channel));
-        if !db3_cmd::keystore::KeyStore::has_key() {
-            db3_cmd::keystore::KeyStore::recover_keypair().unwrap();
-        }
-        let kp = db3_cmd::keystore::KeyStore::get_keypair().unwrap();
-        let signer = Db3MultiSchemeSigner::new(kp);
-        let mutation_sdk = MutationSDK::new(node.clone(), signer);
-        let kp = db3_cmd::keystore::KeyStore::get_keypair().unwrap();
-        let signer = Db3MultiSchemeSigner::new(kp);
-        let store_sdk = StoreSDK::new(node, signer);
-        DB3ClientContext {
+        let (mutation_signer, store_signer) = Self::build_signers(key_name, signer_command)?;
+        let mutation_sdk = MutationSDK::new(node.clone(), mutation_signer);
+        let store_sdk = StoreSDK::new(node, store_signer);
+        Ok(DB3ClientContext {
             mutation_sdk: Some(mutation_sdk),
             store_sdk: Some(store_sdk),
+            default_ns: None,
+            key_name: key_name.map(|n| n.to_string()),
+            output,
+            url: public_grpc_url.to_string(),
+        })
+    }
+
+    ///
+    /// the signers of the mutation sdk and the store sdk, they run the signer command if
+    /// it's set or use the key in keystore
+    ///
+    fn build_signers(
+        key_name: Option<&str>,
+        signer_command: Option<&str>,
+    ) -> std::io::Result<(Box<dyn Signer>, Box<dyn Signer>)> {
+        if let Some(command) = signer_command {
+            let mut parts = command.split_whitespace().map(|part| part.to_string());
+            let program = parts.next().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "the signer command is empty",
+                )
+            })?;
+            let args: Vec<String> = parts.collect();
+            let signer = CommandSigner::new(program.as_str(), &args)?;
+            return Ok((Box::new(signer.clone()), Box::new(signer)));
         }
+        if !db3_cmd::keystore::KeyStore::has_key(key_name) {
+            db3_cmd::keystore::KeyStore::recover_keypair(key_name)?;
+        }
+        let kp = db3_cmd::keystore::KeyStore::get_keypair(key_name)?;
+        Ok((
+            Box::new(Db3MultiSchemeSigner::new(kp.copy())),
+            Box::new(Db3MultiSchemeSigner::new(kp)),
+        ))
     }
 
     pub async fn execute(self) {
         match self {
-            DB3Command::Console { public_grpc_url } => {
-                let ctx = Self::build_context(public_grpc_url.as_ref());
-                db3_cmd::console::start_console(ctx, &mut stdout(), &mut stderr())
+            DB3Command::Version {} => {
+                println!("tag: {}", build::TAG);
+                println!("commit: {}", build::COMMIT_HASH);
+                println!("build time: {}", build::BUILD_TIME);
+                println!("rustc: {}", build::RUST_VERSION);
+            }
+            DB3Command::Compact { db_path } => match db_options::compact_db(&db_path) {
+                Ok(_) => {
+                    println!("compact the store in {db_path} done");
+                }
+                Err(e) => {
+                    println!("fail to compact the store in {db_path} with error {e}");
+                }
+            },
+            DB3Command::Doctor {
+                db_path,
+                db_tree_level_in_memory,
+                abci_port,
+                tendermint_port,
+            } => {
+                let tm_addr = format!("http://127.0.0.1:{tendermint_port}");
+                let results = vec![
+                    doctor::check_db_path(&db_path, db_tree_level_in_memory),
+                    doctor::check_port_free("abci port is free", abci_port),
+                    doctor::check_tendermint(&tm_addr).await,
+                    doctor::check_node_key(),
+                ];
+                for result in results.iter() {
+                    println!("{result}");
+                }
+                if doctor::has_critical_failure(&results) {
+                    std::process::exit(1);
+                }
+            }
+            DB3Command::BenchProof { keys } => {
+                let tmp_dir_path = TempDir::new("bench_proof").unwrap();
+                let merk = Merk::open(tmp_dir_path).unwrap();
+                let mut db = Box::pin(merk);
+                let data: [u8; DB3_ADDRESS_LENGTH] = [0; DB3_ADDRESS_LENGTH];
+                let addr = DB3Address::from(&data);
+                match proof_bench::bench_proof(Pin::as_mut(&mut db), &addr, keys as usize) {
+                    Ok(report) => {
+                        for p in [50, 90, 99, 100] {
+                            println!(
+                                "p{p} generation {:?} verification {:?} proof size {} bytes",
+                                ProofBenchReport::percentile(&report.generation, p).unwrap(),
+                                ProofBenchReport::percentile(&report.verification, p).unwrap(),
+                                ProofBenchReport::percentile(&report.proof_sizes, p).unwrap()
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        println!("fail to bench proof with error {e}");
+                    }
+                }
+            }
+            DB3Command::Console {
+                public_grpc_url,
+                key_name,
+                signer_command,
+                output,
+                timeout,
+                disable_auto_reconnect,
+                skip_health_check,
+            } => {
+                let ctx = match Self::build_context(
+                    public_grpc_url.as_ref(),
+                    key_name.as_deref(),
+                    signer_command.as_deref(),
+                    output,
+                    timeout,
+                ) {
+                    Ok(ctx) => ctx,
+                    Err(e) => {
+                        println!("fail to build the client for error {e}");
+                        return;
+                    }
+                };
+                if !skip_health_check {
+                    if let Err(e) = ctx.store_sdk.as_ref().unwrap().health_check().await {
+                        println!("cannot connect to {public_grpc_url} for error {e}");
+                        return;
+                    }
+                }
+                let reconnect: Option<ContextBuilder> = if disable_auto_reconnect {
+                    None
+                } else {
+                    Some(Box::new(move || {
+                        Self::build_context(
+                            public_grpc_url.as_ref(),
+                            key_name.as_deref(),
+                            signer_command.as_deref(),
+                            output,
+                            timeout,
+                        )
+                    }))
+                };
+                db3_cmd::console::start_console(ctx, reconnect, &mut stdout(), &mut stderr())
                     .await
                     .unwrap();
             }
             DB3Command::Client {
                 cmd,
                 public_grpc_url,
+                key_name,
+                signer_command,
+                output,
+                timeout,
+                from_stdin,
+                continue_on_error,
             } => {
-                let mut ctx = Self::build_context(public_grpc_url.as_ref());
-                if let Some(c) = cmd {
-                    c.execute(&mut ctx).await;
+                let mut ctx = match Self::build_context(
+                    public_grpc_url.as_ref(),
+                    key_name.as_deref(),
+                    signer_command.as_deref(),
+                    output,
+                    timeout,
+                ) {
+                    Ok(ctx) => ctx,
+                    Err(e) => {
+                        println!("fail to build the client for error {e}");
+                        return;
+                    }
+                };
+                if from_stdin {
+                    if let Err(e) = db3_cmd::console::run_batch(
+                        &mut ctx,
+                        &mut stdin().lock(),
+                        &mut stdout(),
+                        continue_on_error,
+                    )
+                    .await
+                    {
+                        println!("{e}");
+                    }
+                } else if let Some(c) = cmd {
+                    if let Err(e) = c.execute(&mut ctx).await {
+                        println!("{e}");
+                    }
                 }
             }
             DB3Command::Start {
@@ -175,11 +522,32 @@ impl DB3Command {
                 abci_port,
                 tendermint_port,
                 read_buf_size,
+                abci_max_connections,
                 verbose,
                 quiet,
+                data_root,
                 db_path,
                 db_tree_level_in_memory,
+                db_cache_size_mb,
+                db_compaction_interval,
                 disable_grpc_web,
+                enable_grpc_metrics,
+                mempool_dedup_size,
+                max_mutation_pairs,
+                max_key_len,
+                max_value_len,
+                max_mutation_bytes,
+                max_tx_per_account_per_sec,
+                admin_token,
+                chain_id,
+                max_queries_per_session,
+                max_open_sessions,
+                genesis_accounts,
+                min_gas_price,
+                retain_blocks,
+                cors_allowed_origins,
+                force_init,
+                yes,
             } => {
                 let log_level = if quiet {
                     LevelFilter::OFF
@@ -190,13 +558,66 @@ impl DB3Command {
                 };
                 tracing_subscriber::fmt().with_max_level(log_level).init();
                 info!("{ABOUT}");
-                let opts = Merk::default_db_opts();
+                if force_init && !yes {
+                    warn!("--force-init deletes all the data of the store, confirm it with --yes");
+                    return;
+                }
+                let data_root = match data_root {
+                    Some(root) => match DataRoot::open(&root) {
+                        Ok(data_root) => {
+                            info!("the data root is {}", data_root.path().display());
+                            Some(data_root)
+                        }
+                        Err(e) => {
+                            warn!("{e}");
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let opts = db_options::build_db_opts(db_cache_size_mb, db_compaction_interval);
+                let chain_config = match genesis_accounts {
+                    Some(path) => {
+                        let path = resolve_path(data_root.as_ref(), &path);
+                        match ChainInitConfig::load(&path) {
+                            Ok(config) => config,
+                            Err(e) => {
+                                warn!("fail to load the genesis from {} for {e}", path.display());
+                                return;
+                            }
+                        }
+                    }
+                    None => ChainInitConfig::default(),
+                };
+                let genesis_credits = match chain_config.genesis_credits() {
+                    Ok(credits) => credits,
+                    Err(e) => {
+                        warn!("fail to load genesis accounts for {e}");
+                        return;
+                    }
+                };
+                let db_path = resolve_path(data_root.as_ref(), &db_path);
+                info!("the db is stored in {}", db_path.display());
                 let merk = Merk::open_opt(&db_path, opts, db_tree_level_in_memory).unwrap();
-                let node_store = Arc::new(Mutex::new(Box::pin(NodeStorage::new(
-                    AuthStorage::new(merk),
-                ))));
+                let node_store = Arc::new(Mutex::new(Box::pin(
+                    NodeStorage::new(
+                        AuthStorage::new(merk)
+                            .with_ns_acl(chain_config.enable_ns_acl)
+                            .with_genesis_credits(genesis_credits)
+                            .with_ns_quotas(chain_config.get_ns_quotas())
+                            .with_nonce_window(chain_config.nonce_window),
+                    )
+                    .with_max_open_sessions(max_open_sessions),
+                )));
                 match node_store.lock() {
                     Ok(mut store) => {
+                        if force_init {
+                            warn!("reset the store in {}", db_path.display());
+                            if let Err(e) = store.get_auth_store().reset() {
+                                warn!("Fail to reset auth storage for {e}");
+                                return;
+                            }
+                        }
                         if store.get_auth_store().init().is_err() {
                             warn!("Fail to init auth storage!");
                             return;
@@ -204,40 +625,102 @@ impl DB3Command {
                     }
                     _ => todo!(),
                 }
-                let (_node_state, abci_handler) =
-                    Self::start_abci_service(abci_port, read_buf_size, node_store.clone());
+                let mutation_limits = MutationLimits {
+                    max_pairs: max_mutation_pairs,
+                    max_key_len,
+                    max_value_len,
+                    max_total_bytes: max_mutation_bytes,
+                };
+                let (node_state, pending_queue, abci_handler) = Self::start_abci_service(
+                    abci_port,
+                    read_buf_size,
+                    abci_max_connections,
+                    mempool_dedup_size,
+                    mutation_limits,
+                    max_tx_per_account_per_sec,
+                    chain_id,
+                    max_queries_per_session,
+                    min_gas_price.clone(),
+                    retain_blocks,
+                    chain_config.batch_verify_signatures,
+                    node_store.clone(),
+                );
                 let tm_addr = format!("http://127.0.0.1:{tendermint_port}");
                 info!("db3 json rpc server will connect to tendermint {tm_addr}");
                 let client = HttpClient::new(tm_addr.as_str()).unwrap();
+                let grpc_metrics = if enable_grpc_metrics {
+                    Some(Arc::new(GrpcMetrics::new().unwrap()))
+                } else {
+                    None
+                };
                 let context = Context {
                     node_store: node_store.clone(),
                     client,
+                    mutation_limits,
+                    pending_queue,
+                    node_state,
+                    admin_token,
+                    min_gas_price,
+                    grpc_metrics,
+                    chain_id,
                 };
                 let json_rpc_handler = Self::start_json_rpc_service(
                     &public_host,
                     public_json_rpc_port,
+                    cors_allowed_origins.clone(),
                     context.clone(),
                 );
-                Self::start_grpc_service(&public_host, public_grpc_port, disable_grpc_web, context)
-                    .await;
-                let running = Arc::new(AtomicBool::new(true));
-                let r = running.clone();
+                Self::start_grpc_service(
+                    &public_host,
+                    public_grpc_port,
+                    disable_grpc_web,
+                    cors_allowed_origins,
+                    context,
+                )
+                .await;
+                let (stop_sender, stop_receiver) = mpsc::channel();
                 ctrlc::set_handler(move || {
-                    r.store(false, Ordering::SeqCst);
+                    let _ = stop_sender.send(());
                 })
                 .expect("Error setting Ctrl-C handler");
-                loop {
-                    if running.load(Ordering::SeqCst) {
-                        let ten_millis = Duration::from_millis(10);
-                        thread::sleep(ten_millis);
-                    } else {
-                        info!("stop db3...");
-                        abci_handler.join().unwrap();
-                        json_rpc_handler.join().unwrap();
-                        break;
-                    }
-                }
+                // block until a shutdown is requested
+                let _ = stop_receiver.recv();
+                info!("stop db3...");
+                abci_handler.join().unwrap();
+                json_rpc_handler.join().unwrap();
+            }
+        }
+    }
+
+    ///
+    /// the node is ready if the store is usable and tendermint responds
+    ///
+    async fn is_node_ready(context: &Context) -> bool {
+        !context.node_store.is_poisoned() && context.client.health().await.is_ok()
+    }
+
+    ///
+    /// report the health of the node to the health service periodically, the node is
+    /// not serving until the first check passes
+    ///
+    async fn report_health(mut reporter: HealthReporter, context: Context) {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        let mut last_status: Option<ServingStatus> = None;
+        loop {
+            interval.tick().await;
+            let status = if Self::is_node_ready(&context).await {
+                ServingStatus::Serving
+            } else {
+                ServingStatus::NotServing
+            };
+            if last_status != Some(status) {
+                info!("the health status of db3 node is {:?}", status);
+                last_status = Some(status);
             }
+            reporter.set_service_status("", status).await;
+            reporter
+                .set_service_status(StorageNodeServer::<StorageNodeImpl>::NAME, status)
+                .await;
         }
     }
 
@@ -246,28 +729,42 @@ impl DB3Command {
         public_host: &str,
         public_grpc_port: u16,
         disable_grpc_web: bool,
+        cors_origins: CorsOrigins,
         context: Context,
     ) {
         let addr = format!("{public_host}:{public_grpc_port}");
         let kp = crate::node_key::get_key_pair(None).unwrap();
         let signer = Db3MultiSchemeSigner::new(kp);
+        let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+        health_reporter
+            .set_service_status("", ServingStatus::NotServing)
+            .await;
+        tokio::spawn(Self::report_health(health_reporter, context.clone()));
+        // the layer is not added if the metrics are disabled
+        let metrics_layer = tower::util::option_layer(
+            context
+                .grpc_metrics
+                .as_ref()
+                .map(|metrics| GrpcMetricsLayer::new(metrics.clone())),
+        );
         let storage_node = StorageNodeImpl::new(context, signer);
         info!("start db3 storage node on public addr {}", addr);
         if disable_grpc_web {
             Server::builder()
+                .layer(metrics_layer)
+                .add_service(health_service)
                 .add_service(StorageNodeServer::new(storage_node))
                 .serve(addr.parse().unwrap())
                 .await
                 .unwrap();
         } else {
-            let cors_layer = CorsLayer::new()
-                .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-                .allow_headers(Any)
-                .allow_origin(Any);
+            let cors_layer = cors_origins.grpc_web_layer();
             Server::builder()
                 .accept_http1(true)
+                .layer(metrics_layer)
                 .layer(cors_layer)
                 .layer(tonic_web::GrpcWebLayer::new())
+                .add_service(health_service)
                 .add_service(StorageNodeServer::new(storage_node))
                 .serve(addr.parse().unwrap())
                 .await
@@ -281,6 +778,7 @@ impl DB3Command {
     fn start_json_rpc_service(
         public_host: &str,
         public_json_rpc_port: u16,
+        cors_origins: CorsOrigins,
         context: Context,
     ) -> JoinHandle<()> {
         let local_public_host = public_host.to_string();
@@ -290,17 +788,17 @@ impl DB3Command {
             rt::System::new()
                 .block_on(async {
                     HttpServer::new(move || {
-                        let cors = Cors::default()
-                            .allow_any_origin()
-                            .allow_any_method()
-                            .allow_any_header()
-                            .max_age(3600);
+                        let cors = cors_origins.json_rpc_cors();
                         App::new()
                             .app_data(web::Data::new(context.clone()))
                             .wrap(cors)
                             .service(
                                 web::resource("/").route(web::post().to(json_rpc_impl::rpc_router)),
                             )
+                            .service(
+                                web::resource("/metrics")
+                                    .route(web::get().to(json_rpc_impl::metrics_handler)),
+                            )
                     })
                     .disable_signals()
                     .bind((local_public_host, public_json_rpc_port))
@@ -319,24 +817,44 @@ impl DB3Command {
     fn start_abci_service(
         abci_port: u16,
         read_buf_size: usize,
+        max_connections: usize,
+        mempool_dedup_size: usize,
+        mutation_limits: MutationLimits,
+        max_tx_per_account_per_sec: u64,
+        chain_id: ChainId,
+        max_queries_per_session: i32,
+        min_gas_price: Units,
+        retain_blocks: u64,
+        batch_verify: bool,
         store: Arc<Mutex<Pin<Box<NodeStorage>>>>,
-    ) -> (Arc<NodeState>, JoinHandle<()>) {
+    ) -> (Arc<NodeState>, PendingTxQueue, JoinHandle<()>) {
         let addr = format!("{}:{}", "127.0.0.1", abci_port);
-        let abci_impl = AbciImpl::new(store);
+        let abci_impl = AbciImpl::new(
+            store,
+            mempool_dedup_size,
+            mutation_limits,
+            max_tx_per_account_per_sec,
+        )
+        .with_chain_id(chain_id)
+        .with_max_queries_per_session(max_queries_per_session)
+        .with_min_gas_price(min_gas_price)
+        .with_retain_blocks(retain_blocks)
+        .with_batch_verify(batch_verify);
         let node_state = abci_impl.get_node_state().clone();
+        let pending_queue = abci_impl.get_pending_queue();
         let handler = thread::spawn(move || {
-            let server = ServerBuilder::new(read_buf_size).bind(addr, abci_impl);
-            match server {
-                Ok(s) => {
-                    if let Err(e) = s.listen() {
-                        warn!("fail to listen addr for error {}", e);
+            let server =
+                match AbciServer::bind(addr.as_str(), abci_impl, read_buf_size, max_connections) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        warn!("fail to bind addr for error {}", e);
+                        return;
                     }
-                }
-                Err(e) => {
-                    warn!("fail to bind addr for error {}", e);
-                }
+                };
+            if let Err(e) = server.listen() {
+                warn!("fail to listen addr for error {}", e);
             }
         });
-        (node_state, handler)
+        (node_state, pending_queue, handler)
     }
 }
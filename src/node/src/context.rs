@@ -15,7 +15,11 @@
 // limitations under the License.
 //
 
+use super::abci_impl::{NodeState, PendingTxQueue};
+use super::grpc_metrics::GrpcMetrics;
 use super::node_storage::NodeStorage;
+use db3_proto::db3_base_proto::{ChainId, Units};
+use db3_storage::kv_store::MutationLimits;
 use std::{
     boxed::Box,
     pin::Pin,
@@ -28,6 +32,18 @@ type ArcNodeStorage = Arc<Mutex<Pin<Box<NodeStorage>>>>;
 pub struct Context {
     pub node_store: ArcNodeStorage,
     pub client: HttpClient,
+    pub mutation_limits: MutationLimits,
+    pub pending_queue: PendingTxQueue,
+    /// the counters of the applied txs
+    pub node_state: Arc<NodeState>,
+    /// the token required by the admin methods, they are disabled if it's none
+    pub admin_token: Option<String>,
+    /// the min gas price of a mutation accepted by the node
+    pub min_gas_price: Units,
+    /// the bytes of grpc messages by method, it's none if the metrics are disabled
+    pub grpc_metrics: Option<Arc<GrpcMetrics>>,
+    /// the chain of the node, the txs signed by the node are for it
+    pub chain_id: ChainId,
 }
 
 #[cfg(test)]
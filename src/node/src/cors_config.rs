@@ -0,0 +1,225 @@
+//
+// cors_config.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use actix_cors::Cors;
+use http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use http::{Method, Uri};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// the seconds the browsers cache the result of a preflight request
+const CORS_MAX_AGE_SECS: u64 = 3600;
+
+/// the headers sent by the grpc web clients
+const GRPC_WEB_REQUEST_HEADERS: [&str; 3] = ["x-grpc-web", "x-user-agent", "grpc-timeout"];
+
+/// the trailers of grpc web read by the clients
+const GRPC_WEB_EXPOSE_HEADERS: [&str; 3] =
+    ["grpc-status", "grpc-message", "grpc-status-details-bin"];
+
+///
+/// the origins allowed to call the grpc web and the json rpc services from a browser
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorsOrigins {
+    Any,
+    List(Vec<String>),
+}
+
+impl CorsOrigins {
+    ///
+    /// parse the comma separated origins like https://app.db3.network,http://localhost:3000,
+    /// * allows any origin
+    ///
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if input == "*" {
+            return Ok(CorsOrigins::Any);
+        }
+        let mut origins: Vec<String> = Vec::new();
+        for origin in input.split(',').map(|o| o.trim()) {
+            if !Self::is_valid_origin(origin) {
+                return Err(format!(
+                    "invalid origin {origin}, * or origins like https://app.db3.network are expected"
+                ));
+            }
+            origins.push(origin.to_string());
+        }
+        Ok(CorsOrigins::List(origins))
+    }
+
+    /// an origin is the scheme and the host with an optional port, e.g. http://localhost:3000
+    fn is_valid_origin(origin: &str) -> bool {
+        let uri = match origin.parse::<Uri>() {
+            Ok(uri) => uri,
+            Err(_) => return false,
+        };
+        matches!(uri.scheme_str(), Some("http") | Some("https"))
+            && uri.host().is_some()
+            && uri.path() == "/"
+            && uri.query().is_none()
+            && !origin.ends_with('/')
+            && HeaderValue::from_str(origin).is_ok()
+    }
+
+    ///
+    /// the cors of grpc web, the clients send POST requests and the browsers send OPTIONS
+    /// preflight requests
+    ///
+    pub fn grpc_web_layer(&self) -> CorsLayer {
+        let allow_origin = match self {
+            CorsOrigins::Any => AllowOrigin::from(Any),
+            CorsOrigins::List(origins) => AllowOrigin::list(
+                origins
+                    .iter()
+                    .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+            ),
+        };
+        let mut allow_headers = vec![CONTENT_TYPE];
+        allow_headers.extend(GRPC_WEB_REQUEST_HEADERS.map(HeaderName::from_static));
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::POST, Method::OPTIONS])
+            .allow_headers(allow_headers)
+            .expose_headers(GRPC_WEB_EXPOSE_HEADERS.map(HeaderName::from_static))
+            .max_age(Duration::from_secs(CORS_MAX_AGE_SECS))
+    }
+
+    ///
+    /// the cors of json rpc, the rpc calls are POST requests and the metrics is a GET request
+    ///
+    pub fn json_rpc_cors(&self) -> Cors {
+        let cors = match self {
+            CorsOrigins::Any => Cors::default().allow_any_origin(),
+            CorsOrigins::List(origins) => origins
+                .iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin)),
+        };
+        cors.allowed_methods(vec!["GET", "POST"])
+            .allowed_header(actix_web::http::header::CONTENT_TYPE)
+            .max_age(CORS_MAX_AGE_SECS as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+    use tower::{ServiceBuilder, ServiceExt};
+
+    #[test]
+    fn it_parse_cors_origins() {
+        assert_eq!(CorsOrigins::Any, CorsOrigins::parse("*").unwrap());
+        assert_eq!(
+            CorsOrigins::List(vec![
+                "https://app.db3.network".to_string(),
+                "http://localhost:3000".to_string()
+            ]),
+            CorsOrigins::parse("https://app.db3.network, http://localhost:3000").unwrap()
+        );
+        for bad in [
+            "",
+            "app.db3.network",
+            "ftp://app.db3.network",
+            "https://app.db3.network/",
+            "https://app.db3.network/path",
+            "*,https://app.db3.network",
+        ] {
+            assert!(CorsOrigins::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn it_reject_disallowed_origin_of_json_rpc() {
+        let origins = CorsOrigins::parse("https://app.db3.network").unwrap();
+        let app = test::init_service(App::new().wrap(origins.json_rpc_cors()).route(
+            "/",
+            web::post().to(|| async { HttpResponse::Ok().finish() }),
+        ))
+        .await;
+        let request = test::TestRequest::post()
+            .uri("/")
+            .insert_header(("Origin", "https://app.db3.network"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+        assert_eq!(
+            Some("https://app.db3.network"),
+            response
+                .headers()
+                .get("access-control-allow-origin")
+                .and_then(|v| v.to_str().ok())
+        );
+        let request = test::TestRequest::post()
+            .uri("/")
+            .insert_header(("Origin", "https://evil.example.com"))
+            .to_request();
+        let status = match test::try_call_service(&app, request).await {
+            Ok(response) => response.status(),
+            Err(e) => e.error_response().status(),
+        };
+        assert!(status.is_client_error());
+        // any origin is allowed by default
+        let app = test::init_service(App::new().wrap(CorsOrigins::Any.json_rpc_cors()).route(
+            "/",
+            web::post().to(|| async { HttpResponse::Ok().finish() }),
+        ))
+        .await;
+        let request = test::TestRequest::post()
+            .uri("/")
+            .insert_header(("Origin", "https://evil.example.com"))
+            .to_request();
+        assert!(test::call_service(&app, request)
+            .await
+            .status()
+            .is_success());
+    }
+
+    #[tokio::test]
+    async fn it_reject_disallowed_origin_of_grpc_web() {
+        let origins = CorsOrigins::parse("https://app.db3.network").unwrap();
+        let preflight = |origin: &str| {
+            http::Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/db3_node_proto.StorageNode/GetLatestState")
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+                .body(hyper::Body::empty())
+                .unwrap()
+        };
+        let allow_origin = |origin: &str| {
+            let service = ServiceBuilder::new()
+                .layer(origins.grpc_web_layer())
+                .service_fn(|_: http::Request<hyper::Body>| async {
+                    Ok::<_, std::convert::Infallible>(http::Response::new(hyper::Body::empty()))
+                });
+            let request = preflight(origin);
+            async move {
+                let response = service.oneshot(request).await.unwrap();
+                response
+                    .headers()
+                    .get("access-control-allow-origin")
+                    .map(|v| v.to_str().unwrap().to_string())
+            }
+        };
+        assert_eq!(
+            Some("https://app.db3.network".to_string()),
+            allow_origin("https://app.db3.network").await
+        );
+        assert_eq!(None, allow_origin("https://evil.example.com").await);
+    }
+}
@@ -0,0 +1,85 @@
+//
+// db_options.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use db3_error::{DB3Error, Result};
+use merkdb::rocksdb::{Options, DB};
+use merkdb::Merk;
+
+///
+/// build the options to open merk, the defaults of merk are kept for the absent settings
+///
+/// a larger cache keeps more tree nodes in memory and saves disk reads for the
+/// writes that update them, at the cost of the memory of the node. a shorter compaction
+/// interval reclaims the space of the replaced tree nodes sooner, at the cost of
+/// extra background io that competes with the writes
+///
+pub fn build_db_opts(cache_size_mb: Option<u64>, compaction_interval_secs: Option<u64>) -> Options {
+    let mut opts = Merk::default_db_opts();
+    if let Some(cache_size_mb) = cache_size_mb {
+        opts.optimize_for_point_lookup(cache_size_mb);
+    }
+    if let Some(interval) = compaction_interval_secs {
+        opts.set_periodic_compaction_seconds(interval);
+    }
+    opts
+}
+
+///
+/// compact all the column families of the store, the node must be stopped
+///
+pub fn compact_db(db_path: &str) -> Result<()> {
+    let opts = Merk::default_db_opts();
+    let cfs =
+        DB::list_cf(&opts, db_path).map_err(|e| DB3Error::CompactStoreError(format!("{e}")))?;
+    let db = DB::open_cf(&opts, db_path, &cfs)
+        .map_err(|e| DB3Error::CompactStoreError(format!("{e}")))?;
+    for name in cfs.iter() {
+        if let Some(cf) = db.cf_handle(name) {
+            db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merkdb::Op;
+    use tempdir::TempDir;
+
+    #[test]
+    fn it_compact_db() {
+        let tmp_dir_path = TempDir::new("compact_db").expect("create temp dir");
+        let db_path = tmp_dir_path.path().to_str().unwrap().to_string();
+        {
+            let opts = build_db_opts(Some(8), Some(3600));
+            let mut merk = Merk::open_opt(&db_path, opts, 16).unwrap();
+            let entries = vec![(b"k1".to_vec(), Op::Put(b"v1".to_vec()))];
+            merk.apply(&entries, &[]).unwrap();
+        }
+        assert!(compact_db(&db_path).is_ok());
+        let merk = Merk::open_opt(&db_path, build_db_opts(None, None), 16).unwrap();
+        assert_eq!(Some(b"v1".to_vec()), merk.get(b"k1").unwrap());
+    }
+
+    #[test]
+    fn it_fail_to_compact_missing_db() {
+        let tmp_dir_path = TempDir::new("compact_missing_db").expect("create temp dir");
+        let db_path = tmp_dir_path.path().join("missing");
+        assert!(compact_db(db_path.to_str().unwrap()).is_err());
+    }
+}
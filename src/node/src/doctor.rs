@@ -0,0 +1,145 @@
+//
+// doctor.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use crate::db_options;
+use crate::node_key;
+use merkdb::Merk;
+use std::fmt;
+use std::net::TcpListener;
+use tendermint_rpc::{Client, HttpClient};
+
+/// the result of a check of doctor
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub name: String,
+    /// the node can not start if a critical check fails
+    pub critical: bool,
+    /// the reason of the failure, none means the check passes
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    fn new(name: &str, critical: bool, error: Option<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            critical,
+            error,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.error, self.critical) {
+            (None, _) => write!(f, "[PASS] {}", self.name),
+            (Some(e), true) => write!(f, "[FAIL] {}: {}", self.name, e),
+            (Some(e), false) => write!(f, "[WARN] {}: {}", self.name, e),
+        }
+    }
+}
+
+///
+/// open the store with the options used by start, it fails if the path is not writable
+/// or the store is locked by a running node
+///
+pub fn check_db_path(db_path: &str, db_tree_level_in_memory: u8) -> CheckResult {
+    let opts = db_options::build_db_opts(None, None);
+    let error = Merk::open_opt(db_path, opts, db_tree_level_in_memory)
+        .err()
+        .map(|e| format!("fail to open {db_path} with error {e}"));
+    CheckResult::new("db path is writable and openable", true, error)
+}
+
+///
+/// check the abci port is not taken by another process
+///
+pub fn check_port_free(name: &str, port: u16) -> CheckResult {
+    let error = TcpListener::bind(("127.0.0.1", port))
+        .err()
+        .map(|e| format!("port {port} is not available with error {e}"));
+    CheckResult::new(name, true, error)
+}
+
+///
+/// query the status of tendermint, tendermint is usually started after db3 so it's
+/// not a critical check
+///
+pub async fn check_tendermint(tm_addr: &str) -> CheckResult {
+    let error = match HttpClient::new(tm_addr) {
+        Ok(client) => client
+            .status()
+            .await
+            .err()
+            .map(|e| format!("fail to connect to {tm_addr} with error {e}")),
+        Err(e) => Some(format!(
+            "invalid tendermint address {tm_addr} with error {e}"
+        )),
+    };
+    CheckResult::new("tendermint is reachable", false, error)
+}
+
+///
+/// load the key used to sign the responses of the node
+///
+pub fn check_node_key() -> CheckResult {
+    let error = node_key::get_key_pair(None).err().map(|e| format!("{e}"));
+    CheckResult::new("node key loads", true, error)
+}
+
+pub fn has_critical_failure(results: &[CheckResult]) -> bool {
+    results.iter().any(|r| r.critical && !r.passed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn it_check_db_path() {
+        let tmp_dir_path = TempDir::new("doctor_db").expect("create temp dir");
+        let db_path = tmp_dir_path.path().to_str().unwrap().to_string();
+        assert!(check_db_path(&db_path, 16).passed());
+        // the store is locked by the node
+        let _merk = Merk::open(&db_path).unwrap();
+        let result = check_db_path(&db_path, 16);
+        assert!(!result.passed());
+        assert!(has_critical_failure(&[result]));
+    }
+
+    #[test]
+    fn it_check_port_free() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let result = check_port_free("abci port is free", port);
+        assert!(!result.passed());
+        assert!(result.to_string().starts_with("[FAIL] abci port is free"));
+        drop(listener);
+        assert!(check_port_free("abci port is free", port).passed());
+    }
+
+    #[tokio::test]
+    async fn it_warn_on_unreachable_tendermint() {
+        let result = check_tendermint("http://127.0.0.1:1").await;
+        assert!(!result.passed());
+        assert!(!has_critical_failure(&[result]));
+    }
+}
@@ -0,0 +1,334 @@
+//
+// grpc_metrics.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use bytes::Bytes;
+use db3_error::{DB3Error, Result};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use http::{HeaderMap, Request, Response};
+use http_body::{Body as HttpBody, SizeHint};
+use hyper::Body;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, Registry, TextEncoder};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// only the methods of the services are labeled, the other paths share one label
+const KNOWN_METHODS: [&str; 21] = [
+    "/db3_node_proto.StorageNode/QueryBill",
+    "/db3_node_proto.StorageNode/GetKey",
+    "/db3_node_proto.StorageNode/GetRange",
+    "/db3_node_proto.StorageNode/OpenQuerySession",
+    "/db3_node_proto.StorageNode/CloseQuerySession",
+    "/db3_node_proto.StorageNode/GetAccount",
+    "/db3_node_proto.StorageNode/BatchGetAccount",
+    "/db3_node_proto.StorageNode/GetSessionInfo",
+    "/db3_node_proto.StorageNode/Broadcast",
+    "/db3_node_proto.StorageNode/ShowDatabase",
+    "/db3_node_proto.StorageNode/CountKeys",
+    "/db3_node_proto.StorageNode/ListNamespaces",
+    "/db3_node_proto.StorageNode/ListDatabases",
+    "/db3_node_proto.StorageNode/GetTx",
+    "/db3_node_proto.StorageNode/GetQuerySessionReceipt",
+    "/db3_node_proto.StorageNode/EstimateGas",
+    "/db3_node_proto.StorageNode/GetMinGasPrice",
+    "/db3_node_proto.StorageNode/GetLatestState",
+    "/db3_node_proto.StorageNode/GetPendingTxs",
+    "/grpc.health.v1.Health/Check",
+    "/grpc.health.v1.Health/Watch",
+];
+const UNKNOWN_METHOD: &str = "unknown";
+
+///
+/// the histograms of the bytes of grpc requests and responses by method
+///
+pub struct GrpcMetrics {
+    registry: Registry,
+    request_bytes: HistogramVec,
+    response_bytes: HistogramVec,
+}
+
+impl GrpcMetrics {
+    pub fn new() -> Result<Self> {
+        // from 64 bytes to 16 MB
+        let buckets = prometheus::exponential_buckets(64.0, 4.0, 10)
+            .map_err(|e| DB3Error::MetricsError(format!("{e}")))?;
+        let request_bytes = HistogramVec::new(
+            HistogramOpts::new(
+                "db3_grpc_request_bytes",
+                "the bytes of grpc requests by method",
+            )
+            .buckets(buckets.clone()),
+            &["method"],
+        )
+        .map_err(|e| DB3Error::MetricsError(format!("{e}")))?;
+        let response_bytes = HistogramVec::new(
+            HistogramOpts::new(
+                "db3_grpc_response_bytes",
+                "the bytes of grpc responses by method",
+            )
+            .buckets(buckets),
+            &["method"],
+        )
+        .map_err(|e| DB3Error::MetricsError(format!("{e}")))?;
+        let registry = Registry::new();
+        registry
+            .register(Box::new(request_bytes.clone()))
+            .map_err(|e| DB3Error::MetricsError(format!("{e}")))?;
+        registry
+            .register(Box::new(response_bytes.clone()))
+            .map_err(|e| DB3Error::MetricsError(format!("{e}")))?;
+        Ok(Self {
+            registry,
+            request_bytes,
+            response_bytes,
+        })
+    }
+
+    /// the label of the path, the unknown paths are not labeled to bound the series
+    fn method_label(path: &str) -> &'static str {
+        KNOWN_METHODS
+            .iter()
+            .find(|method| **method == path)
+            .copied()
+            .unwrap_or(UNKNOWN_METHOD)
+    }
+
+    pub fn observe_request(&self, path: &str, bytes: usize) {
+        self.request_bytes
+            .with_label_values(&[Self::method_label(path)])
+            .observe(bytes as f64);
+    }
+
+    pub fn observe_response(&self, path: &str, bytes: usize) {
+        self.response_bytes
+            .with_label_values(&[Self::method_label(path)])
+            .observe(bytes as f64);
+    }
+
+    /// the metrics in the text format of prometheus
+    pub fn render(&self) -> Result<String> {
+        let mut buf: Vec<u8> = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .map_err(|e| DB3Error::MetricsError(format!("{e}")))?;
+        String::from_utf8(buf).map_err(|e| DB3Error::MetricsError(format!("{e}")))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Request,
+    Response,
+}
+
+///
+/// sum up the bytes of a body and observe the sum when the body is dropped
+///
+struct SizeRecorder {
+    metrics: Arc<GrpcMetrics>,
+    path: String,
+    direction: Direction,
+    bytes: usize,
+}
+
+impl Drop for SizeRecorder {
+    fn drop(&mut self) {
+        match self.direction {
+            Direction::Request => self.metrics.observe_request(&self.path, self.bytes),
+            Direction::Response => self.metrics.observe_response(&self.path, self.bytes),
+        }
+    }
+}
+
+struct CountingBody {
+    inner: BoxBody,
+    recorder: SizeRecorder,
+}
+
+impl HttpBody for CountingBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Self::Data, Self::Error>>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(data))) = &poll {
+            this.recorder.bytes += data.len();
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::result::Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+///
+/// the layer recording the bytes of the requests and the responses of the grpc server,
+/// it's added only if the metrics are enabled
+///
+#[derive(Clone)]
+pub struct GrpcMetricsLayer {
+    metrics: Arc<GrpcMetrics>,
+}
+
+impl GrpcMetricsLayer {
+    pub fn new(metrics: Arc<GrpcMetrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcMetricsService<S> {
+    inner: S,
+    metrics: Arc<GrpcMetrics>,
+}
+
+impl<S> Service<Request<Body>> for GrpcMetricsService<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>> + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, std::result::Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let path = request.uri().path().to_string();
+        let mut request_recorder = SizeRecorder {
+            metrics: self.metrics.clone(),
+            path: path.clone(),
+            direction: Direction::Request,
+            bytes: 0,
+        };
+        let request = request.map(|body| {
+            Body::wrap_stream(body.map(move |chunk| {
+                if let Ok(data) = &chunk {
+                    request_recorder.bytes += data.len();
+                }
+                chunk
+            }))
+        });
+        let response_recorder = SizeRecorder {
+            metrics: self.metrics.clone(),
+            path,
+            direction: Direction::Response,
+            bytes: 0,
+        };
+        let future = self.inner.call(request);
+        Box::pin(async move {
+            let response = future.await?;
+            Ok(response.map(|body| {
+                tonic::body::boxed(CountingBody {
+                    inner: body,
+                    recorder: response_recorder,
+                })
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn it_record_message_bytes_by_method() {
+        let metrics = Arc::new(GrpcMetrics::new().unwrap());
+        let inner = tower::service_fn(|request: Request<Body>| async move {
+            let received = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            let body = Body::from(vec![1u8; received.len() * 2]);
+            let body = tonic::body::boxed(body.map_err(|e| Status::internal(format!("{e}"))));
+            Ok::<_, Infallible>(Response::new(body))
+        });
+        let mut service = GrpcMetricsLayer::new(metrics.clone()).layer(inner);
+        for (path, len) in [
+            ("/db3_node_proto.StorageNode/GetKey", 100),
+            ("/db3_node_proto.StorageNode/GetKey", 300),
+            ("/other/Method", 10),
+            ("/db3_node_proto.StorageNode/NoSuchMethod", 10),
+        ] {
+            let request = Request::builder()
+                .uri(path)
+                .body(Body::from(vec![0u8; len]))
+                .unwrap();
+            let response = service.call(request).await.unwrap();
+            let sent = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(len * 2, sent.len());
+        }
+        let request_bytes = metrics
+            .request_bytes
+            .with_label_values(&["/db3_node_proto.StorageNode/GetKey"]);
+        assert_eq!(2, request_bytes.get_sample_count());
+        assert_eq!(400.0, request_bytes.get_sample_sum());
+        let response_bytes = metrics
+            .response_bytes
+            .with_label_values(&["/db3_node_proto.StorageNode/GetKey"]);
+        assert_eq!(2, response_bytes.get_sample_count());
+        assert_eq!(800.0, response_bytes.get_sample_sum());
+        let unknown = metrics.request_bytes.with_label_values(&[UNKNOWN_METHOD]);
+        assert_eq!(2, unknown.get_sample_count());
+        let text = metrics.render().unwrap();
+        assert!(text.contains("db3_grpc_request_bytes"));
+        assert!(text.contains("db3_grpc_response_bytes"));
+    }
+    #[test]
+    fn it_label_every_method_of_storage_node() {
+        let proto = include_str!("../../proto/proto/db3_node.proto");
+        for line in proto.lines() {
+            if let Some(rpc) = line.trim().strip_prefix("rpc ") {
+                let name = rpc.split('(').next().unwrap().trim();
+                let path = format!("/db3_node_proto.StorageNode/{name}");
+                assert_eq!(path, GrpcMetrics::method_label(path.as_str()));
+            }
+        }
+    }
+}
@@ -15,11 +15,25 @@
 // limitations under the License.
 //
 
+use db3_error::DB3Error;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::{error, fmt};
 pub static JSONRPC_VERSION: &str = "2.0";
 
+/// Invalid JSON was received by the server.
+pub const PARSE_ERROR: i32 = -32700;
+/// The JSON sent is not a valid Request object.
+pub const INVALID_REQUEST: i32 = -32600;
+/// The method does not exist / is not available.
+pub const METHOD_NOT_FOUND: i32 = -32601;
+/// Invalid method parameter(s).
+pub const INVALID_PARAMS: i32 = -32602;
+/// Internal JSON-RPC error.
+pub const INTERNAL_ERROR: i32 = -32603;
+/// The tx is rejected by the check of the node, the code and the log of the check are in data.
+pub const TX_REJECTED: i32 = -32000;
+
 /// When a rpc call encounters an error, the Response Object MUST contain the
 /// error member with a value that is a Object with the following members:
 #[derive(Debug, Serialize, Deserialize)]
@@ -47,18 +61,24 @@ impl ErrorData {
         }
     }
 
+    /// attach the additional information of the error
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = data;
+        self
+    }
+
     pub fn std(code: i32) -> Self {
         match code {
             // Invalid JSON was received by the server. An error occurred on the server while parsing the JSON text.
-            -32700 => ErrorData::new(-32700, "Parse error"),
+            PARSE_ERROR => ErrorData::new(PARSE_ERROR, "Parse error"),
             // The JSON sent is not a valid Request object.
-            -32600 => ErrorData::new(-32600, "Invalid Request"),
+            INVALID_REQUEST => ErrorData::new(INVALID_REQUEST, "Invalid Request"),
             // The method does not exist / is not available.
-            -32601 => ErrorData::new(-32601, "Method not found"),
+            METHOD_NOT_FOUND => ErrorData::new(METHOD_NOT_FOUND, "Method not found"),
             // Invalid method parameter(s).
-            -32602 => ErrorData::new(-32602, "Invalid params"),
+            INVALID_PARAMS => ErrorData::new(INVALID_PARAMS, "Invalid params"),
             // Internal JSON-RPC error.
-            -32603 => ErrorData::new(-32603, "Internal error"),
+            INTERNAL_ERROR => ErrorData::new(INTERNAL_ERROR, "Internal error"),
             // The error codes from and including -32768 to -32000 are reserved for pre-defined errors. Any code within
             // this range, but not defined explicitly below is reserved for future use.
             _ => panic!("Undefined pre-defined error codes"),
@@ -66,6 +86,38 @@ impl ErrorData {
     }
 }
 
+///
+/// the errors of bad inputs are invalid params and the others are internal errors, the
+/// variant of the error is kept in data, e.g. {"error": "InvalidAddress", "detail": "..."}
+///
+impl From<DB3Error> for ErrorData {
+    fn from(e: DB3Error) -> Self {
+        let code = match &e {
+            DB3Error::InvalidAddress
+            | DB3Error::InvalidSigner
+            | DB3Error::InvalidSignature(_)
+            | DB3Error::VerificationError { .. }
+            | DB3Error::InvalidNonceError(_)
+            | DB3Error::StaleNonceError { .. }
+            | DB3Error::InvalidDocumentError(_)
+            | DB3Error::KeyCodecError(_)
+            | DB3Error::DatabaseNotFound(_) => INVALID_PARAMS,
+            _ => INTERNAL_ERROR,
+        };
+        let debug = format!("{e:?}");
+        let variant = debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let detail = format!("{e}");
+        ErrorData::new(code, detail.as_str()).with_data(json!({
+            "error": variant,
+            "detail": detail,
+        }))
+    }
+}
+
 impl error::Error for ErrorData {}
 impl fmt::Display for ErrorData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -86,6 +138,7 @@ pub struct Request {
 
     /// A Structured value that holds the parameter values to be used during the invocation of the method. This member
     /// MAY be omitted.
+    #[serde(default)]
     pub params: Vec<Value>,
 
     /// An identifier established by the Client that MUST contain a String, Number, or NULL value if included. If it is
@@ -109,6 +162,7 @@ pub struct Response {
     // This member is REQUIRED on error.
     // This member MUST NOT exist if there was no error triggered during invocation.
     // The value for this member MUST be an Object as defined in section 5.1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub error: Option<ErrorData>,
 
     /// This member is REQUIRED.
@@ -125,6 +179,18 @@ impl Response {
     }
 }
 
+impl Response {
+    /// the response of a failed call, the id is null if it can't be detected
+    pub fn error(id: Value, error: ErrorData) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.into(),
+            result: Value::Null,
+            error: Some(error),
+            id,
+        }
+    }
+}
+
 impl Default for Response {
     fn default() -> Self {
         Self {
@@ -135,3 +201,36 @@ impl Default for Response {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_map_db3_error_to_error_data() {
+        let e: ErrorData = DB3Error::InvalidAddress.into();
+        assert_eq!(INVALID_PARAMS, e.code);
+        assert_eq!("invalid db3 address", e.message);
+        assert_eq!(json!("InvalidAddress"), e.data["error"]);
+        let e: ErrorData = DB3Error::QueryDatabaseError("io".to_string()).into();
+        assert_eq!(INTERNAL_ERROR, e.code);
+        assert_eq!(json!("QueryDatabaseError"), e.data["error"]);
+        assert_eq!(json!(e.message), e.data["detail"]);
+        let e: ErrorData = DB3Error::VerificationError {
+            reason: "bad".to_string(),
+        }
+        .into();
+        assert_eq!(INVALID_PARAMS, e.code);
+        assert_eq!(json!("VerificationError"), e.data["error"]);
+    }
+
+    #[test]
+    fn it_dump_error_response() {
+        let r = Response::error(json!(7), ErrorData::std(METHOD_NOT_FOUND));
+        let value: Value = serde_json::from_str(r.dump().as_str()).unwrap();
+        assert_eq!(json!("2.0"), value["jsonrpc"]);
+        assert_eq!(json!(7), value["id"]);
+        assert_eq!(json!(METHOD_NOT_FOUND), value["error"]["code"]);
+        assert_eq!(json!("Method not found"), value["error"]["message"]);
+    }
+}
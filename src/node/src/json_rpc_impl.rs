@@ -14,19 +14,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
+use super::abci_impl::NodeState;
 use super::context::Context;
 use super::hash_util;
 use super::json_rpc;
 use actix_web::{web, Error, HttpResponse};
 use bytes::Bytes;
 use db3_crypto::db3_address::DB3Address;
+use db3_crypto::db3_verifier::DB3Verifier;
+use db3_crypto::id::{AccountId, DbId, TxId};
 use db3_proto::db3_base_proto::Units;
 use db3_proto::db3_bill_proto::Bill;
-use db3_proto::db3_mutation_proto::{Mutation, WriteRequest};
+use db3_proto::db3_database_proto::Database;
+use db3_proto::db3_mutation_proto::{Mutation, PayloadType, WriteRequest};
+use db3_storage::kv_store::KvStore;
 use prost::Message;
 use serde::{Deserialize, Serialize};
 use serde_json::Map;
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::str::FromStr;
 use subtle_encoding::base64;
 use tendermint::Hash as TMHash;
@@ -53,6 +58,71 @@ fn bills_to_value(bills: &Vec<Bill>) -> Value {
     Value::Array(new_bills)
 }
 
+///
+/// the database with hex encoded addresses and base64 encoded transaction ids,
+/// the same as the output of show-db
+///
+fn database_to_value(database: &Database) -> Value {
+    let mut new_database: Map<String, Value> = Map::new();
+    let address_ref: &[u8] = database.address.as_ref();
+    if let Ok(db_id) = DbId::try_from(address_ref) {
+        new_database.insert("address".to_string(), Value::from(db_id.to_hex()));
+    }
+    let sender_ref: &[u8] = database.sender.as_ref();
+    if let Ok(account_id) = AccountId::try_from(sender_ref) {
+        new_database.insert("sender".to_string(), Value::from(account_id.to_hex()));
+    }
+    let tx_list: Vec<Value> = database
+        .tx
+        .iter()
+        .filter_map(|tx| TxId::try_from_bytes(tx).ok())
+        .map(|tx| Value::from(tx.to_base64()))
+        .collect();
+    new_database.insert("tx".to_string(), Value::Array(tx_list));
+    let collections: Vec<Value> = database
+        .collections
+        .iter()
+        .filter_map(|c| serde_json::to_value(c).ok())
+        .collect();
+    new_database.insert("collections".to_string(), Value::Array(collections));
+    Value::Object(new_database)
+}
+
+///
+/// the status of the node, the same height, app hash and version reported to tendermint
+/// by the abci info
+///
+fn status_to_value(height: i64, app_hash: &[u8], node_state: &NodeState) -> Value {
+    let mut status: Map<String, Value> = Map::new();
+    status.insert("height".to_string(), Value::from(height));
+    status.insert(
+        "app_hash".to_string(),
+        Value::from(hex::encode_upper(app_hash)),
+    );
+    status.insert("version".to_string(), Value::from(shadow_rs::tag()));
+    status.insert(
+        "total_mutations".to_string(),
+        Value::from(node_state.total_mutations()),
+    );
+    status.insert(
+        "total_query_sessions".to_string(),
+        Value::from(node_state.total_query_sessions()),
+    );
+    status.insert(
+        "total_storage_bytes".to_string(),
+        Value::from(node_state.total_storage_bytes()),
+    );
+    status.insert(
+        "last_commit_txs".to_string(),
+        Value::from(node_state.last_commit_txs()),
+    );
+    status.insert(
+        "last_commit_micros".to_string(),
+        Value::from(node_state.last_commit_micros()),
+    );
+    Value::Object(status)
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Wrapper<R> {
     /// JSON-RPC version
@@ -72,6 +142,11 @@ pub struct ReadableKvPair {
     #[serde(with = "tendermint::serializers::bytes::hexstring")]
     value: Vec<u8>,
     action: i32,
+    /// the namespace of the pair, empty means the namespace of the mutation
+    #[serde(with = "tendermint::serializers::bytes::string")]
+    ns: Vec<u8>,
+    /// the value is gzip compressed
+    compressed: bool,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -93,17 +168,26 @@ enum ResponseWrapper {
     External(String),
 }
 
-fn convert_mutation_to_readable(request: &WriteRequest) -> ReadableMutation {
+fn convert_mutation_to_readable(
+    request: &WriteRequest,
+) -> Result<ReadableMutation, json_rpc::ErrorData> {
     let mut kv_pairs: Vec<ReadableKvPair> = Vec::new();
-    let mutation = Mutation::decode(request.payload.as_ref()).unwrap();
+    let mutation = Mutation::decode(request.payload.as_ref()).map_err(|e| {
+        json_rpc::ErrorData::new(
+            json_rpc::INVALID_PARAMS,
+            format!("the tx is not a mutation for {e}").as_str(),
+        )
+    })?;
     for kv in &mutation.kv_pairs {
         kv_pairs.push(ReadableKvPair {
             key: kv.key.to_owned(),
             value: kv.value.to_owned(),
             action: kv.action,
+            ns: kv.ns.to_owned(),
+            compressed: kv.compressed,
         });
     }
-    ReadableMutation {
+    Ok(ReadableMutation {
         ns: mutation.ns,
         kv_pairs: Some(kv_pairs),
         nonce: mutation.nonce,
@@ -112,62 +196,162 @@ fn convert_mutation_to_readable(request: &WriteRequest) -> ReadableMutation {
         gas_price: mutation.gas_price,
         gas: mutation.gas,
         signature: request.signature.to_owned(),
+    })
+}
+
+///
+/// the grpc metrics in the text format of prometheus, it's not found if the metrics
+/// are disabled
+///
+pub async fn metrics_handler(context: web::Data<Context>) -> Result<HttpResponse, Error> {
+    match &context.grpc_metrics {
+        Some(metrics) => match metrics.render() {
+            Ok(text) => Ok(HttpResponse::Ok()
+                .content_type("text/plain; version=0.0.4")
+                .body(text)),
+            Err(e) => Ok(HttpResponse::InternalServerError().body(format!("{e}"))),
+        },
+        None => Ok(HttpResponse::NotFound().body("the grpc metrics are disabled")),
     }
 }
 
+///
+/// parse the body as a request, the malformed json is a parse error and the json not in
+/// the shape of a request is an invalid request
+///
+fn parse_request(body: &[u8]) -> Result<json_rpc::Request, json_rpc::Response> {
+    let value: Value = serde_json::from_slice(body).map_err(|e| {
+        json_rpc::Response::error(
+            Value::Null,
+            json_rpc::ErrorData::std(json_rpc::PARSE_ERROR).with_data(Value::from(format!("{e}"))),
+        )
+    })?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| {
+        json_rpc::Response::error(
+            id,
+            json_rpc::ErrorData::std(json_rpc::INVALID_REQUEST)
+                .with_data(Value::from(format!("{e}"))),
+        )
+    })
+}
+
+fn json_response(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body)
+}
+
 pub async fn rpc_router(body: Bytes, context: web::Data<Context>) -> Result<HttpResponse, Error> {
-    let request: json_rpc::Request = match serde_json::from_slice(body.as_ref()) {
-        Ok(ok) => ok,
-        Err(e) => {
-            let err_str = format!("{}", e);
-            let r = json_rpc::Response {
-                jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                result: Value::Null,
-                error: Some(json_rpc::ErrorData::new(-32700, err_str.as_str())),
-                id: Value::Null,
-            };
-            return Ok(HttpResponse::Ok()
-                .content_type("application/json")
-                .body(r.dump()));
-        }
+    let request = match parse_request(body.as_ref()) {
+        Ok(request) => request,
+        Err(r) => return Ok(json_response(r.dump())),
     };
     debug!("request method {}", request.method.as_str());
+    let id = request.id.clone();
     let response = match request.method.as_str() {
         "bills" => handle_bills(&context, request.id, request.params).await,
         "latest_blocks" => handle_latestblocks(&context, request.id, request.params).await,
         "block" => handle_block(&context, request.id, request.params).await,
         "mutation" => handle_mutation(&context, request.id, request.params).await,
         "account" => handle_account(&context, request.id, request.params).await,
+        "get_database" => handle_get_database(&context, request.id, request.params).await,
+        "status" => handle_status(&context, request.id, request.params).await,
         "net_info" => handle_netinfo(&context, request.id, request.params).await,
         "validators" => handle_validators(&context, request.id, request.params).await,
         "broadcast" => handle_broadcast(&context, request.id, request.params).await,
-        _ => todo!(),
-    };
-    let r = match response {
-        Ok(r) => r,
-        Err(e) => {
-            let err_str = format!("{}", e);
-            let r = ResponseWrapper::Internal(json_rpc::Response {
-                jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                result: Value::Null,
-                error: Some(json_rpc::ErrorData::new(-32700, err_str.as_str())),
-                id: Value::Null,
-            });
-            r
+        "broadcast_mutation" => {
+            handle_broadcast_mutation(&context, request.id, request.params).await
         }
+        method => Err(json_rpc::ErrorData::std(json_rpc::METHOD_NOT_FOUND)
+            .with_data(json!({ "method": method }))),
     };
-    match r {
-        ResponseWrapper::Internal(i) => Ok(HttpResponse::Ok()
-            .content_type("application/json")
-            .body(i.dump())),
-        ResponseWrapper::External(e) => {
-            Ok(HttpResponse::Ok().content_type("application/json").body(e))
-        }
+    match response {
+        Ok(ResponseWrapper::Internal(i)) => Ok(json_response(i.dump())),
+        Ok(ResponseWrapper::External(e)) => Ok(json_response(e)),
+        Err(e) => Ok(json_response(json_rpc::Response::error(id, e).dump())),
     }
 }
 
 ///
-/// send mutation or query session to tendermint
+/// the id of the tendermint rpc, only an integer or a string is accepted
+///
+fn to_external_id(id: Value) -> Result<Id, json_rpc::ErrorData> {
+    match id {
+        Value::Number(n) if n.is_i64() => Ok(Id::Num(n.as_i64().unwrap_or_default())),
+        Value::String(s) => Ok(Id::Str(s)),
+        _ => Err(json_rpc::ErrorData::new(
+            json_rpc::INVALID_REQUEST,
+            "the id must be an integer or a string",
+        )),
+    }
+}
+
+fn invalid_params() -> json_rpc::ErrorData {
+    json_rpc::ErrorData::std(json_rpc::INVALID_PARAMS)
+}
+
+fn bad_params(e: impl std::fmt::Display) -> json_rpc::ErrorData {
+    json_rpc::ErrorData::new(json_rpc::INVALID_PARAMS, format!("{}", e).as_str())
+}
+
+fn internal_error(e: impl std::fmt::Display) -> json_rpc::ErrorData {
+    json_rpc::ErrorData::new(json_rpc::INTERNAL_ERROR, format!("{}", e).as_str())
+}
+
+///
+/// decode the base64 write request and verify its signature
+///
+fn decode_and_verify_request(s: &str) -> Result<(Vec<u8>, WriteRequest), json_rpc::ErrorData> {
+    let tx = base64::decode(s).map_err(bad_params)?;
+    let request = WriteRequest::decode(tx.as_ref()).map_err(bad_params)?;
+    DB3Verifier::verify_with_scheme(
+        request.payload.as_ref(),
+        request.signature.as_ref(),
+        request.signature_scheme,
+    )
+    .map_err(bad_params)?;
+    Ok((tx, request))
+}
+
+///
+/// send the verified tx to tendermint and return the base64 tx id, the tx is checked before
+/// returning so the rejected one is returned as an error with the code and the log of the check
+///
+async fn broadcast_tx(
+    context: &Context,
+    id: Value,
+    tx: Vec<u8>,
+) -> Result<ResponseWrapper, json_rpc::ErrorData> {
+    let tx_id = TxId::from(tx.as_ref());
+    let response = context
+        .client
+        .broadcast_tx_sync(tx)
+        .await
+        .map_err(internal_error)?;
+    if response.code.is_err() {
+        return Err(json_rpc::ErrorData::new(
+            json_rpc::TX_REJECTED,
+            format!("the request is rejected for {}", response.log).as_str(),
+        )
+        .with_data(json!({
+            "code": response.code.value(),
+            "log": response.log.to_string(),
+        })));
+    }
+    let external_id = to_external_id(id)?;
+    let wrapper = Wrapper {
+        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+        result: Some(tx_id.to_base64()),
+        id: external_id,
+    };
+    return Ok(ResponseWrapper::External(
+        serde_json::to_string(&wrapper).unwrap(),
+    ));
+}
+
+///
+/// send the signed mutation, query session or database mutation to tendermint
 ///
 async fn handle_broadcast(
     context: &Context,
@@ -175,61 +359,123 @@ async fn handle_broadcast(
     params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
     if params.len() == 0 {
-        let err = "invalid parameters";
-        Err(json_rpc::ErrorData::new(-32602, err))
+        Err(invalid_params())
     } else {
         // the param must be encoded as base64 string
         if let Value::String(s) = &params[0] {
-            let tx = base64::decode(s.as_str())
-                .map_err(|e| json_rpc::ErrorData::new(-32602, format!("{}", e).as_str()))?;
-            let response = context
-                .client
-                .broadcast_tx_async(tx)
-                .await
-                .map_err(|e| json_rpc::ErrorData::new(-32603, format!("{}", e).as_str()))?;
-            let external_id = match id {
-                Value::Number(n) => Id::Num(n.as_i64().unwrap()),
-                Value::String(s) => Id::Str(s),
-                _ => todo!(),
-            };
-            let base64_byte = base64::encode(response.hash.as_ref());
-            let hash = String::from_utf8_lossy(base64_byte.as_ref()).to_string();
-            let wrapper = Wrapper {
-                jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                result: Some(hash),
-                id: external_id,
-            };
-            return Ok(ResponseWrapper::External(
-                serde_json::to_string(&wrapper).unwrap(),
-            ));
+            let (tx, _) = decode_and_verify_request(s.as_str())?;
+            broadcast_tx(context, id, tx).await
+        } else {
+            Err(invalid_params())
+        }
+    }
+}
+
+///
+/// verify the mutation in a base64 write request and send it to tendermint
+///
+async fn handle_broadcast_mutation(
+    context: &Context,
+    id: Value,
+    params: Vec<Value>,
+) -> Result<ResponseWrapper, json_rpc::ErrorData> {
+    if params.len() == 0 {
+        Err(invalid_params())
+    } else {
+        if let Value::String(s) = &params[0] {
+            let (tx, request) = decode_and_verify_request(s.as_str())?;
+            if PayloadType::from_i32(request.payload_type) != Some(PayloadType::MutationPayload) {
+                return Err(json_rpc::ErrorData::new(
+                    json_rpc::INVALID_PARAMS,
+                    "the payload type must be mutation",
+                ));
+            }
+            let mutation = Mutation::decode(request.payload.as_ref()).map_err(bad_params)?;
+            if !KvStore::is_valid(&mutation, &context.mutation_limits) {
+                return Err(json_rpc::ErrorData::new(
+                    json_rpc::INVALID_PARAMS,
+                    "invalid mutation",
+                ));
+            }
+            broadcast_tx(context, id, tx).await
         } else {
-            let err = "invalid parameters";
-            Err(json_rpc::ErrorData::new(-32602, err))
+            Err(invalid_params())
         }
     }
 }
 
+///
+/// get the database with a hex format address
+///
+async fn handle_get_database(
+    context: &Context,
+    id: Value,
+    params: Vec<Value>,
+) -> Result<ResponseWrapper, json_rpc::ErrorData> {
+    if let Some(Value::String(addr)) = params.first() {
+        let db_id = DbId::try_from(addr.as_str()).map_err(bad_params)?;
+        match context.node_store.lock() {
+            Ok(mut store) => match store.get_auth_store().get_database(&db_id) {
+                Ok(Some(database)) => Ok(ResponseWrapper::Internal(json_rpc::Response {
+                    jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+                    result: database_to_value(&database),
+                    error: None,
+                    id,
+                })),
+                Ok(None) => Err(json_rpc::ErrorData::new(
+                    json_rpc::INVALID_PARAMS,
+                    format!("database with addr {} was not found", addr).as_str(),
+                )),
+                Err(e) => Err(internal_error(e)),
+            },
+            Err(e) => Err(internal_error(e)),
+        }
+    } else {
+        Err(invalid_params())
+    }
+}
+
+///
+/// report the last committed height, the app hash, the version and the counters
+///
+async fn handle_status(
+    context: &Context,
+    id: Value,
+    _params: Vec<Value>,
+) -> Result<ResponseWrapper, json_rpc::ErrorData> {
+    let (height, app_hash) = match context.node_store.lock() {
+        Ok(mut store) => {
+            let state = store.get_auth_store().get_last_block_state();
+            (state.block_height, state.abci_hash)
+        }
+        Err(e) => {
+            return Err(internal_error(e));
+        }
+    };
+    Ok(ResponseWrapper::Internal(json_rpc::Response {
+        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+        result: status_to_value(height, &app_hash, &context.node_state),
+        error: None,
+        id,
+    }))
+}
+
 async fn handle_validators(
     context: &Context,
     id: Value,
     params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
     if params.len() == 0 {
-        let err = "invalid parameters";
-        Err(json_rpc::ErrorData::new(-32602, err))
+        Err(invalid_params())
     } else {
-        if let Value::Number(n) = &params[0] {
-            let height = n.as_u64().unwrap() as u32;
+        if let Some(height) = params[0].as_u64() {
+            let height = u32::try_from(height).map_err(|_| invalid_params())?;
             let response = context
                 .client
                 .validators(height, Paging::All)
                 .await
-                .map_err(|e| json_rpc::ErrorData::new(-32603, format!("{}", e).as_str()))?;
-            let external_id = match id {
-                Value::Number(n) => Id::Num(n.as_i64().unwrap()),
-                Value::String(s) => Id::Str(s),
-                _ => todo!(),
-            };
+                .map_err(internal_error)?;
+            let external_id = to_external_id(id)?;
             let wrapper = Wrapper {
                 jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
                 result: Some(response),
@@ -239,7 +485,7 @@ async fn handle_validators(
                 serde_json::to_string(&wrapper).unwrap(),
             ));
         }
-        Err(json_rpc::ErrorData::std(-32602))
+        Err(invalid_params())
     }
 }
 
@@ -248,16 +494,8 @@ async fn handle_netinfo(
     id: Value,
     _params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
-    let response = context
-        .client
-        .net_info()
-        .await
-        .map_err(|e| json_rpc::ErrorData::new(-32603, format!("{}", e).as_str()))?;
-    let external_id = match id {
-        Value::Number(n) => Id::Num(n.as_i64().unwrap()),
-        Value::String(s) => Id::Str(s),
-        _ => todo!(),
-    };
+    let response = context.client.net_info().await.map_err(internal_error)?;
+    let external_id = to_external_id(id)?;
     let wrapper = Wrapper {
         jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
         result: Some(response),
@@ -273,35 +511,22 @@ async fn handle_account(
     id: Value,
     params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
-    if params.len() == 0 {
-        let err = "invalid parameters";
-        Err(json_rpc::ErrorData::new(-32601, err))
-    } else {
-        if let Value::String(s) = &params[0] {
-            if let Ok(addr) = DB3Address::try_from(s.as_str()) {
-                let account = match context.node_store.lock() {
-                    Ok(mut store) => store.get_auth_store().get_account(&addr),
-                    _ => todo!(),
-                }
-                .map_err(|_| json_rpc::ErrorData::new(-32601, "fail to get account"))?;
-                let external_id = match id {
-                    Value::Number(n) => Id::Num(n.as_i64().unwrap()),
-                    Value::String(s) => Id::Str(s),
-                    _ => todo!(),
-                };
-                let wrapper = Wrapper {
-                    jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                    result: Some(account),
-                    id: external_id,
-                };
-                return Ok(ResponseWrapper::External(
-                    serde_json::to_string(&wrapper).unwrap(),
-                ));
-            }
-        }
-        let err = "invalid parameters";
-        Err(json_rpc::ErrorData::new(-32601, err))
-    }
+    let addr = match params.first() {
+        Some(Value::String(s)) => DB3Address::try_from(s.as_str())?,
+        _ => return Err(invalid_params()),
+    };
+    let account = match context.node_store.lock() {
+        Ok(mut store) => store.get_auth_store().get_account(&addr)?,
+        Err(e) => return Err(internal_error(e)),
+    };
+    let wrapper = Wrapper {
+        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+        result: Some(account),
+        id: to_external_id(id)?,
+    };
+    Ok(ResponseWrapper::External(
+        serde_json::to_string(&wrapper).map_err(internal_error)?,
+    ))
 }
 
 async fn handle_mutation(
@@ -309,34 +534,27 @@ async fn handle_mutation(
     id: Value,
     params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
-    if params.len() == 0 {
-        let err = "invalid parameters";
-        Err(json_rpc::ErrorData::new(-32601, err))
-    } else {
-        if let Value::String(s) = &params[0] {
-            let tx_hash_ret = hash_util::base64_to_hash(s.as_str());
-            if let Ok(tx_hash) = tx_hash_ret {
-                let response = context.client.tx(tx_hash, false).await.unwrap();
-                let wrequest = WriteRequest::decode(response.tx.as_ref()).unwrap();
-                let readable_mutation = convert_mutation_to_readable(&wrequest);
-                let external_id = match id {
-                    Value::Number(n) => Id::Num(n.as_i64().unwrap()),
-                    Value::String(s) => Id::Str(s),
-                    _ => todo!(),
-                };
-                let wrapper = Wrapper {
-                    jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                    result: Some(readable_mutation),
-                    id: external_id,
-                };
-                return Ok(ResponseWrapper::External(
-                    serde_json::to_string(&wrapper).unwrap(),
-                ));
-            }
+    let tx_hash = match params.first() {
+        Some(Value::String(s)) => {
+            hash_util::base64_to_hash(s.as_str()).map_err(|_| invalid_params())?
         }
-        let err = "respnse errr";
-        Err(json_rpc::ErrorData::new(-32601, err))
-    }
+        _ => return Err(invalid_params()),
+    };
+    let response = context
+        .client
+        .tx(tx_hash, false)
+        .await
+        .map_err(internal_error)?;
+    let wrequest = WriteRequest::decode(response.tx.as_ref()).map_err(internal_error)?;
+    let readable_mutation = convert_mutation_to_readable(&wrequest)?;
+    let wrapper = Wrapper {
+        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+        result: Some(readable_mutation),
+        id: to_external_id(id)?,
+    };
+    Ok(ResponseWrapper::External(
+        serde_json::to_string(&wrapper).map_err(internal_error)?,
+    ))
 }
 
 async fn handle_block(
@@ -344,33 +562,23 @@ async fn handle_block(
     id: Value,
     params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
-    if params.len() == 0 {
-        let err = "invalid parameters";
-        Err(json_rpc::ErrorData::new(-32601, err))
-    } else {
-        if let Value::String(s) = &params[0] {
-            if let Ok(h) = TMHash::from_str(s.as_str()) {
-                let response = context.client.block_by_hash(h).await;
-                let external_id = match id {
-                    Value::Number(n) => Id::Num(n.as_i64().unwrap()),
-                    Value::String(s) => Id::Str(s),
-                    _ => todo!(),
-                };
-                if let Ok(r) = response {
-                    let wrapper = Wrapper {
-                        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                        result: Some(r),
-                        id: external_id,
-                    };
-                    return Ok(ResponseWrapper::External(
-                        serde_json::to_string(&wrapper).unwrap(),
-                    ));
-                }
-            }
-        }
-        let err = "respnse errr";
-        Err(json_rpc::ErrorData::new(-32601, err))
-    }
+    let hash = match params.first() {
+        Some(Value::String(s)) => TMHash::from_str(s.as_str()).map_err(|_| invalid_params())?,
+        _ => return Err(invalid_params()),
+    };
+    let response = context
+        .client
+        .block_by_hash(hash)
+        .await
+        .map_err(internal_error)?;
+    let wrapper = Wrapper {
+        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+        result: Some(response),
+        id: to_external_id(id)?,
+    };
+    Ok(ResponseWrapper::External(
+        serde_json::to_string(&wrapper).map_err(internal_error)?,
+    ))
 }
 
 async fn handle_bills(
@@ -378,31 +586,20 @@ async fn handle_bills(
     id: Value,
     params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
-    if params.len() == 0 {
-        let err = "invalid parameters";
-        Err(json_rpc::ErrorData::new(-32601, err))
-    } else {
-        if let Value::Number(n) = &params[0] {
-            match context.node_store.lock() {
-                Ok(mut store) => {
-                    if let Ok(bills) = store
-                        .get_auth_store()
-                        .get_bills(n.as_u64().unwrap(), 1, 100)
-                    {
-                        let value = bills_to_value(&bills);
-                        return Ok(ResponseWrapper::Internal(json_rpc::Response {
-                            jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                            result: value,
-                            error: None,
-                            id,
-                        }));
-                    }
-                }
-                _ => todo!(),
-            }
-        }
-        Err(json_rpc::ErrorData::std(-32601))
-    }
+    let height = params
+        .first()
+        .and_then(|v| v.as_u64())
+        .ok_or_else(invalid_params)?;
+    let bills = match context.node_store.lock() {
+        Ok(mut store) => store.get_auth_store().get_bills(height, 1, 100)?,
+        Err(e) => return Err(internal_error(e)),
+    };
+    Ok(ResponseWrapper::Internal(json_rpc::Response {
+        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+        result: bills_to_value(&bills),
+        error: None,
+        id,
+    }))
 }
 
 async fn handle_latestblocks(
@@ -410,36 +607,217 @@ async fn handle_latestblocks(
     id: Value,
     _params: Vec<Value>,
 ) -> Result<ResponseWrapper, json_rpc::ErrorData> {
-    let response = context.client.status().await;
-    match response {
-        Ok(status) => {
-            let max_height = status.sync_info.latest_block_height.value();
-            let min_height = max_height - 10;
-            let block_chain_response = context
-                .client
-                .blockchain(min_height as u32, max_height as u32)
-                .await;
-            let external_id = match id {
-                Value::Number(n) => Id::Num(n.as_i64().unwrap()),
-                Value::String(s) => Id::Str(s),
-                _ => todo!(),
-            };
-            if let Ok(r) = block_chain_response {
-                let wrapper = Wrapper {
-                    jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
-                    result: Some(r),
-                    id: external_id,
-                };
-                return Ok(ResponseWrapper::External(
-                    serde_json::to_string(&wrapper).unwrap(),
-                ));
-            } else {
-                Err(json_rpc::ErrorData::std(-32601))
-            }
-        }
-        Err(e) => {
-            let err = format!("{}", e);
-            Err(json_rpc::ErrorData::new(-32601, err.as_str()))
+    let status = context.client.status().await.map_err(internal_error)?;
+    let max_height = status.sync_info.latest_block_height.value();
+    let min_height = max_height.saturating_sub(10);
+    let response = context
+        .client
+        .blockchain(min_height as u32, max_height as u32)
+        .await
+        .map_err(internal_error)?;
+    let wrapper = Wrapper {
+        jsonrpc: String::from(json_rpc::JSONRPC_VERSION),
+        result: Some(response),
+        id: to_external_id(id)?,
+    };
+    Ok(ResponseWrapper::External(
+        serde_json::to_string(&wrapper).map_err(internal_error)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abci_impl::AbciImpl;
+    use crate::auth_storage::AuthStorage;
+    use crate::node_storage::NodeStorage;
+    use crate::tx_dedup_cache::DEFAULT_MEMPOOL_DEDUP_SIZE;
+    use db3_proto::db3_base_proto::ChainId;
+    use db3_storage::kv_store::MutationLimits;
+    use merkdb::Merk;
+    use std::sync::{Arc, Mutex};
+    use tempdir::TempDir;
+    use tendermint_rpc::HttpClient;
+
+    fn build_context(dir: &TempDir) -> Context {
+        let merk = Merk::open(dir.path()).unwrap();
+        let node_store = Arc::new(Mutex::new(Box::pin(NodeStorage::new(AuthStorage::new(
+            merk,
+        )))));
+        let abci = AbciImpl::new(
+            node_store.clone(),
+            DEFAULT_MEMPOOL_DEDUP_SIZE,
+            MutationLimits::default(),
+            0,
+        );
+        Context {
+            node_store,
+            // nothing listens on the port, so the calls to tendermint fail
+            client: HttpClient::new("http://127.0.0.1:1").unwrap(),
+            mutation_limits: MutationLimits::default(),
+            pending_queue: abci.get_pending_queue(),
+            node_state: abci.get_node_state().clone(),
+            admin_token: None,
+            min_gas_price: Units::default(),
+            grpc_metrics: None,
+            chain_id: ChainId::DevNet,
         }
     }
+
+    ///
+    /// answer one json rpc request of tendermint with the result and return the url
+    ///
+    fn mock_tendermint(result: Value) -> String {
+        use std::io::{BufRead, BufReader, Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+            let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+            let response = response.to_string();
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    fn build_signed_tx() -> String {
+        use db3_crypto::db3_signer::Db3MultiSchemeSigner;
+        use db3_crypto::key_derive;
+        use db3_crypto::signature_scheme::SignatureScheme;
+        let seed: [u8; 32] = [0; 32];
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::ED25519).unwrap();
+        let signer = Db3MultiSchemeSigner::new(kp);
+        let tx = db3_sdk::mutation_sdk::build_write_request(
+            &signer,
+            "payload".as_bytes(),
+            PayloadType::MutationPayload,
+        )
+        .unwrap();
+        String::from_utf8(base64::encode(tx)).unwrap()
+    }
+
+    async fn call(context: &Context, body: &str) -> Value {
+        let response = rpc_router(
+            Bytes::from(body.to_string()),
+            web::Data::new(context.clone()),
+        )
+        .await
+        .unwrap();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        serde_json::from_slice(body.as_ref()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_return_json_rpc_errors() {
+        let tmp_dir_path = TempDir::new("json_rpc_errors").expect("create temp dir");
+        let context = build_context(&tmp_dir_path);
+        // the malformed json
+        let r = call(&context, r#"{"jsonrpc": "2.0", "method""#).await;
+        assert_eq!(json!(json_rpc::PARSE_ERROR), r["error"]["code"]);
+        assert_eq!(Value::Null, r["id"]);
+        // the json is not a request
+        let r = call(&context, r#"{"jsonrpc": "2.0", "id": 1}"#).await;
+        assert_eq!(json!(json_rpc::INVALID_REQUEST), r["error"]["code"]);
+        assert_eq!(json!(1), r["id"]);
+        let r = call(
+            &context,
+            r#"{"jsonrpc": "2.0", "method": "no_such_method", "id": 2}"#,
+        )
+        .await;
+        assert_eq!(json!(json_rpc::METHOD_NOT_FOUND), r["error"]["code"]);
+        assert_eq!(json!("no_such_method"), r["error"]["data"]["method"]);
+        assert_eq!(json!(2), r["id"]);
+        let r = call(
+            &context,
+            r#"{"jsonrpc": "2.0", "method": "account", "params": ["bad"], "id": "3"}"#,
+        )
+        .await;
+        assert_eq!(json!(json_rpc::INVALID_PARAMS), r["error"]["code"]);
+        assert_eq!(json!("InvalidAddress"), r["error"]["data"]["error"]);
+        assert_eq!(json!("3"), r["id"]);
+        let r = call(
+            &context,
+            r#"{"jsonrpc": "2.0", "method": "bills", "params": [], "id": 4}"#,
+        )
+        .await;
+        assert_eq!(json!(json_rpc::INVALID_PARAMS), r["error"]["code"]);
+        let r = call(
+            &context,
+            r#"{"jsonrpc": "2.0", "method": "net_info", "id": 5}"#,
+        )
+        .await;
+        assert_eq!(json!(json_rpc::INTERNAL_ERROR), r["error"]["code"]);
+        assert_eq!(json!(5), r["id"]);
+        // the successful call has no error
+        let r = call(
+            &context,
+            r#"{"jsonrpc": "2.0", "method": "status", "id": 6}"#,
+        )
+        .await;
+        assert!(r.get("error").is_none());
+        assert_eq!(json!(0), r["result"]["height"]);
+    }
+
+    #[tokio::test]
+    async fn it_return_rejected_tx_as_error() {
+        let tmp_dir_path = TempDir::new("json_rpc_broadcast").expect("create temp dir");
+        let mut context = build_context(&tmp_dir_path);
+        let url = mock_tendermint(json!({
+            "code": 7,
+            "data": "",
+            "log": "the tx is signed for chain MainNet but the node is on chain DevNet",
+            "codespace": "db3",
+            "hash": "0000000000000000000000000000000000000000000000000000000000000000",
+        }));
+        context.client = HttpClient::new(url.as_str()).unwrap();
+        let body = json!({
+            "jsonrpc": "2.0",
+            "method": "broadcast",
+            "params": [build_signed_tx()],
+            "id": 1,
+        });
+        let r = call(&context, body.to_string().as_str()).await;
+        assert_eq!(json!(json_rpc::TX_REJECTED), r["error"]["code"]);
+        assert_eq!(json!(7), r["error"]["data"]["code"]);
+        assert_eq!(json!(1), r["id"]);
+        // the accepted tx returns its id
+        let url = mock_tendermint(json!({
+            "code": 0,
+            "data": "",
+            "log": "",
+            "codespace": "",
+            "hash": "0000000000000000000000000000000000000000000000000000000000000000",
+        }));
+        context.client = HttpClient::new(url.as_str()).unwrap();
+        let r = call(&context, body.to_string().as_str()).await;
+        assert!(r.get("error").is_none());
+        assert!(r["result"].is_string());
+    }
 }
@@ -16,12 +16,21 @@
 //
 
 pub mod abci_impl;
+pub mod abci_server;
 pub mod auth_storage;
 pub mod command;
 pub mod context;
+pub mod cors_config;
+pub mod db_options;
+pub mod doctor;
+pub mod grpc_metrics;
 mod hash_util;
 mod json_rpc;
 pub mod json_rpc_impl;
+pub mod node_config;
 pub mod node_key;
 pub mod node_storage;
+pub mod proof_bench;
+pub mod rate_limiter;
 pub mod storage_node_impl;
+pub mod tx_dedup_cache;
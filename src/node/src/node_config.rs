@@ -1,6 +1,6 @@
 //
 // node_config.rs
-// Copyright (C) 2022 db3.network Author imotai <codego.me@gmail.com>
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
 //
 // Licensed under the Apache License, Version 2.0 (the "License");
 // you may not use this file except in compliance with the License.
@@ -15,13 +15,246 @@
 // limitations under the License.
 //
 
+use db3_base::strings;
+use db3_crypto::db3_address::DB3Address;
+use db3_error::{DB3Error, Result};
+use db3_proto::db3_base_proto::Units;
+use db3_storage::account_store::MAX_NONCE_WINDOW;
+use db3_storage::kv_store::NsQuotas;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// an account and its initial credits, e.g. {"addr": "0x...", "credits": "100db3"}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    /// the hex address of the account
+    pub addr: String,
+    /// the credits with an optional unit, the unit is tai if it's omitted
+    pub credits: String,
+}
+
+///
+/// the genesis of the chain, the state seeded to an empty store when the chain starts
+/// and the settings deciding which txs are applied. the settings change the app hash,
+/// so every validator of the chain must load the same genesis
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ChainInitConfig {
+    pub accounts: Vec<GenesisAccount>,
+    /// verify the signatures of the txs in a block in one batch when committing it
+    #[serde(default)]
+    pub batch_verify_signatures: bool,
+    /// the max bytes of keys and values in a namespace of an account, 0 means no limit
+    #[serde(default)]
+    pub ns_quota_bytes: u64,
+    /// the quotas in bytes of namespaces overriding ns_quota_bytes, e.g. {"ns1": 1048576}
+    #[serde(default)]
+    pub ns_quotas: NsQuotaConfig,
+    /// reject the writes to the namespaces owned by other accounts unless the owner grants them
+    #[serde(default)]
+    pub enable_ns_acl: bool,
+    /// accept the mutations with a nonce greater than the last applied one of the account
+    /// minus the window, the nonces seen in the window are rejected. 0 disables the check
+    #[serde(default)]
+    pub nonce_window: u64,
+}
+
+impl ChainInitConfig {
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Self = serde_json::from_str(content)
+            .map_err(|e| DB3Error::InvalidGenesisError(format!("{e}")))?;
+        if config.nonce_window > MAX_NONCE_WINDOW {
+            return Err(DB3Error::InvalidGenesisError(format!(
+                "the nonce window {} is greater than {MAX_NONCE_WINDOW}",
+                config.nonce_window
+            )));
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            DB3Error::InvalidGenesisError(format!("fail to read {} for {e}", path.display()))
+        })?;
+        Self::parse(content.as_str())
+    }
+
+    /// the addresses and the credits of the genesis accounts
+    pub fn genesis_credits(&self) -> Result<Vec<(DB3Address, Units)>> {
+        self.accounts
+            .iter()
+            .map(|account| {
+                let addr = DB3Address::try_from(account.addr.as_str()).map_err(|_| {
+                    DB3Error::InvalidGenesisError(format!("invalid address {}", account.addr))
+                })?;
+                let credits = strings::parse_units(account.credits.as_str())
+                    .map_err(DB3Error::InvalidGenesisError)?;
+                Ok((addr, credits))
+            })
+            .collect()
+    }
+
+    /// the quotas of namespaces checked by the mutations
+    pub fn get_ns_quotas(&self) -> NsQuotas {
+        self.ns_quotas.to_ns_quotas(self.ns_quota_bytes)
+    }
+}
+
+///
+/// the quotas of namespaces in bytes overriding the default one, e.g. {"ns1": 1048576}
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NsQuotaConfig(pub HashMap<String, u64>);
+
+impl NsQuotaConfig {
+    pub fn to_ns_quotas(&self, default_bytes: u64) -> NsQuotas {
+        NsQuotas {
+            default_bytes,
+            overrides: self
+                .0
+                .iter()
+                .map(|(ns, bytes)| (ns.as_bytes().to_vec(), *bytes))
+                .collect(),
+        }
+    }
+}
+
+///
+/// the directory housing the db and the config files of the node, the relative paths
+/// are resolved against it
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataRoot(PathBuf);
+
+impl DataRoot {
+    ///
+    /// create the directory if it does not exist and resolve it to an absolute one
+    ///
+    pub fn open(root: &str) -> Result<Self> {
+        std::fs::create_dir_all(root).map_err(|e| {
+            DB3Error::InvalidDataRootError(format!("fail to create {root} for {e}"))
+        })?;
+        let path = std::fs::canonicalize(root).map_err(|e| {
+            DB3Error::InvalidDataRootError(format!("fail to resolve {root} for {e}"))
+        })?;
+        if !path.is_dir() {
+            return Err(DB3Error::InvalidDataRootError(format!(
+                "{} is not a directory",
+                path.display()
+            )));
+        }
+        Ok(Self(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        self.0.as_path()
+    }
+
+    /// the absolute path is kept as it is
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+///
+/// resolve the path against the data root, or the current directory if there is no root
+///
+pub fn resolve_path(data_root: Option<&DataRoot>, path: &str) -> PathBuf {
+    match data_root {
+        Some(root) => root.resolve(path),
+        None => std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| PathBuf::from(path)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use db3_proto::db3_base_proto::UnitType;
+
+    #[test]
+    fn it_parse_genesis_accounts() {
+        let addr = format!("0x{}", hex::encode([1u8; 20]));
+        let content = format!(
+            r#"{{"accounts": [{{"addr": "{addr}", "credits": "100db3"}}, {{"addr": "{addr}", "credits": "5"}}]}}"#
+        );
+        let config = ChainInitConfig::parse(content.as_str()).unwrap();
+        let credits = config.genesis_credits().unwrap();
+        assert_eq!(2, credits.len());
+        assert_eq!(DB3Address::try_from(addr.as_str()).unwrap(), credits[0].0);
+        assert_eq!(
+            Units {
+                utype: UnitType::Db3.into(),
+                amount: 100
+            },
+            credits[0].1
+        );
+        assert_eq!(
+            Units {
+                utype: UnitType::Tai.into(),
+                amount: 5
+            },
+            credits[1].1
+        );
+        let bad = r#"{"accounts": [{"addr": "bad", "credits": "1db3"}]}"#;
+        assert!(ChainInitConfig::parse(bad)
+            .unwrap()
+            .genesis_credits()
+            .is_err());
+        assert!(ChainInitConfig::parse("not json").is_err());
+    }
+
+    #[test]
+    fn it_parse_chain_settings() {
+        let config = ChainInitConfig::parse(r#"{"accounts": []}"#).unwrap();
+        assert!(!config.batch_verify_signatures);
+        let config =
+            ChainInitConfig::parse(r#"{"accounts": [], "batch_verify_signatures": true}"#).unwrap();
+        assert!(config.batch_verify_signatures);
+        assert!(!config.enable_ns_acl);
+        let config = ChainInitConfig::parse(r#"{"accounts": [], "enable_ns_acl": true}"#).unwrap();
+        assert!(config.enable_ns_acl);
+        let config = ChainInitConfig::parse(r#"{"accounts": [], "nonce_window": 64}"#).unwrap();
+        assert_eq!(64, config.nonce_window);
+        assert!(ChainInitConfig::parse(r#"{"accounts": [], "nonce_window": 65}"#).is_err());
+    }
+
+    #[test]
+    fn it_resolve_paths_in_data_root() {
+        let tmp_dir = tempdir::TempDir::new("data_root").expect("create temp dir");
+        let root = tmp_dir.path().join("node1").join("data");
+        let data_root = DataRoot::open(root.to_str().unwrap()).unwrap();
+        assert!(data_root.path().is_absolute());
+        assert!(data_root.path().is_dir());
+        assert_eq!(data_root.path().join("db"), data_root.resolve("db"));
+        assert_eq!(PathBuf::from("/var/db3"), data_root.resolve("/var/db3"));
+        assert_eq!(
+            data_root.path().join("genesis.json"),
+            resolve_path(Some(&data_root), "genesis.json")
+        );
+        assert!(resolve_path(None, "db").is_absolute());
+        // a file can't be the data root
+        let file = tmp_dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(DataRoot::open(file.to_str().unwrap()).is_err());
+    }
 
-	#[test]
-	fn it_works() {
-	}
+    #[test]
+    fn it_parse_ns_quotas() {
+        let config = ChainInitConfig::parse(
+            r#"{"accounts": [], "ns_quota_bytes": 64, "ns_quotas": {"ns1": 1024, "ns2": 0}}"#,
+        )
+        .unwrap();
+        let quotas = config.get_ns_quotas();
+        assert_eq!(1024, quotas.quota("ns1".as_bytes()));
+        // 0 lifts the default quota of the namespace
+        assert_eq!(0, quotas.quota("ns2".as_bytes()));
+        assert_eq!(64, quotas.quota("ns3".as_bytes()));
+        // no quota by default
+        let config = ChainInitConfig::parse(r#"{"accounts": []}"#).unwrap();
+        assert_eq!(0, config.get_ns_quotas().quota("ns1".as_bytes()));
+        assert!(ChainInitConfig::parse(r#"{"accounts": [], "ns_quotas": {"ns1": -1}}"#).is_err());
+    }
 }
@@ -13,6 +13,11 @@ impl NodeStorage {
             session_store: SessionStore::new(),
         }
     }
+    /// limit the query sessions open at the same time, 0 disables the limit
+    pub fn with_max_open_sessions(mut self, max_open_sessions: usize) -> Self {
+        self.session_store = SessionStore::new().with_max_open_sessions(max_open_sessions);
+        self
+    }
     pub fn get_auth_store(&mut self) -> &mut AuthStorage {
         &mut self.auth_store
     }
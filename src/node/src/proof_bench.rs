@@ -0,0 +1,140 @@
+//
+// proof_bench.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use db3_crypto::db3_address::DB3Address;
+use db3_error::{DB3Error, Result};
+use db3_proto::db3_base_proto::{ChainId, ChainRole};
+use db3_proto::db3_mutation_proto::{KvPair, Mutation, MutationAction};
+use db3_storage::key::Key;
+use db3_storage::kv_store::KvStore;
+use merkdb::proofs::query::{verify, Query};
+use merkdb::Merk;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+const BENCH_NS: &str = "bench_proof";
+
+/// the cost of proofs, generation is paid by the node and verification by the client
+pub struct ProofBenchReport {
+    pub generation: Vec<Duration>,
+    pub verification: Vec<Duration>,
+    pub proof_sizes: Vec<usize>,
+}
+
+impl ProofBenchReport {
+    /// the p-th percentile of the sorted values
+    pub fn percentile<T: Copy + Ord>(values: &[T], p: usize) -> Option<T> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        let idx = (sorted.len() * p / 100).min(sorted.len() - 1);
+        Some(sorted[idx])
+    }
+}
+
+///
+/// write `keys` kv pairs to the db and generate then verify the proof of every key
+///
+pub fn bench_proof(
+    mut db: Pin<&mut Merk>,
+    addr: &DB3Address,
+    keys: usize,
+) -> Result<ProofBenchReport> {
+    let kv_pairs: Vec<KvPair> = (0..keys)
+        .map(|i| KvPair {
+            key: format!("key{i}").as_bytes().to_vec(),
+            value: format!("value{i}").as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        })
+        .collect();
+    let mutation = Mutation {
+        ns: BENCH_NS.as_bytes().to_vec(),
+        kv_pairs: kv_pairs.to_vec(),
+        nonce: 1,
+        chain_id: ChainId::DevNet.into(),
+        chain_role: ChainRole::StorageShardChain.into(),
+        gas_price: None,
+        gas: 0,
+    };
+    KvStore::apply(db.as_mut(), addr, &mutation, 1, 0)?;
+    let root_hash = db.root_hash();
+    let mut report = ProofBenchReport {
+        generation: Vec::new(),
+        verification: Vec::new(),
+        proof_sizes: Vec::new(),
+    };
+    for kv in kv_pairs {
+        let encoded_key = Key(*addr, BENCH_NS.as_bytes(), kv.key.as_ref()).encode()?;
+        let mut query = Query::new();
+        query.insert_key(encoded_key.to_vec());
+        let start = Instant::now();
+        let proof = db
+            .prove(query)
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        report.generation.push(start.elapsed());
+        report.proof_sizes.push(proof.len());
+        let start = Instant::now();
+        let map = verify(proof.as_ref(), root_hash)
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        let value = map
+            .get(encoded_key.as_ref())
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        report.verification.push(start.elapsed());
+        if value != Some(kv.value.as_ref()) {
+            return Err(DB3Error::QueryKvError(
+                "the proof does not match the value".to_string(),
+            ));
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db3_crypto::db3_address::DB3_ADDRESS_LENGTH;
+    use std::boxed::Box;
+    use tempdir::TempDir;
+
+    #[test]
+    fn it_bench_proof_smoke() {
+        let tmp_dir_path = TempDir::new("bench_proof").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let data: [u8; DB3_ADDRESS_LENGTH] = [1; DB3_ADDRESS_LENGTH];
+        let addr = DB3Address::from(&data);
+        let report = bench_proof(Pin::as_mut(&mut db), &addr, 10).unwrap();
+        assert_eq!(10, report.generation.len());
+        assert_eq!(10, report.verification.len());
+        assert!(report.proof_sizes.iter().all(|s| *s > 0));
+        assert!(ProofBenchReport::percentile(&report.proof_sizes, 99).is_some());
+    }
+
+    #[test]
+    fn it_percentile() {
+        let values: Vec<usize> = (1..=100).rev().collect();
+        assert_eq!(Some(51), ProofBenchReport::percentile(&values, 50));
+        assert_eq!(Some(100), ProofBenchReport::percentile(&values, 100));
+        assert_eq!(None, ProofBenchReport::percentile::<usize>(&[], 50));
+    }
+}
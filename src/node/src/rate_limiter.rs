@@ -0,0 +1,122 @@
+//
+// rate_limiter.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use db3_crypto::db3_address::DB3Address as AccountAddress;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// the idle buckets are dropped when the number of buckets exceeds it
+const MAX_IDLE_BUCKETS: usize = 4096;
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+///
+/// a token bucket for every account, the bucket holds at most one second of txs
+/// and is refilled with the rate as time goes. a zero rate disables the limiter
+///
+pub struct RateLimiter {
+    rate: u64,
+    buckets: HashMap<AccountAddress, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            buckets: HashMap::new(),
+        }
+    }
+
+    ///
+    /// take a token from the bucket of the account and return false if it's empty
+    ///
+    pub fn try_acquire(&mut self, addr: &AccountAddress, now: Instant) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        if self.buckets.len() > MAX_IDLE_BUCKETS {
+            self.evict_full_buckets(now);
+        }
+        let capacity = self.rate as f64;
+        let bucket = self.buckets.entry(*addr).or_insert(Bucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * capacity).min(capacity);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// the full buckets are the same as the new ones, so they can be dropped
+    fn evict_full_buckets(&mut self, now: Instant) {
+        let capacity = self.rate as f64;
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.saturating_duration_since(bucket.last_refill);
+            bucket.tokens + elapsed.as_secs_f64() * capacity < capacity
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db3_crypto::db3_address::DB3_ADDRESS_LENGTH;
+    use std::time::Duration;
+
+    #[test]
+    fn it_refill_the_bucket() {
+        let mut limiter = RateLimiter::new(2);
+        let addr = AccountAddress::from(&[1; DB3_ADDRESS_LENGTH]);
+        let other = AccountAddress::from(&[2; DB3_ADDRESS_LENGTH]);
+        let now = Instant::now();
+        assert!(limiter.try_acquire(&addr, now));
+        assert!(limiter.try_acquire(&addr, now));
+        assert!(!limiter.try_acquire(&addr, now));
+        // the other account has its own bucket
+        assert!(limiter.try_acquire(&other, now));
+        assert!(limiter.try_acquire(&addr, now + Duration::from_millis(500)));
+        assert!(!limiter.try_acquire(&addr, now + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn it_disable_limiter_with_zero_rate() {
+        let mut limiter = RateLimiter::new(0);
+        let addr = AccountAddress::from(&[1; DB3_ADDRESS_LENGTH]);
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(limiter.try_acquire(&addr, now));
+        }
+        assert!(limiter.is_empty());
+    }
+}
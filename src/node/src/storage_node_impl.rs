@@ -17,17 +17,26 @@
 
 use super::context::Context;
 use db3_crypto::db3_address::DB3Address;
+use db3_crypto::db3_signature::DB3Signature;
 use db3_crypto::db3_signer::Db3MultiSchemeSigner;
-use db3_crypto::{db3_verifier::DB3Verifier, id::DbId};
+use db3_crypto::{
+    db3_verifier::DB3Verifier,
+    id::{DbId, TxId},
+};
 use db3_proto::db3_account_proto::Account;
-use db3_proto::db3_base_proto::{ChainId, ChainRole};
-use db3_proto::db3_mutation_proto::{PayloadType, WriteRequest};
+use db3_proto::db3_base_proto::ChainRole;
+use db3_proto::db3_mutation_proto::{DatabaseMutation, Mutation, PayloadType, WriteRequest};
 use db3_proto::db3_node_proto::{
-    storage_node_server::StorageNode, BroadcastRequest, BroadcastResponse, CloseSessionRequest,
-    CloseSessionResponse, GetAccountRequest, GetKeyRequest, GetKeyResponse, GetRangeRequest,
-    GetRangeResponse, GetSessionInfoRequest, GetSessionInfoResponse, OpenSessionRequest,
-    OpenSessionResponse, QueryBillRequest, QueryBillResponse, ShowDatabaseRequest,
-    ShowDatabaseResponse,
+    storage_node_server::StorageNode, AccountEntry, BatchGetAccountRequest,
+    BatchGetAccountResponse, BroadcastRequest, BroadcastResponse, CloseSessionRequest,
+    CloseSessionResponse, CountKeysRequest, CountKeysResponse, EstimateGasRequest,
+    EstimateGasResponse, GetAccountRequest, GetKeyRequest, GetKeyResponse, GetLatestStateRequest,
+    GetLatestStateResponse, GetMinGasPriceRequest, GetMinGasPriceResponse, GetPendingTxsRequest,
+    GetPendingTxsResponse, GetQuerySessionReceiptRequest, GetQuerySessionReceiptResponse,
+    GetRangeRequest, GetRangeResponse, GetSessionInfoRequest, GetSessionInfoResponse, GetTxRequest,
+    GetTxResponse, ListDatabasesRequest, ListDatabasesResponse, ListNamespacesRequest,
+    ListNamespacesResponse, NamespaceStat, OpenSessionRequest, OpenSessionResponse,
+    QueryBillRequest, QueryBillResponse, ShowDatabaseRequest, ShowDatabaseResponse,
 };
 use db3_proto::db3_session_proto::{
     CloseSessionPayload, OpenSessionPayload, QuerySession, QuerySessionInfo,
@@ -35,14 +44,21 @@ use db3_proto::db3_session_proto::{
 use db3_session::query_session_verifier;
 use db3_session::session_manager::DEFAULT_SESSION_PERIOD;
 use db3_session::session_manager::DEFAULT_SESSION_QUERY_LIMIT;
+use db3_types::cost;
 use prost::Message;
 use std::boxed::Box;
+use tendermint::Hash as TMHash;
 use tendermint_rpc::Client;
 use tonic::{Request, Response, Status};
 
 use bytes::BytesMut;
 use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
 use tracing::info;
+
+const DEFAULT_PENDING_TX_SAMPLE_SIZE: usize = 10;
+const MAX_PENDING_TX_SAMPLE_SIZE: usize = 100;
+
 pub struct StorageNodeImpl {
     context: Context,
     signer: Db3MultiSchemeSigner,
@@ -84,10 +100,16 @@ impl StorageNode for StorageNodeImpl {
                     }
                     None => return Err(Status::internal("Fail to create session")),
                 }
-                let db = node_store
+                let mut db = node_store
                     .get_auth_store()
                     .get_database(&db_id)
                     .map_err(|e| Status::internal(format!("{:?}", e)))?;
+                if show_database_req.collections_only {
+                    if let Some(database) = db.as_mut() {
+                        database.sender.clear();
+                        database.tx.clear();
+                    }
+                }
                 node_store
                     .get_session_store()
                     .get_session_mut(&show_database_req.session_token)
@@ -99,6 +121,147 @@ impl StorageNode for StorageNodeImpl {
         }
     }
 
+    /// count the keys of a namespace owned by the address of the session
+    async fn count_keys(
+        &self,
+        request: Request<CountKeysRequest>,
+    ) -> std::result::Result<Response<CountKeysResponse>, Status> {
+        let count_keys_req = request.into_inner();
+        match self.context.node_store.lock() {
+            Ok(mut node_store) => {
+                match node_store
+                    .get_session_store()
+                    .get_session_mut(&count_keys_req.session_token)
+                {
+                    Some(session) => {
+                        if !session.check_session_running() {
+                            return Err(Status::permission_denied(
+                                "Fail to query in this session. Please restart query session",
+                            ));
+                        }
+                    }
+                    None => return Err(Status::internal("Fail to create session")),
+                }
+                let addr = node_store
+                    .get_session_store()
+                    .get_address(&count_keys_req.session_token)
+                    .ok_or_else(|| {
+                        Status::internal(format!(
+                            "not address found related to current token {}",
+                            &count_keys_req.session_token
+                        ))
+                    })?;
+                let (key_count, total_bytes) = node_store
+                    .get_auth_store()
+                    .count_keys(&addr, count_keys_req.ns.as_ref())
+                    .map_err(|e| Status::internal(format!("{:?}", e)))?;
+                node_store
+                    .get_session_store()
+                    .get_session_mut(&count_keys_req.session_token)
+                    .unwrap()
+                    .increase_query(1);
+                Ok(Response::new(CountKeysResponse {
+                    key_count,
+                    total_bytes,
+                }))
+            }
+            Err(e) => Err(Status::internal(format!("Fail to get lock {}", e))),
+        }
+    }
+
+    /// list the namespaces owned by the address of the session
+    async fn list_namespaces(
+        &self,
+        request: Request<ListNamespacesRequest>,
+    ) -> std::result::Result<Response<ListNamespacesResponse>, Status> {
+        let r = request.into_inner();
+        match self.context.node_store.lock() {
+            Ok(mut node_store) => {
+                match node_store
+                    .get_session_store()
+                    .get_session_mut(&r.session_token)
+                {
+                    Some(session) => {
+                        if !session.check_session_running() {
+                            return Err(Status::permission_denied(
+                                "Fail to query in this session. Please restart query session",
+                            ));
+                        }
+                    }
+                    None => return Err(Status::internal("Fail to create session")),
+                }
+                let addr = node_store
+                    .get_session_store()
+                    .get_address(&r.session_token)
+                    .ok_or_else(|| {
+                        Status::internal(format!(
+                            "not address found related to current token {}",
+                            &r.session_token
+                        ))
+                    })?;
+                let namespaces = node_store
+                    .get_auth_store()
+                    .list_ns(&addr)
+                    .map_err(|e| Status::internal(format!("{:?}", e)))?
+                    .into_iter()
+                    .map(|(ns, key_count, total_bytes)| NamespaceStat {
+                        ns,
+                        key_count,
+                        total_bytes,
+                    })
+                    .collect();
+                node_store
+                    .get_session_store()
+                    .get_session_mut(&r.session_token)
+                    .unwrap()
+                    .increase_query(1);
+                Ok(Response::new(ListNamespacesResponse { namespaces }))
+            }
+            Err(e) => Err(Status::internal(format!("Fail to get lock {}", e))),
+        }
+    }
+
+    /// list the databases created by an account
+    async fn list_databases(
+        &self,
+        request: Request<ListDatabasesRequest>,
+    ) -> std::result::Result<Response<ListDatabasesResponse>, Status> {
+        let r = request.into_inner();
+        let owner = DB3Address::try_from(r.owner.as_str())
+            .map_err(|e| Status::invalid_argument(format!("invalid owner address {e}")))?;
+        match self.context.node_store.lock() {
+            Ok(mut node_store) => {
+                match node_store
+                    .get_session_store()
+                    .get_session_mut(&r.session_token)
+                {
+                    Some(session) => {
+                        if !session.check_session_running() {
+                            return Err(Status::permission_denied(
+                                "Fail to query in this session. Please restart query session",
+                            ));
+                        }
+                    }
+                    None => return Err(Status::internal("Fail to create session")),
+                }
+                let db_ids = node_store
+                    .get_auth_store()
+                    .get_database_ids_by_owner(&owner)
+                    .map_err(|e| Status::internal(format!("{:?}", e)))?
+                    .iter()
+                    .map(|id| id.as_ref().to_vec())
+                    .collect();
+                node_store
+                    .get_session_store()
+                    .get_session_mut(&r.session_token)
+                    .unwrap()
+                    .increase_query(1);
+                Ok(Response::new(ListDatabasesResponse { db_ids }))
+            }
+            Err(e) => Err(Status::internal(format!("Fail to get lock {}", e))),
+        }
+    }
+
     async fn get_range(
         &self,
         request: Request<GetRangeRequest>,
@@ -165,6 +328,12 @@ impl StorageNode for StorageNodeImpl {
         match self.context.node_store.lock() {
             Ok(mut node_store) => {
                 let sess_store = node_store.get_session_store();
+                // the client retries later when some sessions are closed
+                if sess_store.is_full() {
+                    return Err(Status::resource_exhausted(
+                        "too many open query sessions, retry later",
+                    ));
+                }
                 match sess_store.add_new_session(&header, payload.start_time, account_id.addr) {
                     Ok((session_token, query_session_info)) => {
                         // Takes a reference and returns Option<&V>
@@ -234,7 +403,7 @@ impl StorageNode for StorageNodeImpl {
         };
         let query_session = QuerySession {
             nonce,
-            chain_id: ChainId::MainNet.into(),
+            chain_id: self.context.chain_id.into(),
             chain_role: ChainRole::StorageShardChain.into(),
             node_query_session_info: node_query_session_info.clone(),
             client_query_session: client_query_session.to_vec().to_owned(),
@@ -253,6 +422,7 @@ impl StorageNode for StorageNodeImpl {
 
         let request = WriteRequest {
             signature: signature.as_ref().to_vec().to_owned(),
+            signature_scheme: signature.scheme().to_proto(),
             payload: mbuf.as_ref().to_vec().to_owned(),
             payload_type: PayloadType::QuerySessionPayload.into(),
         };
@@ -274,6 +444,12 @@ impl StorageNode for StorageNodeImpl {
             .await
             .map_err(|e| Status::internal(format!("fail to submit query session with error {e}")))?
             .into_inner();
+        if response.code != 0 {
+            return Err(Status::internal(format!(
+                "fail to submit query session for code {} with log {}",
+                response.code, response.log
+            )));
+        }
         // let base64_byte = base64::encode(response.hash);
         // let hash = String::from_utf8_lossy(base64_byte.as_ref()).to_string();
         // TODO(chenjing): sign
@@ -400,6 +576,36 @@ impl StorageNode for StorageNodeImpl {
             Err(e) => Err(Status::internal(format!("{e}"))),
         }
     }
+    async fn batch_get_account(
+        &self,
+        request: Request<BatchGetAccountRequest>,
+    ) -> std::result::Result<Response<BatchGetAccountResponse>, Status> {
+        let r: BatchGetAccountRequest = request.into_inner();
+        let addrs = r
+            .addrs
+            .iter()
+            .map(|addr| {
+                let addr_ref: &[u8] = addr.as_ref();
+                DB3Address::try_from(addr_ref).map_err(|e| Status::invalid_argument(format!("{e}")))
+            })
+            .collect::<std::result::Result<Vec<DB3Address>, Status>>()?;
+        match self.context.node_store.lock() {
+            Ok(mut node_store) => {
+                let accounts = node_store
+                    .get_auth_store()
+                    .batch_get_account(&addrs)
+                    .map_err(|e| Status::internal(format!("{e}")))?;
+                let accounts = r
+                    .addrs
+                    .into_iter()
+                    .zip(accounts.into_iter())
+                    .map(|(addr, account)| AccountEntry { addr, account })
+                    .collect();
+                Ok(Response::new(BatchGetAccountResponse { accounts }))
+            }
+            Err(e) => Err(Status::internal(format!("{e}"))),
+        }
+    }
     async fn get_session_info(
         &self,
         request: Request<GetSessionInfoRequest>,
@@ -425,7 +631,175 @@ impl StorageNode for StorageNodeImpl {
         }
     }
 
-    /// handle broadcast mutations and query sessionss
+    /// get the raw tx and the height of block it's included in from tendermint
+    async fn get_tx(
+        &self,
+        request: Request<GetTxRequest>,
+    ) -> std::result::Result<Response<GetTxResponse>, Status> {
+        let r = request.into_inner();
+        let tx_hash = TMHash::try_from(r.tx_id)
+            .map_err(|e| Status::invalid_argument(format!("invalid tx id {e}")))?;
+        let response = self
+            .context
+            .client
+            .tx(tx_hash, false)
+            .await
+            .map_err(|e| Status::not_found(format!("tx was not found for error {e}")))?;
+        Ok(Response::new(GetTxResponse {
+            tx: response.tx.to_vec(),
+            height: response.height.value(),
+        }))
+    }
+
+    ///
+    /// the receipt of a settled query session signed by the node, the session info is
+    /// read from the tx and the credits and the height from the bill
+    ///
+    async fn get_query_session_receipt(
+        &self,
+        request: Request<GetQuerySessionReceiptRequest>,
+    ) -> std::result::Result<Response<GetQuerySessionReceiptResponse>, Status> {
+        let r = request.into_inner();
+        let tx_id = TxId::try_from_bytes(r.tx_id.as_ref())
+            .map_err(|e| Status::invalid_argument(format!("invalid tx id {e}")))?;
+        let tx_hash = TMHash::try_from(r.tx_id)
+            .map_err(|e| Status::invalid_argument(format!("invalid tx id {e}")))?;
+        let response = self
+            .context
+            .client
+            .tx(tx_hash, false)
+            .await
+            .map_err(|e| Status::not_found(format!("tx was not found for error {e}")))?;
+        let write_request = WriteRequest::decode(response.tx.as_ref())
+            .map_err(|e| Status::internal(format!("invalid write request {e}")))?;
+        if write_request.payload_type != PayloadType::QuerySessionPayload as i32 {
+            return Err(Status::invalid_argument(
+                "the tx is not a query session".to_string(),
+            ));
+        }
+        let query_session = QuerySession::decode(write_request.payload.as_ref())
+            .map_err(|e| Status::internal(format!("invalid query session {e}")))?;
+        let query_session_info = query_session
+            .node_query_session_info
+            .ok_or_else(|| Status::internal("no session info in the tx".to_string()))?;
+        let receipt = match self.context.node_store.lock() {
+            Ok(mut node_store) => node_store
+                .get_auth_store()
+                .get_query_session_receipt(&tx_id, &query_session_info)
+                .map_err(|e| Status::internal(format!("{e}")))?,
+            Err(e) => return Err(Status::internal(format!("{e}"))),
+        };
+        let receipt = receipt.ok_or_else(|| {
+            Status::not_found("the query session has not been settled".to_string())
+        })?;
+        let mut buf = BytesMut::with_capacity(1024);
+        receipt
+            .encode(&mut buf)
+            .map_err(|e| Status::internal(format!("fail to encode receipt {e}")))?;
+        let buf = buf.freeze();
+        let signature = self
+            .signer
+            .sign(buf.as_ref())
+            .map_err(|e| Status::internal(format!("fail to sign receipt {e}")))?;
+        Ok(Response::new(GetQuerySessionReceiptResponse {
+            receipt: buf.as_ref().to_vec(),
+            signature: signature.as_ref().to_vec(),
+            signature_scheme: signature.scheme().to_proto(),
+        }))
+    }
+
+    /// estimate the gas of a mutation in the same way it's charged when applied
+    async fn estimate_gas(
+        &self,
+        request: Request<EstimateGasRequest>,
+    ) -> std::result::Result<Response<EstimateGasResponse>, Status> {
+        let r = request.into_inner();
+        let gas = match PayloadType::from_i32(r.payload_type) {
+            Some(PayloadType::MutationPayload) => {
+                let mutation = Mutation::decode(r.payload.as_ref())
+                    .map_err(|e| Status::invalid_argument(format!("invalid mutation {e}")))?;
+                cost::estimate_gas(&mutation)
+            }
+            Some(PayloadType::DatabasePayload) => {
+                let mutation = DatabaseMutation::decode(r.payload.as_ref()).map_err(|e| {
+                    Status::invalid_argument(format!("invalid database mutation {e}"))
+                })?;
+                cost::estimate_database_gas(&mutation)
+            }
+            _ => {
+                return Err(Status::invalid_argument(format!(
+                    "the gas of payload type {} can not be estimated",
+                    r.payload_type
+                )));
+            }
+        };
+        Ok(Response::new(EstimateGasResponse { gas: Some(gas) }))
+    }
+
+    /// the clients set the gas price of mutations by it
+    async fn get_min_gas_price(
+        &self,
+        _request: Request<GetMinGasPriceRequest>,
+    ) -> std::result::Result<Response<GetMinGasPriceResponse>, Status> {
+        Ok(Response::new(GetMinGasPriceResponse {
+            min_gas_price: Some(self.context.min_gas_price.clone()),
+        }))
+    }
+
+    /// the chain tip of the node, it's the same state returned by the info of abci
+    async fn get_latest_state(
+        &self,
+        _request: Request<GetLatestStateRequest>,
+    ) -> std::result::Result<Response<GetLatestStateResponse>, Status> {
+        match self.context.node_store.lock() {
+            Ok(mut node_store) => {
+                let state = node_store.get_auth_store().get_last_block_state();
+                Ok(Response::new(GetLatestStateResponse {
+                    height: state.block_height as u64,
+                    app_hash: state.abci_hash.to_vec(),
+                }))
+            }
+            Err(e) => Err(Status::internal(format!("{e}"))),
+        }
+    }
+
+    /// show the metadata of the txs waiting for commit to the operators
+    async fn get_pending_txs(
+        &self,
+        request: Request<GetPendingTxsRequest>,
+    ) -> std::result::Result<Response<GetPendingTxsResponse>, Status> {
+        let r = request.into_inner();
+        match &self.context.admin_token {
+            // compare in constant time so the token can not be guessed byte by byte
+            Some(token) if bool::from(token.as_bytes().ct_eq(r.admin_token.as_bytes())) => {}
+            Some(_) => {
+                return Err(Status::permission_denied("invalid admin token".to_string()));
+            }
+            None => {
+                return Err(Status::permission_denied(
+                    "the admin methods are disabled".to_string(),
+                ));
+            }
+        }
+        let sample_size = match r.sample_size as usize {
+            0 => DEFAULT_PENDING_TX_SAMPLE_SIZE,
+            n => n.min(MAX_PENDING_TX_SAMPLE_SIZE),
+        };
+        let queues = self.context.pending_queue.summarize(sample_size);
+        match self.context.node_store.lock() {
+            Ok(mut node_store) => {
+                let state = node_store.get_auth_store().get_last_block_state();
+                Ok(Response::new(GetPendingTxsResponse {
+                    queues,
+                    last_commit_hash: state.abci_hash.to_vec(),
+                    last_commit_height: state.block_height,
+                }))
+            }
+            Err(e) => Err(Status::internal(format!("{e}"))),
+        }
+    }
+
+    /// handle broadcast mutations and query sessionss, the tx is checked before returning
     async fn broadcast(
         &self,
         request: Request<BroadcastRequest>,
@@ -434,17 +808,103 @@ impl StorageNode for StorageNodeImpl {
         let response = self
             .context
             .client
-            .broadcast_tx_async(r.body)
+            .broadcast_tx_sync(r.body)
             .await
             .map_err(|e| Status::internal(format!("{}", e)))?;
+        // the code and the log of check_tx are returned to the client to tell why the
+        // tx is rejected
         Ok(Response::new(BroadcastResponse {
             hash: response.hash.as_ref().to_vec(),
+            code: response.code.value(),
+            log: response.log.to_string(),
         }))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::abci_impl::AbciImpl;
+    use crate::auth_storage::AuthStorage;
+    use crate::node_storage::NodeStorage;
+    use crate::tx_dedup_cache::DEFAULT_MEMPOOL_DEDUP_SIZE;
+    use db3_crypto::key_derive;
+    use db3_crypto::signature_scheme::SignatureScheme;
+    use db3_proto::db3_base_proto::{ChainId, Units};
+    use db3_storage::kv_store::MutationLimits;
+    use merkdb::Merk;
+    use std::sync::{Arc, Mutex};
+    use tempdir::TempDir;
+    use tendermint_rpc::HttpClient;
+
     #[test]
     fn it_works() {}
+
+    fn build_node(dir: &TempDir) -> StorageNodeImpl {
+        let merk = Merk::open(dir.path()).unwrap();
+        let node_store = Arc::new(Mutex::new(Box::pin(NodeStorage::new(AuthStorage::new(
+            merk,
+        )))));
+        let abci = AbciImpl::new(
+            node_store.clone(),
+            DEFAULT_MEMPOOL_DEDUP_SIZE,
+            MutationLimits::default(),
+            0,
+        );
+        let context = Context {
+            node_store,
+            client: HttpClient::new("http://127.0.0.1:1").unwrap(),
+            mutation_limits: MutationLimits::default(),
+            pending_queue: abci.get_pending_queue(),
+            node_state: abci.get_node_state().clone(),
+            admin_token: None,
+            min_gas_price: Units::default(),
+            grpc_metrics: None,
+            chain_id: ChainId::DevNet,
+        };
+        let seed: [u8; 32] = [0; 32];
+        let (_, kp) =
+            key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::ED25519).unwrap();
+        StorageNodeImpl::new(context, Db3MultiSchemeSigner::new(kp))
+    }
+
+    #[tokio::test]
+    async fn it_get_latest_state() {
+        let tmp_dir_path = TempDir::new("latest_state").expect("create temp dir");
+        let node = build_node(&tmp_dir_path);
+        let mut last_hash = vec![];
+        for height in 1..=3 {
+            let mut node_store = node.context.node_store.lock().unwrap();
+            let auth_store = node_store.get_auth_store();
+            auth_store.begin_block(height, height);
+            last_hash = auth_store.commit().unwrap().to_vec();
+        }
+        let response = node
+            .get_latest_state(Request::new(GetLatestStateRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(3, response.height);
+        assert_eq!(last_hash, response.app_hash);
+    }
+
+    #[tokio::test]
+    async fn it_check_admin_token() {
+        let tmp_dir_path = TempDir::new("admin_token").expect("create temp dir");
+        let mut node = build_node(&tmp_dir_path);
+        let request = |token: &str| {
+            Request::new(GetPendingTxsRequest {
+                admin_token: token.to_string(),
+                sample_size: 0,
+            })
+        };
+        let status = node.get_pending_txs(request("secret")).await.unwrap_err();
+        assert_eq!(tonic::Code::PermissionDenied, status.code());
+        node.context.admin_token = Some("secret".to_string());
+        for bad in ["", "secre", "secreT", "secret1"] {
+            let status = node.get_pending_txs(request(bad)).await.unwrap_err();
+            assert_eq!(tonic::Code::PermissionDenied, status.code(), "{bad}");
+        }
+        assert!(node.get_pending_txs(request("secret")).await.is_ok());
+    }
 }
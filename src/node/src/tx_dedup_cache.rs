@@ -0,0 +1,97 @@
+//
+// tx_dedup_cache.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use db3_crypto::id::TxId;
+use std::collections::{BTreeSet, VecDeque};
+
+pub const DEFAULT_MEMPOOL_DEDUP_SIZE: usize = 10000;
+
+///
+/// a bounded cache of the recently accepted tx ids, the oldest one is
+/// evicted when the cache is full and a zero capacity disables the cache
+///
+pub struct TxDedupCache {
+    capacity: usize,
+    ids: BTreeSet<TxId>,
+    order: VecDeque<TxId>,
+}
+
+impl TxDedupCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ids: BTreeSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn contains(&self, tx_id: &TxId) -> bool {
+        self.ids.contains(tx_id)
+    }
+
+    ///
+    /// remember the tx id and return false if it has been seen
+    ///
+    pub fn insert(&mut self, tx_id: TxId) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        if !self.ids.insert(tx_id) {
+            return false;
+        }
+        self.order.push_back(tx_id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_evict_the_oldest_tx() {
+        let mut cache = TxDedupCache::new(2);
+        assert!(cache.insert(TxId::from([1; 32])));
+        assert!(!cache.insert(TxId::from([1; 32])));
+        assert!(cache.insert(TxId::from([2; 32])));
+        assert!(cache.insert(TxId::from([3; 32])));
+        assert_eq!(2, cache.len());
+        assert!(!cache.contains(&TxId::from([1; 32])));
+        assert!(cache.contains(&TxId::from([3; 32])));
+    }
+
+    #[test]
+    fn it_disable_cache_with_zero_capacity() {
+        let mut cache = TxDedupCache::new(0);
+        assert!(cache.insert(TxId::from([1; 32])));
+        assert!(cache.insert(TxId::from([1; 32])));
+        assert_eq!(0, cache.len());
+    }
+}
@@ -3,9 +3,11 @@
 mod node_integration {
     use bytes::BytesMut;
     use db3_base::get_a_random_nonce;
+    use db3_crypto::db3_signature::DB3Signature;
     use db3_crypto::db3_signer::Db3MultiSchemeSigner;
-    use db3_proto::db3_base_proto::{ChainId, ChainRole, UnitType, Units};
-    use db3_proto::db3_database_proto::Database;
+    use db3_crypto::id::TxId;
+    use db3_proto::db3_base_proto::{BroadcastMeta, ChainId, ChainRole, UnitType, Units};
+    use db3_proto::db3_database_proto::{Database, Index};
     use db3_proto::db3_mutation_proto::{
         DatabaseMutation, KvPair, Mutation, MutationAction, PayloadType, WriteRequest,
     };
@@ -24,27 +26,27 @@ mod node_integration {
 
     fn get_mutation_sdk() -> MutationSDK {
         let public_grpc_url = "http://127.0.0.1:26659";
-        db3_cmd::keystore::KeyStore::recover_keypair().unwrap();
+        db3_cmd::keystore::KeyStore::recover_keypair(None).unwrap();
         // create storage node sdk
-        let kp = db3_cmd::keystore::KeyStore::get_keypair().unwrap();
+        let kp = db3_cmd::keystore::KeyStore::get_keypair(None).unwrap();
         let signer = Db3MultiSchemeSigner::new(kp);
         let rpc_endpoint = Endpoint::new(public_grpc_url).unwrap();
         let channel = rpc_endpoint.connect_lazy();
         let client = Arc::new(StorageNodeClient::new(channel));
         // broadcast client
-        let sdk = MutationSDK::new(client, signer);
+        let sdk = MutationSDK::new(client, Box::new(signer));
         sdk
     }
 
     fn get_store_sdk() -> StoreSDK {
         let public_grpc_url = "http://127.0.0.1:26659";
         // create storage node sdk
-        let kp = db3_cmd::keystore::KeyStore::get_keypair().unwrap();
+        let kp = db3_cmd::keystore::KeyStore::get_keypair(None).unwrap();
         let signer = Db3MultiSchemeSigner::new(kp);
         let rpc_endpoint = Endpoint::new(public_grpc_url).unwrap();
         let channel = rpc_endpoint.connect_lazy();
         let client = Arc::new(StorageNodeClient::new(channel));
-        StoreSDK::new(client, signer)
+        StoreSDK::new(client, Box::new(signer))
     }
 
     fn current_seconds() -> u64 {
@@ -100,19 +102,23 @@ mod node_integration {
         let nonce = get_a_random_nonce();
         let json_rpc_url = "http://127.0.0.1:26670";
         let client = awc::Client::default();
-        db3_cmd::keystore::KeyStore::recover_keypair().unwrap();
-        let kp = db3_cmd::keystore::KeyStore::get_keypair().unwrap();
+        db3_cmd::keystore::KeyStore::recover_keypair(None).unwrap();
+        let kp = db3_cmd::keystore::KeyStore::get_keypair(None).unwrap();
         let signer = Db3MultiSchemeSigner::new(kp);
         let kv = KvPair {
             key: format!("kkkkk_tt{}", 1).as_bytes().to_vec(),
             value: format!("vkalue_tt{}", 1).as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let mutation = Mutation {
             ns: "my_twitter".as_bytes().to_vec(),
             kv_pairs: vec![kv],
             nonce,
-            chain_id: ChainId::MainNet.into(),
+            chain_id: ChainId::DevNet.into(),
             chain_role: ChainRole::StorageShardChain.into(),
             gas_price: None,
             gas: 10,
@@ -123,6 +129,7 @@ mod node_integration {
         let signature = signer.sign(mbuf.as_ref()).unwrap();
         let request = WriteRequest {
             signature: signature.as_ref().to_vec(),
+            signature_scheme: signature.scheme().to_proto(),
             payload: mbuf.as_ref().to_vec().to_owned(),
             payload_type: PayloadType::MutationPayload.into(),
         };
@@ -143,13 +150,186 @@ mod node_integration {
         if let serde_json::Value::Object(val) = response.json::<serde_json::Value>().await.unwrap()
         {
             if let Some(serde_json::Value::String(s)) = val.get("result") {
-                assert!(s.len() > 0);
+                assert_eq!(&TxId::from(buf.as_ref()).to_base64(), s);
             } else {
                 assert!(false)
             }
         } else {
             assert!(false)
         }
+        // the request that is not a signed write request is rejected
+        let data = base64::encode("bad request".as_bytes());
+        let request = serde_json::json!(
+            {"method": "broadcast",
+            "params": vec![String::from_utf8_lossy(data.as_ref()).to_string()],
+            "id": 2,
+            "jsonrpc": "2.0"
+            }
+        );
+        let mut response = client.post(json_rpc_url).send_json(&request).await.unwrap();
+        let val = response.json::<serde_json::Value>().await.unwrap();
+        assert!(val.get("error").is_some());
+    }
+
+    #[actix_web::test]
+    async fn json_rpc_broadcast_mutation_test() {
+        let nonce = get_a_random_nonce();
+        let json_rpc_url = "http://127.0.0.1:26670";
+        let client = awc::Client::default();
+        db3_cmd::keystore::KeyStore::recover_keypair(None).unwrap();
+        let kp = db3_cmd::keystore::KeyStore::get_keypair(None).unwrap();
+        let signer = Db3MultiSchemeSigner::new(kp);
+        let kv = KvPair {
+            key: format!("kkkkk_bm{}", 1).as_bytes().to_vec(),
+            value: format!("vkalue_bm{}", 1).as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = Mutation {
+            ns: "my_twitter".as_bytes().to_vec(),
+            kv_pairs: vec![kv],
+            nonce,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let mut mbuf = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut mbuf).unwrap();
+        let mbuf = mbuf.freeze();
+        let signature = signer.sign(mbuf.as_ref()).unwrap();
+        let signature_scheme = signature.scheme().to_proto();
+        for tampered in [false, true] {
+            let mut signature = signature.as_ref().to_vec();
+            if tampered {
+                let last = signature.len() - 1;
+                signature[last] ^= 0xff;
+            }
+            let request = WriteRequest {
+                signature,
+                signature_scheme,
+                payload: mbuf.as_ref().to_vec().to_owned(),
+                payload_type: PayloadType::MutationPayload.into(),
+            };
+            let mut buf = BytesMut::with_capacity(1024 * 4);
+            request.encode(&mut buf).unwrap();
+            let buf = buf.freeze();
+            let data = base64::encode(buf.as_ref());
+            let base64_str = String::from_utf8_lossy(data.as_ref()).to_string();
+            let request = serde_json::json!(
+                {"method": "broadcast_mutation",
+                "params": vec![base64_str],
+                "id": 1,
+                "jsonrpc": "2.0"
+                }
+            );
+            let mut response = client.post(json_rpc_url).send_json(&request).await.unwrap();
+            let val = response.json::<serde_json::Value>().await.unwrap();
+            if tampered {
+                assert!(val.get("error").is_some());
+            } else if let Some(serde_json::Value::String(s)) = val.get("result") {
+                assert!(s.len() > 0);
+            } else {
+                assert!(false)
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn json_rpc_get_database_not_found_test() {
+        let json_rpc_url = "http://127.0.0.1:26670";
+        let client = awc::Client::default();
+        let request = serde_json::json!(
+            {"method": "get_database",
+            "params": vec!["0x0000000000000000000000000000000000000000"],
+            "id": 1,
+            "jsonrpc": "2.0"
+            }
+        );
+        let mut response = client.post(json_rpc_url).send_json(&request).await.unwrap();
+        let val = response.json::<serde_json::Value>().await.unwrap();
+        assert!(val.get("error").is_some());
+        assert_eq!(Some(&serde_json::Value::Null), val.get("result"));
+    }
+
+    #[actix_web::test]
+    async fn json_rpc_status_test() {
+        let json_rpc_url = "http://127.0.0.1:26670";
+        let client = awc::Client::default();
+        let request = serde_json::json!(
+            {"method": "status",
+            "params": [],
+            "id": 1,
+            "jsonrpc": "2.0"
+            }
+        );
+        let mut response = client.post(json_rpc_url).send_json(&request).await.unwrap();
+        let val = response.json::<serde_json::Value>().await.unwrap();
+        let status = val.get("result").unwrap();
+        assert!(status.get("height").unwrap().as_i64().is_some());
+        assert_eq!(64, status.get("app_hash").unwrap().as_str().unwrap().len());
+        assert!(status.get("version").is_some());
+        assert!(status.get("total_mutations").unwrap().as_u64().is_some());
+    }
+
+    #[tokio::test]
+    async fn list_collections_test() {
+        use db3_proto::db3_mutation_proto::{CollectionMutation, DatabaseAction};
+        let sdk = get_mutation_sdk();
+        let mut store_sdk = get_store_sdk();
+        let dm = DatabaseMutation {
+            meta: Some(BroadcastMeta {
+                nonce: get_a_random_nonce(),
+                chain_id: ChainId::DevNet.into(),
+                chain_role: ChainRole::StorageShardChain.into(),
+            }),
+            collection_mutations: vec![CollectionMutation {
+                index: vec![],
+                collection_id: "books".to_string(),
+                schema: vec![],
+            }],
+            db_address: vec![],
+            action: DatabaseAction::CreateDb.into(),
+            ns_grant: None,
+        };
+        let (db_id, _) = sdk.submit_database_mutation(&dm).await.unwrap();
+        thread::sleep(time::Duration::from_millis(2000));
+        let addr = db_id.to_hex();
+        let database = store_sdk
+            .get_database(addr.as_str())
+            .await
+            .unwrap()
+            .unwrap();
+        let collections = store_sdk.list_collections(addr.as_str()).await.unwrap();
+        let expected: Vec<(String, Vec<Index>)> = database
+            .collections
+            .into_iter()
+            .map(|c| (c.name, c.index_list))
+            .collect();
+        assert_eq!(expected, collections);
+        assert_eq!("books", collections[0].0);
+    }
+
+    #[tokio::test]
+    async fn grpc_health_test() {
+        use tonic_health::proto::health_check_response::ServingStatus;
+        use tonic_health::proto::health_client::HealthClient;
+        use tonic_health::proto::HealthCheckRequest;
+        let rpc_endpoint = Endpoint::new("http://127.0.0.1:26659").unwrap();
+        let mut client = HealthClient::new(rpc_endpoint.connect_lazy());
+        for service in ["", "db3_node_proto.StorageNode"] {
+            let response = client
+                .check(HealthCheckRequest {
+                    service: service.to_string(),
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(ServingStatus::Serving as i32, response.status);
+        }
     }
 
     #[tokio::test]
@@ -174,16 +354,28 @@ mod node_integration {
                 key: "k1".as_bytes().to_vec(),
                 value: "v1".as_bytes().to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             },
             KvPair {
                 key: "k2".as_bytes().to_vec(),
                 value: "v2".as_bytes().to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             },
             KvPair {
                 key: "k3".as_bytes().to_vec(),
                 value: "v3".as_bytes().to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             },
         ];
         let mutation = Mutation {
@@ -275,6 +467,10 @@ mod node_integration {
                 key: "k1".as_bytes().to_vec(),
                 value: vec![],
                 action: MutationAction::DeleteKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             }];
             let mutation = Mutation {
                 ns: ns.as_bytes().to_vec(),
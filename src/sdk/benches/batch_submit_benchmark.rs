@@ -0,0 +1,80 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use db3_base::get_a_random_nonce;
+use db3_crypto::key_derive;
+use db3_crypto::{db3_signer::Db3MultiSchemeSigner, signature_scheme::SignatureScheme};
+use db3_proto::db3_base_proto::{ChainId, ChainRole};
+use db3_proto::db3_mutation_proto::{KvPair, Mutation, MutationAction};
+use db3_proto::db3_node_proto::storage_node_client::StorageNodeClient;
+use db3_sdk::mutation_sdk::MutationSDK;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use tonic::transport::Endpoint;
+
+fn build_sdk() -> MutationSDK {
+    let ep = "http://127.0.0.1:26659";
+    let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+    let channel = rpc_endpoint.connect_lazy();
+    let client = Arc::new(StorageNodeClient::new(channel));
+    let seed: [u8; 32] = [0; 32];
+    let (_, keypair) =
+        key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1).unwrap();
+    let signer = Db3MultiSchemeSigner::new(keypair);
+    MutationSDK::new(client, Box::new(signer))
+}
+
+fn build_mutations(count: u64) -> Vec<Mutation> {
+    let nonce = get_a_random_nonce();
+    (0..count)
+        .map(|i| Mutation {
+            ns: "my_twitter".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: format!("bm_batch_submit_key_{nonce}_{i}")
+                    .as_bytes()
+                    .to_vec(),
+                value: format!("bm_batch_submit_value_{nonce}_{i}")
+                    .as_bytes()
+                    .to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: nonce + i,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        })
+        .collect()
+}
+
+// compare submitting the mutations one by one with the batch api
+fn criterion_benchmark(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let sdk = build_sdk();
+    let mut group = c.benchmark_group("submit mutations");
+    group.sample_size(10);
+    for count in [100u64].iter() {
+        group.bench_with_input(BenchmarkId::new("serial", count), count, |b, &count| {
+            b.to_async(&rt).iter(|| async {
+                for mutation in build_mutations(count) {
+                    let result = sdk.submit_mutation(&mutation).await;
+                    assert!(result.is_ok(), "{}", result.err().unwrap());
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batch", count), count, |b, &count| {
+            b.to_async(&rt).iter(|| async {
+                let mutations = build_mutations(count);
+                for result in sdk.submit_mutations(&mutations).await {
+                    assert!(result.is_ok(), "{}", result.err().unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -29,7 +29,7 @@ async fn run_batch_get_key(keys: &Vec<Vec<u8>>) {
     let (_, keypair) =
         key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1).unwrap();
     let signer = Db3MultiSchemeSigner::new(keypair);
-    let mut sdk = StoreSDK::new(client, signer);
+    let mut sdk = StoreSDK::new(client, Box::new(signer));
     let res = sdk.open_session().await;
     assert!(res.is_ok());
     let session_info = res.unwrap();
@@ -61,20 +61,24 @@ async fn init_kv_store(kv_size: i32) {
     let (_, keypair) =
         key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1).unwrap();
     let signer = Db3MultiSchemeSigner::new(keypair);
-    let msdk = MutationSDK::new(mclient, signer);
+    let msdk = MutationSDK::new(mclient, Box::new(signer));
     let mut kv_pairs = vec![];
     for i in 0..kv_size {
         kv_pairs.push(KvPair {
             key: format!("bm_key_{}", i).as_bytes().to_vec(),
             value: format!("bm_value_{}", i).as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         });
     }
     let mutation = Mutation {
         ns: ns_vec.clone(),
         kv_pairs,
         nonce,
-        chain_id: ChainId::MainNet.into(),
+        chain_id: ChainId::DevNet.into(),
         chain_role: ChainRole::StorageShardChain.into(),
         gas_price: None,
         gas: 10,
@@ -31,7 +31,7 @@ async fn run_batch_get_key(keys: &Vec<Vec<u8>>) {
         key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1).unwrap();
     let signer = Db3MultiSchemeSigner::new(keypair);
 
-    let mut sdk = StoreSDK::new(client, signer);
+    let mut sdk = StoreSDK::new(client, Box::new(signer));
     let res = sdk.open_session().await;
     assert!(res.is_ok());
     let session_info = res.unwrap();
@@ -71,7 +71,7 @@ async fn run_submit_mutation(submit_count: i32, kv_size: i32) {
         key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1).unwrap();
     let signer = Db3MultiSchemeSigner::new(keypair);
 
-    let msdk = MutationSDK::new(mclient, signer);
+    let msdk = MutationSDK::new(mclient, Box::new(signer));
 
     let ts = Utc::now().timestamp_nanos();
     let process_id = process::id();
@@ -87,6 +87,10 @@ async fn run_submit_mutation(submit_count: i32, kv_size: i32) {
                     .as_bytes()
                     .to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             });
         }
         println!(
@@ -101,7 +105,7 @@ async fn run_submit_mutation(submit_count: i32, kv_size: i32) {
             ns: ns_vec.clone(),
             kv_pairs,
             nonce,
-            chain_id: ChainId::MainNet.into(),
+            chain_id: ChainId::DevNet.into(),
             chain_role: ChainRole::StorageShardChain.into(),
             gas_price: None,
             gas: 10,
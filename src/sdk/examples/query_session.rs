@@ -0,0 +1,57 @@
+//
+// query_session.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//
+// open a query session, run three batch gets and close it, the node will
+// settle the session and bill the account for the queries
+//
+// cargo run --example query_session
+//
+
+use db3_crypto::{db3_signer::Db3MultiSchemeSigner, key_derive, signature_scheme::SignatureScheme};
+use db3_proto::db3_node_proto::storage_node_client::StorageNodeClient;
+use db3_sdk::store_sdk::StoreSDK;
+use std::sync::Arc;
+use tonic::transport::Endpoint;
+
+#[tokio::main]
+async fn main() {
+    let seed: [u8; 32] = [0; 32];
+    let (_, kp) =
+        key_derive::derive_key_pair_from_path(&seed, None, &SignatureScheme::Secp256k1).unwrap();
+    let signer = Db3MultiSchemeSigner::new(kp);
+    let rpc_endpoint = Endpoint::new("http://127.0.0.1:26659".to_string()).unwrap();
+    let channel = rpc_endpoint.connect_lazy();
+    let client = Arc::new(StorageNodeClient::new(channel));
+    let mut sdk = StoreSDK::new(client, Box::new(signer));
+    let session = sdk.open_session().await.unwrap();
+    let token = session.session_token;
+    println!("open session {token}");
+    for i in 0..3 {
+        let key = format!("key{i}").as_bytes().to_vec();
+        match sdk.batch_get("my_ns".as_bytes(), vec![key], &token).await {
+            Ok(Some(values)) => println!("get {} values", values.values.len()),
+            Ok(None) => println!("get no values"),
+            Err(e) => println!("fail to get for error {e}"),
+        }
+    }
+    let (node_info, client_info) = sdk.close_session(&token).await.unwrap();
+    println!(
+        "close session with node query count {} and client query count {}",
+        node_info.query_count, client_info.query_count
+    );
+}
@@ -0,0 +1,212 @@
+//
+// event_subscriber.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use db3_crypto::{db3_address::DB3Address, db3_verifier::DB3Verifier, id::TxId};
+use db3_proto::db3_mutation_proto::{Mutation, PayloadType, WriteRequest};
+use futures_util::stream::{self, Stream, StreamExt};
+use prost::Message;
+use std::time::Duration;
+use tendermint_rpc::event::{Event, EventData};
+use tendermint_rpc::query::{EventType, Query};
+use tendermint_rpc::{SubscriptionClient, Url, WebSocketClient};
+use tokio::sync::mpsc;
+
+/// the interval to wait before connecting to tendermint again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(3);
+const EVENT_CHANNEL_SIZE: usize = 128;
+
+/// a block committed by the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewBlockEvent {
+    pub height: u64,
+    pub block_hash: String,
+    /// the block time in rfc3339
+    pub time: String,
+    /// the hex app hash after the previous block is applied
+    pub app_hash: String,
+    pub tx_count: usize,
+}
+
+/// a mutation included in a committed block
+#[derive(Clone, PartialEq)]
+pub struct MutationEvent {
+    pub height: i64,
+    pub tx_id: TxId,
+    pub sender: DB3Address,
+    pub mutation: Mutation,
+}
+
+///
+/// decode the mutation from a raw tx, none is returned if the tx is not a mutation
+/// or none of the namespaces written by the mutation matches the filter
+///
+pub fn decode_mutation_event(tx: &[u8], height: i64, ns: Option<&[u8]>) -> Option<MutationEvent> {
+    let request = WriteRequest::decode(tx).ok()?;
+    if PayloadType::from_i32(request.payload_type) != Some(PayloadType::MutationPayload) {
+        return None;
+    }
+    let account_id = DB3Verifier::verify_with_scheme(
+        request.payload.as_ref(),
+        request.signature.as_ref(),
+        request.signature_scheme,
+    )
+    .ok()?;
+    let mutation = Mutation::decode(request.payload.as_ref()).ok()?;
+    if let Some(ns) = ns {
+        let written = mutation.kv_pairs.iter().any(|kv| {
+            let kv_ns = if kv.ns.is_empty() {
+                &mutation.ns
+            } else {
+                &kv.ns
+            };
+            kv_ns.as_slice() == ns
+        });
+        if !written {
+            return None;
+        }
+    }
+    Some(MutationEvent {
+        height,
+        tx_id: TxId::from(tx),
+        sender: account_id.addr,
+        mutation,
+    })
+}
+
+///
+/// subscribe the new blocks from tendermint
+///
+pub fn subscribe_blocks(url: Url) -> impl Stream<Item = NewBlockEvent> {
+    subscribe(url, EventType::NewBlock.into()).filter_map(|event| async move {
+        match event.data {
+            EventData::NewBlock {
+                block: Some(block), ..
+            } => Some(NewBlockEvent {
+                height: block.header.height.value(),
+                block_hash: block.header.hash().to_string(),
+                time: block.header.time.to_string(),
+                app_hash: hex::encode_upper(block.header.app_hash.as_bytes()),
+                tx_count: block.data.len(),
+            }),
+            _ => None,
+        }
+    })
+}
+
+///
+/// subscribe the mutations from tendermint and keep the ones in the namespace if it's given
+///
+pub fn subscribe_mutations(url: Url, ns: Option<Vec<u8>>) -> impl Stream<Item = MutationEvent> {
+    subscribe(url, EventType::Tx.into()).filter_map(move |event| {
+        let ns = ns.clone();
+        async move {
+            match event.data {
+                EventData::Tx { tx_result } => {
+                    decode_mutation_event(tx_result.tx.as_ref(), tx_result.height, ns.as_deref())
+                }
+                _ => None,
+            }
+        }
+    })
+}
+
+///
+/// forward the events of the query to a stream, the websocket connection is opened again
+/// when it's dropped and the subscription stops when the stream is dropped
+///
+fn subscribe(url: Url, query: Query) -> impl Stream<Item = Event> {
+    let (sender, receiver) = mpsc::channel(EVENT_CHANNEL_SIZE);
+    tokio::spawn(async move {
+        while !sender.is_closed() {
+            if let Ok((client, driver)) = WebSocketClient::new(url.clone()).await {
+                let driver_handle = tokio::spawn(async move { driver.run().await });
+                if let Ok(mut subs) = client.subscribe(query.clone()).await {
+                    while let Some(Ok(event)) = subs.next().await {
+                        if sender.send(event).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                let _ = client.close();
+                let _ = driver_handle.await;
+            }
+            if !sender.is_closed() {
+                tokio::time::sleep(RECONNECT_INTERVAL).await;
+            }
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (event, receiver))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdk_test;
+    use bytes::BytesMut;
+    use db3_crypto::db3_signer::Signer;
+    use db3_proto::db3_base_proto::{ChainId, ChainRole};
+    use db3_proto::db3_mutation_proto::{KvPair, MutationAction};
+
+    fn build_mutation_tx(ns: &str) -> Vec<u8> {
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let mutation = Mutation {
+            ns: ns.as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let mut mbuf = BytesMut::with_capacity(1024 * 4);
+        mutation.encode(&mut mbuf).unwrap();
+        let signature = signer.sign(mbuf.as_ref()).unwrap();
+        let request = WriteRequest {
+            signature: signature.as_ref().to_vec(),
+            signature_scheme: signature.scheme().to_proto(),
+            payload: mbuf.as_ref().to_vec(),
+            payload_type: PayloadType::MutationPayload.into(),
+        };
+        let mut buf = BytesMut::with_capacity(1024 * 4);
+        request.encode(&mut buf).unwrap();
+        buf.as_ref().to_vec()
+    }
+
+    #[test]
+    fn it_decode_mutation_event_with_ns_filter() {
+        let (addr, _) = sdk_test::gen_ed25519_signer();
+        let tx = build_mutation_tx("ns1");
+        let event = decode_mutation_event(tx.as_ref(), 10, None).unwrap();
+        assert_eq!(10, event.height);
+        assert!(addr == event.sender);
+        assert!(TxId::from(tx.as_ref()) == event.tx_id);
+        assert_eq!("ns1".as_bytes(), event.mutation.ns.as_slice());
+        assert!(decode_mutation_event(tx.as_ref(), 10, Some("ns1".as_bytes())).is_some());
+        assert!(decode_mutation_event(tx.as_ref(), 10, Some("ns2".as_bytes())).is_none());
+        assert!(decode_mutation_event(&[1, 2, 3], 10, None).is_none());
+    }
+}
@@ -15,7 +15,9 @@
 // limitations under the License.
 //
 
+pub mod event_subscriber;
 pub mod mutation_sdk;
+pub mod read_cache;
 #[cfg(test)]
 pub mod sdk_test;
 pub mod store_sdk;
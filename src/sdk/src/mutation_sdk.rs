@@ -15,28 +15,206 @@
 // limitations under the License.
 //
 
+use crate::read_cache::ReadCache;
 use bytes::BytesMut;
+use db3_base::value_codec;
 use db3_crypto::{
-    db3_signer::Db3MultiSchemeSigner,
+    db3_signature::DB3Signature,
+    db3_signer::Signer,
     id::{DbId, TxId, TX_ID_LENGTH},
 };
 use db3_error::{DB3Error, Result};
-use db3_proto::db3_mutation_proto::{DatabaseMutation, Mutation, PayloadType, WriteRequest};
-use db3_proto::db3_node_proto::{storage_node_client::StorageNodeClient, BroadcastRequest};
+use db3_proto::db3_mutation_proto::{
+    DatabaseMutation, Mutation, MutationAction, PayloadType, WriteRequest,
+};
+use db3_proto::db3_node_proto::{
+    storage_node_client::StorageNodeClient, BroadcastRequest, BroadcastResponse,
+};
+use futures_util::stream::{self, StreamExt};
 use prost::Message;
+use rand::Rng;
 use std::sync::Arc;
+use std::time::Duration;
+use tonic::{Code, Status};
+
+/// the policy to retry the transient failures of submitting
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// the max attempts including the first one
+    pub max_attempts: u32,
+    /// the delay before the first retry, it doubles for every retry
+    pub base_delay: Duration,
+    /// the upper bound of the delay
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// submit only once, it's the default policy
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(0),
+            max_delay: Duration::from_millis(0),
+        }
+    }
+
+    /// the exponential backoff delay before the retry with a random jitter up to half of it
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(1u32 << retry.min(16))
+            .min(self.max_delay);
+        let jitter_ms = rand::thread_rng().gen_range(0..=backoff.as_millis() as u64 / 2);
+        backoff + Duration::from_millis(jitter_ms)
+    }
+
+    /// the node is unavailable or busy, e.g. the mempool is full or the node is committing.
+    /// the connection failures of the channel come as unknown with a transport error message
+    pub fn is_transient(status: &Status) -> bool {
+        match status.code() {
+            Code::Unavailable | Code::ResourceExhausted => true,
+            Code::Unknown => status.message().contains("transport error"),
+            _ => false,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::no_retry()
+    }
+}
+
+/// the max in-flight submissions of a batch by default
+pub const DEFAULT_SUBMIT_CONCURRENCY: usize = 16;
+
+///
+/// compress the values of the inserted pairs not shorter than the threshold and flag
+/// them as compressed, the node stores them compressed and StoreSDK decompresses them
+/// on reading
+///
+pub fn compress_mutation(mutation: &Mutation, threshold: usize) -> Mutation {
+    let mut compressed = mutation.clone();
+    for kv in compressed.kv_pairs.iter_mut() {
+        if kv.action != MutationAction::InsertKv as i32 || kv.compressed {
+            continue;
+        }
+        if let Some(value) = value_codec::compress(kv.value.as_ref(), threshold) {
+            kv.value = value;
+            kv.compressed = true;
+        }
+    }
+    compressed
+}
+
+///
+/// sign the payload and encode the write request without connecting to the node, so
+/// the tx can be built on an offline machine and broadcasted by broadcast_tx later
+///
+pub fn build_write_request(
+    signer: &dyn Signer,
+    payload: &[u8],
+    payload_type: PayloadType,
+) -> Result<Vec<u8>> {
+    let signature = signer.sign(payload)?;
+    let request = WriteRequest {
+        signature: signature.as_ref().to_vec(),
+        signature_scheme: signature.scheme().to_proto(),
+        payload: payload.to_vec(),
+        payload_type: payload_type.into(),
+    };
+    let mut buf = BytesMut::with_capacity(1024 * 4);
+    request
+        .encode(&mut buf)
+        .map_err(|e| DB3Error::SubmitMutationError(format!("{e}")))?;
+    Ok(buf.as_ref().to_vec())
+}
 
 pub struct MutationSDK {
-    signer: Db3MultiSchemeSigner,
+    signer: Box<dyn Signer>,
     client: Arc<StorageNodeClient<tonic::transport::Channel>>,
+    retry_policy: RetryPolicy,
+    submit_concurrency: usize,
+    // the values are sent raw if it's none
+    compress_threshold: Option<usize>,
+    // the cache of StoreSDK to drop the written keys from
+    read_cache: Option<ReadCache>,
 }
 
 impl MutationSDK {
     pub fn new(
         client: Arc<StorageNodeClient<tonic::transport::Channel>>,
-        signer: Db3MultiSchemeSigner,
+        signer: Box<dyn Signer>,
     ) -> Self {
-        Self { client, signer }
+        Self {
+            client,
+            signer,
+            retry_policy: RetryPolicy::no_retry(),
+            submit_concurrency: DEFAULT_SUBMIT_CONCURRENCY,
+            compress_threshold: None,
+            read_cache: None,
+        }
+    }
+
+    /// drop the keys of the submitted mutations from the read cache of StoreSDK
+    pub fn with_read_cache(mut self, read_cache: ReadCache) -> Self {
+        self.read_cache = Some(read_cache);
+        self
+    }
+
+    /// gzip the values not shorter than the threshold before submitting
+    pub fn with_compress_threshold(mut self, threshold: usize) -> Self {
+        self.compress_threshold = Some(threshold);
+        self
+    }
+
+    /// the max in-flight submissions of submit_mutations
+    pub fn with_submit_concurrency(mut self, submit_concurrency: usize) -> Self {
+        self.submit_concurrency = submit_concurrency.max(1);
+        self
+    }
+
+    /// retry the transient failures of submitting with the policy
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    ///
+    /// broadcast the request to the node and retry on the transient errors. the
+    /// permanent errors like invalid argument fail fast and the last error is returned.
+    /// the tx rejected by check_tx is returned as TxRejectedError with the code in
+    /// CheckTxCode
+    ///
+    async fn broadcast(&self, r: BroadcastRequest) -> Result<BroadcastResponse> {
+        let mut attempts: u32 = 0;
+        loop {
+            attempts += 1;
+            let request = tonic::Request::new(r.clone());
+            let mut client = self.client.as_ref().clone();
+            match client.broadcast(request).await {
+                Ok(response) => {
+                    let response = response.into_inner();
+                    if response.code != 0 {
+                        // the rejection of check_tx is permanent and not retried
+                        return Err(DB3Error::TxRejectedError {
+                            code: response.code,
+                            log: response.log,
+                        });
+                    }
+                    return Ok(response);
+                }
+                Err(e) => {
+                    if attempts >= self.retry_policy.max_attempts || !RetryPolicy::is_transient(&e)
+                    {
+                        return Err(DB3Error::SubmitMutationError(format!(
+                            "{e} after {attempts} attempts"
+                        )));
+                    }
+                    tokio::time::sleep(self.retry_policy.delay(attempts - 1)).await;
+                }
+            }
+        }
     }
 
     pub async fn submit_database_mutation(
@@ -54,77 +232,97 @@ impl MutationSDK {
             .encode(&mut mbuf)
             .map_err(|e| DB3Error::SubmitMutationError(format!("{e}")))?;
         let mbuf = mbuf.freeze();
-        let signature = self.signer.sign(mbuf.as_ref())?;
-        let request = WriteRequest {
-            signature: signature.as_ref().to_vec().to_owned(),
-            payload: mbuf.as_ref().to_vec().to_owned(),
-            payload_type: PayloadType::DatabasePayload.into(),
-        };
-        //
-        //TODO generate the address from local currently
-        //
-        let mut buf = BytesMut::with_capacity(1024 * 4);
-        request
-            .encode(&mut buf)
-            .map_err(|e| DB3Error::SubmitMutationError(format!("{e}")))?;
-        let buf = buf.freeze();
-        let r = BroadcastRequest {
-            body: buf.as_ref().to_vec(),
-        };
+        let tx = build_write_request(
+            self.signer.as_ref(),
+            mbuf.as_ref(),
+            PayloadType::DatabasePayload,
+        )?;
+        let tx_id = self.broadcast_tx(&tx).await?;
+        let db_id = self.derive_db_id(nonce)?;
+        Ok((db_id, tx_id))
+    }
 
-        let request = tonic::Request::new(r);
-        let mut client = self.client.as_ref().clone();
-        let response = client
-            .broadcast(request)
-            .await
-            .map_err(|e| DB3Error::SubmitMutationError(format!("{e}")))?
-            .into_inner();
-        let hash: [u8; TX_ID_LENGTH] = response
-            .hash
-            .try_into()
-            .map_err(|_| DB3Error::InvalidAddress)?;
-        let tx_id = TxId::from(hash);
+    ///
+    /// the address of the database created by the signer with the nonce, it's the one
+    /// assigned by the node when the mutation is applied
+    ///
+    pub fn derive_db_id(&self, nonce: u64) -> Result<DbId> {
         let sender = self.signer.get_address()?;
-        let db_id = DbId::try_from((&sender, nonce))?;
-        Ok((db_id, tx_id))
+        DbId::derive(&sender, nonce)
     }
 
     pub async fn submit_mutation(&self, mutation: &Mutation) -> Result<TxId> {
         //TODO update gas and nonce
+        let compressed = self
+            .compress_threshold
+            .map(|threshold| compress_mutation(mutation, threshold));
+        let mutation = compressed.as_ref().unwrap_or(mutation);
         let mut mbuf = BytesMut::with_capacity(1024 * 4);
         mutation
             .encode(&mut mbuf)
             .map_err(|e| DB3Error::SubmitMutationError(format!("{e}")))?;
         let mbuf = mbuf.freeze();
-        let signature = self.signer.sign(mbuf.as_ref())?;
-        let request = WriteRequest {
-            signature: signature.as_ref().to_vec().to_owned(),
-            payload: mbuf.as_ref().to_vec().to_owned(),
-            payload_type: PayloadType::MutationPayload.into(),
-        };
+        let tx = build_write_request(
+            self.signer.as_ref(),
+            mbuf.as_ref(),
+            PayloadType::MutationPayload,
+        )?;
+        // the keys are dropped again after broadcasting in case they are read back in between
+        if let Some(cache) = &self.read_cache {
+            cache.invalidate_mutation(mutation);
+        }
+        let result = self.broadcast_tx(&tx).await;
+        if let Some(cache) = &self.read_cache {
+            cache.invalidate_mutation(mutation);
+        }
+        result
+    }
 
-        //TODO add the capacity to mutation sdk configuration
-        let mut buf = BytesMut::with_capacity(1024 * 4);
-        request
-            .encode(&mut buf)
-            .map_err(|e| DB3Error::SubmitMutationError(format!("{e}")))?;
-        let buf = buf.freeze();
-        let r = BroadcastRequest {
-            body: buf.as_ref().to_vec(),
-        };
-        let request = tonic::Request::new(r);
-        let mut client = self.client.as_ref().clone();
-        let response = client
-            .broadcast(request)
-            .await
-            .map_err(|e| DB3Error::SubmitMutationError(format!("{e}")))?
-            .into_inner();
+    ///
+    /// broadcast an encoded write request signed by build_write_request, the signer of
+    /// the sdk is not used
+    ///
+    pub async fn broadcast_tx(&self, tx: &[u8]) -> Result<TxId> {
+        let r = BroadcastRequest { body: tx.to_vec() };
+        let response = self.broadcast(r).await?;
         let hash: [u8; TX_ID_LENGTH] = response
             .hash
             .try_into()
             .map_err(|_| DB3Error::InvalidAddress)?;
-        let tx_id = TxId::from(hash);
-        Ok(tx_id)
+        Ok(TxId::from(hash))
+    }
+
+    ///
+    /// submit the mutations with bounded concurrency and return the results in the
+    /// order of the input. the nonce of every mutation must be greater than the one
+    /// before it, a mutation breaking the order is rejected without being submitted
+    ///
+    pub async fn submit_mutations(&self, mutations: &[Mutation]) -> Vec<Result<TxId>> {
+        let mut last_nonce: Option<u64> = None;
+        let checked: Vec<(&Mutation, bool)> = mutations
+            .iter()
+            .map(|m| {
+                let monotonic = last_nonce.map_or(true, |n| m.nonce > n);
+                if monotonic {
+                    last_nonce = Some(m.nonce);
+                }
+                (m, monotonic)
+            })
+            .collect();
+        stream::iter(checked)
+            .map(|(mutation, monotonic)| async move {
+                if monotonic {
+                    self.submit_mutation(mutation).await
+                } else {
+                    Err(DB3Error::SubmitMutationError(format!(
+                        "the nonce {} is not greater than the previous one",
+                        mutation.nonce
+                    )))
+                }
+            })
+            .buffered(self.submit_concurrency)
+            .collect()
+            .await
     }
 }
 
@@ -132,6 +330,7 @@ impl MutationSDK {
 mod tests {
     use super::Mutation;
     use super::MutationSDK;
+    use super::RetryPolicy;
     use crate::mutation_sdk::StorageNodeClient;
     use crate::sdk_test;
     use crate::store_sdk::StoreSDK;
@@ -140,8 +339,168 @@ mod tests {
     use db3_proto::db3_mutation_proto::{KvPair, MutationAction};
     use rand::Rng;
     use std::sync::Arc;
+    use std::time::Duration;
     use std::{thread, time};
     use tonic::transport::Endpoint;
+    use tonic::Status;
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+        let delay = policy.delay(0);
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(150));
+        let delay = policy.delay(1);
+        assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(300));
+        let delay = policy.delay(10);
+        assert!(delay >= Duration::from_millis(300) && delay <= Duration::from_millis(450));
+        assert!(RetryPolicy::is_transient(&Status::unavailable("busy")));
+        assert!(RetryPolicy::is_transient(&Status::resource_exhausted(
+            "full"
+        )));
+        assert!(!RetryPolicy::is_transient(&Status::invalid_argument("bad")));
+        assert!(!RetryPolicy::is_transient(&Status::unauthenticated("bad")));
+    }
+
+    #[test]
+    fn test_compress_mutation() {
+        use super::compress_mutation;
+        use db3_base::value_codec;
+        let value = "db3".repeat(1000).as_bytes().to_vec();
+        let mutation = Mutation {
+            ns: "my_twitter".as_bytes().to_vec(),
+            kv_pairs: vec![
+                KvPair {
+                    key: "k1".as_bytes().to_vec(),
+                    value: value.clone(),
+                    action: MutationAction::InsertKv.into(),
+                    ns: vec![],
+                    expected_value: vec![],
+                    ttl: 0,
+                    compressed: false,
+                },
+                KvPair {
+                    key: "k2".as_bytes().to_vec(),
+                    value: "v2".as_bytes().to_vec(),
+                    action: MutationAction::InsertKv.into(),
+                    ns: vec![],
+                    expected_value: vec![],
+                    ttl: 0,
+                    compressed: false,
+                },
+            ],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let compressed = compress_mutation(&mutation, value_codec::DEFAULT_COMPRESS_THRESHOLD);
+        assert!(compressed.kv_pairs[0].compressed);
+        assert_eq!(
+            value,
+            value_codec::decompress(&compressed.kv_pairs[0].value, usize::MAX).unwrap()
+        );
+        assert_eq!("v2".as_bytes(), compressed.kv_pairs[1].value.as_slice());
+        assert!(!compressed.kv_pairs[1].compressed);
+    }
+
+    #[test]
+    fn test_build_write_request_offline() {
+        use super::build_write_request;
+        use db3_crypto::db3_verifier::DB3Verifier;
+        use db3_proto::db3_mutation_proto::{PayloadType, WriteRequest};
+        use prost::Message;
+        let (address, signer) = sdk_test::gen_secp256k1_signer();
+        let payload = "payload".as_bytes();
+        let tx = build_write_request(&signer, payload, PayloadType::MutationPayload).unwrap();
+        let request = WriteRequest::decode(tx.as_ref()).unwrap();
+        assert_eq!(payload, request.payload.as_slice());
+        let sender = DB3Verifier::verify_with_scheme(
+            request.payload.as_ref(),
+            request.signature.as_ref(),
+            request.signature_scheme,
+        )
+        .unwrap();
+        assert_eq!(address, sender.addr);
+    }
+
+    #[tokio::test]
+    async fn test_submit_mutation_with_retry() {
+        // nothing listens on the port
+        let ep = "http://127.0.0.1:1";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (_, signer) = sdk_test::gen_secp256k1_signer();
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        };
+        let sdk = MutationSDK::new(client, Box::new(signer)).with_retry_policy(policy);
+        let mutation = Mutation {
+            ns: "my_twitter".as_bytes().to_vec(),
+            kv_pairs: vec![KvPair {
+                key: "k1".as_bytes().to_vec(),
+                value: "v1".as_bytes().to_vec(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce: get_a_random_nonce(),
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let result = sdk.submit_mutation(&mutation).await;
+        assert!(result.is_err());
+        assert!(format!("{}", result.err().unwrap()).ends_with("after 3 attempts"));
+    }
+
+    #[tokio::test]
+    async fn test_submit_mutations_keep_order() {
+        let ep = "http://127.0.0.1:26659";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (_, signer) = sdk_test::gen_secp256k1_signer();
+        let sdk = MutationSDK::new(client, Box::new(signer)).with_submit_concurrency(4);
+        let nonce = get_a_random_nonce();
+        let mut mutations: Vec<Mutation> = (0..10)
+            .map(|i| Mutation {
+                ns: "my_twitter".as_bytes().to_vec(),
+                kv_pairs: vec![KvPair {
+                    key: format!("batch_key{i}").as_bytes().to_vec(),
+                    value: format!("batch_value{i}").as_bytes().to_vec(),
+                    action: MutationAction::InsertKv.into(),
+                    ns: vec![],
+                    expected_value: vec![],
+                    ttl: 0,
+                    compressed: false,
+                }],
+                nonce: nonce + i,
+                chain_id: ChainId::DevNet.into(),
+                chain_role: ChainRole::StorageShardChain.into(),
+                gas_price: None,
+                gas: 10,
+            })
+            .collect();
+        // the nonce of the last one goes backward
+        mutations[9].nonce = nonce;
+        let results = sdk.submit_mutations(&mutations).await;
+        assert_eq!(10, results.len());
+        for result in &results[..9] {
+            assert!(result.is_ok(), "{}", result.as_ref().err().unwrap());
+        }
+        assert!(results[9].is_err());
+    }
 
     #[tokio::test]
     async fn test_submit_duplicated_key_mutation() {
@@ -153,17 +512,21 @@ mod tests {
         let (_, signer) = sdk_test::gen_secp256k1_signer();
         let ns = "my_twitter";
         {
-            let sdk = MutationSDK::new(client.clone(), signer);
+            let sdk = MutationSDK::new(client.clone(), Box::new(signer));
             let kv = KvPair {
                 key: format!("kk{}", 1).as_bytes().to_vec(),
                 value: format!("dkalue{}", 1).as_bytes().to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             };
             let mutation = Mutation {
                 ns: ns.as_bytes().to_vec(),
                 kv_pairs: vec![kv],
                 nonce,
-                chain_id: ChainId::MainNet.into(),
+                chain_id: ChainId::DevNet.into(),
                 chain_role: ChainRole::StorageShardChain.into(),
                 gas_price: None,
                 gas: 10,
@@ -175,12 +538,16 @@ mod tests {
                 key: format!("dkkkk{}", 1).as_bytes().to_vec(),
                 value: format!("dkalue{}", 1).as_bytes().to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             };
             let mutation = Mutation {
                 ns: ns.as_bytes().to_vec(),
                 kv_pairs: vec![kv.clone(), kv],
                 nonce,
-                chain_id: ChainId::MainNet.into(),
+                chain_id: ChainId::DevNet.into(),
                 chain_role: ChainRole::StorageShardChain.into(),
                 gas_price: None,
                 gas: 10,
@@ -192,7 +559,7 @@ mod tests {
         let millis = time::Duration::from_millis(2000);
         thread::sleep(millis);
         let (_, signer) = sdk_test::gen_secp256k1_signer();
-        let mut store_sdk = StoreSDK::new(client, signer);
+        let mut store_sdk = StoreSDK::new(client, Box::new(signer));
         let sess_token = store_sdk.open_session().await.unwrap().session_token;
         let values = store_sdk
             .batch_get(
@@ -216,19 +583,23 @@ mod tests {
         let channel = rpc_endpoint.connect_lazy();
         let client = Arc::new(StorageNodeClient::new(channel));
         let (_, signer) = sdk_test::gen_secp256k1_signer();
-        let sdk = MutationSDK::new(client, signer);
+        let sdk = MutationSDK::new(client, Box::new(signer));
         let mut count = 1;
         loop {
             let kv = KvPair {
                 key: format!("kkkkk{}", count).as_bytes().to_vec(),
                 value: format!("vkalue{}", count).as_bytes().to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             };
             let mutation = Mutation {
                 ns: "my_twitter".as_bytes().to_vec(),
                 kv_pairs: vec![kv],
                 nonce,
-                chain_id: ChainId::MainNet.into(),
+                chain_id: ChainId::DevNet.into(),
                 chain_role: ChainRole::StorageShardChain.into(),
                 gas_price: None,
                 gas: 10,
@@ -0,0 +1,166 @@
+//
+// read_cache.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use db3_proto::db3_mutation_proto::{KvPair, Mutation};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type CacheKey = (Vec<u8>, Vec<u8>);
+
+///
+/// a small ttl cache of the values read by StoreSDK keyed by (ns, key). it's shared
+/// with MutationSDK to drop the keys written by the client, the writes of other
+/// clients are seen only after the ttl expires
+///
+#[derive(Clone)]
+pub struct ReadCache {
+    entries: Arc<Mutex<HashMap<CacheKey, (KvPair, Instant)>>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl ReadCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// the value cached within the ttl
+    pub fn get(&self, ns: &[u8], key: &[u8]) -> Option<KvPair> {
+        let mut entries = self.entries.lock().ok()?;
+        let cache_key = (ns.to_vec(), key.to_vec());
+        match entries.get(&cache_key) {
+            Some((kv, cached_at)) if cached_at.elapsed() < self.ttl => Some(kv.clone()),
+            Some(_) => {
+                entries.remove(&cache_key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// cache the value, the oldest one is evicted if the cache is full
+    pub fn put(&self, ns: &[u8], kv: &KvPair) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Ok(mut entries) = self.entries.lock() {
+            let cache_key = (ns.to_vec(), kv.key.clone());
+            if !entries.contains_key(&cache_key) && entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, (_, cached_at))| *cached_at)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+            entries.insert(cache_key, (kv.clone(), Instant::now()));
+        }
+    }
+
+    pub fn invalidate(&self, ns: &[u8], key: &[u8]) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(&(ns.to_vec(), key.to_vec()));
+        }
+    }
+
+    /// drop the keys written by the mutation
+    pub fn invalidate_mutation(&self, mutation: &Mutation) {
+        for kv in mutation.kv_pairs.iter() {
+            let ns = if kv.ns.is_empty() {
+                mutation.ns.as_ref()
+            } else {
+                kv.ns.as_ref()
+            };
+            self.invalidate(ns, kv.key.as_ref());
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .map(|entries| entries.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db3_proto::db3_mutation_proto::MutationAction;
+
+    fn kv(key: &str, value: &str) -> KvPair {
+        KvPair {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        }
+    }
+
+    #[test]
+    fn it_cache_values() {
+        let cache = ReadCache::new(2, Duration::from_secs(60));
+        let ns = "ns1".as_bytes();
+        cache.put(ns, &kv("k1", "v1"));
+        assert_eq!(Some(kv("k1", "v1")), cache.get(ns, "k1".as_bytes()));
+        assert_eq!(None, cache.get("ns2".as_bytes(), "k1".as_bytes()));
+        cache.put(ns, &kv("k2", "v2"));
+        cache.put(ns, &kv("k3", "v3"));
+        // k1 is evicted as the oldest one
+        assert_eq!(2, cache.len());
+        assert_eq!(None, cache.get(ns, "k1".as_bytes()));
+        let mutation = Mutation {
+            ns: ns.to_vec(),
+            kv_pairs: vec![kv("k2", "v22")],
+            ..Default::default()
+        };
+        cache.invalidate_mutation(&mutation);
+        assert_eq!(None, cache.get(ns, "k2".as_bytes()));
+        assert_eq!(Some(kv("k3", "v3")), cache.get(ns, "k3".as_bytes()));
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn it_expire_values() {
+        let cache = ReadCache::new(2, Duration::from_millis(10));
+        let ns = "ns1".as_bytes();
+        cache.put(ns, &kv("k1", "v1"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(None, cache.get(ns, "k1".as_bytes()));
+        assert!(cache.is_empty());
+    }
+}
@@ -15,45 +15,244 @@
 // limitations under the License.
 //
 
+use crate::event_subscriber::{self, MutationEvent, NewBlockEvent};
+use crate::read_cache::ReadCache;
 use bytes::BytesMut;
 use chrono::Utc;
-use db3_crypto::{db3_address::DB3Address, db3_signer::Db3MultiSchemeSigner};
+use db3_base::value_codec;
+use db3_crypto::{
+    db3_address::DB3Address,
+    db3_signer::Signer,
+    db3_verifier::DB3Verifier,
+    id::{AccountId, DbId, TxId},
+};
 use db3_proto::db3_account_proto::Account;
+use db3_proto::db3_base_proto::Units;
 use db3_proto::db3_bill_proto::Bill;
-use db3_proto::db3_database_proto::Database;
+use db3_proto::db3_database_proto::{Database, Index};
+use db3_proto::db3_mutation_proto::{KvPair, PayloadType};
 use db3_proto::db3_node_proto::{
-    storage_node_client::StorageNodeClient, BatchGetKey, BatchGetValue, CloseSessionRequest,
-    GetAccountRequest, GetKeyRequest, GetRangeRequest, GetSessionInfoRequest, OpenSessionRequest,
-    OpenSessionResponse, QueryBillKey, QueryBillRequest, Range as DB3Range, RangeKey, RangeValue,
-    SessionIdentifier, ShowDatabaseRequest,
+    storage_node_client::StorageNodeClient, BatchGetAccountRequest, BatchGetKey, BatchGetValue,
+    CloseSessionRequest, CountKeysRequest, CountKeysResponse, EstimateGasRequest,
+    GetAccountRequest, GetKeyRequest, GetLatestStateRequest, GetMinGasPriceRequest,
+    GetQuerySessionReceiptRequest, GetQuerySessionReceiptResponse, GetRangeRequest,
+    GetSessionInfoRequest, GetTxRequest, GetTxResponse, ListDatabasesRequest,
+    ListNamespacesRequest, NamespaceStat, OpenSessionRequest, OpenSessionResponse, QueryBillKey,
+    QueryBillRequest, Range as DB3Range, RangeKey, RangeValue, SessionIdentifier,
+    ShowDatabaseRequest,
+};
+use db3_proto::db3_session_proto::{
+    CloseSessionPayload, OpenSessionPayload, QuerySessionInfo, QuerySessionReceipt,
 };
-use db3_proto::db3_session_proto::{CloseSessionPayload, OpenSessionPayload, QuerySessionInfo};
 use db3_session::session_manager::{SessionPool, SessionStatus};
+use futures_util::stream::Stream;
 use num_traits::cast::FromPrimitive;
 use prost::Message;
 use std::sync::Arc;
-use tonic::Status;
+use std::time::{Duration, Instant};
+use tendermint_rpc::Url;
+use tonic::{Code, Status};
 use uuid::Uuid;
 
+/// the interval to poll the node for the tx
+const WAIT_FOR_TX_INTERVAL: Duration = Duration::from_millis(200);
+
+/// the max length of a user key, every key in a namespace is less than the key of this length
+/// filled with 0xff
+const SCAN_END_KEY_LEN: usize = 512;
+
+/// the pairs fetched in one request by scan_ns
+const SCAN_PAGE_SIZE: u32 = 1024;
+
+///
+/// the smallest key greater than all the keys starting with the prefix, it's none if
+/// the prefix is empty or all 0xff and no such key exists
+///
+pub fn prefix_end_key(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+///
+/// decompress the values flagged as compressed by the node, the raw values are kept
+///
+fn decompress_values(values: &mut [KvPair]) -> std::result::Result<(), Status> {
+    for kv in values.iter_mut() {
+        if kv.compressed {
+            kv.value = value_codec::decompress(kv.value.as_ref(), usize::MAX)
+                .map_err(|e| Status::data_loss(format!("fail to decompress value for {e}")))?;
+            kv.compressed = false;
+        }
+    }
+    Ok(())
+}
+
+///
+/// the result of a requested key, the value is none if the key does not exist. the
+/// height is set only if the metadata is requested
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchGetEntry {
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+    pub height: Option<u64>,
+}
+
+impl BatchGetEntry {
+    pub fn is_found(&self) -> bool {
+        self.value.is_some()
+    }
+}
+
+///
+/// match the returned pairs to the requested keys, one entry per key in the order of keys
+///
+pub fn to_batch_get_entries(
+    keys: &[Vec<u8>],
+    batch_get_value: Option<&BatchGetValue>,
+) -> Vec<BatchGetEntry> {
+    keys.iter()
+        .map(|key| {
+            let found = batch_get_value.and_then(|v| {
+                v.values
+                    .iter()
+                    .position(|kv| &kv.key == key)
+                    .map(|i| (v.values[i].value.to_vec(), v.heights.get(i).copied()))
+            });
+            match found {
+                Some((value, height)) => BatchGetEntry {
+                    key: key.to_vec(),
+                    value: Some(value),
+                    height,
+                },
+                None => BatchGetEntry {
+                    key: key.to_vec(),
+                    value: None,
+                    height: None,
+                },
+            }
+        })
+        .collect()
+}
+
+///
+/// decode the receipt and check it's signed by the node serving the query session
+///
+pub fn verify_query_session_receipt(
+    response: &GetQuerySessionReceiptResponse,
+) -> std::result::Result<QuerySessionReceipt, Status> {
+    let account_id = DB3Verifier::verify_with_scheme(
+        response.receipt.as_ref(),
+        response.signature.as_ref(),
+        response.signature_scheme,
+    )
+    .map_err(|e| Status::data_loss(format!("invalid signature of receipt for {e}")))?;
+    let receipt = QuerySessionReceipt::decode(response.receipt.as_ref())
+        .map_err(|e| Status::data_loss(format!("invalid receipt for {e}")))?;
+    if account_id.addr.as_ref() != receipt.node_addr.as_slice() {
+        return Err(Status::data_loss(
+            "the receipt is not signed by the node of the query session".to_string(),
+        ));
+    }
+    Ok(receipt)
+}
+
 pub struct StoreSDK {
     client: Arc<StorageNodeClient<tonic::transport::Channel>>,
-    signer: Db3MultiSchemeSigner,
+    signer: Box<dyn Signer>,
     session_pool: SessionPool,
+    // the websocket url of tendermint for subscribing the events
+    ws_url: Option<Url>,
+    // the values are always read from the node if it's none
+    read_cache: Option<ReadCache>,
 }
 
 impl StoreSDK {
     pub fn new(
         client: Arc<StorageNodeClient<tonic::transport::Channel>>,
-        signer: Db3MultiSchemeSigner,
+        signer: Box<dyn Signer>,
     ) -> Self {
         Self {
             client,
             signer,
             session_pool: SessionPool::new(),
+            ws_url: None,
+            read_cache: None,
+        }
+    }
+
+    ///
+    /// cache the values of batch_get for the ttl, pass the cache to MutationSDK to drop
+    /// the keys written by this client
+    ///
+    pub fn with_read_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.read_cache = Some(ReadCache::new(capacity, ttl));
+        self
+    }
+
+    pub fn read_cache(&self) -> Option<ReadCache> {
+        self.read_cache.clone()
+    }
+
+    /// drop all the cached values
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.read_cache {
+            cache.clear();
         }
     }
 
-    async fn keep_session(&mut self) -> std::result::Result<String, Status> {
+    ///
+    /// set the tendermint websocket url, eg ws://127.0.0.1:26657/websocket
+    ///
+    pub fn with_ws_url(mut self, ws_url: &str) -> std::result::Result<Self, Status> {
+        let url = ws_url
+            .parse::<Url>()
+            .map_err(|e| Status::invalid_argument(format!("invalid websocket url {e}")))?;
+        self.ws_url = Some(url);
+        Ok(self)
+    }
+
+    ///
+    /// subscribe the new committed blocks, the subscription reconnects when it's dropped
+    ///
+    pub fn subscribe_blocks(
+        &self,
+    ) -> std::result::Result<impl Stream<Item = NewBlockEvent>, Status> {
+        let url = self.get_ws_url()?;
+        Ok(event_subscriber::subscribe_blocks(url))
+    }
+
+    ///
+    /// subscribe the committed mutations, only the mutations in the namespace are returned
+    /// if it's given
+    ///
+    pub fn subscribe_mutations(
+        &self,
+        ns: Option<&[u8]>,
+    ) -> std::result::Result<impl Stream<Item = MutationEvent>, Status> {
+        let url = self.get_ws_url()?;
+        Ok(event_subscriber::subscribe_mutations(
+            url,
+            ns.map(|ns| ns.to_vec()),
+        ))
+    }
+
+    fn get_ws_url(&self) -> std::result::Result<Url, Status> {
+        self.ws_url.clone().ok_or_else(|| {
+            Status::failed_precondition("the websocket url of tendermint is not set".to_string())
+        })
+    }
+
+    ///
+    /// get the token of the running session or open a new one
+    ///
+    pub async fn keep_session(&mut self) -> std::result::Result<String, Status> {
         if let Some(token) = self.session_pool.get_last_token() {
             match self.session_pool.get_session_mut(token.as_ref()) {
                 Some(session) => {
@@ -76,12 +275,139 @@ impl StoreSDK {
         }
     }
 
+    ///
+    /// count the keys and the total bytes in a namespace, a missing namespace gets zero
+    ///
+    pub async fn count_keys(
+        &mut self,
+        ns: &[u8],
+    ) -> std::result::Result<CountKeysResponse, Status> {
+        let token = self.keep_session().await?;
+        match self.session_pool.get_session_mut(token.as_ref()) {
+            Some(session) => {
+                if session.check_session_running() {
+                    let r = CountKeysRequest {
+                        session_token: token.to_string(),
+                        ns: ns.to_vec(),
+                    };
+                    let request = tonic::Request::new(r);
+                    let mut client = self.client.as_ref().clone();
+                    let response = client.count_keys(request).await?.into_inner();
+                    session.increase_query(1);
+                    Ok(response)
+                } else {
+                    Err(Status::permission_denied(
+                        "Fail to query in this session. Please restart query session",
+                    ))
+                }
+            }
+            None => Err(Status::not_found(format!(
+                "Fail to query, session with token {token} not found"
+            ))),
+        }
+    }
+
+    ///
+    /// list the namespaces owned by the account with their key count and total bytes
+    ///
+    pub async fn list_namespaces(&mut self) -> std::result::Result<Vec<NamespaceStat>, Status> {
+        let token = self.keep_session().await?;
+        match self.session_pool.get_session_mut(token.as_ref()) {
+            Some(session) => {
+                if session.check_session_running() {
+                    let r = ListNamespacesRequest {
+                        session_token: token.to_string(),
+                    };
+                    let request = tonic::Request::new(r);
+                    let mut client = self.client.as_ref().clone();
+                    let response = client.list_namespaces(request).await?.into_inner();
+                    session.increase_query(1);
+                    Ok(response.namespaces)
+                } else {
+                    Err(Status::permission_denied(
+                        "Fail to query in this session. Please restart query session",
+                    ))
+                }
+            }
+            None => Err(Status::not_found(format!(
+                "Fail to query, session with token {token} not found"
+            ))),
+        }
+    }
+
+    ///
+    /// list the ids of the databases created by the owner
+    ///
+    pub async fn list_databases_by_owner(
+        &mut self,
+        owner: &DB3Address,
+    ) -> std::result::Result<Vec<DbId>, Status> {
+        let token = self.keep_session().await?;
+        match self.session_pool.get_session_mut(token.as_ref()) {
+            Some(session) => {
+                if session.check_session_running() {
+                    let r = ListDatabasesRequest {
+                        session_token: token.to_string(),
+                        owner: AccountId::new(*owner).to_hex(),
+                    };
+                    let request = tonic::Request::new(r);
+                    let mut client = self.client.as_ref().clone();
+                    let response = client.list_databases(request).await?.into_inner();
+                    session.increase_query(1);
+                    response
+                        .db_ids
+                        .iter()
+                        .map(|id| {
+                            DbId::try_from(id.as_slice())
+                                .map_err(|e| Status::internal(format!("invalid database id {e}")))
+                        })
+                        .collect()
+                } else {
+                    Err(Status::permission_denied(
+                        "Fail to query in this session. Please restart query session",
+                    ))
+                }
+            }
+            None => Err(Status::not_found(format!(
+                "Fail to query, session with token {token} not found"
+            ))),
+        }
+    }
+
     ///
     /// get the information of database with a hex format address
     ///
     pub async fn get_database(
         &mut self,
         addr: &str,
+    ) -> std::result::Result<Option<Database>, Status> {
+        self.do_get_database(addr, false).await
+    }
+
+    ///
+    /// list the names and the indexes of the collections in the database, the node
+    /// returns the collections only
+    ///
+    pub async fn list_collections(
+        &mut self,
+        addr: &str,
+    ) -> std::result::Result<Vec<(String, Vec<Index>)>, Status> {
+        match self.do_get_database(addr, true).await? {
+            Some(database) => Ok(database
+                .collections
+                .into_iter()
+                .map(|collection| (collection.name, collection.index_list))
+                .collect()),
+            None => Err(Status::not_found(format!(
+                "no database with address {addr}"
+            ))),
+        }
+    }
+
+    async fn do_get_database(
+        &mut self,
+        addr: &str,
+        collections_only: bool,
     ) -> std::result::Result<Option<Database>, Status> {
         let token = self.keep_session().await?;
         match self.session_pool.get_session_mut(token.as_ref()) {
@@ -90,6 +416,7 @@ impl StoreSDK {
                     let r = ShowDatabaseRequest {
                         session_token: token.to_string(),
                         address: addr.to_string(),
+                        collections_only,
                     };
                     let request = tonic::Request::new(r);
                     let mut client = self.client.as_ref().clone();
@@ -140,6 +467,28 @@ impl StoreSDK {
         }
     }
 
+    ///
+    /// record the queries sent to the node without the sdk, e.g. with the raw grpc client.
+    /// the count is signed when the session is closed and must match the node's one
+    ///
+    pub fn record_query(&mut self, token: &str, count: i32) -> std::result::Result<(), Status> {
+        match self.session_pool.get_session_mut(token) {
+            Some(session) => {
+                if session.check_session_running() {
+                    session.increase_query(count);
+                    Ok(())
+                } else {
+                    Err(Status::permission_denied(
+                        "Fail to record query in this session. Please restart query session",
+                    ))
+                }
+            }
+            None => Err(Status::not_found(format!(
+                "Fail to record query, session with token {token} not found"
+            ))),
+        }
+    }
+
     /// close session
     /// 1. verify Account
     /// 2. request close_query_session
@@ -234,6 +583,150 @@ impl StoreSDK {
         Ok(account)
     }
 
+    ///
+    /// get the accounts of the addresses in one call, the result is in the same order
+    /// as the addresses and none is returned for the unknown address
+    ///
+    pub async fn batch_get_account(
+        &self,
+        addrs: &[DB3Address],
+    ) -> std::result::Result<Vec<Option<Account>>, Status> {
+        let r = BatchGetAccountRequest {
+            addrs: addrs.iter().map(|addr| addr.to_vec()).collect(),
+        };
+        let request = tonic::Request::new(r);
+        let mut client = self.client.as_ref().clone();
+        let response = client.batch_get_account(request).await?.into_inner();
+        if response.accounts.len() != addrs.len() {
+            return Err(Status::internal(format!(
+                "expect {} accounts but got {}",
+                addrs.len(),
+                response.accounts.len()
+            )));
+        }
+        Ok(response
+            .accounts
+            .into_iter()
+            .map(|entry| entry.account)
+            .collect())
+    }
+
+    ///
+    /// get the raw tx and the height of block it's included in
+    ///
+    pub async fn get_tx(&self, tx_id: &TxId) -> std::result::Result<GetTxResponse, Status> {
+        let r = GetTxRequest {
+            tx_id: tx_id.as_ref().to_vec(),
+        };
+        let request = tonic::Request::new(r);
+        let mut client = self.client.as_ref().clone();
+        let response = client.get_tx(request).await?.into_inner();
+        Ok(response)
+    }
+
+    ///
+    /// get the receipt of the query session closed with the tx, it's available after
+    /// the tx is included in a block
+    ///
+    pub async fn get_query_session_receipt(
+        &self,
+        tx_id: &TxId,
+    ) -> std::result::Result<QuerySessionReceipt, Status> {
+        let r = GetQuerySessionReceiptRequest {
+            tx_id: tx_id.as_ref().to_vec(),
+        };
+        let request = tonic::Request::new(r);
+        let mut client = self.client.as_ref().clone();
+        let response = client
+            .get_query_session_receipt(request)
+            .await?
+            .into_inner();
+        verify_query_session_receipt(&response)
+    }
+
+    ///
+    /// estimate the gas of the encoded payload without applying it
+    ///
+    pub async fn estimate_gas(
+        &self,
+        payload_type: PayloadType,
+        payload: &[u8],
+    ) -> std::result::Result<Units, Status> {
+        let r = EstimateGasRequest {
+            payload: payload.to_vec(),
+            payload_type: payload_type.into(),
+        };
+        let request = tonic::Request::new(r);
+        let mut client = self.client.as_ref().clone();
+        let response = client.estimate_gas(request).await?.into_inner();
+        response
+            .gas
+            .ok_or_else(|| Status::internal("no gas in the response".to_string()))
+    }
+
+    ///
+    /// get the min gas price of the node, the mutations with a lower gas price are
+    /// rejected by it
+    ///
+    pub async fn get_min_gas_price(&self) -> std::result::Result<Units, Status> {
+        let request = tonic::Request::new(GetMinGasPriceRequest {});
+        let mut client = self.client.as_ref().clone();
+        let response = client.get_min_gas_price(request).await?.into_inner();
+        response
+            .min_gas_price
+            .ok_or_else(|| Status::internal("no min gas price in the response".to_string()))
+    }
+
+    ///
+    /// get the height and the app hash of the last block committed by the node
+    ///
+    pub async fn get_latest_state(&self) -> std::result::Result<(u64, Vec<u8>), Status> {
+        let request = tonic::Request::new(GetLatestStateRequest {});
+        let mut client = self.client.as_ref().clone();
+        let response = client.get_latest_state(request).await?.into_inner();
+        Ok((response.height, response.app_hash))
+    }
+
+    ///
+    /// poll the node until the tx is included in a committed block and return the height
+    /// of the block, the tx is not found until the block is committed
+    ///
+    pub async fn wait_for_tx(
+        &self,
+        tx_id: &TxId,
+        timeout: Duration,
+    ) -> std::result::Result<u64, Status> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.get_tx(tx_id).await {
+                Ok(response) => return Ok(response.height),
+                Err(e) if e.code() == Code::NotFound => {
+                    if Instant::now() >= deadline {
+                        return Err(Status::deadline_exceeded(format!(
+                            "tx {} is not committed in {:?}",
+                            tx_id.to_base64(),
+                            timeout
+                        )));
+                    }
+                    tokio::time::sleep(WAIT_FOR_TX_INTERVAL).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    ///
+    /// make a lightweight round trip to the node to check the connection
+    ///
+    pub async fn health_check(&self) -> std::result::Result<(), Status> {
+        let addr = self
+            .signer
+            .get_address()
+            .map_err(|e| Status::internal(format!("{e}")))?;
+        self.get_account(&addr).await?;
+        Ok(())
+    }
+
     pub async fn get_session_info(
         &self,
         session_token: &String,
@@ -257,6 +750,33 @@ impl StoreSDK {
         ns: &[u8],
         range: &std::ops::Range<Vec<u8>>,
         token: &str,
+    ) -> std::result::Result<Option<RangeValue>, Status> {
+        self.do_get_range(ns, range, &[], 0, token).await
+    }
+
+    ///
+    /// get at most limit pairs of the range after the cursor, pass the next_cursor of the
+    /// returned page to get the next one. the cursor of the first page is empty and the
+    /// next_cursor of the last page is empty
+    ///
+    pub async fn get_range_page(
+        &mut self,
+        ns: &[u8],
+        range: &std::ops::Range<Vec<u8>>,
+        cursor: &[u8],
+        limit: u32,
+        token: &str,
+    ) -> std::result::Result<Option<RangeValue>, Status> {
+        self.do_get_range(ns, range, cursor, limit, token).await
+    }
+
+    async fn do_get_range(
+        &mut self,
+        ns: &[u8],
+        range: &std::ops::Range<Vec<u8>>,
+        cursor: &[u8],
+        limit: u32,
+        token: &str,
     ) -> std::result::Result<Option<RangeValue>, Status> {
         match self.session_pool.get_session_mut(token) {
             Some(session) => {
@@ -269,13 +789,18 @@ impl StoreSDK {
                         ns: ns.to_vec(),
                         range: Some(db3_range),
                         session_token: token.to_string(),
+                        limit,
+                        cursor: cursor.to_vec(),
                     });
                     let r = GetRangeRequest { range_keys };
                     let request = tonic::Request::new(r);
                     let mut client = self.client.as_ref().clone();
-                    let response = client.get_range(request).await?.into_inner();
+                    let mut response = client.get_range(request).await?.into_inner();
                     // TODO(cj): batch keys query should be count as a query or multi queries?
                     session.increase_query(1);
+                    if let Some(range_value) = response.range_value.as_mut() {
+                        decompress_values(&mut range_value.values)?;
+                    }
                     Ok(response.range_value)
                 } else {
                     Err(Status::permission_denied(
@@ -289,11 +814,172 @@ impl StoreSDK {
         }
     }
 
+    ///
+    /// scan all the kv pairs in the namespace of the session owner page by page, every
+    /// page counts as a query
+    ///
+    pub async fn scan_ns(
+        &mut self,
+        ns: &[u8],
+        token: &str,
+    ) -> std::result::Result<Vec<KvPair>, Status> {
+        let range = std::ops::Range {
+            start: vec![],
+            end: vec![u8::MAX; SCAN_END_KEY_LEN],
+        };
+        let mut kv_pairs: Vec<KvPair> = Vec::new();
+        let mut cursor: Vec<u8> = vec![];
+        loop {
+            let page = match self
+                .get_range_page(ns, &range, &cursor, SCAN_PAGE_SIZE, token)
+                .await?
+            {
+                Some(page) => page,
+                None => break,
+            };
+            kv_pairs.extend(page.values);
+            if page.next_cursor.is_empty() {
+                break;
+            }
+            cursor = page.next_cursor;
+        }
+        Ok(kv_pairs)
+    }
+
+    ///
+    /// scan at most limit kv pairs starting with the prefix in key order, all of them are
+    /// returned if the limit is 0 and every page counts as a query
+    ///
+    pub async fn scan_prefix(
+        &mut self,
+        ns: &[u8],
+        prefix: &[u8],
+        limit: u32,
+        token: &str,
+    ) -> std::result::Result<Vec<KvPair>, Status> {
+        let range = std::ops::Range {
+            start: prefix.to_vec(),
+            end: prefix_end_key(prefix).unwrap_or_else(|| vec![u8::MAX; SCAN_END_KEY_LEN]),
+        };
+        let mut kv_pairs: Vec<KvPair> = Vec::new();
+        let mut cursor: Vec<u8> = vec![];
+        loop {
+            let page_size = if limit == 0 {
+                SCAN_PAGE_SIZE
+            } else {
+                SCAN_PAGE_SIZE.min(limit - kv_pairs.len() as u32)
+            };
+            let page = match self
+                .get_range_page(ns, &range, &cursor, page_size, token)
+                .await?
+            {
+                Some(page) => page,
+                None => break,
+            };
+            kv_pairs.extend(page.values);
+            if page.next_cursor.is_empty() || (limit > 0 && kv_pairs.len() as u32 >= limit) {
+                break;
+            }
+            cursor = page.next_cursor;
+        }
+        if limit > 0 {
+            kv_pairs.truncate(limit as usize);
+        }
+        Ok(kv_pairs)
+    }
+
+    ///
+    /// batch get the values, the cached values are returned without a query if the read
+    /// cache is enabled
+    ///
     pub async fn batch_get(
         &mut self,
         ns: &[u8],
         keys: Vec<Vec<u8>>,
         token: &str,
+    ) -> std::result::Result<Option<BatchGetValue>, Status> {
+        let cache = match &self.read_cache {
+            Some(cache) => cache.clone(),
+            None => return self.do_batch_get(ns, keys, token, false).await,
+        };
+        let mut values: Vec<KvPair> = Vec::new();
+        let mut missed_keys: Vec<Vec<u8>> = Vec::new();
+        for key in keys.iter() {
+            match cache.get(ns, key.as_ref()) {
+                Some(kv) => values.push(kv),
+                None => missed_keys.push(key.clone()),
+            }
+        }
+        if !missed_keys.is_empty() {
+            if let Some(batch_get_value) = self.do_batch_get(ns, missed_keys, token, false).await? {
+                for kv in batch_get_value.values.iter() {
+                    cache.put(ns, kv);
+                }
+                values.extend(batch_get_value.values);
+            }
+        }
+        // keep the order of the keys
+        values.sort_by_key(|kv| keys.iter().position(|key| key == &kv.key));
+        Ok(Some(BatchGetValue {
+            values,
+            session_token: token.to_string(),
+            ns: ns.to_vec(),
+            heights: vec![],
+        }))
+    }
+
+    ///
+    /// batch get the values after the tx is committed, so the values written by the tx
+    /// are read
+    ///
+    pub async fn batch_get_after_tx(
+        &mut self,
+        ns: &[u8],
+        keys: Vec<Vec<u8>>,
+        token: &str,
+        tx_id: &TxId,
+        timeout: Duration,
+    ) -> std::result::Result<Option<BatchGetValue>, Status> {
+        self.wait_for_tx(tx_id, timeout).await?;
+        self.do_batch_get(ns, keys, token, false).await
+    }
+
+    /// batch get the values along with the block height each of them was last written at
+    pub async fn batch_get_with_metadata(
+        &mut self,
+        ns: &[u8],
+        keys: Vec<Vec<u8>>,
+        token: &str,
+    ) -> std::result::Result<Option<BatchGetValue>, Status> {
+        self.do_batch_get(ns, keys, token, true).await
+    }
+
+    ///
+    /// batch get the keys and tell the missing ones from the ones with an empty value,
+    /// one entry is returned for every key in the order of keys
+    ///
+    pub async fn batch_get_entries(
+        &mut self,
+        ns: &[u8],
+        keys: Vec<Vec<u8>>,
+        token: &str,
+        with_metadata: bool,
+    ) -> std::result::Result<Vec<BatchGetEntry>, Status> {
+        let batch_get_value = if with_metadata {
+            self.batch_get_with_metadata(ns, keys.clone(), token)
+                .await?
+        } else {
+            self.batch_get(ns, keys.clone(), token).await?
+        };
+        Ok(to_batch_get_entries(&keys, batch_get_value.as_ref()))
+    }
+
+    async fn do_batch_get(
+        &mut self,
+        ns: &[u8],
+        keys: Vec<Vec<u8>>,
+        token: &str,
+        with_metadata: bool,
     ) -> std::result::Result<Option<BatchGetValue>, Status> {
         match self.session_pool.get_session_mut(token) {
             Some(session) => {
@@ -302,13 +988,17 @@ impl StoreSDK {
                         ns: ns.to_vec(),
                         keys,
                         session_token: token.to_string(),
+                        with_metadata,
                     });
                     let r = GetKeyRequest { batch_get };
                     let request = tonic::Request::new(r);
                     let mut client = self.client.as_ref().clone();
-                    let response = client.get_key(request).await?.into_inner();
+                    let mut response = client.get_key(request).await?.into_inner();
                     // TODO(cj): batch keys query should be count as a query or multi queries?
                     session.increase_query(1);
+                    if let Some(batch_get_values) = response.batch_get_values.as_mut() {
+                        decompress_values(&mut batch_get_values.values)?;
+                    }
                     Ok(response.batch_get_values)
                 } else {
                     Err(Status::permission_denied(
@@ -332,6 +1022,7 @@ mod tests {
     use bytes::BytesMut;
     use chrono::Utc;
     use db3_base::get_a_random_nonce;
+    use db3_crypto::db3_signature::DB3Signature;
     use db3_proto::db3_base_proto::{ChainId, ChainRole};
     use db3_proto::db3_mutation_proto::KvPair;
     use db3_proto::db3_mutation_proto::{Mutation, MutationAction};
@@ -343,6 +1034,156 @@ mod tests {
     use tonic::transport::Endpoint;
     use uuid::Uuid;
 
+    #[test]
+    fn it_compute_prefix_end_key() {
+        assert_eq!(Some(b"fop".to_vec()), prefix_end_key(b"foo"));
+        assert_eq!(
+            Some(vec![0x01, 0x03]),
+            prefix_end_key(&[0x01, 0x02, 0xff, 0xff])
+        );
+        assert_eq!(None, prefix_end_key(&[0xff, 0xff]));
+        assert_eq!(None, prefix_end_key(&[]));
+        let end = prefix_end_key(b"user/").unwrap();
+        assert!(b"user/".to_vec() < end);
+        assert!(b"user/zzz".to_vec() < end);
+        assert!(b"user0".to_vec() >= end);
+    }
+
+    #[test]
+    fn it_mark_missing_keys() {
+        let kv = |key: &str, value: &str| KvPair {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            action: MutationAction::Nonce.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let keys: Vec<Vec<u8>> = vec!["k3", "k1", "k2"]
+            .iter()
+            .map(|k| k.as_bytes().to_vec())
+            .collect();
+        let batch_get_value = BatchGetValue {
+            values: vec![kv("k1", "v1"), kv("k3", "")],
+            session_token: "token".to_string(),
+            ns: "ns1".as_bytes().to_vec(),
+            heights: vec![2, 3],
+        };
+        let entries = to_batch_get_entries(&keys, Some(&batch_get_value));
+        assert_eq!(3, entries.len());
+        // the empty value is found
+        assert_eq!("k3".as_bytes(), entries[0].key.as_slice());
+        assert_eq!(Some(vec![]), entries[0].value);
+        assert_eq!(Some(3), entries[0].height);
+        assert_eq!(Some("v1".as_bytes().to_vec()), entries[1].value);
+        assert_eq!(Some(2), entries[1].height);
+        assert!(!entries[2].is_found());
+        assert_eq!(None, entries[2].height);
+        let entries = to_batch_get_entries(&keys, None);
+        assert!(entries.iter().all(|entry| !entry.is_found()));
+    }
+
+    #[test]
+    fn it_verify_query_session_receipt() {
+        let (node_addr, signer) = sdk_test::gen_ed25519_signer();
+        let (client_addr, _) = sdk_test::gen_secp256k1_signer();
+        let info = QuerySessionInfo {
+            id: 1,
+            start_time: Utc::now().timestamp(),
+            query_count: 10,
+        };
+        let sign_receipt = |receipt: &QuerySessionReceipt| {
+            let mut buf = BytesMut::with_capacity(1024);
+            receipt.encode(&mut buf).unwrap();
+            let signature = signer.sign(buf.as_ref()).unwrap();
+            GetQuerySessionReceiptResponse {
+                receipt: buf.to_vec(),
+                signature: signature.as_ref().to_vec(),
+                signature_scheme: signature.scheme().to_proto(),
+            }
+        };
+        let receipt = QuerySessionReceipt {
+            tx_id: vec![1; 32],
+            session_id: info.id,
+            query_count: info.query_count,
+            credits: Some(Units {
+                utype: 0,
+                amount: 100,
+            }),
+            block_height: 2,
+            client_addr: client_addr.to_vec(),
+            node_addr: node_addr.to_vec(),
+        };
+        let response = sign_receipt(&receipt);
+        let verified = verify_query_session_receipt(&response).unwrap();
+        assert_eq!(receipt, verified);
+        assert_eq!(info.query_count, verified.query_count);
+        assert_eq!(info.id, verified.session_id);
+        // a tampered receipt
+        let mut tampered = response.clone();
+        tampered.receipt = QuerySessionReceipt {
+            query_count: 1,
+            ..receipt.clone()
+        }
+        .encode_to_vec();
+        assert!(verify_query_session_receipt(&tampered).is_err());
+        // a receipt signed by another node
+        let other = sign_receipt(&QuerySessionReceipt {
+            node_addr: client_addr.to_vec(),
+            ..receipt
+        });
+        assert!(verify_query_session_receipt(&other).is_err());
+    }
+
+    #[tokio::test]
+    async fn it_read_cached_values_until_put() {
+        // nothing listens on the port, so only the cached values can be read
+        let rpc_endpoint = Endpoint::new("http://127.0.0.1:1".to_string()).unwrap();
+        let client = Arc::new(StorageNodeClient::new(rpc_endpoint.connect_lazy()));
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let mut sdk = StoreSDK::new(client.clone(), Box::new(signer))
+            .with_read_cache(16, Duration::from_secs(60));
+        let cache = sdk.read_cache().unwrap();
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let msdk = MutationSDK::new(client, Box::new(signer)).with_read_cache(cache.clone());
+        let ns = "ns1".as_bytes();
+        let kv = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "v1".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        cache.put(ns, &kv);
+        let values = sdk
+            .batch_get(ns, vec![kv.key.clone()], "no_session")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec![kv.clone()], values.values);
+        let mutation = Mutation {
+            ns: ns.to_vec(),
+            kv_pairs: vec![kv.clone()],
+            nonce: 1,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 0,
+        };
+        // the key is dropped even if the node is unreachable
+        assert!(msdk.submit_mutation(&mutation).await.is_err());
+        assert!(sdk
+            .batch_get(ns, vec![kv.key.clone()], "no_session")
+            .await
+            .is_err());
+        cache.put(ns, &kv);
+        sdk.clear_cache();
+        assert!(cache.is_empty());
+    }
+
     #[tokio::test]
     async fn it_get_bills() {
         let nonce = get_a_random_nonce();
@@ -353,17 +1194,21 @@ mod tests {
         let mclient = client.clone();
         {
             let (_, signer) = sdk_test::gen_ed25519_signer();
-            let msdk = MutationSDK::new(mclient, signer);
+            let msdk = MutationSDK::new(mclient, Box::new(signer));
             let kv = KvPair {
                 key: format!("kkkkk_tt{}", 1).as_bytes().to_vec(),
                 value: format!("vkalue_tt{}", 1).as_bytes().to_vec(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             };
             let mutation = Mutation {
                 ns: "my_twitter".as_bytes().to_vec(),
                 kv_pairs: vec![kv],
                 nonce,
-                chain_id: ChainId::MainNet.into(),
+                chain_id: ChainId::DevNet.into(),
                 chain_role: ChainRole::StorageShardChain.into(),
                 gas_price: None,
                 gas: 10,
@@ -374,7 +1219,7 @@ mod tests {
             std::thread::sleep(ten_millis);
         }
         let (_, signer) = sdk_test::gen_ed25519_signer();
-        let mut sdk = StoreSDK::new(client, signer);
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
         let res = sdk.open_session().await;
         assert!(res.is_ok());
         let session_info = res.unwrap();
@@ -399,27 +1244,39 @@ mod tests {
         let mclient = client.clone();
         let ns_vec = "my_data".as_bytes().to_vec();
         let (_, signer) = sdk_test::gen_ed25519_signer();
-        let msdk = MutationSDK::new(mclient, signer);
+        let msdk = MutationSDK::new(mclient, Box::new(signer));
         let k1 = KvPair {
             key: "k1".as_bytes().to_vec(),
             value: "v1".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let k2 = KvPair {
             key: "k2".as_bytes().to_vec(),
             value: "v2".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let k3 = KvPair {
             key: "k3".as_bytes().to_vec(),
             value: "v3".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let mutation = Mutation {
             ns: ns_vec.clone(),
             kv_pairs: vec![k1, k2, k3],
             nonce,
-            chain_id: ChainId::MainNet.into(),
+            chain_id: ChainId::DevNet.into(),
             chain_role: ChainRole::StorageShardChain.into(),
             gas_price: None,
             gas: 10,
@@ -429,7 +1286,7 @@ mod tests {
         let two_sec = time::Duration::from_millis(2000);
         std::thread::sleep(two_sec);
         let (_, signer) = sdk_test::gen_ed25519_signer();
-        let mut sdk = StoreSDK::new(client, signer);
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
         let res = sdk.open_session().await;
         assert!(res.is_ok());
         let session_info = res.unwrap();
@@ -448,6 +1305,128 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn count_keys_of_missing_ns() {
+        let ep = "http://127.0.0.1:26659";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
+        let ns = format!("ns{}", get_a_random_nonce() % 100000);
+        let result = sdk.count_keys(ns.as_bytes()).await;
+        assert!(result.is_ok(), "{}", result.err().unwrap());
+        let response = result.unwrap();
+        assert_eq!(0, response.key_count);
+        assert_eq!(0, response.total_bytes);
+    }
+
+    #[tokio::test]
+    async fn batch_get_after_tx_reads_the_write() {
+        let nonce = get_a_random_nonce();
+        let ep = "http://127.0.0.1:26659";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let msdk = MutationSDK::new(client.clone(), Box::new(signer));
+        let ns_vec = "my_twitter".as_bytes().to_vec();
+        let key_vec = format!("ryw_key{nonce}").as_bytes().to_vec();
+        let value_vec = format!("ryw_value{nonce}").as_bytes().to_vec();
+        let mutation = Mutation {
+            ns: ns_vec.clone(),
+            kv_pairs: vec![KvPair {
+                key: key_vec.clone(),
+                value: value_vec.clone(),
+                action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
+            }],
+            nonce,
+            chain_id: ChainId::DevNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        };
+        let result = msdk.submit_mutation(&mutation).await;
+        assert!(result.is_ok(), "{}", result.err().unwrap());
+        let tx_id = result.unwrap();
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
+        let token = sdk.keep_session().await.unwrap();
+        // no sleep before reading, the read waits for the tx
+        let values = sdk
+            .batch_get_after_tx(
+                &ns_vec,
+                vec![key_vec.clone()],
+                &token,
+                &tx_id,
+                time::Duration::from_secs(10),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(1, values.values.len());
+        assert_eq!(value_vec, values.values[0].value);
+        let height = sdk
+            .wait_for_tx(&tx_id, time::Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(height > 0);
+    }
+
+    #[tokio::test]
+    async fn wait_for_missing_tx_times_out() {
+        let ep = "http://127.0.0.1:26659";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let sdk = StoreSDK::new(client, Box::new(signer));
+        let tx_id = TxId::from("missing tx".as_bytes());
+        let result = sdk
+            .wait_for_tx(&tx_id, time::Duration::from_millis(500))
+            .await;
+        assert_eq!(Code::DeadlineExceeded, result.err().unwrap().code());
+    }
+
+    #[tokio::test]
+    async fn subscribe_requires_ws_url() {
+        let ep = "http://127.0.0.1:26659";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let sdk = StoreSDK::new(client, Box::new(signer));
+        assert!(sdk.subscribe_blocks().is_err());
+        assert!(sdk.subscribe_mutations(None).is_err());
+        let sdk = sdk.with_ws_url("ws://127.0.0.1:26657/websocket").unwrap();
+        assert!(sdk.subscribe_mutations(Some("ns1".as_bytes())).is_ok());
+    }
+
+    #[tokio::test]
+    async fn batch_get_account_with_missing_address() {
+        let ep = "http://127.0.0.1:26659";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (addr, signer) = sdk_test::gen_ed25519_signer();
+        let (missing_addr, _) = sdk_test::gen_ed25519_signer();
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
+        // open a session to make sure the account of addr exists
+        let res = sdk.open_session().await;
+        assert!(res.is_ok());
+        std::thread::sleep(time::Duration::from_millis(2000));
+        let result = sdk.batch_get_account(&[missing_addr, addr]).await;
+        assert!(result.is_ok(), "{}", result.err().unwrap());
+        let accounts = result.unwrap();
+        assert_eq!(2, accounts.len());
+        assert!(accounts[0].is_none());
+        assert!(accounts[1].is_some());
+    }
+
     #[tokio::test]
     async fn close_session_happy_path() {
         let nonce = get_a_random_nonce();
@@ -461,17 +1440,21 @@ mod tests {
         let ns_vec = "my_twitter".as_bytes().to_vec();
         {
             let (_, signer) = sdk_test::gen_ed25519_signer();
-            let msdk = MutationSDK::new(mclient, signer);
+            let msdk = MutationSDK::new(mclient, Box::new(signer));
             let kv = KvPair {
                 key: key_vec.clone(),
                 value: value_vec.clone(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             };
             let mutation = Mutation {
                 ns: ns_vec.clone(),
                 kv_pairs: vec![kv],
                 nonce,
-                chain_id: ChainId::MainNet.into(),
+                chain_id: ChainId::DevNet.into(),
                 chain_role: ChainRole::StorageShardChain.into(),
                 gas_price: None,
                 gas: 10,
@@ -482,7 +1465,7 @@ mod tests {
             std::thread::sleep(two_sec);
         }
         let (addr, signer) = sdk_test::gen_ed25519_signer();
-        let mut sdk = StoreSDK::new(client, signer);
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
         let res = sdk.open_session().await;
         assert!(res.is_ok());
         let session_info = res.unwrap();
@@ -531,17 +1514,21 @@ mod tests {
         let ns_vec = "my_twitter".as_bytes().to_vec();
         {
             let (_, signer) = sdk_test::gen_ed25519_signer();
-            let msdk = MutationSDK::new(mclient, signer);
+            let msdk = MutationSDK::new(mclient, Box::new(signer));
             let kv = KvPair {
                 key: key_vec.clone(),
                 value: value_vec.clone(),
                 action: MutationAction::InsertKv.into(),
+                ns: vec![],
+                expected_value: vec![],
+                ttl: 0,
+                compressed: false,
             };
             let mutation = Mutation {
                 ns: ns_vec.clone(),
                 kv_pairs: vec![kv],
                 nonce,
-                chain_id: ChainId::MainNet.into(),
+                chain_id: ChainId::DevNet.into(),
                 chain_role: ChainRole::StorageShardChain.into(),
                 gas_price: None,
                 gas: 10,
@@ -553,7 +1540,7 @@ mod tests {
         }
 
         let (_, signer) = sdk_test::gen_ed25519_signer();
-        let mut sdk = StoreSDK::new(client, signer);
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
         let res = sdk.open_session().await;
         assert!(res.is_ok());
         let session_info = res.unwrap();
@@ -581,6 +1568,19 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn record_query_without_session() {
+        let ep = "http://127.0.0.1:26659";
+        let rpc_endpoint = Endpoint::new(ep.to_string()).unwrap();
+        let channel = rpc_endpoint.connect_lazy();
+        let client = Arc::new(StorageNodeClient::new(channel));
+        let (_, signer) = sdk_test::gen_ed25519_signer();
+        let mut sdk = StoreSDK::new(client, Box::new(signer));
+        let res = sdk.record_query("no_such_token", 1);
+        assert!(res.is_err());
+        assert_eq!(tonic::Code::NotFound, res.err().unwrap().code());
+    }
+
     #[tokio::test]
     async fn open_session_replay_attach() {
         let ep = "http://127.0.0.1:26659";
@@ -12,18 +12,27 @@ pub fn verify_query_session(query_session: &QuerySession) -> Result<(AccountId,
         ) {
             Ok(client_account) => {
                 match CloseSessionPayload::decode(query_session.client_query_session.as_ref()) {
-                    Ok(client_query_session) => {
-                        if check_query_session_info(
-                            &node_query_session_info,
-                            &client_query_session.session_info.as_ref().unwrap(),
-                        ) {
-                            Ok((client_account, node_query_session_info.clone()))
-                        } else {
-                            Err(DB3Error::QuerySessionVerifyError(format!(
-                                "node query count and client query count inconsistent"
-                            )))
+                    Ok(client_query_session) => match client_query_session.session_info.as_ref() {
+                        Some(client_query_session_info) => {
+                            if check_query_session_info(
+                                &node_query_session_info,
+                                client_query_session_info,
+                            ) {
+                                Ok((client_account, node_query_session_info.clone()))
+                            } else {
+                                Err(DB3Error::QuerySessionVerifyError(format!(
+                                    "node query session {}:{} and client query session {}:{} inconsistent",
+                                    node_query_session_info.id,
+                                    node_query_session_info.query_count,
+                                    client_query_session_info.id,
+                                    client_query_session_info.query_count
+                                )))
+                            }
                         }
-                    }
+                        None => Err(DB3Error::QuerySessionVerifyError(format!(
+                            "client query session info is none"
+                        ))),
+                    },
                     Err(e) => Err(DB3Error::VerifyFailed(format!(
                         "invalid client query session info {}",
                         e
@@ -38,11 +47,16 @@ pub fn verify_query_session(query_session: &QuerySession) -> Result<(AccountId,
     }
 }
 
+///
+/// the node is paid by the query count it reports, so the report must be the
+/// same session the client signed and must not claim more queries than the client did
+///
 pub fn check_query_session_info(
     node_query_session: &QuerySessionInfo,
     client_query_session: &QuerySessionInfo,
 ) -> bool {
-    node_query_session.query_count == client_query_session.query_count
+    node_query_session.id == client_query_session.id
+        && node_query_session.query_count == client_query_session.query_count
 }
 
 #[cfg(test)]
@@ -128,4 +142,68 @@ mod tests {
         assert!(res.is_err());
         Ok(())
     }
+
+    fn build_query_session(
+        client_query_session_info: QuerySessionInfo,
+        node_query_session_info: QuerySessionInfo,
+    ) -> Result<QuerySession> {
+        let client_query_session = CloseSessionPayload {
+            session_info: Some(client_query_session_info),
+            session_token: "DummyToken".to_string(),
+        };
+        let kp = get_a_static_keypair();
+        let mut buf = BytesMut::with_capacity(1024 * 8);
+        client_query_session.encode(&mut buf).unwrap();
+        let buf = buf.freeze();
+        let signer = Db3MultiSchemeSigner::new(kp);
+        let signature_raw = signer.sign(buf.as_ref())?;
+        Ok(QuerySession {
+            nonce: 1,
+            chain_id: ChainId::MainNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            node_query_session_info: Some(node_query_session_info),
+            client_query_session: buf.as_ref().to_vec().to_owned(),
+            client_signature: signature_raw.as_ref().to_vec().to_owned(),
+        })
+    }
+
+    #[test]
+    fn test_verify_node_over_report() -> Result<()> {
+        let start_time = Utc::now().timestamp();
+        let client_query_session_info = QuerySessionInfo {
+            id: 1,
+            start_time,
+            query_count: 10,
+        };
+        let node_query_session_info = QuerySessionInfo {
+            id: 1,
+            start_time,
+            query_count: 11,
+        };
+        let query_session =
+            build_query_session(client_query_session_info, node_query_session_info)?;
+        let res = verify_query_session(&query_session);
+        assert!(res.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_session_id_mismatch() -> Result<()> {
+        let start_time = Utc::now().timestamp();
+        let client_query_session_info = QuerySessionInfo {
+            id: 1,
+            start_time,
+            query_count: 10,
+        };
+        let node_query_session_info = QuerySessionInfo {
+            id: 2,
+            start_time,
+            query_count: 10,
+        };
+        let query_session =
+            build_query_session(client_query_session_info, node_query_session_info)?;
+        let res = verify_query_session(&query_session);
+        assert!(res.is_err());
+        Ok(())
+    }
 }
@@ -120,6 +120,14 @@ impl SessionPool {
         self.session_pool.len()
     }
 
+    /// the number of sessions still running, the expired ones are marked blocked
+    pub fn get_running_session_count(&mut self) -> usize {
+        self.session_pool
+            .values_mut()
+            .filter(|v| v.check_session_running())
+            .count()
+    }
+
     pub fn get_last_token(&self) -> Option<String> {
         if let Some(k) = self.session_pool.keys().next() {
             Some(k.to_string())
@@ -134,6 +142,8 @@ pub struct SessionStore {
     token_account_map: HashMap<String, DB3Address>,
     open_session_headers: HashSet<String>,
     sid: i32,
+    // the max open sessions of all the accounts, 0 means no limit
+    max_open_sessions: usize,
 }
 
 impl SessionStore {
@@ -143,9 +153,35 @@ impl SessionStore {
             token_account_map: HashMap::new(),
             open_session_headers: HashSet::new(),
             sid: 0,
+            max_open_sessions: 0,
         }
     }
 
+    ///
+    /// limit the sessions running at the same time, the new sessions are rejected at the
+    /// limit until some sessions are closed or expire. 0 disables the limit
+    ///
+    pub fn with_max_open_sessions(mut self, max_open_sessions: usize) -> Self {
+        self.max_open_sessions = max_open_sessions;
+        self
+    }
+
+    ///
+    /// the number of sessions still running. the blocked and stopped sessions wait in the
+    /// pools to be settled but don't count, so an abandoned session frees its slot once it
+    /// expires
+    ///
+    pub fn get_open_session_count(&mut self) -> usize {
+        self.session_pools
+            .values_mut()
+            .map(|pool| pool.get_running_session_count())
+            .sum()
+    }
+
+    pub fn is_full(&mut self) -> bool {
+        self.max_open_sessions > 0 && self.get_open_session_count() >= self.max_open_sessions
+    }
+
     fn gen_token(&self) -> String {
         Uuid::new_v4().to_string()
     }
@@ -182,6 +218,12 @@ impl SessionStore {
         if self.is_session_header_exit(header) {
             return Err(format!("Session HEADER {} already exist", header));
         }
+        if self.is_full() {
+            return Err(format!(
+                "Fail to create new session since open sessions reach limit {}",
+                self.max_open_sessions
+            ));
+        }
         self.sid += 1;
         let token = self.generate_unique_token().map_err(|e| e)?;
         match self.session_pools.get_mut(&addr) {
@@ -383,6 +425,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn add_session_exceed_max_open_sessions() {
+        let mut sess_store = SessionStore::new().with_max_open_sessions(2);
+        let addr = gen_address();
+        let other = DB3Address::from(&[1; 20]);
+        let ts = Utc::now().timestamp();
+        let (token, _) = sess_store
+            .add_new_session(&Uuid::new_v4().to_string(), ts, addr)
+            .unwrap();
+        assert!(sess_store
+            .add_new_session(&Uuid::new_v4().to_string(), ts, other)
+            .is_ok());
+        assert!(sess_store.is_full());
+        // the limit counts the sessions of all the accounts
+        let res = sess_store.add_new_session(&Uuid::new_v4().to_string(), ts, addr);
+        assert_eq!(
+            "Fail to create new session since open sessions reach limit 2",
+            res.err().unwrap()
+        );
+        assert_eq!(2, sess_store.get_open_session_count());
+        // a closed session frees the slot
+        assert!(sess_store.remove_session(&token).is_ok());
+        assert!(!sess_store.is_full());
+        assert!(sess_store
+            .add_new_session(&Uuid::new_v4().to_string(), ts, addr)
+            .is_ok());
+        assert_eq!(2, sess_store.get_open_session_count());
+    }
+
+    #[test]
+    fn add_session_after_abandoned_sessions() {
+        let mut sess_store = SessionStore::new().with_max_open_sessions(2);
+        let addr = gen_address();
+        let ts = Utc::now().timestamp();
+        let (token1, _) = sess_store
+            .add_new_session(&Uuid::new_v4().to_string(), ts, addr)
+            .unwrap();
+        let (token2, _) = sess_store
+            .add_new_session(&Uuid::new_v4().to_string(), ts, addr)
+            .unwrap();
+        assert!(sess_store.is_full());
+        // the session using up its queries is blocked and never closed by the client
+        sess_store
+            .get_session_mut(&token1)
+            .unwrap()
+            .increase_query(DEFAULT_SESSION_QUERY_LIMIT);
+        assert_eq!(1, sess_store.get_open_session_count());
+        // the stopped session is waiting to be settled
+        sess_store.get_session_mut(&token2).unwrap().close_session();
+        assert_eq!(0, sess_store.get_open_session_count());
+        assert!(!sess_store.is_full());
+        assert!(sess_store
+            .add_new_session(&Uuid::new_v4().to_string(), ts, addr)
+            .is_ok());
+        assert!(sess_store.is_session_exist(&token1));
+        assert_eq!(1, sess_store.get_open_session_count());
+    }
+
     #[test]
     fn get_session() {
         let mut sess_store = SessionStore::new();
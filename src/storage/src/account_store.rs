@@ -25,6 +25,9 @@ use merkdb::{Merk, Op};
 use prost::Message;
 use std::pin::Pin;
 
+/// the max nonce window, the seen nonces of the window are kept in a u64 bitmap
+pub const MAX_NONCE_WINDOW: u64 = 64;
+
 pub struct AccountStore {}
 
 impl AccountStore {
@@ -49,18 +52,61 @@ impl AccountStore {
         Ok(())
     }
 
-    pub fn get_account(db: Pin<&Merk>, addr: &DB3Address) -> Result<Account> {
+    ///
+    /// get the account of the address, none is returned if the account has never been written
+    ///
+    pub fn try_get_account(db: Pin<&Merk>, addr: &DB3Address) -> Result<Option<Account>> {
         let key = AccountKey(*addr);
         let encoded_key = key.encode()?;
         //TODO verify the result
         let values = db
             .get(encoded_key.as_ref())
             .map_err(|e| DB3Error::GetAccountError(format!("{}", e)))?;
-        if let Some(v) = values {
-            match Account::decode(v.as_ref()) {
-                Ok(a) => Ok(a),
+        match values {
+            Some(v) => match Account::decode(v.as_ref()) {
+                Ok(a) => Ok(Some(a)),
                 Err(e) => Err(DB3Error::GetAccountError(format!("{}", e))),
-            }
+            },
+            None => Ok(None),
+        }
+    }
+
+    ///
+    /// record the nonce of the account if it's greater than nonce - window and has not been
+    /// seen, the nonce of the account is the greatest one applied. the window 0 accepts any nonce
+    ///
+    pub fn accept_nonce(account: &mut Account, nonce: u64, window: u64) -> Result<()> {
+        if window == 0 {
+            return Ok(());
+        }
+        let window = window.min(MAX_NONCE_WINDOW);
+        if nonce > account.nonce {
+            let shift = nonce - account.nonce;
+            account.seen_nonces = if shift >= MAX_NONCE_WINDOW {
+                1
+            } else {
+                (account.seen_nonces << shift) | 1
+            };
+            account.nonce = nonce;
+            return Ok(());
+        }
+        let offset = account.nonce - nonce;
+        if offset >= window {
+            return Err(DB3Error::StaleNonceError {
+                nonce,
+                floor: account.nonce - window,
+            });
+        }
+        if account.seen_nonces & (1 << offset) != 0 {
+            return Err(DB3Error::InvalidNonceError(nonce));
+        }
+        account.seen_nonces |= 1 << offset;
+        Ok(())
+    }
+
+    pub fn get_account(db: Pin<&Merk>, addr: &DB3Address) -> Result<Account> {
+        if let Some(a) = Self::try_get_account(db, addr)? {
+            Ok(a)
         } else {
             //TODO assign 10 db3 credits
             Ok(Account {
@@ -77,6 +123,7 @@ impl AccountStore {
                 }),
                 nonce: 0,
                 bill_next_id: 0,
+                seen_nonces: 0,
             })
         }
     }
@@ -116,6 +163,7 @@ mod tests {
             }),
             nonce: 10,
             bill_next_id: 10,
+            seen_nonces: 0,
         };
         let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
         let result = AccountStore::apply(db_m, &addr, &account);
@@ -128,4 +176,57 @@ mod tests {
             assert!(false);
         }
     }
+
+    #[test]
+    fn it_try_get_missing_account() {
+        let tmp_dir_path = TempDir::new("try_get_account").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let db = Box::pin(merk);
+        assert_eq!(
+            None,
+            AccountStore::try_get_account(db.as_ref(), &addr).unwrap()
+        );
+        // the missing account gets the default credits
+        let account = AccountStore::get_account(db.as_ref(), &addr).unwrap();
+        assert_eq!(0, account.total_mutation_count);
+    }
+
+    #[test]
+    fn it_accept_nonce_in_window() {
+        let tmp_dir_path = TempDir::new("accept_nonce").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let db = Box::pin(merk);
+        let mut account = AccountStore::get_account(db.as_ref(), &gen_address()).unwrap();
+        assert!(AccountStore::accept_nonce(&mut account, 10, 4).is_ok());
+        assert!(AccountStore::accept_nonce(&mut account, 12, 4).is_ok());
+        assert_eq!(12, account.nonce);
+        // the nonces delivered out of order are accepted in the window
+        assert!(AccountStore::accept_nonce(&mut account, 11, 4).is_ok());
+        assert!(AccountStore::accept_nonce(&mut account, 9, 4).is_ok());
+        assert_eq!(12, account.nonce);
+        // the old nonce out of the window
+        match AccountStore::accept_nonce(&mut account, 8, 4) {
+            Err(DB3Error::StaleNonceError { nonce, floor }) => {
+                assert_eq!(8, nonce);
+                assert_eq!(8, floor);
+            }
+            _ => assert!(false),
+        }
+        // the duplicated nonces
+        for nonce in [9, 10, 11, 12] {
+            match AccountStore::accept_nonce(&mut account, nonce, 4) {
+                Err(DB3Error::InvalidNonceError(n)) => assert_eq!(nonce, n),
+                _ => assert!(false),
+            }
+        }
+        // a big jump forgets the seen nonces out of the window
+        assert!(AccountStore::accept_nonce(&mut account, 100, 4).is_ok());
+        assert_eq!(1, account.seen_nonces);
+        assert!(AccountStore::accept_nonce(&mut account, 99, 4).is_ok());
+        assert!(AccountStore::accept_nonce(&mut account, 96, 4).is_err());
+        // the window 0 accepts any nonce
+        assert!(AccountStore::accept_nonce(&mut account, 1, 0).is_ok());
+        assert_eq!(100, account.nonce);
+    }
 }
@@ -27,12 +27,18 @@ impl CommitStore {
         Self {}
     }
 
-    pub fn apply(db: Pin<&mut Merk>, height: u64) -> Result<()> {
+    ///
+    /// record the height and the time of the committed block
+    ///
+    pub fn apply(db: Pin<&mut Merk>, height: u64, block_time: u64) -> Result<()> {
         let key = COMMIT_KEY.as_bytes().to_vec();
         let mut value: Vec<u8> = Vec::new();
         value
             .write_u64::<BigEndian>(height)
             .map_err(|e| DB3Error::KeyCodecError(format!("{}", e)))?;
+        value
+            .write_u64::<BigEndian>(block_time)
+            .map_err(|e| DB3Error::KeyCodecError(format!("{}", e)))?;
         let entries = vec![(key, Op::Put(value))];
         unsafe {
             Pin::get_unchecked_mut(db)
@@ -43,16 +49,31 @@ impl CommitStore {
     }
 
     pub fn get_applied_height(db: Pin<&Merk>) -> Result<Option<u64>> {
+        Ok(Self::get_applied_block(db)?.map(|(height, _)| height))
+    }
+
+    ///
+    /// get the height and the time of the last committed block, the time is 0 if the
+    /// block was committed before the time was recorded
+    ///
+    pub fn get_applied_block(db: Pin<&Merk>) -> Result<Option<(u64, u64)>> {
         let key = COMMIT_KEY.as_bytes().to_vec();
         let value = db
             .get(key.as_ref())
             .map_err(|e| DB3Error::GetCommitError(format!("{}", e)))?;
         if let Some(v) = value {
-            let ref_value: &[u8] = v.as_ref();
-            let height = (&ref_value[0..])
+            let mut reader: &[u8] = v.as_ref();
+            let height = reader
                 .read_u64::<BigEndian>()
                 .map_err(|e| DB3Error::GetCommitError(format!("{}", e)))?;
-            Ok(Some(height))
+            let block_time = if reader.is_empty() {
+                0
+            } else {
+                reader
+                    .read_u64::<BigEndian>()
+                    .map_err(|e| DB3Error::GetCommitError(format!("{}", e)))?
+            };
+            Ok(Some((height, block_time)))
         } else {
             // for the first time
             Ok(None)
@@ -73,7 +94,7 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.unwrap().is_none());
         let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
-        let result = CommitStore::apply(db_m, 1);
+        let result = CommitStore::apply(db_m, 1, 1000);
         assert!(result.is_ok());
         let result = CommitStore::get_applied_height(db.as_ref());
         if let Ok(Some(v)) = result {
@@ -81,5 +102,28 @@ mod tests {
         } else {
             assert!(false);
         }
+        assert_eq!(
+            Some((1, 1000)),
+            CommitStore::get_applied_block(db.as_ref()).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_read_commit_without_block_time() {
+        let tmp_dir_path = TempDir::new("commit_store_legacy").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let mut value: Vec<u8> = Vec::new();
+        value.write_u64::<BigEndian>(7).unwrap();
+        let entries = vec![(COMMIT_KEY.as_bytes().to_vec(), Op::Put(value))];
+        unsafe {
+            Pin::get_unchecked_mut(Pin::as_mut(&mut db))
+                .apply(&entries, &[])
+                .unwrap();
+        }
+        assert_eq!(
+            Some((7, 0)),
+            CommitStore::get_applied_block(db.as_ref()).unwrap()
+        );
     }
 }
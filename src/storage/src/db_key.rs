@@ -14,13 +14,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use db3_crypto::db3_address::{DB3Address, DB3_ADDRESS_LENGTH};
 use db3_crypto::id::{DbId, DBID_LENGTH};
 use db3_error::{DB3Error, Result};
+use std::ops::Range;
 
 /// /db/{db_address}
 pub struct DbKey(pub DbId);
 
 const DATABASE: &str = "/db/";
+const DATABASE_OWNER: &str = "/db_owner/";
 
 impl DbKey {
     ///
@@ -61,6 +64,52 @@ impl DbKey {
     }
 }
 
+///
+/// the key of the owner index of databases, the owner is followed by the database id
+///
+pub struct DbOwnerKey<'a>(pub &'a DB3Address, pub DbId);
+
+impl<'a> DbOwnerKey<'a> {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut encoded_key = Self::owner_prefix(self.0);
+        encoded_key.extend_from_slice(self.1.as_ref());
+        Ok(encoded_key)
+    }
+
+    ///
+    /// decode the database id of the key
+    ///
+    pub fn decode_id(data: &[u8]) -> Result<DbId> {
+        const KEY_TOTAL_LEN: usize = DATABASE_OWNER.len() + DB3_ADDRESS_LENGTH + DBID_LENGTH;
+        if data.len() != KEY_TOTAL_LEN {
+            return Err(DB3Error::KeyCodecError(
+                "the length of data is invalid".to_string(),
+            ));
+        }
+        let data_slice: &[u8; DBID_LENGTH] = &data[KEY_TOTAL_LEN - DBID_LENGTH..]
+            .try_into()
+            .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+        Ok(DbId::from(data_slice))
+    }
+
+    ///
+    /// the range of the keys of the databases created by the owner
+    ///
+    pub fn owner_range(owner: &DB3Address) -> Range<Vec<u8>> {
+        let start = Self::owner_prefix(owner);
+        let mut end = start.clone();
+        // longer than any database id
+        end.extend_from_slice(&[0xff; DBID_LENGTH + 1]);
+        Range { start, end }
+    }
+
+    fn owner_prefix(owner: &DB3Address) -> Vec<u8> {
+        let mut prefix = DATABASE_OWNER.as_bytes().to_vec();
+        prefix.extend_from_slice(owner.as_ref());
+        prefix
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,6 +136,23 @@ mod tests {
         assert!(key2.0 == id);
     }
 
+    #[test]
+    fn it_owner_key_in_owner_range() {
+        let addr = gen_address();
+        let id = DbId::from(addr);
+        let key = DbOwnerKey(&addr, id).encode().unwrap();
+        assert!(DbOwnerKey(&addr, id).encode().unwrap() == key);
+        assert!(DbOwnerKey::decode_id(key.as_ref()).unwrap() == id);
+        assert!(DbOwnerKey::owner_range(&addr).contains(&key));
+        let max_key = DbOwnerKey(&addr, DbId::max_id()).encode().unwrap();
+        assert!(DbOwnerKey::owner_range(&addr).contains(&max_key));
+        let other = DB3Address::from(&[0; DB3_ADDRESS_LENGTH]);
+        assert!(!DbOwnerKey::owner_range(&other).contains(&key));
+        // the owner index is out of the range of databases
+        let db_range = DbKey::min().encode().unwrap()..DbKey::max().encode().unwrap();
+        assert!(!db_range.contains(&key));
+    }
+
     #[test]
     fn it_cmp() -> Result<()> {
         let min = DbKey::min().encode()?;
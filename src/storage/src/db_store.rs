@@ -15,17 +15,21 @@
 // limitations under the License.
 //
 
-use super::db_key::DbKey;
+use super::db_key::{DbKey, DbOwnerKey};
+use super::ns_acl_store::NsAclStore;
 use bytes::BytesMut;
 use db3_crypto::{db3_address::DB3Address, id::DbId, id::TxId};
 use db3_error::{DB3Error, Result};
-use db3_proto::db3_database_proto::{Collection, Database};
+use db3_proto::db3_database_proto::{
+    field_schema::FieldType, index::index_field::ValueMode, Collection, Database, FieldSchema,
+    Index,
+};
 use db3_proto::db3_mutation_proto::{DatabaseAction, DatabaseMutation};
-use merkdb::proofs::{query::Query, Op as ProofOp};
+use merkdb::proofs::{query::Query, Node, Op as ProofOp};
 use merkdb::{BatchEntry, Merk, Op};
 use prost::Message;
-use std::collections::HashSet;
 use std::collections::LinkedList;
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use std::pin::Pin;
 use tracing::warn;
@@ -37,38 +41,175 @@ impl DbStore {
         Self {}
     }
 
+    ///
+    /// make sure the collection names in mutation are unique and do not exist in the database
+    ///
+    fn check_collection_names(
+        collections: &[Collection],
+        mutation: &DatabaseMutation,
+    ) -> Result<()> {
+        let mut collection_ids: HashSet<&str> =
+            HashSet::from_iter(collections.iter().map(|x| x.name.as_str()));
+        for collection_mutation in &mutation.collection_mutations {
+            if !collection_ids.insert(collection_mutation.collection_id.as_str()) {
+                return Err(DB3Error::ApplyDatabaseError(format!(
+                    "duplicated collection name {}",
+                    collection_mutation.collection_id
+                )));
+            }
+            Self::check_collection_indexes(
+                collection_mutation.collection_id.as_str(),
+                collection_mutation.index.as_ref(),
+            )?;
+            Self::check_collection_schema(
+                collection_mutation.collection_id.as_str(),
+                collection_mutation.index.as_ref(),
+                collection_mutation.schema.as_ref(),
+            )?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// make sure every field of the schema is indexed, declared once and has a known type
+    ///
+    fn check_collection_schema(
+        collection_id: &str,
+        indexes: &[Index],
+        schema: &[FieldSchema],
+    ) -> Result<()> {
+        let indexed_fields: HashSet<&str> = indexes
+            .iter()
+            .flat_map(|index| index.fields.iter().map(|f| f.field_path.as_str()))
+            .collect();
+        let mut schema_fields: HashSet<&str> = HashSet::new();
+        for field in schema {
+            let field_path = field.field_path.as_str();
+            if !indexed_fields.contains(field_path) {
+                return Err(DB3Error::ApplyDatabaseError(format!(
+                    "field {} in the schema of collection {} is not indexed",
+                    field_path, collection_id
+                )));
+            }
+            if !schema_fields.insert(field_path) {
+                return Err(DB3Error::ApplyDatabaseError(format!(
+                    "duplicated field {} in the schema of collection {}",
+                    field_path, collection_id
+                )));
+            }
+            match FieldType::from_i32(field.field_type) {
+                Some(FieldType::Unspecified) | None => {
+                    return Err(DB3Error::ApplyDatabaseError(format!(
+                        "no type of field {} in the schema of collection {}",
+                        field_path, collection_id
+                    )));
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// check the fields of the document declared by the schema of the collection, the field
+    /// path like a.b refers to a nested field and the missing or null fields are skipped
+    ///
+    pub fn check_document(collection: &Collection, document: &serde_json::Value) -> Result<()> {
+        if !document.is_object() {
+            return Err(DB3Error::InvalidDocumentError(format!(
+                "collection {}, a json object is required",
+                collection.name
+            )));
+        }
+        for field in &collection.schema {
+            let value = field
+                .field_path
+                .split('.')
+                .try_fold(document, |value, name| value.get(name));
+            let value = match value {
+                Some(value) if !value.is_null() => value,
+                _ => continue,
+            };
+            let matched = match FieldType::from_i32(field.field_type) {
+                Some(FieldType::String) => value.is_string(),
+                Some(FieldType::Int64) => value.is_i64(),
+                Some(FieldType::Double) => value.is_number(),
+                Some(FieldType::Bool) => value.is_boolean(),
+                Some(FieldType::Array) => value.is_array(),
+                Some(FieldType::Map) => value.is_object(),
+                Some(FieldType::Unspecified) | None => true,
+            };
+            if !matched {
+                return Err(DB3Error::InvalidDocumentError(format!(
+                    "collection {}, field {} should be {:?} but it's {}",
+                    collection.name,
+                    field.field_path,
+                    field.field_type(),
+                    value
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// make sure a field is not declared twice in an index and the indexes of a collection
+    /// do not declare the same field with conflicting options
+    ///
+    fn check_collection_indexes(collection_id: &str, indexes: &[Index]) -> Result<()> {
+        let mut field_modes: HashMap<&str, &Option<ValueMode>> = HashMap::new();
+        for index in indexes {
+            let mut index_fields: HashSet<&str> = HashSet::new();
+            for field in &index.fields {
+                let field_path = field.field_path.as_str();
+                if !index_fields.insert(field_path) {
+                    return Err(DB3Error::ApplyDatabaseError(format!(
+                        "duplicated field {} in index {} of collection {}",
+                        field_path, index.name, collection_id
+                    )));
+                }
+                match field_modes.get(field_path) {
+                    Some(mode) if *mode != &field.value_mode => {
+                        return Err(DB3Error::ApplyDatabaseError(format!(
+                            "conflicting index options for field {} of collection {}",
+                            field_path, collection_id
+                        )));
+                    }
+                    Some(_) => {}
+                    None => {
+                        field_modes.insert(field_path, &field.value_mode);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn update_database(
         old_db: &Database,
         mutation: &DatabaseMutation,
         tx_id: &TxId,
     ) -> Result<Database> {
-        let collection_ids: HashSet<String> =
-            HashSet::from_iter(old_db.collections.iter().map(|x| x.name.to_string()));
+        Self::check_collection_names(old_db.collections.as_ref(), mutation)?;
         let new_collections: Vec<Collection> = mutation
             .collection_mutations
             .iter()
-            .filter(|x| !collection_ids.contains(&x.collection_id))
             .map(|x| Collection {
                 name: x.collection_id.to_string(),
                 index_list: x.index.to_vec(),
+                schema: x.schema.to_vec(),
             })
             .collect();
-        if new_collections.len() != mutation.collection_mutations.len() {
-            Err(DB3Error::ApplyDatabaseError(
-                "duplicated collection names".to_string(),
-            ))
-        } else {
-            let mut collections = old_db.collections.to_vec();
-            collections.extend_from_slice(new_collections.as_ref());
-            let mut tx_list = old_db.tx.to_vec();
-            tx_list.push(tx_id.as_ref().to_vec());
-            Ok(Database {
-                address: old_db.address.to_vec(),
-                sender: old_db.sender.to_vec(),
-                tx: tx_list,
-                collections,
-            })
-        }
+        let mut collections = old_db.collections.to_vec();
+        collections.extend_from_slice(new_collections.as_ref());
+        let mut tx_list = old_db.tx.to_vec();
+        tx_list.push(tx_id.as_ref().to_vec());
+        Ok(Database {
+            address: old_db.address.to_vec(),
+            sender: old_db.sender.to_vec(),
+            tx: tx_list,
+            collections,
+        })
     }
 
     fn new_database(
@@ -76,23 +217,24 @@ impl DbStore {
         sender: &DB3Address,
         txid: &TxId,
         mutation: &DatabaseMutation,
-    ) -> Database {
-        //TODO check the duplicated collection id
+    ) -> Result<Database> {
+        Self::check_collection_names(&[], mutation)?;
         let collections: Vec<Collection> = mutation
             .collection_mutations
             .iter()
             .map(move |x| Collection {
                 name: x.collection_id.to_string(),
                 index_list: x.index.to_vec(),
+                schema: x.schema.to_vec(),
             })
             .collect();
 
-        Database {
+        Ok(Database {
             address: id.as_ref().to_vec(),
             sender: sender.as_ref().to_vec(),
             tx: vec![txid.as_ref().to_vec()],
             collections,
-        }
+        })
     }
 
     fn convert(
@@ -101,8 +243,8 @@ impl DbStore {
         tx: &TxId,
         mutation: &DatabaseMutation,
     ) -> Result<(BatchEntry, usize)> {
-        let dbid = DbId::try_from((sender, nonce))?;
-        let db = Self::new_database(&dbid, sender, tx, mutation);
+        let dbid = DbId::derive(sender, nonce)?;
+        let db = Self::new_database(&dbid, sender, tx, mutation)?;
         Self::encode_database(dbid, &db)
     }
 
@@ -134,6 +276,10 @@ impl DbStore {
         let mut entries: Vec<BatchEntry> = Vec::new();
         let (batch_entry, _) = Self::convert(sender, nonce, tx, mutation)?;
         entries.push(batch_entry);
+        // index the database by its owner, the value is unused
+        let owner_key = DbOwnerKey(sender, DbId::derive(sender, nonce)?).encode()?;
+        entries.push((owner_key, Op::Put(vec![1])));
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
         unsafe {
             Pin::get_unchecked_mut(db)
                 .apply(&entries, &[])
@@ -194,6 +340,19 @@ impl DbStore {
                 Self::create_database(db, sender, nonce, tx, mutation)
             }
             Some(DatabaseAction::AddCollection) => Self::add_collection(db, sender, tx, mutation),
+            Some(action @ (DatabaseAction::GrantNs | DatabaseAction::RevokeNs)) => {
+                match &mutation.ns_grant {
+                    Some(ns_grant) => NsAclStore::apply_grant(
+                        db,
+                        sender,
+                        ns_grant,
+                        action == DatabaseAction::GrantNs,
+                    ),
+                    None => Err(DB3Error::ApplyDatabaseError(
+                        "no namespace and grantee for the grant".to_string(),
+                    )),
+                }
+            }
             None => Ok(()),
         }
     }
@@ -215,6 +374,24 @@ impl DbStore {
         }
     }
 
+    ///
+    /// the ids of the databases created by the owner in the order of ids
+    ///
+    pub fn get_database_ids_by_owner(db: Pin<&Merk>, owner: &DB3Address) -> Result<Vec<DbId>> {
+        let mut query = Query::new();
+        query.insert_range(DbOwnerKey::owner_range(owner));
+        let ops = db
+            .execute_query(query)
+            .map_err(|e| DB3Error::QueryDatabaseError(format!("{e}")))?;
+        let mut ids: Vec<DbId> = Vec::new();
+        for op in ops {
+            if let ProofOp::Push(Node::KV(k, _)) = op {
+                ids.push(DbOwnerKey::decode_id(k.as_ref())?);
+            }
+        }
+        Ok(ids)
+    }
+
     pub fn get_databases(db: Pin<&Merk>) -> Result<LinkedList<ProofOp>> {
         let start_key = DbKey::min();
         let end_key = DbKey::max();
@@ -234,6 +411,7 @@ impl DbStore {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use db3_crypto::db3_address::DB3_ADDRESS_LENGTH;
     use db3_crypto::key_derive;
     use db3_crypto::signature_scheme::SignatureScheme;
     use db3_proto::db3_database_proto::{
@@ -266,6 +444,7 @@ mod tests {
         let index_mutation = CollectionMutation {
             index: vec![index],
             collection_id: "collection1".to_string(),
+            schema: vec![],
         };
 
         let dm = DatabaseMutation {
@@ -273,6 +452,7 @@ mod tests {
             collection_mutations: vec![index_mutation],
             db_address: vec![],
             action: DatabaseAction::CreateDb.into(),
+            ns_grant: None,
         };
         let json_data = serde_json::to_string(&dm).unwrap();
         println!("{json_data}");
@@ -295,4 +475,234 @@ mod tests {
             assert!(false);
         }
     }
+
+    fn build_add_collection_mutation(db_id: &DbId, names: &[&str]) -> DatabaseMutation {
+        let collection_mutations: Vec<CollectionMutation> = names
+            .iter()
+            .map(|name| CollectionMutation {
+                index: vec![],
+                collection_id: name.to_string(),
+                schema: vec![],
+            })
+            .collect();
+        DatabaseMutation {
+            meta: None,
+            collection_mutations,
+            db_address: db_id.as_ref().to_vec(),
+            action: DatabaseAction::AddCollection.into(),
+            ns_grant: None,
+        }
+    }
+
+    #[test]
+    fn it_derive_the_assigned_db_id() {
+        let tmp_dir_path = TempDir::new("db_store_test").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let nonce = 1677000000;
+        let db_id = DbId::derive(&addr, nonce).unwrap();
+        assert!(DbStore::get_database(db.as_ref(), &db_id)
+            .unwrap()
+            .is_none());
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(
+            db_m,
+            &addr,
+            nonce,
+            &TxId::zero(),
+            &build_database_mutation(),
+        );
+        assert!(result.is_ok());
+        let database = DbStore::get_database(db.as_ref(), &db_id).unwrap().unwrap();
+        assert_eq!(db_id.as_ref(), database.address.as_slice());
+        // another nonce derives another address
+        assert!(DbId::derive(&addr, nonce + 1).unwrap() != db_id);
+    }
+
+    #[test]
+    fn it_list_databases_by_owner() {
+        let tmp_dir_path = TempDir::new("db_store_test").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        assert!(DbStore::get_database_ids_by_owner(db.as_ref(), &addr)
+            .unwrap()
+            .is_empty());
+        for nonce in [1, 2] {
+            let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+            let result = DbStore::apply_mutation(
+                db_m,
+                &addr,
+                nonce,
+                &TxId::zero(),
+                &build_database_mutation(),
+            );
+            assert!(result.is_ok());
+        }
+        let other = DB3Address::from(&[9; DB3_ADDRESS_LENGTH]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result =
+            DbStore::apply_mutation(db_m, &other, 1, &TxId::zero(), &build_database_mutation());
+        assert!(result.is_ok());
+        let mut expected = vec![
+            DbId::derive(&addr, 1).unwrap(),
+            DbId::derive(&addr, 2).unwrap(),
+        ];
+        expected.sort();
+        let ids = DbStore::get_database_ids_by_owner(db.as_ref(), &addr).unwrap();
+        assert!(ids == expected);
+        let ids = DbStore::get_database_ids_by_owner(db.as_ref(), &other).unwrap();
+        assert!(ids == vec![DbId::derive(&other, 1).unwrap()]);
+        // the owner index is not listed as a database
+        assert_eq!(3, DbStore::get_databases(db.as_ref()).unwrap().len());
+    }
+
+    #[test]
+    fn it_reject_duplicated_collection() {
+        let tmp_dir_path = TempDir::new("db_store_test").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let db_mutation = build_database_mutation();
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 1, &TxId::zero(), &db_mutation);
+        assert!(result.is_ok());
+        let db_id = DbId::try_from((&addr, 1)).unwrap();
+        // add a new collection
+        let mutation = build_add_collection_mutation(&db_id, &["collection2"]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 2, &TxId::zero(), &mutation);
+        assert!(result.is_ok());
+        // add the collection again
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 3, &TxId::zero(), &mutation);
+        assert!(result.is_err());
+        // add two collections with the same name in one mutation
+        let mutation = build_add_collection_mutation(&db_id, &["collection3", "collection3"]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 4, &TxId::zero(), &mutation);
+        assert!(result.is_err());
+        // add a collection with a different name
+        let mutation = build_add_collection_mutation(&db_id, &["collection3"]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 5, &TxId::zero(), &mutation);
+        assert!(result.is_ok());
+        let database = DbStore::get_database(db.as_ref(), &db_id).unwrap().unwrap();
+        let names: Vec<&str> = database
+            .collections
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(vec!["collection1", "collection2", "collection3"], names);
+    }
+
+    fn build_index(name: &str, fields: &[(&str, Order)]) -> Index {
+        Index {
+            name: name.to_string(),
+            fields: fields
+                .iter()
+                .map(|(path, order)| IndexField {
+                    field_path: path.to_string(),
+                    value_mode: Some(ValueMode::Order(*order as i32)),
+                })
+                .collect(),
+        }
+    }
+
+    fn build_create_db_mutation(index: Vec<Index>) -> DatabaseMutation {
+        DatabaseMutation {
+            meta: None,
+            collection_mutations: vec![CollectionMutation {
+                index,
+                collection_id: "collection1".to_string(),
+                schema: vec![],
+            }],
+            db_address: vec![],
+            action: DatabaseAction::CreateDb.into(),
+            ns_grant: None,
+        }
+    }
+
+    #[test]
+    fn it_reject_duplicated_index_definition() {
+        let tmp_dir_path = TempDir::new("db_store_test").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        // the same field twice in one index
+        let mutation = build_create_db_mutation(vec![build_index(
+            "idx1",
+            &[("name", Order::Ascending), ("name", Order::Ascending)],
+        )]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 1, &TxId::zero(), &mutation);
+        assert!(result.is_err());
+        // the same field with conflicting orders in two indexes
+        let mutation = build_create_db_mutation(vec![
+            build_index("idx1", &[("name", Order::Ascending)]),
+            build_index("idx2", &[("name", Order::Descending)]),
+        ]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 2, &TxId::zero(), &mutation);
+        assert!(result.is_err());
+        let db_id = DbId::try_from((&addr, 2)).unwrap();
+        assert!(DbStore::get_database(db.as_ref(), &db_id)
+            .unwrap()
+            .is_none());
+        // the same field with the same order in a composite index is fine
+        let mutation = build_create_db_mutation(vec![
+            build_index("idx1", &[("name", Order::Ascending)]),
+            build_index(
+                "idx2",
+                &[("name", Order::Ascending), ("age", Order::Descending)],
+            ),
+        ]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        let result = DbStore::apply_mutation(db_m, &addr, 3, &TxId::zero(), &mutation);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn it_check_document_against_schema() {
+        let tmp_dir_path = TempDir::new("db_store_test").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let field = |path: &str, field_type: FieldType| FieldSchema {
+            field_path: path.to_string(),
+            field_type: field_type.into(),
+        };
+        let mut mutation = build_create_db_mutation(vec![build_index(
+            "idx1",
+            &[
+                ("name", Order::Ascending),
+                ("profile.age", Order::Ascending),
+            ],
+        )]);
+        // the field of the schema must be indexed
+        mutation.collection_mutations[0].schema = vec![field("city", FieldType::String)];
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        assert!(DbStore::apply_mutation(db_m, &addr, 1, &TxId::zero(), &mutation).is_err());
+        mutation.collection_mutations[0].schema = vec![
+            field("name", FieldType::String),
+            field("profile.age", FieldType::Int64),
+        ];
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        assert!(DbStore::apply_mutation(db_m, &addr, 2, &TxId::zero(), &mutation).is_ok());
+        let db_id = DbId::try_from((&addr, 2)).unwrap();
+        let database = DbStore::get_database(db.as_ref(), &db_id).unwrap().unwrap();
+        let collection = &database.collections[0];
+        assert_eq!(2, collection.schema.len());
+        let conforming = serde_json::json!({"name": "alice", "profile": {"age": 18}, "city": 1});
+        assert!(DbStore::check_document(collection, &conforming).is_ok());
+        // the missing fields are not checked
+        assert!(DbStore::check_document(collection, &serde_json::json!({"name": "bob"})).is_ok());
+        let non_conforming = serde_json::json!({"name": "alice", "profile": {"age": "18"}});
+        match DbStore::check_document(collection, &non_conforming) {
+            Err(DB3Error::InvalidDocumentError(msg)) => assert!(msg.contains("profile.age")),
+            _ => assert!(false),
+        }
+        assert!(DbStore::check_document(collection, &serde_json::json!([1])).is_err());
+    }
 }
@@ -17,6 +17,8 @@
 use db3_crypto::db3_address::{DB3Address, DB3_ADDRESS_LENGTH};
 use db3_error::{DB3Error, Result};
 const NAMESPACE: &str = "_NS_";
+const META: &str = "_META_";
+const NS_STAT: &str = "_NS_STAT_";
 const MAX_USE_KEY_LEN: usize = 128 * 4;
 const MAX_NAMESPACE_LEN: usize = 16;
 const MIN_KEY_TOTAL_LEN: usize = DB3_ADDRESS_LENGTH + NAMESPACE.len();
@@ -42,6 +44,37 @@ impl<'a> Key<'a> {
         Ok(encoded_key)
     }
 
+    ///
+    /// encode the key which keeps the metadata of the value, e.g. the last modified height
+    ///
+    pub fn encode_meta_key(&self) -> Result<Vec<u8>> {
+        let mut encoded_key = META.as_bytes().to_vec();
+        encoded_key.extend_from_slice(self.encode()?.as_ref());
+        Ok(encoded_key)
+    }
+
+    ///
+    /// encode the key which keeps the key count and total bytes of a namespace
+    ///
+    pub fn encode_ns_stat_key(addr: &DB3Address, ns: &[u8]) -> Result<Vec<u8>> {
+        let mut encoded_key = NS_STAT.as_bytes().to_vec();
+        encoded_key.extend_from_slice(Key(*addr, ns, &[]).encode()?.as_ref());
+        Ok(encoded_key)
+    }
+
+    ///
+    /// the range of the stat keys of all namespaces owned by the address
+    ///
+    pub fn ns_stat_range(addr: &DB3Address) -> Result<std::ops::Range<Vec<u8>>> {
+        let start = Self::encode_ns_stat_key(addr, &[])?;
+        let mut end = start.to_vec();
+        // the last byte of the prefix is not 0xff, so the end is the next prefix
+        if let Some(last) = end.last_mut() {
+            *last += 1;
+        }
+        Ok(start..end)
+    }
+
     ///
     /// decode the key
     ///
@@ -103,4 +136,32 @@ mod tests {
         assert!(key_encoded1.cmp(&key_encoded2) == std::cmp::Ordering::Less);
         Ok(())
     }
+
+    #[test]
+    fn it_meta_key_not_in_ns_range() -> Result<()> {
+        let addr = gen_address();
+        let key = Key(addr, "ns1".as_bytes(), "k1".as_bytes());
+        let meta_key = key.encode_meta_key()?;
+        assert!(!meta_key.starts_with(addr.as_ref()));
+        assert!(meta_key.ends_with(key.encode()?.as_ref()));
+        assert!(meta_key != Key::encode_ns_stat_key(&addr, "ns1".as_bytes())?);
+        Ok(())
+    }
+
+    #[test]
+    fn it_ns_stat_key_not_in_ns_range() -> Result<()> {
+        let addr = gen_address();
+        let stat_key = Key::encode_ns_stat_key(&addr, "ns1".as_bytes())?;
+        assert!(!stat_key.starts_with(addr.as_ref()));
+        let other_key = Key::encode_ns_stat_key(&addr, "ns2".as_bytes())?;
+        assert!(stat_key != other_key);
+        let range = Key::ns_stat_range(&addr)?;
+        assert!(range.contains(&stat_key));
+        assert!(range.contains(&other_key));
+        let long_ns = [0xff; MAX_NAMESPACE_LEN];
+        assert!(range.contains(&Key::encode_ns_stat_key(&addr, &long_ns)?));
+        let other_addr = DB3Address::from(&[0xff; DB3_ADDRESS_LENGTH]);
+        assert!(!range.contains(&Key::encode_ns_stat_key(&other_addr, "ns1".as_bytes())?));
+        Ok(())
+    }
 }
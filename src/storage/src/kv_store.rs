@@ -16,37 +16,219 @@
 //
 
 use super::key::Key;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use db3_base::value_codec;
 use db3_crypto::db3_address::DB3Address;
 use db3_error::{DB3Error, Result};
 use db3_proto::db3_base_proto::Units;
 use db3_proto::db3_mutation_proto::{KvPair, Mutation, MutationAction};
 use db3_proto::db3_node_proto::{BatchGetKey, RangeKey};
 use db3_types::cost;
-use merkdb::proofs::{query::Query, Op as ProofOp};
+use merkdb::proofs::{query::Query, Node, Op as ProofOp};
 use merkdb::{BatchEntry, Merk, Op};
+use prost::Message;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::LinkedList;
 use std::pin::Pin;
 use tracing::warn;
 
+pub const DEFAULT_MAX_PAIRS_PER_MUTATION: usize = 1024;
+pub const DEFAULT_MAX_KEY_LEN: usize = 512;
+pub const DEFAULT_MAX_VALUE_LEN: usize = 64 * 1024;
+pub const DEFAULT_MAX_MUTATION_BYTES: usize = 1024 * 1024;
+
+/// the limits on the size of a mutation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MutationLimits {
+    pub max_pairs: usize,
+    pub max_key_len: usize,
+    pub max_value_len: usize,
+    /// the max length of the encoded mutation
+    pub max_total_bytes: usize,
+}
+
+impl Default for MutationLimits {
+    fn default() -> Self {
+        Self {
+            max_pairs: DEFAULT_MAX_PAIRS_PER_MUTATION,
+            max_key_len: DEFAULT_MAX_KEY_LEN,
+            max_value_len: DEFAULT_MAX_VALUE_LEN,
+            max_total_bytes: DEFAULT_MAX_MUTATION_BYTES,
+        }
+    }
+}
+
+///
+/// the max bytes of keys and values stored in a namespace of an account, 0 means no limit.
+/// the quota of a namespace overrides the default one
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NsQuotas {
+    pub default_bytes: u64,
+    pub overrides: HashMap<Vec<u8>, u64>,
+}
+
+impl NsQuotas {
+    pub fn quota(&self, ns: &[u8]) -> u64 {
+        self.overrides
+            .get(ns)
+            .copied()
+            .unwrap_or(self.default_bytes)
+    }
+}
+
+const META_FLAG_COMPRESSED: u8 = 1;
+const META_LEN: usize = 17;
+
+///
+/// the metadata of a value kept in one record next to it, so a put writes the value and
+/// the record only
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueMeta {
+    /// the block height the value was last written at
+    pub height: u64,
+    /// the block time the value expires at, 0 if the value never expires
+    pub expiry: u64,
+    /// the value is kept gzip compressed in the db
+    pub compressed: bool,
+}
+
+impl ValueMeta {
+    /// the height, the expiry and the flags
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut encoded: Vec<u8> = Vec::with_capacity(META_LEN);
+        encoded
+            .write_u64::<BigEndian>(self.height)
+            .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+        encoded
+            .write_u64::<BigEndian>(self.expiry)
+            .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+        let flags = if self.compressed {
+            META_FLAG_COMPRESSED
+        } else {
+            0
+        };
+        encoded.push(flags);
+        Ok(encoded)
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != META_LEN {
+            return Err(DB3Error::QueryKvError(format!(
+                "bad metadata of {} bytes",
+                data.len()
+            )));
+        }
+        let mut reader = &data[0..];
+        let height = reader
+            .read_u64::<BigEndian>()
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        let expiry = reader
+            .read_u64::<BigEndian>()
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        Ok(Self {
+            height,
+            expiry,
+            compressed: reader[0] & META_FLAG_COMPRESSED != 0,
+        })
+    }
+
+    /// the expired value is kept in the db and taken as a missing one by the readers
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiry > 0 && self.expiry <= now
+    }
+}
+
 pub struct KvStore {}
 impl KvStore {
     pub fn new() -> Self {
         Self {}
     }
 
-    pub fn is_valid(mutation: &Mutation) -> bool {
+    /// the namespace of the pair, the one of the mutation if the pair has none
+    pub fn pair_ns<'a>(mutation: &'a Mutation, kv: &'a KvPair) -> &'a [u8] {
+        if kv.ns.is_empty() {
+            mutation.ns.as_ref()
+        } else {
+            kv.ns.as_ref()
+        }
+    }
+
+    /// the sorted distinct namespaces written by the mutation
+    pub fn namespaces(mutation: &Mutation) -> Vec<&[u8]> {
+        let mut namespaces: Vec<&[u8]> = mutation
+            .kv_pairs
+            .iter()
+            .map(|kv| Self::pair_ns(mutation, kv))
+            .collect();
+        namespaces.sort();
+        namespaces.dedup();
+        namespaces
+    }
+
+    pub fn is_valid(mutation: &Mutation, limits: &MutationLimits) -> bool {
         if mutation.ns.len() <= 0 {
             warn!("empty namespace");
             return false;
         }
-        let mut keys: HashSet<&[u8]> = HashSet::new();
+        if mutation.kv_pairs.len() > limits.max_pairs {
+            warn!(
+                "the {} kv pairs exceed the limit {}",
+                mutation.kv_pairs.len(),
+                limits.max_pairs
+            );
+            return false;
+        }
+        let total_bytes = mutation.encoded_len();
+        if total_bytes > limits.max_total_bytes {
+            warn!(
+                "the mutation of {} bytes exceeds the limit {}",
+                total_bytes, limits.max_total_bytes
+            );
+            return false;
+        }
+        let mut keys: HashSet<(&[u8], &[u8])> = HashSet::new();
         for ref kv in &mutation.kv_pairs {
-            if keys.contains(&kv.key.as_ref()) {
+            if kv.key.len() > limits.max_key_len || kv.value.len() > limits.max_value_len {
+                warn!(
+                    "the key of {} bytes or the value of {} bytes exceeds the limit",
+                    kv.key.len(),
+                    kv.value.len()
+                );
+                return false;
+            }
+            // the limit of value applies to the uncompressed one
+            if kv.compressed {
+                if let Err(e) = value_codec::decompress(kv.value.as_ref(), limits.max_value_len) {
+                    warn!("invalid compressed value for {e}");
+                    return false;
+                }
+            }
+            let ns_key = (Self::pair_ns(mutation, kv), kv.key.as_ref());
+            if keys.contains(&ns_key) {
                 warn!("deplicated key");
                 return false;
             }
-            keys.insert(kv.key.as_ref());
+            keys.insert(ns_key);
+            match MutationAction::from_i32(kv.action) {
+                Some(MutationAction::InsertKv) | Some(MutationAction::DeleteKv) => {}
+                Some(MutationAction::CompareAndSwapKv) => {
+                    if kv.expected_value.len() > limits.max_value_len {
+                        warn!(
+                            "the expected value of {} bytes exceeds the limit",
+                            kv.expected_value.len()
+                        );
+                        return false;
+                    }
+                }
+                _ => {
+                    warn!("invalid action type {} in kv pairs", kv.action);
+                    return false;
+                }
+            }
         }
         return true;
     }
@@ -56,35 +238,203 @@ impl KvStore {
         let encoded_key = key.encode()?;
         let action = MutationAction::from_i32(kp.action);
         match action {
-            Some(MutationAction::InsertKv) => {
+            Some(MutationAction::InsertKv) | Some(MutationAction::CompareAndSwapKv) => {
                 //TODO avoid copying operation
                 let total_in_bytes = encoded_key.len() + kp.value.len();
                 Ok(((encoded_key, Op::Put(kp.value.to_vec())), total_in_bytes))
             }
             Some(MutationAction::DeleteKv) => Ok(((encoded_key, Op::Delete), 0)),
-            Some(MutationAction::Nonce) => todo!(),
-            None => Err(DB3Error::ApplyMutationError(
+            Some(MutationAction::Nonce) | None => Err(DB3Error::ApplyMutationError(
                 "invalid action type".to_string(),
             )),
         }
     }
 
+    ///
+    /// the value stored for the key, the compressed one is decompressed and the one
+    /// expired at the block time `now` is taken as a missing one
+    ///
+    fn current_value(
+        stored: Option<Vec<u8>>,
+        meta: Option<&ValueMeta>,
+        now: u64,
+    ) -> Result<Option<Vec<u8>>> {
+        match (stored, meta) {
+            (_, Some(meta)) if meta.is_expired(now) => Ok(None),
+            (Some(v), Some(meta)) if meta.compressed => {
+                let value = value_codec::decompress(v.as_ref(), usize::MAX)
+                    .map_err(DB3Error::QueryKvError)?;
+                Ok(Some(value))
+            }
+            (stored, _) => Ok(stored),
+        }
+    }
+
+    ///
+    /// the current value must equal the expected value of a compare and swap pair,
+    /// a missing key only matches an empty expected value. the expected value is
+    /// compared with the uncompressed current value
+    ///
+    fn check_expected_value(kv: &KvPair, current: Option<&[u8]>) -> Result<()> {
+        let current = current.unwrap_or_default();
+        if current == kv.expected_value.as_slice() {
+            return Ok(());
+        }
+        Err(DB3Error::CompareAndSwapConflict(format!(
+            "the key {} has {} bytes but {} bytes are expected",
+            String::from_utf8_lossy(kv.key.as_ref()),
+            current.len(),
+            kv.expected_value.len()
+        )))
+    }
+
+    ///
+    /// check the compare and swap pairs of a mutation against the current values
+    /// without writing anything, the value expired at the block time `now` is taken as
+    /// a missing one
+    ///
+    pub fn check_compare_and_swap(
+        db: Pin<&Merk>,
+        addr: &DB3Address,
+        mutation: &Mutation,
+        now: u64,
+    ) -> Result<()> {
+        for kv in &mutation.kv_pairs {
+            if kv.action != MutationAction::CompareAndSwapKv as i32 {
+                continue;
+            }
+            let ns = Self::pair_ns(mutation, kv);
+            let encoded_key = Key(*addr, ns, kv.key.as_ref()).encode()?;
+            let stored = db
+                .get(encoded_key.as_ref())
+                .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+            let meta = Self::get_meta(db.as_ref(), addr, ns, kv.key.as_ref())?;
+            let current = Self::current_value(stored, meta.as_ref(), now)?;
+            Self::check_expected_value(kv, current.as_deref())?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// apply all the kv pairs of a mutation as a whole. the pairs are converted
+    /// before touching the db and written in a single merk batch, so a mutation
+    /// mixing insert and delete actions either takes effect entirely or not at all.
+    /// the `height` is recorded as the last modified height of every inserted key and
+    /// the key count and total bytes of every namespace are updated in the same batch.
+    /// the inserted pair with a ttl expires at `block_time` + ttl and a compare and
+    /// swap conflict fails the whole mutation
+    ///
     pub fn apply(
         db: Pin<&mut Merk>,
         addr: &DB3Address,
         mutation: &Mutation,
+        height: u64,
+        block_time: u64,
+    ) -> Result<(Units, usize)> {
+        Self::apply_with_quotas(db, addr, mutation, height, block_time, &NsQuotas::default())
+    }
+
+    ///
+    /// apply the mutation as apply does, but reject it as a whole if it grows a namespace
+    /// over its quota. the mutation shrinking a namespace is always accepted
+    ///
+    pub fn apply_with_quotas(
+        db: Pin<&mut Merk>,
+        addr: &DB3Address,
+        mutation: &Mutation,
+        height: u64,
+        block_time: u64,
+        quotas: &NsQuotas,
     ) -> Result<(Units, usize)> {
-        let ns = mutation.ns.as_ref();
-        //TODO avoid copying operation
-        let mut ordered_kv_pairs = mutation.kv_pairs.to_vec();
-        ordered_kv_pairs.sort_by(|a, b| a.key.cmp(&b.key));
         let mut entries: Vec<BatchEntry> = Vec::new();
         let mut total_in_bytes: usize = 0;
-        for kv in ordered_kv_pairs {
-            let (batch_entry, bytes) = Self::convert(&kv, addr, ns)?;
+        // the key count and total bytes of the namespaces written by the mutation
+        let mut ns_stats: BTreeMap<&[u8], (u64, u64)> = BTreeMap::new();
+        for ns in Self::namespaces(mutation) {
+            ns_stats.insert(ns, Self::get_ns_stat(db.as_ref(), addr, ns)?);
+        }
+        for kv in &mutation.kv_pairs {
+            let ns = Self::pair_ns(mutation, kv);
+            let (key_count, key_bytes) = ns_stats
+                .get_mut(ns)
+                .ok_or_else(|| DB3Error::ApplyMutationError("no stat of namespace".to_string()))?;
+            let (batch_entry, bytes) = Self::convert(kv, addr, ns)?;
+            let meta_key = Key(*addr, ns, kv.key.as_ref()).encode_meta_key()?;
+            let old_value = db
+                .get(batch_entry.0.as_ref())
+                .map_err(|e| DB3Error::ApplyMutationError(format!("{e}")))?;
+            let old_meta = Self::get_meta(db.as_ref(), addr, ns, kv.key.as_ref())?;
+            if kv.action == MutationAction::CompareAndSwapKv as i32 {
+                let current =
+                    Self::current_value(old_value.clone(), old_meta.as_ref(), block_time)?;
+                Self::check_expected_value(kv, current.as_deref())?;
+            }
+            // the keys written before the stat was recorded are not counted, so saturate
+            match &old_value {
+                Some(v) => {
+                    *key_bytes = key_bytes.saturating_sub((kv.key.len() + v.len()) as u64);
+                    if let Op::Delete = batch_entry.1 {
+                        *key_count = key_count.saturating_sub(1);
+                    }
+                }
+                None => {
+                    if let Op::Put(_) = batch_entry.1 {
+                        *key_count += 1;
+                    }
+                }
+            }
+            match batch_entry.1 {
+                Op::Put(_) => {
+                    *key_bytes += (kv.key.len() + kv.value.len()) as u64;
+                    // the pair written without a ttl never expires
+                    let expiry = if kv.ttl > 0 {
+                        block_time.saturating_add(kv.ttl)
+                    } else {
+                        0
+                    };
+                    let meta = ValueMeta {
+                        height,
+                        expiry,
+                        compressed: kv.compressed,
+                    };
+                    entries.push((meta_key, Op::Put(meta.encode()?)));
+                }
+                _ => {
+                    if old_meta.is_some() {
+                        entries.push((meta_key, Op::Delete));
+                    }
+                }
+            }
             total_in_bytes += bytes;
             entries.push(batch_entry);
         }
+        for (ns, (_, key_bytes)) in ns_stats.iter() {
+            let quota = quotas.quota(ns);
+            if quota == 0 || *key_bytes <= quota {
+                continue;
+            }
+            let (_, old_key_bytes) = Self::get_ns_stat(db.as_ref(), addr, ns)?;
+            if *key_bytes > old_key_bytes {
+                return Err(DB3Error::QuotaExceededError(format!(
+                    "the namespace {} would store {} bytes over the quota {}",
+                    String::from_utf8_lossy(ns),
+                    key_bytes,
+                    quota
+                )));
+            }
+        }
+        for (ns, (key_count, key_bytes)) in ns_stats {
+            let mut encoded_stat: Vec<u8> = Vec::new();
+            encoded_stat
+                .write_u64::<BigEndian>(key_count)
+                .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+            encoded_stat
+                .write_u64::<BigEndian>(key_bytes)
+                .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+            entries.push((Key::encode_ns_stat_key(addr, ns)?, Op::Put(encoded_stat)));
+        }
+        // merk requires the entries of a batch to be sorted by key
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
         let gas = cost::estimate_gas(mutation);
         unsafe {
             Pin::get_unchecked_mut(db)
@@ -94,6 +444,77 @@ impl KvStore {
         Ok((gas, total_in_bytes))
     }
 
+    ///
+    /// get the metadata of the value of key, none if the key has no value
+    ///
+    pub fn get_meta(
+        db: Pin<&Merk>,
+        addr: &DB3Address,
+        ns: &[u8],
+        key: &[u8],
+    ) -> Result<Option<ValueMeta>> {
+        let meta_key = Key(*addr, ns, key).encode_meta_key()?;
+        let value = db
+            .get(meta_key.as_ref())
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        value.map(|v| ValueMeta::decode(v.as_ref())).transpose()
+    }
+
+    ///
+    /// get the key count and the total bytes of keys and values in a namespace,
+    /// a namespace without any key gets zero
+    ///
+    pub fn get_ns_stat(db: Pin<&Merk>, addr: &DB3Address, ns: &[u8]) -> Result<(u64, u64)> {
+        let stat_key = Key::encode_ns_stat_key(addr, ns)?;
+        let value = db
+            .get(stat_key.as_ref())
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        match value {
+            Some(v) => {
+                let mut reader = &v[0..];
+                let key_count = reader
+                    .read_u64::<BigEndian>()
+                    .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+                let key_bytes = reader
+                    .read_u64::<BigEndian>()
+                    .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+                Ok((key_count, key_bytes))
+            }
+            None => Ok((0, 0)),
+        }
+    }
+
+    ///
+    /// list the namespaces owned by the address with their key count and total bytes,
+    /// the namespaces without any key are skipped
+    ///
+    pub fn list_ns(db: Pin<&Merk>, addr: &DB3Address) -> Result<Vec<(Vec<u8>, u64, u64)>> {
+        let range = Key::ns_stat_range(addr)?;
+        let prefix_len = range.start.len();
+        let mut query = Query::new();
+        query.insert_range(range);
+        let ops = db
+            .execute_query(query)
+            .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+        let mut namespaces: Vec<(Vec<u8>, u64, u64)> = Vec::new();
+        for op in ops {
+            if let ProofOp::Push(Node::KV(k, v)) = op {
+                let mut reader = &v[0..];
+                let key_count = reader
+                    .read_u64::<BigEndian>()
+                    .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+                let key_bytes = reader
+                    .read_u64::<BigEndian>()
+                    .map_err(|e| DB3Error::QueryKvError(format!("{e}")))?;
+                if key_count == 0 && key_bytes == 0 {
+                    continue;
+                }
+                namespaces.push((k[prefix_len..].to_vec(), key_count, key_bytes));
+            }
+        }
+        Ok(namespaces)
+    }
+
     pub fn batch_get(
         db: Pin<&Merk>,
         addr: &DB3Address,
@@ -130,7 +551,19 @@ impl KvStore {
                 if range.start.cmp(&range.end) < std::cmp::Ordering::Less {
                     return Err(DB3Error::QueryKvError("bad range order".to_string()));
                 }
-                let start_key = Key(*addr, range_key.ns.as_ref(), range.start.as_ref()).encode()?;
+                // the smallest key after the cursor is the cursor followed by a zero byte
+                let mut resume_key = range_key.cursor.to_vec();
+                resume_key.push(0);
+                let start = if !range_key.cursor.is_empty() && resume_key > range.start {
+                    resume_key.as_ref()
+                } else {
+                    range.start.as_ref()
+                };
+                if !range_key.cursor.is_empty() && start >= range.end.as_slice() {
+                    // the cursor is at the end of the range
+                    return Ok(LinkedList::new());
+                }
+                let start_key = Key(*addr, range_key.ns.as_ref(), start).encode()?;
                 let end_key = Key(*addr, range_key.ns.as_ref(), range.end.as_ref()).encode()?;
                 let std_range = std::ops::Range {
                     start: start_key,
@@ -155,7 +588,6 @@ mod tests {
     use db3_proto::db3_base_proto::{ChainId, ChainRole};
     use db3_proto::db3_node_proto::Range as DB3Range;
 
-    use merkdb::proofs::Node;
     use std::boxed::Box;
     use tempdir::TempDir;
 
@@ -181,6 +613,8 @@ mod tests {
             ns: ns.as_bytes().to_vec(),
             range: Some(range),
             session_token: "token".to_string(),
+            limit: 0,
+            cursor: vec![],
         };
         let result = KvStore::get_range(db.as_ref(), &addr, &range_key);
         assert!(result.is_err());
@@ -196,16 +630,28 @@ mod tests {
             key: "k1".as_bytes().to_vec(),
             value: "value1".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let kv2 = KvPair {
             key: "k2".as_bytes().to_vec(),
             value: "value2".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let kv3 = KvPair {
             key: "k3".as_bytes().to_vec(),
             value: "value3".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let mutation = Mutation {
             ns: "my_twitter".as_bytes().to_vec(),
@@ -217,7 +663,7 @@ mod tests {
             gas: 10,
         };
         let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
-        let result = KvStore::apply(db_m, &addr, &mutation);
+        let result = KvStore::apply(db_m, &addr, &mutation, 1, 0);
         assert!(result.is_ok());
         let range = DB3Range {
             start: "k0".as_bytes().to_vec(),
@@ -228,6 +674,8 @@ mod tests {
             ns: ns.as_bytes().to_vec(),
             range: Some(range),
             session_token: "token".to_string(),
+            limit: 0,
+            cursor: vec![],
         };
         let result = KvStore::get_range(db.as_ref(), &addr, &range_key);
         if let Ok(r) = result {
@@ -254,6 +702,8 @@ mod tests {
             ns: ns.as_bytes().to_vec(),
             range: Some(all),
             session_token: "token".to_string(),
+            limit: 0,
+            cursor: vec![],
         };
         let result = KvStore::get_range(db.as_ref(), &addr, &range_key);
         if let Ok(r) = result {
@@ -284,6 +734,7 @@ mod tests {
             ns: "my_twitter".as_bytes().to_vec(),
             keys: vec![key],
             session_token: "MOCK_TOKEN".to_string(),
+            with_metadata: false,
         };
         let result = KvStore::batch_get(db.as_ref(), &addr, &keys);
         assert!(!result.is_ok());
@@ -299,11 +750,19 @@ mod tests {
             key: "k1".as_bytes().to_vec(),
             value: "value1".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let kv2 = KvPair {
             key: "k2".as_bytes().to_vec(),
             value: "value1".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let mutation = Mutation {
             ns: "my_twitter".as_bytes().to_vec(),
@@ -315,7 +774,7 @@ mod tests {
             gas: 10,
         };
         let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
-        let result = KvStore::apply(db_m, &addr, &mutation);
+        let result = KvStore::apply(db_m, &addr, &mutation, 1, 0);
         assert!(result.is_ok());
         let key = "k1".as_bytes().to_vec();
         let ns = "my_twitter";
@@ -323,6 +782,7 @@ mod tests {
             ns: ns.as_bytes().to_vec(),
             keys: vec![key.to_vec()],
             session_token: "MOCK_TOKEN".to_string(),
+            with_metadata: false,
         };
         let result = KvStore::batch_get(db.as_ref(), &addr, &keys);
         assert!(result.is_ok());
@@ -342,4 +802,697 @@ mod tests {
             assert!(false);
         }
     }
+
+    fn get_value(db: Pin<&Merk>, addr: &DB3Address, ns: &str, key: &str) -> Option<Vec<u8>> {
+        let keys = BatchGetKey {
+            ns: ns.as_bytes().to_vec(),
+            keys: vec![key.as_bytes().to_vec()],
+            session_token: "MOCK_TOKEN".to_string(),
+            with_metadata: false,
+        };
+        match KvStore::batch_get(db, addr, &keys) {
+            Ok(ops) => ops.into_iter().find_map(|op| match op {
+                ProofOp::Push(Node::KV(_, v)) => Some(v),
+                _ => None,
+            }),
+            Err(_) => None,
+        }
+    }
+
+    fn new_mutation(kv_pairs: Vec<KvPair>) -> Mutation {
+        Mutation {
+            ns: "my_twitter".as_bytes().to_vec(),
+            kv_pairs,
+            nonce: 1,
+            chain_id: ChainId::MainNet.into(),
+            chain_role: ChainRole::StorageShardChain.into(),
+            gas_price: None,
+            gas: 10,
+        }
+    }
+
+    #[test]
+    fn it_apply_mixed_actions() {
+        let tmp_dir_path = TempDir::new("mixed_actions").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value1".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let kv2 = KvPair {
+            key: "k2".as_bytes().to_vec(),
+            value: "value2".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv1, kv2]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        let del_kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: vec![],
+            action: MutationAction::DeleteKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let kv3 = KvPair {
+            key: "k3".as_bytes().to_vec(),
+            value: "value3".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv3, del_kv1]);
+        assert!(KvStore::is_valid(&mutation, &MutationLimits::default()));
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        assert_eq!(None, get_value(db.as_ref(), &addr, "my_twitter", "k1"));
+        assert_eq!(
+            Some("value2".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k2")
+        );
+        assert_eq!(
+            Some("value3".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k3")
+        );
+    }
+
+    #[test]
+    fn it_apply_mixed_actions_all_or_nothing() {
+        let tmp_dir_path = TempDir::new("mixed_actions_fail").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value1".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv1]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        let del_kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: vec![],
+            action: MutationAction::DeleteKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let kv2 = KvPair {
+            key: "k2".as_bytes().to_vec(),
+            value: "value2".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        // the last pair carries an unknown action and fails the whole batch
+        let bad_kv = KvPair {
+            key: "k3".as_bytes().to_vec(),
+            value: "value3".as_bytes().to_vec(),
+            action: 100,
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![del_kv1.clone(), kv2.clone(), bad_kv]);
+        assert!(!KvStore::is_valid(&mutation, &MutationLimits::default()));
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_err());
+        // the oversized key fails the whole batch too
+        let big_kv = KvPair {
+            key: vec![b'k'; 1024],
+            value: "value3".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![del_kv1, kv2, big_kv]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_err());
+        assert_eq!(
+            Some("value1".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k1")
+        );
+        assert_eq!(None, get_value(db.as_ref(), &addr, "my_twitter", "k2"));
+        assert_eq!(None, get_value(db.as_ref(), &addr, "my_twitter", "k3"));
+    }
+
+    #[test]
+    fn it_apply_multi_ns_mutation_all_or_nothing() {
+        let tmp_dir_path = TempDir::new("multi_ns").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value1".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let kv2 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value2".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: "other_ns".as_bytes().to_vec(),
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        // the same key in two namespaces is not duplicated
+        let mutation = new_mutation(vec![kv1.clone(), kv2.clone()]);
+        assert!(KvStore::is_valid(&mutation, &MutationLimits::default()));
+        assert_eq!(
+            vec!["my_twitter".as_bytes(), "other_ns".as_bytes()],
+            KvStore::namespaces(&mutation)
+        );
+        // the bad pair of the other namespace fails the pair of my_twitter too
+        let bad_kv = KvPair {
+            key: vec![b'k'; 1024],
+            value: "value3".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: "other_ns".as_bytes().to_vec(),
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv1.clone(), bad_kv]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_err());
+        assert_eq!(None, get_value(db.as_ref(), &addr, "my_twitter", "k1"));
+        assert_eq!(
+            (0, 0),
+            KvStore::get_ns_stat(db.as_ref(), &addr, "my_twitter".as_bytes()).unwrap()
+        );
+        let mutation = new_mutation(vec![kv1, kv2]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        assert_eq!(
+            Some("value1".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k1")
+        );
+        assert_eq!(
+            Some("value2".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "other_ns", "k1")
+        );
+        assert_eq!(
+            (1, 8),
+            KvStore::get_ns_stat(db.as_ref(), &addr, "other_ns".as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_check_uncompressed_value_len() {
+        let limits = MutationLimits {
+            max_value_len: 1024,
+            ..MutationLimits::default()
+        };
+        let value = vec![b'v'; 1024];
+        let kv = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: value_codec::compress(&value, 0).unwrap(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: true,
+        };
+        assert!(KvStore::is_valid(&new_mutation(vec![kv]), &limits));
+        // the compressed value is small but the uncompressed one exceeds the limit
+        let value = vec![b'v'; 1025];
+        let kv = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: value_codec::compress(&value, 0).unwrap(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: true,
+        };
+        assert!(kv.value.len() < 1024);
+        assert!(!KvStore::is_valid(&new_mutation(vec![kv]), &limits));
+        // the raw value is never taken as a compressed one
+        let kv = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: vec![0xdb, 0x3c, 0x01, 0x02],
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        assert!(KvStore::is_valid(&new_mutation(vec![kv.clone()]), &limits));
+        let kv = KvPair {
+            compressed: true,
+            ..kv
+        };
+        assert!(!KvStore::is_valid(&new_mutation(vec![kv]), &limits));
+    }
+
+    #[test]
+    fn it_record_compressed_flag() {
+        let tmp_dir_path = TempDir::new("compressed_flag").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let ns = "my_twitter".as_bytes();
+        let value = vec![b'v'; 2048];
+        let kv = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: value_codec::compress(&value, 0).unwrap(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: true,
+        };
+        let mutation = new_mutation(vec![kv]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        assert!(KvStore::apply(db_m, &addr, &mutation, 1, 0).is_ok());
+        let meta = KvStore::get_meta(db.as_ref(), &addr, ns, "k1".as_bytes()).unwrap();
+        assert!(meta.unwrap().compressed);
+        // the raw value overwriting the compressed one clears the flag
+        let kv = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: vec![0xdb, 0x3c, 0x01, 0x02],
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv]);
+        let db_m: Pin<&mut Merk> = Pin::as_mut(&mut db);
+        assert!(KvStore::apply(db_m, &addr, &mutation, 2, 0).is_ok());
+        let meta = KvStore::get_meta(db.as_ref(), &addr, ns, "k1".as_bytes()).unwrap();
+        assert!(!meta.unwrap().compressed);
+        assert_eq!(
+            Some(vec![0xdb, 0x3c, 0x01, 0x02]),
+            get_value(db.as_ref(), &addr, "my_twitter", "k1")
+        );
+    }
+
+    #[test]
+    fn it_encode_value_meta() {
+        let meta = ValueMeta {
+            height: 3,
+            expiry: 100,
+            compressed: true,
+        };
+        let encoded = meta.encode().unwrap();
+        assert_eq!(META_LEN, encoded.len());
+        assert_eq!(meta, ValueMeta::decode(encoded.as_ref()).unwrap());
+        assert!(!meta.is_expired(99));
+        assert!(meta.is_expired(100));
+        // the value without a ttl never expires
+        assert!(!ValueMeta::default().is_expired(u64::MAX));
+        assert!(ValueMeta::decode(&encoded[1..]).is_err());
+    }
+
+    #[test]
+    fn it_record_last_modified_height() {
+        let tmp_dir_path = TempDir::new("last_modified_height").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let ns = "my_twitter".as_bytes();
+        let kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value1".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv1]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        let meta = KvStore::get_meta(db.as_ref(), &addr, ns, "k1".as_bytes());
+        assert_eq!(Some(1), meta.unwrap().map(|m| m.height));
+        let kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value2".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv1]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 2, 0).is_ok());
+        let meta = KvStore::get_meta(db.as_ref(), &addr, ns, "k1".as_bytes());
+        assert_eq!(Some(2), meta.unwrap().map(|m| m.height));
+        let del_kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: vec![],
+            action: MutationAction::DeleteKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![del_kv1]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 3, 0).is_ok());
+        let meta = KvStore::get_meta(db.as_ref(), &addr, ns, "k1".as_bytes());
+        assert_eq!(None, meta.unwrap());
+    }
+
+    #[test]
+    fn it_count_keys_in_ns() {
+        let tmp_dir_path = TempDir::new("count_keys").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let ns = "my_twitter".as_bytes();
+        assert_eq!(
+            (0, 0),
+            KvStore::get_ns_stat(db.as_ref(), &addr, ns).unwrap()
+        );
+        let kv = |key: &str, value: &str, action: MutationAction| KvPair {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            action: action.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![
+            kv("k1", "value1", MutationAction::InsertKv),
+            kv("k2", "value2", MutationAction::InsertKv),
+        ]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        assert_eq!(
+            (2, 16),
+            KvStore::get_ns_stat(db.as_ref(), &addr, ns).unwrap()
+        );
+        // overwrite k1 and delete k2 and a missing key
+        let mutation = new_mutation(vec![
+            kv("k1", "v1", MutationAction::InsertKv),
+            kv("k2", "", MutationAction::DeleteKv),
+            kv("k3", "", MutationAction::DeleteKv),
+        ]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 2, 0).is_ok());
+        assert_eq!(
+            (1, 4),
+            KvStore::get_ns_stat(db.as_ref(), &addr, ns).unwrap()
+        );
+        let other_ns = "other_ns".as_bytes();
+        assert_eq!(
+            (0, 0),
+            KvStore::get_ns_stat(db.as_ref(), &addr, other_ns).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_list_ns() {
+        let tmp_dir_path = TempDir::new("list_ns").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        assert!(KvStore::list_ns(db.as_ref(), &addr).unwrap().is_empty());
+        let kv = |ns: &str, key: &str, action: MutationAction| KvPair {
+            key: key.as_bytes().to_vec(),
+            value: "v1".as_bytes().to_vec(),
+            action: action.into(),
+            ns: ns.as_bytes().to_vec(),
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![
+            kv("", "k1", MutationAction::InsertKv),
+            kv("ns2", "k1", MutationAction::InsertKv),
+            kv("ns2", "k2", MutationAction::InsertKv),
+        ]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        // the namespaces of another account are not listed
+        let other_addr = DB3Address::from(&[1; 20]);
+        let mutation = new_mutation(vec![kv("ns3", "k1", MutationAction::InsertKv)]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &other_addr, &mutation, 2, 0).is_ok());
+        assert_eq!(
+            vec![
+                ("my_twitter".as_bytes().to_vec(), 1, 4),
+                ("ns2".as_bytes().to_vec(), 2, 8),
+            ],
+            KvStore::list_ns(db.as_ref(), &addr).unwrap()
+        );
+        // the namespace without keys is skipped
+        let mutation = new_mutation(vec![kv("", "k1", MutationAction::DeleteKv)]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 3, 0).is_ok());
+        assert_eq!(
+            vec![("ns2".as_bytes().to_vec(), 2, 8)],
+            KvStore::list_ns(db.as_ref(), &addr).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_reject_oversized_mutation() {
+        let kv = |key: &str, value_len: usize| KvPair {
+            key: key.as_bytes().to_vec(),
+            value: vec![1; value_len],
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let limits = MutationLimits {
+            max_pairs: 2,
+            max_key_len: 4,
+            max_value_len: 16,
+            max_total_bytes: 128,
+        };
+        assert!(KvStore::is_valid(
+            &new_mutation(vec![kv("k1", 16), kv("k2", 4)]),
+            &limits
+        ));
+        assert!(!KvStore::is_valid(
+            &new_mutation(vec![kv("k1", 1), kv("k2", 1), kv("k3", 1)]),
+            &limits
+        ));
+        assert!(!KvStore::is_valid(
+            &new_mutation(vec![kv("key10", 1)]),
+            &limits
+        ));
+        assert!(!KvStore::is_valid(
+            &new_mutation(vec![kv("k1", 17)]),
+            &limits
+        ));
+        let limits = MutationLimits {
+            max_total_bytes: 32,
+            ..limits
+        };
+        assert!(!KvStore::is_valid(
+            &new_mutation(vec![kv("k1", 16), kv("k2", 16)]),
+            &limits
+        ));
+    }
+
+    #[test]
+    fn it_compare_and_swap() {
+        let tmp_dir_path = TempDir::new("compare_and_swap").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let cas = |key: &str, expected: &str, value: &str| KvPair {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            action: MutationAction::CompareAndSwapKv.into(),
+            ns: vec![],
+            expected_value: expected.as_bytes().to_vec(),
+            ttl: 0,
+            compressed: false,
+        };
+        // a missing key matches the empty expected value
+        let mutation = new_mutation(vec![cas("k1", "", "value1")]);
+        assert!(KvStore::is_valid(&mutation, &MutationLimits::default()));
+        assert!(KvStore::check_compare_and_swap(db.as_ref(), &addr, &mutation, 0).is_ok());
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        assert_eq!(
+            Some("value1".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k1")
+        );
+        let mutation = new_mutation(vec![cas("k1", "value1", "value2")]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 2, 0).is_ok());
+        assert_eq!(
+            Some("value2".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k1")
+        );
+        assert_eq!(
+            (1, 8),
+            KvStore::get_ns_stat(db.as_ref(), &addr, "my_twitter".as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_compare_and_swap_compressed_value() {
+        let tmp_dir_path = TempDir::new("compare_and_swap_compressed").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let value = "value1".repeat(400).as_bytes().to_vec();
+        let compressed = value_codec::compress(&value, 0).unwrap();
+        let kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: compressed.clone(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: true,
+        };
+        let mutation = new_mutation(vec![kv1]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        let cas = |expected: Vec<u8>| KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value2".as_bytes().to_vec(),
+            action: MutationAction::CompareAndSwapKv.into(),
+            ns: vec![],
+            expected_value: expected,
+            ttl: 0,
+            compressed: false,
+        };
+        // the stored compressed bytes are not the value the client has written
+        let mutation = new_mutation(vec![cas(compressed)]);
+        assert!(KvStore::check_compare_and_swap(db.as_ref(), &addr, &mutation, 0).is_err());
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 2, 0).is_err());
+        let mutation = new_mutation(vec![cas(value)]);
+        assert!(KvStore::check_compare_and_swap(db.as_ref(), &addr, &mutation, 0).is_ok());
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 2, 0).is_ok());
+        assert_eq!(
+            Some("value2".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k1")
+        );
+        let meta = KvStore::get_meta(db.as_ref(), &addr, "my_twitter".as_bytes(), "k1".as_bytes());
+        assert!(!meta.unwrap().unwrap().compressed);
+    }
+
+    #[test]
+    fn it_reject_compare_and_swap_conflict() {
+        let tmp_dir_path = TempDir::new("compare_and_swap_conflict").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let kv1 = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value1".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let mutation = new_mutation(vec![kv1]);
+        assert!(KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 1, 0).is_ok());
+        let stale_cas = KvPair {
+            key: "k1".as_bytes().to_vec(),
+            value: "value3".as_bytes().to_vec(),
+            action: MutationAction::CompareAndSwapKv.into(),
+            ns: vec![],
+            expected_value: "value0".as_bytes().to_vec(),
+            ttl: 0,
+            compressed: false,
+        };
+        let kv2 = KvPair {
+            key: "k2".as_bytes().to_vec(),
+            value: "value2".as_bytes().to_vec(),
+            action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        // the conflict fails the other pairs of the mutation too
+        let mutation = new_mutation(vec![kv2, stale_cas]);
+        assert!(KvStore::is_valid(&mutation, &MutationLimits::default()));
+        assert!(matches!(
+            KvStore::check_compare_and_swap(db.as_ref(), &addr, &mutation, 0),
+            Err(DB3Error::CompareAndSwapConflict(_))
+        ));
+        assert!(matches!(
+            KvStore::apply(Pin::as_mut(&mut db), &addr, &mutation, 2, 0),
+            Err(DB3Error::CompareAndSwapConflict(_))
+        ));
+        assert_eq!(
+            Some("value1".as_bytes().to_vec()),
+            get_value(db.as_ref(), &addr, "my_twitter", "k1")
+        );
+        assert_eq!(None, get_value(db.as_ref(), &addr, "my_twitter", "k2"));
+    }
+
+    #[test]
+    fn it_reject_mutation_over_ns_quota() {
+        let tmp_dir_path = TempDir::new("ns_quota").expect("create temp dir");
+        let addr = gen_address();
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let kv = |key: &str, value: &str, action: MutationAction| KvPair {
+            key: key.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            action: action.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
+        };
+        let quotas = NsQuotas {
+            default_bytes: 8,
+            overrides: HashMap::from([("my_twitter".as_bytes().to_vec(), 16)]),
+        };
+        assert_eq!(16, quotas.quota("my_twitter".as_bytes()));
+        assert_eq!(8, quotas.quota("other_ns".as_bytes()));
+        // fill the namespace to the quota
+        let mutation = new_mutation(vec![
+            kv("k1", "value1", MutationAction::InsertKv),
+            kv("k2", "value2", MutationAction::InsertKv),
+        ]);
+        assert!(
+            KvStore::apply_with_quotas(Pin::as_mut(&mut db), &addr, &mutation, 1, 0, &quotas)
+                .is_ok()
+        );
+        assert_eq!(
+            (2, 16),
+            KvStore::get_ns_stat(db.as_ref(), &addr, "my_twitter".as_bytes()).unwrap()
+        );
+        let mutation = new_mutation(vec![kv("k3", "v", MutationAction::InsertKv)]);
+        assert!(matches!(
+            KvStore::apply_with_quotas(Pin::as_mut(&mut db), &addr, &mutation, 2, 0, &quotas),
+            Err(DB3Error::QuotaExceededError(_))
+        ));
+        assert_eq!(None, get_value(db.as_ref(), &addr, "my_twitter", "k3"));
+        // the write freeing some bytes is accepted
+        let mutation = new_mutation(vec![
+            kv("k1", "", MutationAction::DeleteKv),
+            kv("k3", "v", MutationAction::InsertKv),
+        ]);
+        assert!(
+            KvStore::apply_with_quotas(Pin::as_mut(&mut db), &addr, &mutation, 3, 0, &quotas)
+                .is_ok()
+        );
+        assert_eq!(
+            (2, 11),
+            KvStore::get_ns_stat(db.as_ref(), &addr, "my_twitter".as_bytes()).unwrap()
+        );
+    }
 }
@@ -22,3 +22,5 @@ mod db_key;
 pub mod db_store;
 pub mod key;
 pub mod kv_store;
+pub mod ns_acl_store;
+pub mod tx_store;
@@ -0,0 +1,175 @@
+//
+// ns_acl_store.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use db3_crypto::{db3_address::DB3Address, id::AccountId};
+use db3_error::{DB3Error, Result};
+use db3_proto::db3_mutation_proto::NsGrant;
+use merkdb::{BatchEntry, Merk, Op};
+use std::pin::Pin;
+
+/// _NS_OWNER_ + ns
+const NS_OWNER: &str = "_NS_OWNER_";
+/// _NS_GRANT_ + len(ns) + ns + grantee
+const NS_GRANT: &str = "_NS_GRANT_";
+
+///
+/// the owner of a namespace is the account writing it first, the other accounts
+/// can write the namespace only if the owner grants them
+///
+pub struct NsAclStore {}
+
+impl NsAclStore {
+    fn encode_owner_key(ns: &[u8]) -> Vec<u8> {
+        let mut encoded_key = NS_OWNER.as_bytes().to_vec();
+        encoded_key.extend_from_slice(ns);
+        encoded_key
+    }
+
+    fn encode_grant_key(ns: &[u8], grantee: &DB3Address) -> Result<Vec<u8>> {
+        let ns_len = u8::try_from(ns.len()).map_err(|_| {
+            DB3Error::KeyCodecError("the length of namespace exceeds the limit".to_string())
+        })?;
+        let mut encoded_key = NS_GRANT.as_bytes().to_vec();
+        encoded_key.push(ns_len);
+        encoded_key.extend_from_slice(ns);
+        encoded_key.extend_from_slice(grantee.as_ref());
+        Ok(encoded_key)
+    }
+
+    pub fn get_owner(db: Pin<&Merk>, ns: &[u8]) -> Result<Option<DB3Address>> {
+        let value = db
+            .get(Self::encode_owner_key(ns).as_ref())
+            .map_err(|e| DB3Error::ApplyMutationError(format!("{e}")))?;
+        match value {
+            Some(v) => Ok(Some(DB3Address::try_from(v.as_ref())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_granted(db: Pin<&Merk>, ns: &[u8], addr: &DB3Address) -> Result<bool> {
+        let value = db
+            .get(Self::encode_grant_key(ns, addr)?.as_ref())
+            .map_err(|e| DB3Error::ApplyMutationError(format!("{e}")))?;
+        Ok(value.is_some())
+    }
+
+    ///
+    /// check the sender can write the namespace, true is returned if the namespace
+    /// has no owner and the sender should claim it
+    ///
+    pub fn check_write(db: Pin<&Merk>, ns: &[u8], sender: &DB3Address) -> Result<bool> {
+        match Self::get_owner(db, ns)? {
+            None => Ok(true),
+            Some(owner) if owner == *sender => Ok(false),
+            Some(_) => {
+                if Self::is_granted(db, ns, sender)? {
+                    Ok(false)
+                } else {
+                    Err(DB3Error::NsPermissionError(format!(
+                        "{} has no permission to write namespace {}",
+                        AccountId::new(*sender).to_hex(),
+                        String::from_utf8_lossy(ns)
+                    )))
+                }
+            }
+        }
+    }
+
+    pub fn claim(db: Pin<&mut Merk>, ns: &[u8], owner: &DB3Address) -> Result<()> {
+        let entries: Vec<BatchEntry> = vec![(Self::encode_owner_key(ns), Op::Put(owner.to_vec()))];
+        unsafe {
+            Pin::get_unchecked_mut(db)
+                .apply(&entries, &[])
+                .map_err(|e| DB3Error::ApplyMutationError(format!("{e}")))?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// grant or revoke the permission of writing the namespace, only the owner can do
+    /// it and the sender claims the namespace if it has no owner
+    ///
+    pub fn apply_grant(
+        db: Pin<&mut Merk>,
+        sender: &DB3Address,
+        ns_grant: &NsGrant,
+        grant: bool,
+    ) -> Result<()> {
+        let ns: &[u8] = ns_grant.ns.as_ref();
+        let grantee = DB3Address::try_from(ns_grant.grantee.as_ref())?;
+        let mut entries: Vec<BatchEntry> = Vec::new();
+        match Self::get_owner(db.as_ref(), ns)? {
+            Some(owner) if owner != *sender => {
+                return Err(DB3Error::NsPermissionError(format!(
+                    "{} is not the owner of namespace {}",
+                    AccountId::new(*sender).to_hex(),
+                    String::from_utf8_lossy(ns)
+                )));
+            }
+            Some(_) => {}
+            None => {
+                entries.push((Self::encode_owner_key(ns), Op::Put(sender.to_vec())));
+            }
+        }
+        let grant_key = Self::encode_grant_key(ns, &grantee)?;
+        if grant {
+            entries.push((grant_key, Op::Put(vec![1])));
+        } else if Self::is_granted(db.as_ref(), ns, &grantee)? {
+            entries.push((grant_key, Op::Delete));
+        }
+        // merk requires the entries of a batch to be sorted by key
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        unsafe {
+            Pin::get_unchecked_mut(db)
+                .apply(&entries, &[])
+                .map_err(|e| DB3Error::ApplyMutationError(format!("{e}")))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db3_crypto::db3_address::DB3_ADDRESS_LENGTH;
+    use std::boxed::Box;
+    use tempdir::TempDir;
+
+    #[test]
+    fn it_grant_and_revoke_ns() {
+        let tmp_dir_path = TempDir::new("ns_acl_store").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let owner = DB3Address::from(&[1; DB3_ADDRESS_LENGTH]);
+        let other = DB3Address::from(&[2; DB3_ADDRESS_LENGTH]);
+        let ns = "ns1".as_bytes();
+        assert!(NsAclStore::check_write(db.as_ref(), ns, &other).unwrap());
+        assert!(NsAclStore::claim(Pin::as_mut(&mut db), ns, &owner).is_ok());
+        assert!(!NsAclStore::check_write(db.as_ref(), ns, &owner).unwrap());
+        assert!(NsAclStore::check_write(db.as_ref(), ns, &other).is_err());
+        let ns_grant = NsGrant {
+            ns: ns.to_vec(),
+            grantee: other.to_vec(),
+        };
+        // only the owner can grant
+        assert!(NsAclStore::apply_grant(Pin::as_mut(&mut db), &other, &ns_grant, true).is_err());
+        assert!(NsAclStore::apply_grant(Pin::as_mut(&mut db), &owner, &ns_grant, true).is_ok());
+        assert!(!NsAclStore::check_write(db.as_ref(), ns, &other).unwrap());
+        assert!(NsAclStore::apply_grant(Pin::as_mut(&mut db), &owner, &ns_grant, false).is_ok());
+        assert!(NsAclStore::check_write(db.as_ref(), ns, &other).is_err());
+    }
+}
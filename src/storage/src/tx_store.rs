@@ -0,0 +1,189 @@
+//
+// tx_store.rs
+// Copyright (C) 2023 db3.network Author imotai <codego.me@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use db3_crypto::id::TxId;
+use db3_error::{DB3Error, Result};
+use merkdb::proofs::{query::Query, Node, Op as ProofOp};
+use merkdb::{BatchEntry, Merk, Op};
+use std::pin::Pin;
+
+/// _APPLIED_TX_ + tx_id
+const APPLIED_TX: &str = "_APPLIED_TX_";
+/// _TX_BY_HEIGHT_ + height + tx_id, the index to prune the old ids
+const TX_BY_HEIGHT: &str = "_TX_BY_HEIGHT_";
+
+///
+/// the ids of the txs applied in the last blocks are kept. the redelivered txs come from
+/// the last blocks, the older ids are pruned so the store does not grow with every tx.
+/// the ids are part of the app hash, so every node must keep the same blocks
+///
+pub const APPLIED_TX_RETAIN_BLOCKS: u64 = 1000;
+
+///
+/// keep the ids of the applied txs with the height they were applied at, so a tx
+/// delivered again after a crash is not applied twice
+///
+pub struct TxStore {}
+
+impl TxStore {
+    fn encode_key(tx_id: &TxId) -> Vec<u8> {
+        let mut encoded_key = APPLIED_TX.as_bytes().to_vec();
+        encoded_key.extend_from_slice(tx_id.as_ref());
+        encoded_key
+    }
+
+    fn encode_height_key(height: u64, tx_id: &[u8]) -> Result<Vec<u8>> {
+        let mut encoded_key = TX_BY_HEIGHT.as_bytes().to_vec();
+        encoded_key
+            .write_u64::<BigEndian>(height)
+            .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+        encoded_key.extend_from_slice(tx_id);
+        Ok(encoded_key)
+    }
+
+    pub fn apply(db: Pin<&mut Merk>, tx_id: &TxId, height: u64) -> Result<()> {
+        let mut value: Vec<u8> = Vec::new();
+        value
+            .write_u64::<BigEndian>(height)
+            .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+        let mut entries: Vec<BatchEntry> = vec![
+            (Self::encode_key(tx_id), Op::Put(value.to_vec())),
+            (
+                Self::encode_height_key(height, tx_id.as_ref())?,
+                Op::Put(value),
+            ),
+        ];
+        // merk requires the entries of a batch to be sorted by key
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        unsafe {
+            Pin::get_unchecked_mut(db)
+                .apply(&entries, &[])
+                .map_err(|e| DB3Error::ApplyCommitError(format!("{e}")))?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// delete the ids of the txs applied below the height and return the number of them
+    ///
+    pub fn prune(db: Pin<&mut Merk>, below_height: u64) -> Result<usize> {
+        // the empty tree can't be queried
+        let root_hash: [u8; 32] = db.root_hash().clone();
+        if root_hash == [0; 32] {
+            return Ok(0);
+        }
+        let start = TX_BY_HEIGHT.as_bytes().to_vec();
+        let end = Self::encode_height_key(below_height, &[])?;
+        let mut query = Query::new();
+        query.insert_range(start..end);
+        let ops = db
+            .execute_query(query)
+            .map_err(|e| DB3Error::ApplyCommitError(format!("{e}")))?;
+        let prefix_len = TX_BY_HEIGHT.len() + 8;
+        let mut entries: Vec<BatchEntry> = Vec::new();
+        for op in ops {
+            if let ProofOp::Push(Node::KV(k, _)) = op {
+                let mut applied_key = APPLIED_TX.as_bytes().to_vec();
+                applied_key.extend_from_slice(&k[prefix_len..]);
+                entries.push((applied_key, Op::Delete));
+                entries.push((k, Op::Delete));
+            }
+        }
+        if entries.is_empty() {
+            return Ok(0);
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        unsafe {
+            Pin::get_unchecked_mut(db)
+                .apply(&entries, &[])
+                .map_err(|e| DB3Error::ApplyCommitError(format!("{e}")))?;
+        }
+        Ok(entries.len() / 2)
+    }
+
+    pub fn is_applied(db: Pin<&Merk>, tx_id: &TxId) -> Result<bool> {
+        let value = db
+            .get(Self::encode_key(tx_id).as_ref())
+            .map_err(|e| DB3Error::GetCommitError(format!("{e}")))?;
+        Ok(value.is_some())
+    }
+
+    /// the height the tx was applied at
+    pub fn get_applied_height(db: Pin<&Merk>, tx_id: &TxId) -> Result<Option<u64>> {
+        let value = db
+            .get(Self::encode_key(tx_id).as_ref())
+            .map_err(|e| DB3Error::GetCommitError(format!("{e}")))?;
+        match value {
+            Some(v) => {
+                let height = v
+                    .as_slice()
+                    .read_u64::<BigEndian>()
+                    .map_err(|e| DB3Error::KeyCodecError(format!("{e}")))?;
+                Ok(Some(height))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use tempdir::TempDir;
+
+    #[test]
+    fn it_record_applied_tx() {
+        let tmp_dir_path = TempDir::new("tx_store").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        let tx_id = TxId::from([1; 32]);
+        let other_tx_id = TxId::from([2; 32]);
+        assert!(!TxStore::is_applied(db.as_ref(), &tx_id).unwrap());
+        assert!(TxStore::apply(Pin::as_mut(&mut db), &tx_id, 1).is_ok());
+        assert!(TxStore::is_applied(db.as_ref(), &tx_id).unwrap());
+        assert!(!TxStore::is_applied(db.as_ref(), &other_tx_id).unwrap());
+        assert_eq!(
+            Some(1),
+            TxStore::get_applied_height(db.as_ref(), &tx_id).unwrap()
+        );
+        assert_eq!(
+            None,
+            TxStore::get_applied_height(db.as_ref(), &other_tx_id).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_prune_old_applied_txs() {
+        let tmp_dir_path = TempDir::new("tx_store_prune").expect("create temp dir");
+        let merk = Merk::open(tmp_dir_path).unwrap();
+        let mut db = Box::pin(merk);
+        assert_eq!(0, TxStore::prune(Pin::as_mut(&mut db), 10).unwrap());
+        for height in 1..=4u8 {
+            let tx_id = TxId::from([height; 32]);
+            assert!(TxStore::apply(Pin::as_mut(&mut db), &tx_id, height as u64).is_ok());
+        }
+        // the ids applied below the height 3 are deleted
+        assert_eq!(2, TxStore::prune(Pin::as_mut(&mut db), 3).unwrap());
+        assert!(!TxStore::is_applied(db.as_ref(), &TxId::from([1; 32])).unwrap());
+        assert!(!TxStore::is_applied(db.as_ref(), &TxId::from([2; 32])).unwrap());
+        assert!(TxStore::is_applied(db.as_ref(), &TxId::from([3; 32])).unwrap());
+        assert!(TxStore::is_applied(db.as_ref(), &TxId::from([4; 32])).unwrap());
+        assert_eq!(0, TxStore::prune(Pin::as_mut(&mut db), 3).unwrap());
+    }
+}
@@ -15,7 +15,7 @@
 // limitations under the License.
 //
 use db3_proto::db3_base_proto::{UnitType, Units};
-use db3_proto::db3_mutation_proto::{Mutation, MutationAction};
+use db3_proto::db3_mutation_proto::{DatabaseMutation, Mutation, MutationAction};
 use db3_proto::db3_session_proto::QuerySessionInfo;
 
 const COMPUTAION_GAS_PRICE: u64 = 10; // unit in tai
@@ -27,9 +27,14 @@ pub fn estimate_gas(mutation: &Mutation) -> Units {
     for kv in &mutation.kv_pairs {
         let action = MutationAction::from_i32(kv.action);
         match action {
-            Some(MutationAction::InsertKv) => {
-                gas +=
-                    (mutation.ns.len() + kv.key.len() + kv.value.len()) as u64 * STORAGE_GAS_PRICE;
+            Some(MutationAction::InsertKv) | Some(MutationAction::CompareAndSwapKv) => {
+                // the pair without a namespace is written to the one of the mutation
+                let ns_len = if kv.ns.is_empty() {
+                    mutation.ns.len()
+                } else {
+                    kv.ns.len()
+                };
+                gas += (ns_len + kv.key.len() + kv.value.len()) as u64 * STORAGE_GAS_PRICE;
             }
             _ => {}
         }
@@ -39,6 +44,17 @@ pub fn estimate_gas(mutation: &Mutation) -> Units {
         amount: gas as i64,
     }
 }
+
+///
+/// the database mutations are not billed yet
+///
+pub fn estimate_database_gas(_mutation: &DatabaseMutation) -> Units {
+    Units {
+        utype: UnitType::Tai.into(),
+        amount: 0,
+    }
+}
+
 pub fn estimate_query_session_gas(query_session_info: &QuerySessionInfo) -> Units {
     let mut gas: u64 = 0;
     gas += query_session_info.query_count as u64 * COMPUTAION_GAS_PRICE;
@@ -62,6 +78,10 @@ mod tests {
             key: "k1".as_bytes().to_vec(),
             value: "value1".as_bytes().to_vec(),
             action: MutationAction::InsertKv.into(),
+            ns: vec![],
+            expected_value: vec![],
+            ttl: 0,
+            compressed: false,
         };
         let mutation = Mutation {
             ns: "my_twitter".as_bytes().to_vec(),
@@ -77,6 +97,13 @@ mod tests {
         assert_eq!(190, units.amount);
     }
 
+    #[test]
+    fn it_estimate_database_gas() {
+        let units = estimate_database_gas(&DatabaseMutation::default());
+        assert_eq!(1, units.utype);
+        assert_eq!(0, units.amount);
+    }
+
     #[test]
     fn it_query_session_estimate_gas() {
         let node_query_session_info = QuerySessionInfo {